@@ -0,0 +1,213 @@
+// 连接认证
+// 每次启动生成随机令牌，WebSocket 握手时校验，防止其他本地进程或网页连接服务器
+
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue, StatusCode};
+
+/// 令牌请求头名称
+pub const TOKEN_HEADER: &str = "x-smart-workflow-token";
+
+/// 令牌查询参数名称
+pub const TOKEN_QUERY_PARAM: &str = "token";
+
+/// 携带令牌的子协议前缀 (格式: `sw-token.<token>`)
+pub const TOKEN_SUBPROTOCOL_PREFIX: &str = "sw-token.";
+
+/// 令牌字节长度 (编码为十六进制后为 64 个字符)
+const TOKEN_BYTES: usize = 32;
+
+// ============================================================================
+// 认证令牌
+// ============================================================================
+
+/// 认证令牌
+///
+/// 服务器启动时生成，通过 stdout 的启动信息传给父进程
+#[derive(Clone)]
+pub struct AuthToken {
+    token: String,
+}
+
+impl AuthToken {
+    /// 生成新的随机令牌
+    pub fn generate() -> Self {
+        let bytes: [u8; TOKEN_BYTES] = rand::random();
+        let token = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        Self { token }
+    }
+
+    /// 获取令牌字符串
+    pub fn as_str(&self) -> &str {
+        &self.token
+    }
+
+    /// 校验令牌 (常量时间比较，避免时序攻击)
+    pub fn verify(&self, candidate: &str) -> bool {
+        let expected = self.token.as_bytes();
+        let candidate = candidate.as_bytes();
+        if expected.len() != candidate.len() {
+            return false;
+        }
+        expected
+            .iter()
+            .zip(candidate)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+    }
+
+    /// 校验 WebSocket 握手请求
+    ///
+    /// 依次检查请求头、子协议和查询参数，任一处携带有效令牌即通过。
+    /// 令牌通过子协议传递时，需要在响应中回显该子协议，否则浏览器会拒绝连接。
+    #[allow(clippy::result_large_err)] // ErrorResponse 由 tungstenite 定义
+    pub fn check_handshake(&self, request: &Request, mut response: Response) -> Result<Response, ErrorResponse> {
+        // 1. 请求头: X-Smart-Workflow-Token 或 Authorization: Bearer
+        if let Some(value) = header_str(request, TOKEN_HEADER) {
+            if self.verify(value.trim()) {
+                return Ok(response);
+            }
+        }
+        if let Some(value) = header_str(request, header::AUTHORIZATION.as_str()) {
+            if let Some(token) = value.trim().strip_prefix("Bearer ") {
+                if self.verify(token.trim()) {
+                    return Ok(response);
+                }
+            }
+        }
+
+        // 2. 子协议: Sec-WebSocket-Protocol: sw-token.<token>
+        if let Some(value) = header_str(request, header::SEC_WEBSOCKET_PROTOCOL.as_str()) {
+            for protocol in value.split(',').map(str::trim) {
+                if let Some(token) = protocol.strip_prefix(TOKEN_SUBPROTOCOL_PREFIX) {
+                    if self.verify(token) {
+                        if let Ok(v) = HeaderValue::from_str(protocol) {
+                            response.headers_mut().insert(header::SEC_WEBSOCKET_PROTOCOL, v);
+                        }
+                        return Ok(response);
+                    }
+                }
+            }
+        }
+
+        // 3. 查询参数: ?token=<token>
+        if let Some(query) = request.uri().query() {
+            for pair in query.split('&') {
+                if let Some((key, value)) = pair.split_once('=') {
+                    if key == TOKEN_QUERY_PARAM && self.verify(value) {
+                        return Ok(response);
+                    }
+                }
+            }
+        }
+
+        Err(unauthorized_response())
+    }
+}
+
+impl std::fmt::Debug for AuthToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // 不在日志中输出令牌内容
+        f.write_str("AuthToken(***)")
+    }
+}
+
+/// 读取字符串形式的请求头
+fn header_str<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+/// 构建 401 拒绝响应
+fn unauthorized_response() -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some("Unauthorized".to_string()));
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, headers: &[(&str, &str)]) -> Request {
+        let mut builder = Request::builder().uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn test_generate_token() {
+        let a = AuthToken::generate();
+        let b = AuthToken::generate();
+
+        assert_eq!(a.as_str().len(), TOKEN_BYTES * 2);
+        assert!(a.as_str().chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a.as_str(), b.as_str());
+    }
+
+    #[test]
+    fn test_verify() {
+        let token = AuthToken::generate();
+
+        assert!(token.verify(token.as_str()));
+        assert!(!token.verify(""));
+        assert!(!token.verify("not-the-token"));
+        assert!(!token.verify(&token.as_str()[1..]));
+    }
+
+    #[test]
+    fn test_debug_hides_token() {
+        let token = AuthToken::generate();
+        assert!(!format!("{:?}", token).contains(token.as_str()));
+    }
+
+    #[test]
+    fn test_check_handshake_header() {
+        let token = AuthToken::generate();
+
+        let req = request("/", &[(TOKEN_HEADER, token.as_str())]);
+        assert!(token.check_handshake(&req, Response::new(())).is_ok());
+
+        let bearer = format!("Bearer {}", token.as_str());
+        let req = request("/", &[("Authorization", bearer.as_str())]);
+        assert!(token.check_handshake(&req, Response::new(())).is_ok());
+    }
+
+    #[test]
+    fn test_check_handshake_subprotocol() {
+        let token = AuthToken::generate();
+        let protocol = format!("{}{}", TOKEN_SUBPROTOCOL_PREFIX, token.as_str());
+        let protocols = format!("smart-workflow, {}", protocol);
+
+        let req = request("/", &[("Sec-WebSocket-Protocol", protocols.as_str())]);
+        let response = token.check_handshake(&req, Response::new(())).unwrap();
+
+        let echoed = response.headers().get(header::SEC_WEBSOCKET_PROTOCOL).unwrap();
+        assert_eq!(echoed.to_str().unwrap(), protocol);
+    }
+
+    #[test]
+    fn test_check_handshake_query() {
+        let token = AuthToken::generate();
+
+        let uri = format!("/?foo=bar&token={}", token.as_str());
+        let req = request(&uri, &[]);
+        assert!(token.check_handshake(&req, Response::new(())).is_ok());
+    }
+
+    #[test]
+    fn test_check_handshake_rejects_missing_or_wrong_token() {
+        let token = AuthToken::generate();
+
+        let req = request("/", &[]);
+        let err = token.check_handshake(&req, Response::new(())).unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNAUTHORIZED);
+
+        let req = request("/?token=wrong", &[(TOKEN_HEADER, "wrong")]);
+        assert!(token.check_handshake(&req, Response::new(())).is_err());
+    }
+}
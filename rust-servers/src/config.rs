@@ -0,0 +1,612 @@
+// 服务器配置
+// 命令行参数与可选的 TOML 配置文件，合并后生成 ServerConfig 和 LogConfig
+//
+// 优先级: 命令行参数 > 配置文件 > 默认值
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::Parser;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::logging::{Level, LogConfig};
+use crate::router::ModuleType;
+use crate::server::ServerConfig;
+use crate::transport::{StdioFormat, Transport};
+
+// ============================================================================
+// 命令行参数
+// ============================================================================
+
+/// 命令行参数
+#[derive(Debug, Default, Parser)]
+#[command(name = "smart-workflow-server", version, about = "Obsidian Smart Workflow 统一后端服务器")]
+pub struct Cli {
+    /// TOML 配置文件路径 (命令行参数优先于配置文件)
+    #[arg(short, long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// 监听地址 [默认: 127.0.0.1]
+    #[arg(long, value_name = "ADDR")]
+    pub bind: Option<IpAddr>,
+
+    /// 改为监听 Unix 套接字 (权限 0600，不校验令牌)
+    #[arg(long, value_name = "PATH", conflicts_with = "stdio")]
+    pub unix_socket: Option<PathBuf>,
+
+    /// 改为通过标准输入/输出通信，格式: lines (换行分隔 JSON)、length (长度前缀) [默认: lines]
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "lines")]
+    pub stdio: Option<StdioFormat>,
+
+    /// 监听端口 (0 表示随机端口) [默认: 0]
+    #[arg(short, long, value_name = "PORT")]
+    pub port: Option<u16>,
+
+    /// 连接断开后 PTY 会话的保留秒数 (0 表示立即终止) [默认: 60]
+    #[arg(long, value_name = "SECS")]
+    pub pty_grace_period: Option<u64>,
+
+    /// 父进程 PID，父进程退出后自动关闭
+    #[arg(long, value_name = "PID")]
+    pub parent_pid: Option<u32>,
+
+    /// 没有连接超过指定秒数后自动关闭 (0 表示不启用)
+    #[arg(long, value_name = "SECS")]
+    pub idle_timeout: Option<u64>,
+
+    /// 在 TCP 端口上应答 GET /healthz (无需令牌)
+    #[arg(long)]
+    pub healthz: bool,
+
+    /// 日志级别: error, warn, info, debug
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<Level>,
+
+    /// 以 JSON 行格式输出日志
+    #[arg(long)]
+    pub log_json: bool,
+
+    /// 同时写入日志文件 (超过 10 MiB 时滚动，保留 3 个历史文件)
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// 启用的模块，逗号分隔: pty, voice, llm, utils [默认: 全部]
+    #[arg(long, value_name = "MODULES", value_delimiter = ',')]
+    pub modules: Option<Vec<ModuleType>>,
+
+    /// init 未指定 shell_type 时使用的 shell (如 zsh、powershell、custom:/bin/fish)
+    #[arg(long, value_name = "SHELL")]
+    pub shell: Option<String>,
+
+    /// 输出消息协议的 JSON Schema 后退出
+    #[arg(long)]
+    pub print_schema: bool,
+}
+
+// ============================================================================
+// 配置文件
+// ============================================================================
+
+/// 配置文件 (TOML)
+///
+/// 所有字段均可省略，省略时使用默认值
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub server: ServerSection,
+    pub log: LogSection,
+    pub modules: ModulesSection,
+    pub pty: PtySection,
+    pub llm: LlmSection,
+    pub asr: AsrSection,
+}
+
+/// [server] 段
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSection {
+    /// 监听地址
+    pub bind: Option<IpAddr>,
+    /// 监听端口
+    pub port: Option<u16>,
+    /// 父进程 PID
+    pub parent_pid: Option<u32>,
+    /// 空闲超时秒数 (0 表示不启用)
+    pub idle_timeout: Option<u64>,
+    /// 在 TCP 端口上应答 GET /healthz
+    pub healthz: Option<bool>,
+    /// Unix 套接字路径
+    pub unix_socket: Option<PathBuf>,
+    /// stdio 消息格式
+    pub stdio: Option<StdioFormat>,
+}
+
+/// [log] 段
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSection {
+    /// 日志级别
+    pub level: Option<Level>,
+    /// 以 JSON 行格式输出
+    pub json: Option<bool>,
+    /// 日志文件路径
+    pub file: Option<PathBuf>,
+    /// 单个日志文件的最大字节数
+    pub max_file_size: Option<u64>,
+    /// 保留的历史日志文件数
+    pub max_files: Option<usize>,
+}
+
+/// [modules] 段
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModulesSection {
+    /// 启用的模块 (只接受内置模块名称)
+    #[serde(deserialize_with = "deserialize_modules")]
+    pub enabled: Option<Vec<ModuleType>>,
+}
+
+/// 按内置模块名称解析模块列表
+fn deserialize_modules<'de, D>(deserializer: D) -> Result<Option<Vec<ModuleType>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let names = Vec::<String>::deserialize(deserializer)?;
+    names
+        .iter()
+        .map(|name| name.parse().map_err(serde::de::Error::custom))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// [pty] 段
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PtySection {
+    /// 默认 shell 类型
+    pub shell: Option<String>,
+    /// 默认 shell 启动参数
+    pub shell_args: Option<Vec<String>>,
+    /// 每个会话的输出缓冲区字节数 (重新连接时回放)
+    pub scrollback: Option<usize>,
+    /// 每个会话虚拟终端的回滚历史行数 (snapshot / scrollback_range)
+    pub screen_history: Option<usize>,
+    /// 每个会话检索缓冲区的字节数 (search)
+    pub search_buffer: Option<usize>,
+    /// 连接断开后会话的保留秒数
+    pub grace_period: Option<u64>,
+}
+
+/// [llm] 段
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmSection {
+    /// 建立连接的超时秒数
+    pub connect_timeout: Option<u64>,
+    /// 两次读取之间的最长等待秒数 (0 表示不限制)
+    pub read_timeout: Option<u64>,
+    /// HTTP/HTTPS 代理地址
+    pub proxy: Option<String>,
+}
+
+/// [asr] 段
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AsrSection {
+    /// 最大重试次数
+    pub max_retries: Option<u32>,
+    /// 重试基础延迟 (毫秒，指数退避)
+    pub base_delay_ms: Option<u64>,
+    /// 单次请求超时 (毫秒)
+    pub timeout_ms: Option<u64>,
+}
+
+impl FileConfig {
+    /// 从文件读取配置
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&content).map_err(|e| match e {
+            ConfigError::Parse { message, .. } => ConfigError::Parse { path: path.to_path_buf(), message },
+            other => other,
+        })
+    }
+
+    /// 解析 TOML 文本
+    pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        toml::from_str(content).map_err(|e| ConfigError::Parse {
+            path: PathBuf::new(),
+            message: e.to_string(),
+        })
+    }
+}
+
+// ============================================================================
+// 错误
+// ============================================================================
+
+/// 配置错误
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// 配置文件读取失败
+    #[error("无法读取配置文件 {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// 配置文件格式错误
+    #[error("配置文件 {} 格式错误: {message}", path.display())]
+    Parse { path: PathBuf, message: String },
+
+    /// 配置值无效
+    #[error("配置无效: {0}")]
+    Invalid(String),
+}
+
+// ============================================================================
+// 合并
+// ============================================================================
+
+/// 解析命令行参数并读取配置文件
+///
+/// 命令行参数有误时打印用法并退出
+pub fn load() -> Result<(ServerConfig, LogConfig), ConfigError> {
+    let cli = Cli::parse();
+    if cli.print_schema {
+        let schema = serde_json::to_string_pretty(&crate::protocol::schema()).unwrap_or_default();
+        println!("{}", schema);
+        std::process::exit(0);
+    }
+    let file = match &cli.config {
+        Some(path) => FileConfig::load(path)?,
+        None => FileConfig::default(),
+    };
+    resolve(file, cli)
+}
+
+/// 合并配置文件和命令行参数
+pub fn resolve(file: FileConfig, cli: Cli) -> Result<(ServerConfig, LogConfig), ConfigError> {
+    let mut config = ServerConfig::default();
+    let mut log = LogConfig::default();
+
+    // [server]
+    // 命令行指定了传输方式时整体覆盖配置文件中的传输方式
+    let (unix_socket, stdio) = if cli.unix_socket.is_some() || cli.stdio.is_some() {
+        (cli.unix_socket, cli.stdio)
+    } else {
+        (file.server.unix_socket, file.server.stdio)
+    };
+    config.transport = match (unix_socket, stdio) {
+        (Some(_), Some(_)) => {
+            return Err(ConfigError::Invalid("unix_socket 和 stdio 不能同时使用".to_string()));
+        }
+        (Some(path), None) => Transport::Unix(path),
+        (None, Some(format)) => Transport::Stdio(format),
+        (None, None) => Transport::Tcp,
+    };
+    if let Some(bind) = cli.bind.or(file.server.bind) {
+        config.bind_address = bind;
+    }
+    if let Some(port) = cli.port.or(file.server.port) {
+        config.port = port;
+    }
+    config.parent_pid = cli.parent_pid.or(file.server.parent_pid);
+    config.idle_timeout = cli.idle_timeout.or(file.server.idle_timeout)
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs);
+    config.healthz = cli.healthz || file.server.healthz.unwrap_or(false);
+
+    // [log]
+    if let Some(level) = cli.log_level.or(file.log.level) {
+        log.level = level;
+    }
+    log.json = cli.log_json || file.log.json.unwrap_or(false);
+    log.file = cli.log_file.or(file.log.file);
+    if let Some(size) = file.log.max_file_size {
+        if size == 0 {
+            return Err(ConfigError::Invalid("log.max_file_size 必须大于 0".to_string()));
+        }
+        log.max_file_size = size;
+    }
+    if let Some(count) = file.log.max_files {
+        log.max_files = count;
+    }
+
+    // [modules]
+    let modules = &mut config.modules;
+    if let Some(enabled) = cli.modules.or(file.modules.enabled) {
+        let mut unique = Vec::new();
+        for module in enabled {
+            if !unique.contains(&module) {
+                unique.push(module);
+            }
+        }
+        if unique.is_empty() {
+            return Err(ConfigError::Invalid("至少需要启用一个模块".to_string()));
+        }
+        modules.enabled = unique;
+    }
+
+    // 各模块的配置只在模块已编译时生效，否则忽略
+
+    // [pty]
+    #[cfg(not(feature = "pty"))]
+    let _ = (cli.shell, cli.pty_grace_period, file.pty);
+    #[cfg(feature = "pty")]
+    apply_pty_config(&mut modules.pty, cli.shell, cli.pty_grace_period, file.pty)?;
+
+    // [llm]
+    #[cfg(not(feature = "llm"))]
+    let _ = file.llm;
+    #[cfg(feature = "llm")]
+    apply_llm_config(&mut modules.llm, file.llm)?;
+
+    // [asr]
+    #[cfg(not(feature = "voice"))]
+    let _ = file.asr;
+    #[cfg(feature = "voice")]
+    apply_asr_config(&mut modules.asr_retry, file.asr);
+
+    Ok((config, log))
+}
+
+/// 合并 PTY 模块配置
+#[cfg(feature = "pty")]
+fn apply_pty_config(
+    pty: &mut crate::pty::PtyConfig,
+    shell: Option<String>,
+    grace_period: Option<u64>,
+    file: PtySection,
+) -> Result<(), ConfigError> {
+    pty.default_shell = shell.or(file.shell);
+    pty.default_shell_args = file.shell_args;
+    if let Some(secs) = grace_period.or(file.grace_period) {
+        pty.detach.grace_period = Duration::from_secs(secs);
+    }
+    if let Some(size) = file.scrollback {
+        if size == 0 {
+            return Err(ConfigError::Invalid("pty.scrollback 必须大于 0".to_string()));
+        }
+        pty.detach.buffer_size = size;
+    }
+    if let Some(lines) = file.screen_history {
+        pty.detach.screen_history = lines;
+    }
+    if let Some(size) = file.search_buffer {
+        if size == 0 {
+            return Err(ConfigError::Invalid("pty.search_buffer 必须大于 0".to_string()));
+        }
+        pty.detach.search_buffer_size = size;
+    }
+    Ok(())
+}
+
+/// 合并 LLM 模块配置
+#[cfg(feature = "llm")]
+fn apply_llm_config(llm: &mut crate::llm::LlmConfig, file: LlmSection) -> Result<(), ConfigError> {
+    if let Some(secs) = file.connect_timeout {
+        llm.connect_timeout = Duration::from_secs(secs);
+    }
+    if let Some(secs) = file.read_timeout {
+        llm.read_timeout = (secs > 0).then(|| Duration::from_secs(secs));
+    }
+    if let Some(proxy) = file.proxy {
+        reqwest::Proxy::all(&proxy)
+            .map_err(|e| ConfigError::Invalid(format!("llm.proxy 无效 ({}): {}", proxy, e)))?;
+        llm.proxy = Some(proxy);
+    }
+    Ok(())
+}
+
+/// 合并 ASR 重试配置
+#[cfg(feature = "voice")]
+fn apply_asr_config(retry: &mut crate::voice::asr::RetryConfig, file: AsrSection) {
+    if let Some(max_retries) = file.max_retries {
+        retry.max_retries = max_retries;
+    }
+    if let Some(delay) = file.base_delay_ms {
+        retry.base_delay_ms = delay;
+    }
+    if let Some(timeout) = file.timeout_ms {
+        retry.timeout_ms = timeout;
+    }
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_cli(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("smart-workflow-server").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let (config, log) = resolve(FileConfig::default(), Cli::default()).unwrap();
+        assert_eq!(config.bind_address, crate::server::DEFAULT_BIND_ADDRESS);
+        assert_eq!(config.port, 0);
+        assert_eq!(config.idle_timeout, None);
+        assert!(!config.healthz);
+        assert_eq!(config.modules.enabled.len(), crate::router::BUILTIN_MODULES.len());
+        #[cfg(feature = "pty")]
+        assert_eq!(config.modules.pty.detach.grace_period, crate::pty::DEFAULT_GRACE_PERIOD);
+        #[cfg(feature = "voice")]
+        assert_eq!(config.modules.asr_retry.max_retries, 2);
+        assert_eq!(log.level, Level::default_level());
+        assert!(!log.json);
+    }
+
+    #[test]
+    #[cfg(all(feature = "pty", feature = "utils"))]
+    fn test_cli_args() {
+        let cli = parse_cli(&[
+            "--port", "8080",
+            "--bind=0.0.0.0",
+            "--pty-grace-period", "10",
+            "--idle-timeout=0",
+            "--log-level", "warn",
+            "--log-json",
+            "--modules", "pty,utils",
+            "--shell", "zsh",
+        ]);
+        let (config, log) = resolve(FileConfig::default(), cli).unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.bind_address.to_string(), "0.0.0.0");
+        assert_eq!(config.modules.pty.detach.grace_period, Duration::from_secs(10));
+        assert_eq!(config.idle_timeout, None);
+        assert_eq!(config.modules.enabled, vec![ModuleType::PTY, ModuleType::UTILS]);
+        assert_eq!(config.modules.pty.default_shell.as_deref(), Some("zsh"));
+        assert_eq!(log.level, Level::Warn);
+        assert!(log.json);
+    }
+
+    #[test]
+    fn test_cli_rejects_invalid_values() {
+        let args = |extra: &[&'static str]| std::iter::once("smart-workflow-server").chain(extra.to_vec());
+        assert!(Cli::try_parse_from(args(&["--log-level", "verbose"])).is_err());
+        assert!(Cli::try_parse_from(args(&["--modules", "pty,audio"])).is_err());
+        assert!(Cli::try_parse_from(args(&["--port", "70000"])).is_err());
+        assert!(Cli::try_parse_from(args(&["--unknown"])).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "pty", feature = "voice", feature = "llm", feature = "utils"))]
+    fn test_file_config() {
+        let file = FileConfig::parse(r#"
+            [server]
+            port = 9000
+            idle_timeout = 60
+            healthz = true
+
+            [log]
+            level = "error"
+            file = "/tmp/server.log"
+            max_files = 5
+
+            [modules]
+            enabled = ["llm", "utils"]
+
+            [pty]
+            shell = "bash"
+            shell_args = ["--login"]
+            scrollback = 4096
+            screen_history = 500
+            search_buffer = 65536
+            grace_period = 30
+
+            [llm]
+            connect_timeout = 5
+            read_timeout = 120
+            proxy = "http://127.0.0.1:7890"
+
+            [asr]
+            max_retries = 4
+            base_delay_ms = 250
+            timeout_ms = 10000
+        "#).unwrap();
+        let (config, log) = resolve(file, Cli::default()).unwrap();
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.idle_timeout, Some(Duration::from_secs(60)));
+        assert!(config.healthz);
+        assert_eq!(config.modules.enabled, vec![ModuleType::LLM, ModuleType::UTILS]);
+        assert_eq!(config.modules.pty.default_shell.as_deref(), Some("bash"));
+        assert_eq!(config.modules.pty.default_shell_args, Some(vec!["--login".to_string()]));
+        assert_eq!(config.modules.pty.detach.buffer_size, 4096);
+        assert_eq!(config.modules.pty.detach.screen_history, 500);
+        assert_eq!(config.modules.pty.detach.search_buffer_size, 65536);
+        assert_eq!(config.modules.pty.detach.grace_period, Duration::from_secs(30));
+        assert_eq!(config.modules.llm.connect_timeout, Duration::from_secs(5));
+        assert_eq!(config.modules.llm.read_timeout, Some(Duration::from_secs(120)));
+        assert_eq!(config.modules.llm.proxy.as_deref(), Some("http://127.0.0.1:7890"));
+        assert_eq!(config.modules.asr_retry.max_retries, 4);
+        assert_eq!(config.modules.asr_retry.base_delay_ms, 250);
+        assert_eq!(config.modules.asr_retry.timeout_ms, 10000);
+        assert_eq!(log.level, Level::Error);
+        assert_eq!(log.file, Some(PathBuf::from("/tmp/server.log")));
+        assert_eq!(log.max_files, 5);
+    }
+
+    #[test]
+    fn test_cli_overrides_file() {
+        let file = FileConfig::parse(r#"
+            [server]
+            port = 9000
+            [log]
+            level = "error"
+            [pty]
+            shell = "bash"
+        "#).unwrap();
+        let cli = parse_cli(&["-p", "9001", "--log-level", "debug"]);
+        let (config, log) = resolve(file, cli).unwrap();
+        assert_eq!(config.port, 9001);
+        assert_eq!(log.level, Level::Debug);
+        #[cfg(feature = "pty")]
+        assert_eq!(config.modules.pty.default_shell.as_deref(), Some("bash"));
+    }
+
+    #[test]
+    fn test_transport() {
+        let (config, _) = resolve(FileConfig::default(), parse_cli(&["--stdio"])).unwrap();
+        assert_eq!(config.transport, Transport::Stdio(StdioFormat::Lines));
+
+        let (config, _) = resolve(FileConfig::default(), parse_cli(&["--stdio", "length"])).unwrap();
+        assert_eq!(config.transport, Transport::Stdio(StdioFormat::Length));
+
+        let file = || FileConfig::parse("[server]\nunix_socket = \"/tmp/sw.sock\"").unwrap();
+        let (config, _) = resolve(file(), Cli::default()).unwrap();
+        assert_eq!(config.transport, Transport::Unix(PathBuf::from("/tmp/sw.sock")));
+
+        // 命令行覆盖配置文件
+        let (config, _) = resolve(file(), parse_cli(&["--stdio=length"])).unwrap();
+        assert_eq!(config.transport, Transport::Stdio(StdioFormat::Length));
+
+        let both = FileConfig::parse("[server]\nunix_socket = \"/tmp/sw.sock\"\nstdio = \"lines\"").unwrap();
+        assert!(matches!(resolve(both, Cli::default()), Err(ConfigError::Invalid(_))));
+        assert!(Cli::try_parse_from(["smart-workflow-server", "--stdio", "--unix-socket", "/tmp/sw.sock"]).is_err());
+        assert!(Cli::try_parse_from(["smart-workflow-server", "--stdio", "json"]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_file_config() {
+        // 未知字段
+        assert!(matches!(FileConfig::parse("[server]\nprot = 1"), Err(ConfigError::Parse { .. })));
+        assert!(matches!(FileConfig::parse("[unknown]"), Err(ConfigError::Parse { .. })));
+        // 类型错误
+        assert!(matches!(FileConfig::parse("[modules]\nenabled = [\"audio\"]"), Err(ConfigError::Parse { .. })));
+
+        let invalid = |toml: &str| {
+            matches!(resolve(FileConfig::parse(toml).unwrap(), Cli::default()), Err(ConfigError::Invalid(_)))
+        };
+        assert!(invalid("[modules]\nenabled = []"));
+        #[cfg(feature = "pty")]
+        assert!(invalid("[pty]\nscrollback = 0"));
+        #[cfg(feature = "pty")]
+        assert!(invalid("[pty]\nsearch_buffer = 0"));
+        #[cfg(feature = "llm")]
+        assert!(invalid("[llm]\nproxy = \"not a url\""));
+    }
+
+    #[test]
+    #[cfg(not(feature = "pty"))]
+    fn test_uncompiled_module_config_ignored() {
+        // 未编译模块的配置段和选项被忽略，但不能启用该模块
+        let file = FileConfig::parse("[pty]\nshell = \"bash\"\nscrollback = 0").unwrap();
+        assert!(resolve(file, parse_cli(&["--shell", "zsh"])).is_ok());
+        assert!(Cli::try_parse_from(["smart-workflow-server", "--modules", "pty"]).is_err());
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let result = FileConfig::load(Path::new("/nonexistent/smart-workflow-server.toml"));
+        assert!(matches!(result, Err(ConfigError::Io { .. })));
+    }
+}
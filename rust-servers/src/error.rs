@@ -0,0 +1,269 @@
+// 错误码
+// 各模块共用的错误码和结构化错误详情 (HTTP 状态码、供应商、是否可重试、重试等待时间)，
+// 客户端据此决定重试、重新认证或打开设置，无需匹配错误文本
+
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::i18n::{Locale, Msg};
+use crate::protocol::Payload;
+
+// ============================================================================
+// 错误码
+// ============================================================================
+
+/// 错误码 (序列化为 `SCREAMING_SNAKE_CASE` 字符串)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    // 协议
+    /// 未知模块
+    UnknownModule,
+    /// 消息格式无效 (包括未知的消息类型)
+    InvalidMessage,
+    /// 请求与当前状态冲突 (如已在录音时再次开始录音)
+    InvalidState,
+    /// 消息解析失败
+    ParseError,
+    /// JSON 序列化/反序列化失败
+    JsonError,
+    /// 模块已被服务器配置禁用
+    ModuleDisabled,
+    /// 未分类的模块错误
+    ModuleError,
+
+    // 上游服务
+    /// 网络错误 (连接失败、连接中断)
+    NetworkError,
+    /// 请求超时
+    Timeout,
+    /// 上游返回 HTTP 错误状态
+    HttpError,
+    /// 认证失败 (API Key 无效或已过期)
+    AuthFailed,
+    /// 请求过于频繁或配额超限
+    RateLimited,
+    /// 请求已取消
+    Cancelled,
+
+    // 配置
+    /// 配置无效或缺失
+    InvalidConfig,
+
+    // 语音
+    /// 转录失败
+    TranscriptionFailed,
+    /// 音频数据无效
+    InvalidAudio,
+    /// 音频设备不可用或出错
+    AudioDeviceError,
+
+    // PTY
+    /// 会话不存在或已结束
+    SessionNotFound,
+    /// 创建 PTY 会话失败
+    PtySpawnFailed,
+    /// 读写 PTY 失败
+    PtyIoError,
+
+    /// 服务器内部错误
+    InternalError,
+}
+
+impl ErrorCode {
+    /// 该错误码默认是否可重试
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::NetworkError | Self::Timeout | Self::RateLimited)
+    }
+
+    /// 根据 HTTP 状态码选择错误码
+    #[cfg_attr(not(feature = "llm"), allow(dead_code))]
+    pub fn from_http_status(status: u16) -> Self {
+        match status {
+            401 | 403 => Self::AuthFailed,
+            408 | 504 => Self::Timeout,
+            429 => Self::RateLimited,
+            _ => Self::HttpError,
+        }
+    }
+}
+
+// ============================================================================
+// 结构化错误
+// ============================================================================
+
+/// 错误详情 (均为可选字段，未设置时不序列化)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorDetails {
+    /// 上游 HTTP 状态码
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// 出错的供应商或引擎
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// 建议的重试等待时间 (毫秒)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+}
+
+impl ErrorDetails {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 错误消息的负载 (LLM 的 `stream_error` 同样包含这些字段)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorPayload {
+    /// 错误码
+    pub code: ErrorCode,
+    /// 按连接语言渲染的错误描述 (供显示，客户端不应依赖其内容)
+    pub message: String,
+    /// 是否可以原样重试
+    pub retryable: bool,
+    /// 错误详情 (没有详情时省略)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<ErrorDetails>,
+}
+
+impl Payload for ErrorPayload {
+    const TYPE: &'static str = "error";
+}
+
+/// 带错误码的错误
+///
+/// 按连接语言渲染为错误消息的负载 [`ErrorPayload`]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{message}")]
+pub struct ServerError {
+    /// 错误码
+    pub code: ErrorCode,
+    /// 错误描述 (供显示和日志使用，客户端不应依赖其内容)
+    pub message: Msg,
+    /// 是否可以原样重试
+    pub retryable: bool,
+    /// 错误详情
+    pub details: ErrorDetails,
+}
+
+impl ServerError {
+    /// 创建错误，是否可重试取错误码的默认值
+    pub fn new(code: ErrorCode, message: Msg) -> Self {
+        Self {
+            code,
+            message,
+            retryable: code.is_retryable(),
+            details: ErrorDetails::default(),
+        }
+    }
+
+    /// 创建上游 HTTP 错误 (5xx、408、429 可重试)
+    #[cfg_attr(not(feature = "llm"), allow(dead_code))]
+    pub fn http(status: u16, message: Msg) -> Self {
+        let mut error = Self::new(ErrorCode::from_http_status(status), message);
+        error.retryable = error.retryable || status >= 500;
+        error.details.status = Some(status);
+        error
+    }
+
+    /// 设置供应商
+    #[cfg_attr(not(feature = "voice"), allow(dead_code))]
+    pub fn with_provider(mut self, provider: impl Into<String>) -> Self {
+        self.details.provider = Some(provider.into());
+        self
+    }
+
+    /// 设置建议的重试等待时间
+    #[cfg_attr(not(feature = "llm"), allow(dead_code))]
+    pub fn with_retry_after(mut self, retry_after: Option<Duration>) -> Self {
+        self.details.retry_after_ms = retry_after.map(|d| d.as_millis() as u64);
+        self
+    }
+
+    /// 替换错误描述 (保留错误码和详情)
+    #[cfg_attr(not(feature = "voice"), allow(dead_code))]
+    pub fn with_message(mut self, message: Msg) -> Self {
+        self.message = message;
+        self
+    }
+
+    /// 按指定语言渲染为错误消息的负载
+    pub fn to_payload(&self, locale: Locale) -> ErrorPayload {
+        ErrorPayload {
+            code: self.code,
+            message: self.message.render(locale),
+            retryable: self.retryable,
+            details: (!self.details.is_empty()).then(|| self.details.clone()),
+        }
+    }
+}
+
+/// 解析 HTTP `Retry-After` 头 (仅支持秒数格式)
+#[cfg_attr(not(feature = "llm"), allow(dead_code))]
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code_serialization() {
+        assert_eq!(serde_json::to_string(&ErrorCode::RateLimited).unwrap(), r#""RATE_LIMITED""#);
+        assert_eq!(serde_json::to_string(&ErrorCode::PtySpawnFailed).unwrap(), r#""PTY_SPAWN_FAILED""#);
+        let code: ErrorCode = serde_json::from_str(r#""AUTH_FAILED""#).unwrap();
+        assert_eq!(code, ErrorCode::AuthFailed);
+    }
+
+    #[test]
+    fn test_http_error_mapping() {
+        let error = ServerError::http(401, Msg::Text("unauthorized".into()));
+        assert_eq!(error.code, ErrorCode::AuthFailed);
+        assert!(!error.retryable);
+        assert_eq!(error.details.status, Some(401));
+
+        let error = ServerError::http(429, Msg::Text("slow down".into()));
+        assert_eq!(error.code, ErrorCode::RateLimited);
+        assert!(error.retryable);
+
+        let error = ServerError::http(503, Msg::Text("unavailable".into()));
+        assert_eq!(error.code, ErrorCode::HttpError);
+        assert!(error.retryable);
+
+        let error = ServerError::http(400, Msg::Text("bad request".into()));
+        assert_eq!(error.code, ErrorCode::HttpError);
+        assert!(!error.retryable);
+    }
+
+    #[test]
+    fn test_payload() {
+        let error = ServerError::new(ErrorCode::InvalidConfig, Msg::MissingApiKey { key: "api_key".into() });
+        assert_eq!(serde_json::to_value(error.to_payload(Locale::En)).unwrap(), serde_json::json!({
+            "code": "INVALID_CONFIG",
+            "message": "Missing required API key: api_key",
+            "retryable": false,
+        }));
+        assert_eq!(error.to_payload(Locale::ZhCn).message, "缺少必需的 API Key: api_key");
+
+        let payload = ServerError::http(429, Msg::Text("slow down".into()))
+            .with_provider("qwen")
+            .with_retry_after(parse_retry_after(" 2 "))
+            .to_payload(Locale::En);
+        let payload = serde_json::to_value(payload).unwrap();
+        assert_eq!(payload["retryable"], true);
+        assert_eq!(payload["details"], serde_json::json!({
+            "status": 429,
+            "provider": "qwen",
+            "retry_after_ms": 2000,
+        }));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+}
@@ -0,0 +1,157 @@
+// 二进制帧格式
+// 为二进制 WebSocket 消息加上通道 (模块) 和流 ID 帧头，使二进制数据可以在同一连接上复用
+//
+// 帧布局:
+//   +---------+----------------------+-----------------+
+//   | channel | stream_id (u32, BE)  | payload ...     |
+//   | 1 字节  | 4 字节               | 其余字节        |
+//   +---------+----------------------+-----------------+
+//
+// 客户端在 hello 握手中声明 `binary_frames: true` 后启用分帧模式；
+// 未声明的旧客户端保持旧版模式: 二进制输入直接写入 PTY，PTY 输出以原始二进制发送。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::i18n::Msg;
+use crate::outbound::{OutboundClosed, Priority};
+use crate::router::RouterError;
+use crate::server::WsSender;
+
+/// 帧头长度 (通道 1 字节 + 流 ID 4 字节)
+pub const FRAME_HEADER_LEN: usize = 5;
+
+// ============================================================================
+// 通道 ID
+// ============================================================================
+
+// 通道 ID 由接收二进制帧的模块通过 `ModuleHandler::binary_channel` 声明，0 保留
+
+/// PTY 模块通道
+pub const PTY_CHANNEL: u8 = 1;
+
+// 2 (语音)、3 (LLM)、4 (工具) 保留，对应模块接入二进制输入 (如音频流) 时再声明
+
+/// 旧版 (未分帧) 二进制数据对应的通道
+pub const LEGACY_CHANNEL: u8 = PTY_CHANNEL;
+
+// ============================================================================
+// 编解码
+// ============================================================================
+
+/// 解码后的二进制帧
+#[derive(Debug, PartialEq, Eq)]
+pub struct BinaryFrame<'a> {
+    /// 通道 ID (对应接收的模块)
+    pub channel: u8,
+    /// 流 ID (PTY 为 session_id)
+    pub stream_id: u32,
+    /// 负载数据
+    pub payload: &'a [u8],
+}
+
+/// 编码二进制帧
+#[cfg_attr(not(feature = "pty"), allow(dead_code))]
+pub fn encode(channel: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+    frame.push(channel);
+    frame.extend_from_slice(&stream_id.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// 解码二进制帧
+pub fn decode(data: &[u8]) -> Result<BinaryFrame<'_>, RouterError> {
+    if data.len() < FRAME_HEADER_LEN {
+        return Err(RouterError::InvalidMessage(Msg::FrameTooShort { len: data.len() }));
+    }
+
+    let stream_id = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+
+    Ok(BinaryFrame {
+        channel: data[0],
+        stream_id,
+        payload: &data[FRAME_HEADER_LEN..],
+    })
+}
+
+// ============================================================================
+// 二进制发送器
+// ============================================================================
+
+/// 二进制发送器
+///
+/// 按连接当前的二进制模式发送模块数据: 分帧模式下加帧头，旧版模式下只发送旧版通道 (PTY) 的原始数据
+#[cfg_attr(not(feature = "pty"), allow(dead_code))]
+#[derive(Clone)]
+pub struct BinarySender {
+    ws_sender: WsSender,
+    framed: Arc<AtomicBool>,
+}
+
+#[cfg_attr(not(feature = "pty"), allow(dead_code))]
+impl BinarySender {
+    /// 创建二进制发送器
+    pub fn new(ws_sender: WsSender, framed: Arc<AtomicBool>) -> Self {
+        Self { ws_sender, framed }
+    }
+
+    /// 发送二进制数据
+    pub async fn send(
+        &self,
+        channel: u8,
+        stream_id: u32,
+        payload: &[u8],
+    ) -> Result<(), OutboundClosed> {
+        let data = if self.framed.load(Ordering::Relaxed) {
+            encode(channel, stream_id, payload)
+        } else if channel == LEGACY_CHANNEL {
+            // 旧版客户端只识别不带帧头的 PTY 输出
+            payload.to_vec()
+        } else {
+            return Ok(());
+        };
+
+        // 批量优先级: 队列满时等待，PTY 读取随之暂停
+        self.ws_sender.send(Priority::Bulk, Message::Binary(data.into())).await
+    }
+
+    /// 发送文本消息
+    ///
+    /// 与二进制数据同为批量优先级，保证排在之前发送的数据之后 (如会话输出之后的退出通知)
+    pub async fn send_text(&self, text: String) -> Result<(), OutboundClosed> {
+        self.ws_sender.send_text(Priority::Bulk, text).await
+    }
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let frame = encode(PTY_CHANNEL, 0x0102_0304, b"ls\r");
+        assert_eq!(&frame[..FRAME_HEADER_LEN], &[1, 1, 2, 3, 4]);
+
+        let decoded = decode(&frame).unwrap();
+        assert_eq!(decoded.channel, PTY_CHANNEL);
+        assert_eq!(decoded.stream_id, 0x0102_0304);
+        assert_eq!(decoded.payload, b"ls\r");
+    }
+
+    #[test]
+    fn test_decode_invalid_frames() {
+        assert!(matches!(decode(&[1, 0, 0]), Err(RouterError::InvalidMessage(_))));
+
+        // 空负载是合法的
+        let decoded = decode(&[2, 0, 0, 0, 7]).unwrap();
+        assert_eq!(decoded.channel, 2);
+        assert!(decoded.payload.is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+// 英文消息目录 (对应插件的 en.ts)
+
+use super::Msg;
+
+pub(super) fn render(msg: &Msg) -> String {
+    match msg {
+        Msg::Text(text) => text.clone(),
+
+        // 协议
+        Msg::UnknownModule { module } => format!("Unknown module: {}", module),
+        Msg::ModuleDisabled { module } => format!("Module disabled: {}", module),
+        Msg::JsonError { error } => format!("JSON error: {}", error),
+        Msg::ParseFailed { reason } => format!("Failed to parse message: {}", render(reason)),
+        Msg::FrameTooShort { len } => format!("Binary frame too short: {} bytes", len),
+        Msg::BinaryNotSupported { module } => format!("Module does not accept binary data: {}", module),
+        Msg::UnknownMessageType { module, msg_type } => {
+            format!("Unknown {} message type: {}", module, msg_type)
+        }
+        Msg::InvalidField { field, error } => format!("Invalid field {}: {}", field, error),
+        Msg::InvalidRequest { msg_type, error } => format!("Invalid {} request: {}", msg_type, error),
+        Msg::SerializeFailed { error } => format!("Failed to serialize message: {}", error),
+        Msg::SendFailed { error } => format!("Failed to send message: {}", error),
+        Msg::ConnectionNotReady => "Connection not ready".to_string(),
+
+        // 服务器关闭
+        Msg::ShutdownSignal { signal } => format!("Received {} signal", signal),
+        Msg::ShutdownRequested => "Shutdown requested".to_string(),
+        Msg::ShutdownParentExited { pid } => format!("Parent process {} exited", pid),
+        Msg::ShutdownIdleTimeout => "Idle timeout".to_string(),
+        Msg::ShutdownStdinClosed => "Standard input closed".to_string(),
+
+        // PTY
+        Msg::SessionTokenInvalid => "Invalid session token or session has ended".to_string(),
+        Msg::SessionNotInitialized => "PTY session not initialized".to_string(),
+        Msg::SessionNotFound { session_id } => format!("PTY session not found: {}", session_id),
+        Msg::PtySpawnFailed { error } => format!("Failed to create PTY session: {}", error),
+        Msg::PtyWriteFailed { error } => format!("Failed to write to PTY: {}", error),
+        Msg::PtyResizeFailed { error } => format!("Failed to resize terminal: {}", error),
+        Msg::PtyReplayFailed { error } => format!("Failed to replay PTY output: {}", error),
+
+        // 语音
+        Msg::AlreadyRecording => "Already recording".to_string(),
+        Msg::NotRecording => "Not recording".to_string(),
+        Msg::AsrConfigMissing => "ASR configuration not set".to_string(),
+        Msg::RecorderNotInitialized => "Recorder not initialized".to_string(),
+        Msg::CreateRecorderFailed { error } => format!("Failed to create recorder: {}", error),
+        Msg::StartRecordingFailed { error } => format!("Failed to start recording: {}", error),
+        Msg::StopRecordingFailed { error } => format!("Failed to stop recording: {}", error),
+        Msg::RealtimeFallbackFailed { realtime, fallback } => format!(
+            "Realtime transcription failed: {}; HTTP fallback also failed: {}",
+            render(realtime),
+            render(fallback)
+        ),
+        Msg::RealtimeTaskFallbackFailed { fallback } => format!(
+            "Realtime transcription task failed; HTTP fallback also failed: {}",
+            render(fallback)
+        ),
+        Msg::AsrAuthFailed { engine, error } => format!("Authentication failed ({}): {}", engine, error),
+        Msg::AsrQuotaExceeded { engine } => format!("Quota exceeded ({})", engine),
+        Msg::AsrInvalidAudio { error } => format!("Invalid audio: {}", error),
+        Msg::AsrTimeout { timeout_ms } => format!("Request timed out ({}ms)", timeout_ms),
+        Msg::AsrAllEnginesFailed { primary, fallback } => match fallback {
+            Some(fallback) => format!("All ASR engines failed: primary: {}; fallback: {}", primary, fallback),
+            None => format!("All ASR engines failed: primary: {}", primary),
+        },
+        Msg::AsrNotInitialized => "Engine not initialized".to_string(),
+        Msg::AsrUnsupported { detail } => format!("Unsupported operation: {}", detail),
+        Msg::AsrInternal { detail } => format!("Internal error: {}", detail),
+        Msg::MissingApiKey { key } => format!("Missing required API key: {}", key),
+        Msg::UnsupportedMode { provider, mode } => {
+            format!("Provider {} does not support {} mode", provider, mode)
+        }
+
+        // 上游服务
+        Msg::NetworkError { error } => format!("Network error: {}", error),
+        Msg::WebSocketError { error } => format!("WebSocket error: {}", error),
+        Msg::ResponseParseFailed { error } => format!("Failed to parse response: {}", error),
+        Msg::HttpError { status, body } => format!("HTTP {}: {}", status, body),
+        Msg::Cancelled => "Request cancelled".to_string(),
+        Msg::InvalidConfig { detail } => format!("Invalid configuration: {}", detail),
+    }
+}
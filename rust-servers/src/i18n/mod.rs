@@ -0,0 +1,239 @@
+// 本地化
+// 客户端在 hello 握手中声明语言，服务器按该语言从消息目录渲染错误和状态消息
+// 消息目录与插件的语言文件对应: en.rs ↔ en.ts，zh_cn.rs ↔ zh-CN.ts
+
+mod en;
+mod zh_cn;
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+// ============================================================================
+// 语言
+// ============================================================================
+
+/// 支持的语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum Locale {
+    /// 英语 (客户端未声明语言时使用)
+    #[default]
+    En,
+    /// 简体中文
+    ZhCn,
+}
+
+impl Locale {
+    /// 所有支持的语言
+    pub const ALL: &'static [Locale] = &[Locale::En, Locale::ZhCn];
+
+    /// 语言标签
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::ZhCn => "zh-CN",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Locale::ZhCn,
+            _ => Locale::En,
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    /// 按语言标签的主语言匹配 (`en-US` → `en`，`zh`、`zh-Hans`、`zh_CN` → `zh-CN`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let primary = s.split(['-', '_']).next().unwrap_or_default();
+        match primary.to_ascii_lowercase().as_str() {
+            "en" => Ok(Locale::En),
+            "zh" => Ok(Locale::ZhCn),
+            _ => Err(format!("不支持的语言: {}", s)),
+        }
+    }
+}
+
+impl serde::Serialize for Locale {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl schemars::JsonSchema for Locale {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Locale".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        let tags: Vec<&str> = Locale::ALL.iter().map(|locale| locale.as_str()).collect();
+        schemars::json_schema!({
+            "description": "语言标签",
+            "type": "string",
+            "enum": tags,
+        })
+    }
+}
+
+/// 连接的当前语言 (路由器与各模块共享，hello 握手时更新)
+#[derive(Debug, Clone, Default)]
+pub struct SharedLocale(Arc<AtomicU8>);
+
+impl SharedLocale {
+    /// 当前语言
+    pub fn get(&self) -> Locale {
+        Locale::from_u8(self.0.load(Ordering::Relaxed))
+    }
+
+    /// 切换语言
+    pub fn set(&self, locale: Locale) {
+        self.0.store(locale as u8, Ordering::Relaxed);
+    }
+}
+
+// ============================================================================
+// 消息目录
+// ============================================================================
+
+/// 消息目录中的条目
+///
+/// 字段为插入消息的参数；来自上游服务或系统调用的错误描述按原文插入，不做翻译
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    not(all(feature = "pty", feature = "voice", feature = "llm", feature = "utils")),
+    allow(dead_code)
+)]
+pub enum Msg {
+    /// 未经翻译的原文 (第三方模块的错误描述)
+    Text(String),
+
+    // 协议
+    UnknownModule { module: String },
+    ModuleDisabled { module: String },
+    JsonError { error: String },
+    ParseFailed { reason: Box<Msg> },
+    FrameTooShort { len: usize },
+    BinaryNotSupported { module: String },
+    UnknownMessageType { module: String, msg_type: String },
+    InvalidField { field: &'static str, error: String },
+    InvalidRequest { msg_type: &'static str, error: String },
+    SerializeFailed { error: String },
+    SendFailed { error: String },
+    ConnectionNotReady,
+
+    // 服务器关闭
+    ShutdownSignal { signal: &'static str },
+    ShutdownRequested,
+    ShutdownParentExited { pid: u32 },
+    ShutdownIdleTimeout,
+    ShutdownStdinClosed,
+
+    // PTY
+    SessionTokenInvalid,
+    SessionNotInitialized,
+    SessionNotFound { session_id: u32 },
+    PtySpawnFailed { error: String },
+    PtyWriteFailed { error: String },
+    PtyResizeFailed { error: String },
+    PtyReplayFailed { error: String },
+
+    // 语音
+    AlreadyRecording,
+    NotRecording,
+    AsrConfigMissing,
+    RecorderNotInitialized,
+    CreateRecorderFailed { error: String },
+    StartRecordingFailed { error: String },
+    StopRecordingFailed { error: String },
+    RealtimeFallbackFailed { realtime: Box<Msg>, fallback: Box<Msg> },
+    RealtimeTaskFallbackFailed { fallback: Box<Msg> },
+    AsrAuthFailed { engine: String, error: String },
+    AsrQuotaExceeded { engine: String },
+    AsrInvalidAudio { error: String },
+    AsrTimeout { timeout_ms: u64 },
+    AsrAllEnginesFailed { primary: String, fallback: Option<String> },
+    AsrNotInitialized,
+    AsrUnsupported { detail: String },
+    AsrInternal { detail: String },
+    MissingApiKey { key: String },
+    UnsupportedMode { provider: String, mode: String },
+
+    // 上游服务
+    NetworkError { error: String },
+    WebSocketError { error: String },
+    ResponseParseFailed { error: String },
+    HttpError { status: u16, body: String },
+    Cancelled,
+    InvalidConfig { detail: String },
+}
+
+impl Msg {
+    /// 按指定语言渲染消息
+    pub fn render(&self, locale: Locale) -> String {
+        match locale {
+            Locale::En => en::render(self),
+            Locale::ZhCn => zh_cn::render(self),
+        }
+    }
+}
+
+/// 默认语言渲染 (用于日志)
+impl fmt::Display for Msg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(Locale::default()))
+    }
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_from_str() {
+        assert_eq!("en".parse::<Locale>(), Ok(Locale::En));
+        assert_eq!("en-US".parse::<Locale>(), Ok(Locale::En));
+        assert_eq!("zh-CN".parse::<Locale>(), Ok(Locale::ZhCn));
+        assert_eq!("zh_Hans".parse::<Locale>(), Ok(Locale::ZhCn));
+        assert_eq!("ZH".parse::<Locale>(), Ok(Locale::ZhCn));
+        assert!("fr".parse::<Locale>().is_err());
+    }
+
+    #[test]
+    fn test_shared_locale() {
+        let locale = SharedLocale::default();
+        let other = locale.clone();
+        assert_eq!(other.get(), Locale::En);
+        locale.set(Locale::ZhCn);
+        assert_eq!(other.get(), Locale::ZhCn);
+    }
+
+    #[test]
+    fn test_render() {
+        let msg = Msg::SessionNotInitialized;
+        assert_eq!(msg.render(Locale::En), "PTY session not initialized");
+        assert_eq!(msg.render(Locale::ZhCn), "PTY 会话未初始化");
+
+        let msg = Msg::ParseFailed { reason: Box::new(Msg::UnknownModule { module: "x".into() }) };
+        assert_eq!(msg.render(Locale::En), "Failed to parse message: Unknown module: x");
+        assert_eq!(msg.render(Locale::ZhCn), "消息解析失败: 未知模块: x");
+
+        // 原文不翻译
+        let msg = Msg::Text("custom".into());
+        assert_eq!(msg.render(Locale::ZhCn), "custom");
+    }
+}
@@ -0,0 +1,79 @@
+// 简体中文消息目录 (对应插件的 zh-CN.ts)
+
+use super::Msg;
+
+pub(super) fn render(msg: &Msg) -> String {
+    match msg {
+        Msg::Text(text) => text.clone(),
+
+        // 协议
+        Msg::UnknownModule { module } => format!("未知模块: {}", module),
+        Msg::ModuleDisabled { module } => format!("模块已禁用: {}", module),
+        Msg::JsonError { error } => format!("JSON 错误: {}", error),
+        Msg::ParseFailed { reason } => format!("消息解析失败: {}", render(reason)),
+        Msg::FrameTooShort { len } => format!("二进制帧过短: {} 字节", len),
+        Msg::BinaryNotSupported { module } => format!("模块不支持二进制数据: {}", module),
+        Msg::UnknownMessageType { module, msg_type } => {
+            format!("未知的 {} 消息类型: {}", module, msg_type)
+        }
+        Msg::InvalidField { field, error } => format!("无效的 {} 字段: {}", field, error),
+        Msg::InvalidRequest { msg_type, error } => format!("无效的 {} 请求: {}", msg_type, error),
+        Msg::SerializeFailed { error } => format!("JSON 序列化失败: {}", error),
+        Msg::SendFailed { error } => format!("发送消息失败: {}", error),
+        Msg::ConnectionNotReady => "连接尚未就绪".to_string(),
+
+        // 服务器关闭
+        Msg::ShutdownSignal { signal } => format!("收到 {} 信号", signal),
+        Msg::ShutdownRequested => "请求关闭".to_string(),
+        Msg::ShutdownParentExited { pid } => format!("父进程 {} 已退出", pid),
+        Msg::ShutdownIdleTimeout => "空闲超时".to_string(),
+        Msg::ShutdownStdinClosed => "标准输入已关闭".to_string(),
+
+        // PTY
+        Msg::SessionTokenInvalid => "无效的会话令牌或会话已结束".to_string(),
+        Msg::SessionNotInitialized => "PTY 会话未初始化".to_string(),
+        Msg::SessionNotFound { session_id } => format!("PTY 会话不存在: {}", session_id),
+        Msg::PtySpawnFailed { error } => format!("创建 PTY 会话失败: {}", error),
+        Msg::PtyWriteFailed { error } => format!("写入 PTY 失败: {}", error),
+        Msg::PtyResizeFailed { error } => format!("调整终端尺寸失败: {}", error),
+        Msg::PtyReplayFailed { error } => format!("回放 PTY 输出失败: {}", error),
+
+        // 语音
+        Msg::AlreadyRecording => "已在录音中".to_string(),
+        Msg::NotRecording => "未在录音中".to_string(),
+        Msg::AsrConfigMissing => "ASR 配置未设置".to_string(),
+        Msg::RecorderNotInitialized => "录音器未初始化".to_string(),
+        Msg::CreateRecorderFailed { error } => format!("创建录音器失败: {}", error),
+        Msg::StartRecordingFailed { error } => format!("启动录音失败: {}", error),
+        Msg::StopRecordingFailed { error } => format!("停止录音失败: {}", error),
+        Msg::RealtimeFallbackFailed { realtime, fallback } => format!(
+            "实时转录失败: {}; HTTP 回退也失败: {}",
+            render(realtime),
+            render(fallback)
+        ),
+        Msg::RealtimeTaskFallbackFailed { fallback } => {
+            format!("实时转录任务异常; HTTP 回退也失败: {}", render(fallback))
+        }
+        Msg::AsrAuthFailed { engine, error } => format!("认证失败 ({}): {}", engine, error),
+        Msg::AsrQuotaExceeded { engine } => format!("配额超限 ({})", engine),
+        Msg::AsrInvalidAudio { error } => format!("无效的音频格式: {}", error),
+        Msg::AsrTimeout { timeout_ms } => format!("请求超时 ({}ms)", timeout_ms),
+        Msg::AsrAllEnginesFailed { primary, fallback } => match fallback {
+            Some(fallback) => format!("所有 ASR 引擎失败: 主引擎: {}; 备用引擎: {}", primary, fallback),
+            None => format!("所有 ASR 引擎失败: 主引擎: {}", primary),
+        },
+        Msg::AsrNotInitialized => "引擎未初始化".to_string(),
+        Msg::AsrUnsupported { detail } => format!("不支持的操作: {}", detail),
+        Msg::AsrInternal { detail } => format!("内部错误: {}", detail),
+        Msg::MissingApiKey { key } => format!("缺少必需的 API Key: {}", key),
+        Msg::UnsupportedMode { provider, mode } => format!("供应商 {} 不支持 {} 模式", provider, mode),
+
+        // 上游服务
+        Msg::NetworkError { error } => format!("网络错误: {}", error),
+        Msg::WebSocketError { error } => format!("WebSocket 错误: {}", error),
+        Msg::ResponseParseFailed { error } => format!("解析响应失败: {}", error),
+        Msg::HttpError { status, body } => format!("HTTP {}: {}", status, body),
+        Msg::Cancelled => "请求已取消".to_string(),
+        Msg::InvalidConfig { detail } => format!("无效的配置: {}", detail),
+    }
+}
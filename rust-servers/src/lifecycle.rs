@@ -0,0 +1,385 @@
+// 服务器生命周期
+// 统一的关闭信号、退出信号监听、连接计数，以及父进程监控和空闲超时
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures_util::future::{self, BoxFuture, FutureExt};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+use crate::i18n::Msg;
+
+/// 父进程检测间隔
+const PARENT_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 空闲检测的最大间隔
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// ============================================================================
+// 关闭信号
+// ============================================================================
+
+/// 关闭原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownReason {
+    /// 收到退出信号 (SIGINT / SIGTERM / SIGHUP / Ctrl+C 等)
+    Signal(&'static str),
+    /// 程序内部请求关闭
+    Requested,
+    /// 父进程已退出
+    ParentExited(u32),
+    /// 空闲超时
+    IdleTimeout,
+    /// stdio 传输的标准输入已关闭
+    StdinClosed,
+}
+
+impl std::fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShutdownReason::Signal(name) => write!(f, "收到 {} 信号", name),
+            ShutdownReason::Requested => write!(f, "请求关闭"),
+            ShutdownReason::ParentExited(pid) => write!(f, "父进程 {} 已退出", pid),
+            ShutdownReason::IdleTimeout => write!(f, "空闲超时"),
+            ShutdownReason::StdinClosed => write!(f, "标准输入已关闭"),
+        }
+    }
+}
+
+impl ShutdownReason {
+    /// 发给客户端的关闭说明 (按连接语言渲染)
+    pub fn message(&self) -> Msg {
+        match *self {
+            ShutdownReason::Signal(signal) => Msg::ShutdownSignal { signal },
+            ShutdownReason::Requested => Msg::ShutdownRequested,
+            ShutdownReason::ParentExited(pid) => Msg::ShutdownParentExited { pid },
+            ShutdownReason::IdleTimeout => Msg::ShutdownIdleTimeout,
+            ShutdownReason::StdinClosed => Msg::ShutdownStdinClosed,
+        }
+    }
+
+    /// 机器可读的原因代码 (用于 `server_shutting_down` 广播)
+    pub fn code(&self) -> &'static str {
+        match self {
+            ShutdownReason::Signal(_) => "signal",
+            ShutdownReason::Requested => "requested",
+            ShutdownReason::ParentExited(_) => "parent_exited",
+            ShutdownReason::IdleTimeout => "idle_timeout",
+            ShutdownReason::StdinClosed => "stdin_closed",
+        }
+    }
+}
+
+/// 关闭信号
+///
+/// 所有关闭途径 (信号、父进程退出、空闲超时) 都通过它触发同一套优雅关闭流程
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    reason: Arc<Mutex<Option<ShutdownReason>>>,
+}
+
+impl Shutdown {
+    /// 创建关闭信号
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 触发关闭 (只记录第一次触发的原因)
+    pub fn trigger(&self, reason: ShutdownReason) {
+        {
+            let mut current = self.reason.lock().unwrap();
+            if current.is_some() {
+                return;
+            }
+            *current = Some(reason);
+        }
+        log_info!("触发服务器关闭: {}", reason);
+        self.token.cancel();
+    }
+
+    /// 获取关闭原因 (尚未触发时为 None)
+    pub fn reason(&self) -> Option<ShutdownReason> {
+        *self.reason.lock().unwrap()
+    }
+
+    /// 等待关闭被触发
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await;
+    }
+
+    /// 等待关闭被触发并返回原因
+    pub async fn wait(&self) -> ShutdownReason {
+        self.token.cancelled().await;
+        self.reason().unwrap_or(ShutdownReason::Requested)
+    }
+}
+
+// ============================================================================
+// 退出信号
+// ============================================================================
+
+/// 等待进程退出信号，返回信号名称
+///
+/// Unix 上监听 SIGINT / SIGTERM / SIGHUP (插件通过 SIGTERM 停止服务器)
+#[cfg(unix)]
+pub async fn wait_for_signal() -> std::io::Result<&'static str> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    Ok(first_signal(vec![
+        ("SIGINT", interrupt.recv().boxed()),
+        ("SIGTERM", terminate.recv().boxed()),
+        ("SIGHUP", hangup.recv().boxed()),
+    ]).await)
+}
+
+/// 等待进程退出信号，返回信号名称
+///
+/// Windows 上监听 Ctrl+C / Ctrl+Break / 关闭控制台
+#[cfg(windows)]
+pub async fn wait_for_signal() -> std::io::Result<&'static str> {
+    use tokio::signal::windows::{ctrl_break, ctrl_c, ctrl_close};
+
+    let mut interrupt = ctrl_c()?;
+    let mut brk = ctrl_break()?;
+    let mut close = ctrl_close()?;
+
+    Ok(first_signal(vec![
+        ("CTRL_C", interrupt.recv().boxed()),
+        ("CTRL_BREAK", brk.recv().boxed()),
+        ("CTRL_CLOSE", close.recv().boxed()),
+    ]).await)
+}
+
+/// 等待任一信号到达，返回其名称
+///
+/// 与信号注册分开，测试时可注入模拟的信号源，无需向测试进程发送真实信号
+async fn first_signal(signals: Vec<(&'static str, BoxFuture<'_, Option<()>>)>) -> &'static str {
+    let waits = signals.into_iter().map(|(name, recv)| recv.map(move |_| name));
+    future::select_all(waits).await.0
+}
+
+// ============================================================================
+// 连接计数
+// ============================================================================
+
+/// 活动连接计数
+pub struct ConnectionTracker {
+    /// 活动连接数
+    active: AtomicUsize,
+    /// 最后一次连接数变化的时间
+    last_change: Mutex<Instant>,
+    /// 连接关闭通知
+    closed: Notify,
+}
+
+impl ConnectionTracker {
+    /// 创建连接计数
+    pub fn new() -> Self {
+        Self {
+            active: AtomicUsize::new(0),
+            last_change: Mutex::new(Instant::now()),
+            closed: Notify::new(),
+        }
+    }
+
+    /// 登记新连接，返回的守卫释放时自动注销
+    pub fn track(self: &Arc<Self>) -> ConnectionGuard {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        *self.last_change.lock().unwrap() = Instant::now();
+        ConnectionGuard {
+            tracker: Arc::clone(self),
+        }
+    }
+
+    /// 活动连接数
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// 没有活动连接的持续时间 (有活动连接时为 None)
+    pub fn idle_for(&self) -> Option<Duration> {
+        if self.active() > 0 {
+            return None;
+        }
+        Some(self.last_change.lock().unwrap().elapsed())
+    }
+
+    /// 等待所有连接关闭
+    pub async fn wait_idle(&self) {
+        loop {
+            // 先注册通知再检查，避免错过检查与等待之间的关闭
+            let notified = self.closed.notified();
+            if self.active() == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Default for ConnectionTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 连接守卫
+pub struct ConnectionGuard {
+    tracker: Arc<ConnectionTracker>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.tracker.active.fetch_sub(1, Ordering::SeqCst);
+        *self.tracker.last_change.lock().unwrap() = Instant::now();
+        self.tracker.closed.notify_waiters();
+    }
+}
+
+// ============================================================================
+// 监控任务
+// ============================================================================
+
+/// 启动父进程监控
+///
+/// 父进程 (如 Obsidian) 崩溃后服务器不会收到信号，定期检查其是否存活，退出后触发关闭
+pub fn spawn_parent_watchdog(pid: u32, shutdown: Shutdown) {
+    log_info!("监控父进程: pid={}", pid);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PARENT_CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => return,
+            }
+
+            if !process_alive(pid) {
+                shutdown.trigger(ShutdownReason::ParentExited(pid));
+                return;
+            }
+        }
+    });
+}
+
+/// 启动空闲超时监控
+///
+/// 没有任何连接的时间超过 `timeout` 后触发关闭
+pub fn spawn_idle_watchdog(timeout: Duration, connections: Arc<ConnectionTracker>, shutdown: Shutdown) {
+    log_info!("空闲超时: {} 秒", timeout.as_secs());
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(timeout.min(IDLE_CHECK_INTERVAL));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => return,
+            }
+
+            if connections.idle_for().is_some_and(|idle| idle >= timeout) {
+                shutdown.trigger(ShutdownReason::IdleTimeout);
+                return;
+            }
+        }
+    });
+}
+
+/// 检查进程是否存活
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    // 信号 0 只做存在性和权限检查；EPERM 表示进程存在但属于其他用户
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// 检查进程是否存活
+#[cfg(windows)]
+fn process_alive(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, ERROR_ACCESS_DENIED, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            // 无权限打开说明进程仍存在
+            return GetLastError() == ERROR_ACCESS_DENIED;
+        }
+
+        let mut exit_code: u32 = 0;
+        let ok = GetExitCodeProcess(handle, &mut exit_code);
+        CloseHandle(handle);
+        ok != 0 && exit_code == STILL_ACTIVE as u32
+    }
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shutdown_keeps_first_reason() {
+        let shutdown = Shutdown::new();
+        assert_eq!(shutdown.reason(), None);
+
+        shutdown.trigger(ShutdownReason::IdleTimeout);
+        shutdown.trigger(ShutdownReason::Signal("SIGTERM"));
+        assert_eq!(shutdown.reason(), Some(ShutdownReason::IdleTimeout));
+    }
+
+    #[tokio::test]
+    async fn test_first_signal() {
+        // 注入模拟的信号源，不向测试进程发送真实信号
+        let (_interrupt_tx, mut interrupt) = tokio::sync::mpsc::channel::<()>(1);
+        let (hangup_tx, mut hangup) = tokio::sync::mpsc::channel(1);
+        hangup_tx.send(()).await.unwrap();
+
+        let name = first_signal(vec![
+            ("SIGINT", interrupt.recv().boxed()),
+            ("SIGHUP", hangup.recv().boxed()),
+        ]).await;
+        assert_eq!(name, "SIGHUP");
+    }
+
+    #[tokio::test]
+    async fn test_connection_tracker() {
+        let tracker = Arc::new(ConnectionTracker::new());
+        assert!(tracker.idle_for().is_some());
+
+        let guard = tracker.track();
+        assert_eq!(tracker.active(), 1);
+        assert!(tracker.idle_for().is_none());
+
+        let waiter = {
+            let tracker = Arc::clone(&tracker);
+            tokio::spawn(async move { tracker.wait_idle().await })
+        };
+        drop(guard);
+        waiter.await.unwrap();
+        assert_eq!(tracker.active(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_idle_watchdog_triggers_shutdown() {
+        let tracker = Arc::new(ConnectionTracker::new());
+        let shutdown = Shutdown::new();
+
+        spawn_idle_watchdog(Duration::from_millis(100), Arc::clone(&tracker), shutdown.clone());
+        assert_eq!(shutdown.wait().await, ShutdownReason::IdleTimeout);
+    }
+
+    #[test]
+    fn test_process_alive() {
+        assert!(process_alive(std::process::id()));
+    }
+}
@@ -7,46 +7,30 @@ pub mod response;
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex as TokioMutex;
 use tokio_util::sync::CancellationToken;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::router::{ModuleHandler, ModuleMessage, ModuleType, RouterError, ServerResponse};
+use crate::error::{parse_retry_after, ErrorCode, ErrorPayload, ServerError};
+use crate::i18n::{Locale, Msg, SharedLocale};
+use crate::metrics::Metrics;
+use crate::protocol::{ModuleProtocol, Payload};
+use crate::router::{ModuleCapabilities, ModuleContext, ModuleHandler, ModuleMessage, ModuleType, RequestId, RouterError, ServerResponse};
+use crate::outbound::Priority;
 use crate::server::WsSender;
 
-use futures_util::SinkExt;
-
 use self::sse_parser::{SSEParser, SSEEvent};
 use self::thinking::StreamingThinkingFilter;
 use self::response::{ApiFormat, ResponseParser};
 
-/// 日志宏
-macro_rules! log_info {
-    ($($arg:tt)*) => {
-        eprintln!("[INFO] [LLM] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_error {
-    ($($arg:tt)*) => {
-        eprintln!("[ERROR] [LLM] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        if cfg!(debug_assertions) {
-            eprintln!("[DEBUG] [LLM] {}", format!($($arg)*));
-        }
-    };
-}
-
 // ============================================================================
 // 配置和消息类型
 // ============================================================================
 
-/// LLM 流式请求配置
-#[derive(Debug, Clone, Deserialize)]
+/// LLM 流式请求配置 (`stream_start` 请求)
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct StreamConfig {
     /// API 端点
     pub endpoint: String,
@@ -63,6 +47,57 @@ pub struct StreamConfig {
     pub request_id: Option<String>,
 }
 
+impl Payload for StreamConfig {
+    const TYPE: &'static str = "stream_start";
+}
+
+/// stream_cancel 请求: 取消进行中的流
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct StreamCancelRequest {}
+
+impl Payload for StreamCancelRequest {
+    const TYPE: &'static str = "stream_cancel";
+}
+
+/// LLM 连接配置（来自命令行 / 配置文件）
+#[derive(Debug, Clone)]
+pub struct LlmConfig {
+    /// 建立连接的超时时间
+    pub connect_timeout: Duration,
+    /// 两次读取之间的最长等待时间（None 表示不限制）
+    pub read_timeout: Option<Duration>,
+    /// HTTP/HTTPS 代理地址
+    pub proxy: Option<String>,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: None,
+            proxy: None,
+        }
+    }
+}
+
+impl LlmConfig {
+    /// 按配置构建 HTTP 客户端
+    pub fn build_client(&self) -> Result<reqwest::Client, LLMError> {
+        let mut builder = reqwest::Client::builder().connect_timeout(self.connect_timeout);
+        if let Some(read_timeout) = self.read_timeout {
+            builder = builder.read_timeout(read_timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| LLMError::InvalidConfig(format!("代理地址无效: {}", e)))?;
+            builder = builder.proxy(proxy);
+        }
+        builder
+            .build()
+            .map_err(|e| LLMError::InvalidConfig(e.to_string()))
+    }
+}
+
 /// LLM 模块错误
 #[derive(Debug, thiserror::Error)]
 pub enum LLMError {
@@ -79,56 +114,146 @@ pub enum LLMError {
     InvalidConfig(String),
     
     #[error("HTTP error: {status} - {message}")]
-    HttpError { status: u16, message: String },
+    HttpError {
+        status: u16,
+        message: String,
+        /// 响应头 `Retry-After` 指定的等待时间
+        retry_after: Option<Duration>,
+    },
+}
+
+impl From<LLMError> for ServerError {
+    fn from(error: LLMError) -> Self {
+        match error {
+            LLMError::NetworkError(error) => ServerError::new(ErrorCode::NetworkError, Msg::NetworkError { error }),
+            LLMError::ParseError(error) => ServerError::new(ErrorCode::ParseError, Msg::ResponseParseFailed { error }),
+            LLMError::Cancelled => ServerError::new(ErrorCode::Cancelled, Msg::Cancelled),
+            LLMError::InvalidConfig(detail) => ServerError::new(ErrorCode::InvalidConfig, Msg::InvalidConfig { detail }),
+            LLMError::HttpError { status, message, retry_after } => {
+                ServerError::http(status, Msg::HttpError { status, body: message }).with_retry_after(retry_after)
+            }
+        }
+    }
+}
+
+/// 流式消息的关联 ID
+///
+/// `request_id` 来自流配置，`id` 来自 `stream_start` 消息顶层，两者都原样回传
+#[derive(Debug, Clone, Default)]
+struct StreamIds {
+    request_id: Option<String>,
+    id: Option<RequestId>,
+}
+
+/// 首 token 延迟的记录 (每个流只记录一次)
+struct FirstToken<'a> {
+    /// 请求发出的时间，记录后置为 None
+    started: Option<Instant>,
+    metrics: &'a Metrics,
+}
+
+impl FirstToken<'_> {
+    /// 收到内容或推理内容时调用
+    fn record(&mut self) {
+        if let Some(started) = self.started.take() {
+            self.metrics.record_llm_ttft(started.elapsed());
+        }
+    }
 }
 
 // ============================================================================
 // 响应消息类型
 // ============================================================================
 
+/// stream_start 的确认 (先于该流的数据块发送)
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct StreamStarted {}
+
+impl Payload for StreamStarted {
+    const TYPE: &'static str = "stream_started";
+}
+
+/// stream_cancel 的确认
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct StreamCancelled {}
+
+impl Payload for StreamCancelled {
+    const TYPE: &'static str = "stream_cancelled";
+}
+
 /// 流式数据块消息
-#[derive(Debug, Serialize)]
-struct StreamChunkMessage {
-    module: &'static str,
-    #[serde(rename = "type")]
-    msg_type: &'static str,
-    content: String,
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct StreamChunkMessage {
+    /// 正文增量
+    pub content: String,
+    /// 流配置中的请求 ID
     #[serde(skip_serializing_if = "Option::is_none")]
-    request_id: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl Payload for StreamChunkMessage {
+    const TYPE: &'static str = "stream_chunk";
 }
 
 /// 思考内容消息
-#[derive(Debug, Serialize)]
-struct StreamThinkingMessage {
-    module: &'static str,
-    #[serde(rename = "type")]
-    msg_type: &'static str,
-    content: String,
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct StreamThinkingMessage {
+    /// 思考内容增量
+    pub content: String,
+    /// 流配置中的请求 ID
     #[serde(skip_serializing_if = "Option::is_none")]
-    request_id: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl Payload for StreamThinkingMessage {
+    const TYPE: &'static str = "stream_thinking";
 }
 
 /// 流式完成消息
-#[derive(Debug, Serialize)]
-struct StreamCompleteMessage {
-    module: &'static str,
-    #[serde(rename = "type")]
-    msg_type: &'static str,
-    full_content: String,
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct StreamCompleteMessage {
+    /// 完整正文 (不含思考内容)
+    pub full_content: String,
+    /// 流配置中的请求 ID
     #[serde(skip_serializing_if = "Option::is_none")]
-    request_id: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl Payload for StreamCompleteMessage {
+    const TYPE: &'static str = "stream_complete";
 }
 
 /// 流式错误消息
-#[derive(Debug, Serialize)]
-struct StreamErrorMessage {
-    module: &'static str,
-    #[serde(rename = "type")]
-    msg_type: &'static str,
-    code: String,
-    message: String,
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct StreamErrorMessage {
+    /// 按连接语言渲染的错误 (`code`、`message`、`retryable`、`details`)
+    #[serde(flatten)]
+    pub error: ErrorPayload,
+    /// 流配置中的请求 ID
     #[serde(skip_serializing_if = "Option::is_none")]
-    request_id: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl Payload for StreamErrorMessage {
+    const TYPE: &'static str = "stream_error";
+}
+
+/// LLM 模块的请求和服务器消息
+pub fn protocol() -> ModuleProtocol {
+    ModuleProtocol::new()
+        .request::<StreamConfig>()
+        .request::<StreamCancelRequest>()
+        .event::<StreamStarted>()
+        .event::<StreamCancelled>()
+        .event::<StreamChunkMessage>()
+        .event::<StreamThinkingMessage>()
+        .event::<StreamCompleteMessage>()
+        .event::<StreamErrorMessage>()
+}
+
+/// 流式消息 (附加 `stream_start` 消息的 ID)
+fn stream_event<P: Payload + Serialize>(payload: &P, ids: &StreamIds) -> ServerResponse {
+    ServerResponse::from_payload(ModuleType::LLM, payload).with_id(ids.id.clone())
 }
 
 // ============================================================================
@@ -139,19 +264,34 @@ struct StreamErrorMessage {
 pub struct LLMHandler {
     /// WebSocket 发送器
     ws_sender: Arc<TokioMutex<Option<WsSender>>>,
+    /// 连接语言 (渲染流式错误消息)
+    locale: TokioMutex<SharedLocale>,
     /// 当前请求的取消令牌
     cancel_token: Arc<TokioMutex<Option<CancellationToken>>>,
     /// HTTP 客户端
     http_client: reqwest::Client,
+    /// 运行指标 (服务器范围共享)
+    metrics: Arc<Metrics>,
 }
 
 impl LLMHandler {
     /// 创建新的 LLM 处理器
     pub fn new() -> Self {
+        Self::with_config(&LlmConfig::default(), Arc::default())
+    }
+    
+    /// 使用指定的连接配置和运行指标创建 LLM 处理器
+    pub fn with_config(config: &LlmConfig, metrics: Arc<Metrics>) -> Self {
+        let http_client = config.build_client().unwrap_or_else(|e| {
+            log_warn!("LLM HTTP 客户端配置无效，使用默认配置: {}", e);
+            reqwest::Client::new()
+        });
         Self {
             ws_sender: Arc::new(TokioMutex::new(None)),
+            locale: TokioMutex::new(SharedLocale::default()),
             cancel_token: Arc::new(TokioMutex::new(None)),
-            http_client: reqwest::Client::new(),
+            http_client,
+            metrics,
         }
     }
     
@@ -161,8 +301,13 @@ impl LLMHandler {
         *ws = Some(sender);
     }
     
+    /// 设置连接语言
+    pub async fn set_locale(&self, locale: SharedLocale) {
+        *self.locale.lock().await = locale;
+    }
+    
     /// 开始流式请求
-    async fn start_stream(&self, config: StreamConfig) -> Result<(), LLMError> {
+    async fn start_stream(&self, config: StreamConfig, id: Option<RequestId>) -> Result<(), LLMError> {
         log_info!("开始流式请求: endpoint={}", config.endpoint);
         
         // 创建取消令牌
@@ -183,26 +328,32 @@ impl LLMHandler {
         let headers = config.headers.clone();
         let body = config.body.clone();
         let api_format = config.api_format;
-        let request_id = config.request_id.clone();
+        let ids = StreamIds { request_id: config.request_id.clone(), id };
         let http_client = self.http_client.clone();
+        let locale = self.locale.lock().await.clone();
+        let metrics = Arc::clone(&self.metrics);
         
         // 在后台任务中执行流式请求
         tokio::spawn(async move {
+            let _active = metrics.llm_streams.enter();
             let result = Self::execute_stream(
                 http_client,
                 endpoint,
                 headers,
                 body,
                 api_format,
-                request_id.clone(),
+                ids.clone(),
                 ws_sender.clone(),
                 cancel_token,
+                &metrics,
             ).await;
             
             if let Err(e) = result {
                 log_error!("流式请求失败: {}", e);
                 // 发送错误消息
-                let _ = Self::send_error(&ws_sender, &e, request_id.as_deref()).await;
+                let error = ServerError::from(e);
+                metrics.record_error(&ModuleType::LLM, error.code);
+                let _ = Self::send_error(&ws_sender, &error, &ids, locale.get()).await;
             }
         });
         
@@ -210,16 +361,20 @@ impl LLMHandler {
     }
     
     /// 执行流式请求
+    #[allow(clippy::too_many_arguments)]
     async fn execute_stream(
         client: reqwest::Client,
         endpoint: String,
         headers: HashMap<String, String>,
         body: String,
         api_format: ApiFormat,
-        request_id: Option<String>,
+        ids: StreamIds,
         ws_sender: WsSender,
         cancel_token: CancellationToken,
+        metrics: &Metrics,
     ) -> Result<(), LLMError> {
+        let started = Instant::now();
+        
         // 构建请求
         let mut request = client.post(&endpoint)
             .header("Content-Type", "application/json")
@@ -237,10 +392,15 @@ impl LLMHandler {
         // 检查响应状态
         let status = response.status();
         if !status.is_success() {
+            let retry_after = response.headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_retry_after);
             let error_text = response.text().await.unwrap_or_default();
             return Err(LLMError::HttpError {
                 status: status.as_u16(),
                 message: error_text,
+                retry_after,
             });
         }
         
@@ -248,9 +408,10 @@ impl LLMHandler {
         Self::process_stream(
             response,
             api_format,
-            request_id,
+            ids,
             ws_sender,
             cancel_token,
+            FirstToken { started: Some(started), metrics },
         ).await
     }
     
@@ -258,9 +419,10 @@ impl LLMHandler {
     async fn process_stream(
         response: reqwest::Response,
         api_format: ApiFormat,
-        request_id: Option<String>,
+        ids: StreamIds,
         ws_sender: WsSender,
         cancel_token: CancellationToken,
+        mut first_token: FirstToken<'_>,
     ) -> Result<(), LLMError> {
         use futures_util::StreamExt;
         
@@ -299,20 +461,24 @@ impl LLMHandler {
                                             full_content.push_str(&remaining);
                                         }
                                         if let Some(t) = thinking {
-                                            Self::send_thinking(&ws_sender, &t, request_id.as_deref()).await?;
+                                            Self::send_thinking(&ws_sender, &t, &ids).await?;
                                         }
                                         
                                         // 发送完成消息
-                                        Self::send_complete(&ws_sender, &full_content, request_id.as_deref()).await?;
+                                        Self::send_complete(&ws_sender, &full_content, &ids).await?;
                                         return Ok(());
                                     }
                                     SSEEvent::Data(data) => {
                                         // 解析响应数据
                                         match ResponseParser::parse(&data, api_format) {
                                             Ok(extracted) => {
+                                                if extracted.reasoning.is_some() || extracted.content.is_some() {
+                                                    first_token.record();
+                                                }
+                                                
                                                 // 处理推理内容
                                                 if let Some(reasoning) = extracted.reasoning {
-                                                    Self::send_thinking(&ws_sender, &reasoning, request_id.as_deref()).await?;
+                                                    Self::send_thinking(&ws_sender, &reasoning, &ids).await?;
                                                 }
                                                 
                                                 // 处理主要内容
@@ -322,13 +488,13 @@ impl LLMHandler {
                                                     
                                                     // 发送思考内容
                                                     if let Some(t) = thinking {
-                                                        Self::send_thinking(&ws_sender, &t, request_id.as_deref()).await?;
+                                                        Self::send_thinking(&ws_sender, &t, &ids).await?;
                                                     }
                                                     
                                                     // 发送过滤后的内容
                                                     if !filtered.is_empty() {
                                                         full_content.push_str(&filtered);
-                                                        Self::send_chunk(&ws_sender, &filtered, request_id.as_deref()).await?;
+                                                        Self::send_chunk(&ws_sender, &filtered, &ids).await?;
                                                     }
                                                 }
                                                 
@@ -342,11 +508,11 @@ impl LLMHandler {
                                                         full_content.push_str(&remaining);
                                                     }
                                                     if let Some(t) = thinking {
-                                                        Self::send_thinking(&ws_sender, &t, request_id.as_deref()).await?;
+                                                        Self::send_thinking(&ws_sender, &t, &ids).await?;
                                                     }
                                                     
                                                     // 发送完成消息
-                                                    Self::send_complete(&ws_sender, &full_content, request_id.as_deref()).await?;
+                                                    Self::send_complete(&ws_sender, &full_content, &ids).await?;
                                                     return Ok(());
                                                 }
                                             }
@@ -364,13 +530,14 @@ impl LLMHandler {
                                         // 某些 API 使用 event 字段，尝试解析 data
                                         if let Ok(extracted) = ResponseParser::parse(&data, api_format) {
                                             if let Some(content) = extracted.content {
+                                                first_token.record();
                                                 let (filtered, thinking) = thinking_filter.process_chunk(&content);
                                                 if let Some(t) = thinking {
-                                                    Self::send_thinking(&ws_sender, &t, request_id.as_deref()).await?;
+                                                    Self::send_thinking(&ws_sender, &t, &ids).await?;
                                                 }
                                                 if !filtered.is_empty() {
                                                     full_content.push_str(&filtered);
-                                                    Self::send_chunk(&ws_sender, &filtered, request_id.as_deref()).await?;
+                                                    Self::send_chunk(&ws_sender, &filtered, &ids).await?;
                                                 }
                                             }
                                         }
@@ -391,11 +558,11 @@ impl LLMHandler {
                                 full_content.push_str(&remaining);
                             }
                             if let Some(t) = thinking {
-                                Self::send_thinking(&ws_sender, &t, request_id.as_deref()).await?;
+                                Self::send_thinking(&ws_sender, &t, &ids).await?;
                             }
                             
                             // 发送完成消息
-                            Self::send_complete(&ws_sender, &full_content, request_id.as_deref()).await?;
+                            Self::send_complete(&ws_sender, &full_content, &ids).await?;
                             return Ok(());
                         }
                     }
@@ -404,89 +571,56 @@ impl LLMHandler {
         }
     }
     
-    /// 发送数据块消息
-    async fn send_chunk(ws_sender: &WsSender, content: &str, request_id: Option<&str>) -> Result<(), LLMError> {
-        let msg = StreamChunkMessage {
-            module: "llm",
-            msg_type: "stream_chunk",
-            content: content.to_string(),
-            request_id: request_id.map(|s| s.to_string()),
-        };
-        
-        let json = serde_json::to_string(&msg)
+    /// 发送流式消息
+    async fn send_event<P: Payload + Serialize>(ws_sender: &WsSender, payload: &P, ids: &StreamIds) -> Result<(), LLMError> {
+        let json = serde_json::to_string(&stream_event(payload, ids))
             .map_err(|e| LLMError::ParseError(e.to_string()))?;
         
-        let mut sender = ws_sender.lock().await;
-        sender.send(tokio_tungstenite::tungstenite::Message::Text(json.into())).await
+        ws_sender.send_text(Priority::Stream, json).await
             .map_err(|e| LLMError::NetworkError(e.to_string()))?;
         
         Ok(())
     }
     
+    /// 发送数据块消息
+    async fn send_chunk(ws_sender: &WsSender, content: &str, ids: &StreamIds) -> Result<(), LLMError> {
+        let msg = StreamChunkMessage {
+            content: content.to_string(),
+            request_id: ids.request_id.clone(),
+        };
+        Self::send_event(ws_sender, &msg, ids).await
+    }
+    
     /// 发送思考内容消息
-    async fn send_thinking(ws_sender: &WsSender, content: &str, request_id: Option<&str>) -> Result<(), LLMError> {
+    async fn send_thinking(ws_sender: &WsSender, content: &str, ids: &StreamIds) -> Result<(), LLMError> {
         let msg = StreamThinkingMessage {
-            module: "llm",
-            msg_type: "stream_thinking",
             content: content.to_string(),
-            request_id: request_id.map(|s| s.to_string()),
+            request_id: ids.request_id.clone(),
         };
-        
-        let json = serde_json::to_string(&msg)
-            .map_err(|e| LLMError::ParseError(e.to_string()))?;
-        
-        let mut sender = ws_sender.lock().await;
-        sender.send(tokio_tungstenite::tungstenite::Message::Text(json.into())).await
-            .map_err(|e| LLMError::NetworkError(e.to_string()))?;
-        
-        Ok(())
+        Self::send_event(ws_sender, &msg, ids).await
     }
     
     /// 发送完成消息
-    async fn send_complete(ws_sender: &WsSender, full_content: &str, request_id: Option<&str>) -> Result<(), LLMError> {
+    async fn send_complete(ws_sender: &WsSender, full_content: &str, ids: &StreamIds) -> Result<(), LLMError> {
         let msg = StreamCompleteMessage {
-            module: "llm",
-            msg_type: "stream_complete",
             full_content: full_content.to_string(),
-            request_id: request_id.map(|s| s.to_string()),
+            request_id: ids.request_id.clone(),
         };
-        
-        let json = serde_json::to_string(&msg)
-            .map_err(|e| LLMError::ParseError(e.to_string()))?;
-        
-        let mut sender = ws_sender.lock().await;
-        sender.send(tokio_tungstenite::tungstenite::Message::Text(json.into())).await
-            .map_err(|e| LLMError::NetworkError(e.to_string()))?;
-        
-        Ok(())
+        Self::send_event(ws_sender, &msg, ids).await
     }
     
     /// 发送错误消息
-    async fn send_error(ws_sender: &WsSender, error: &LLMError, request_id: Option<&str>) -> Result<(), LLMError> {
-        let (code, message) = match error {
-            LLMError::NetworkError(msg) => ("NETWORK_ERROR", msg.clone()),
-            LLMError::ParseError(msg) => ("PARSE_ERROR", msg.clone()),
-            LLMError::Cancelled => ("CANCELLED", "Request cancelled".to_string()),
-            LLMError::InvalidConfig(msg) => ("INVALID_CONFIG", msg.clone()),
-            LLMError::HttpError { status, message } => ("HTTP_ERROR", format!("{}: {}", status, message)),
-        };
-        
+    async fn send_error(
+        ws_sender: &WsSender,
+        error: &ServerError,
+        ids: &StreamIds,
+        locale: Locale,
+    ) -> Result<(), LLMError> {
         let msg = StreamErrorMessage {
-            module: "llm",
-            msg_type: "stream_error",
-            code: code.to_string(),
-            message,
-            request_id: request_id.map(|s| s.to_string()),
+            error: error.to_payload(locale),
+            request_id: ids.request_id.clone(),
         };
-        
-        let json = serde_json::to_string(&msg)
-            .map_err(|e| LLMError::ParseError(e.to_string()))?;
-        
-        let mut sender = ws_sender.lock().await;
-        sender.send(tokio_tungstenite::tungstenite::Message::Text(json.into())).await
-            .map_err(|e| LLMError::NetworkError(e.to_string()))?;
-        
-        Ok(())
+        Self::send_event(ws_sender, &msg, ids).await
     }
     
     /// 取消流式请求
@@ -500,12 +634,6 @@ impl LLMHandler {
         
         Ok(())
     }
-    
-    /// 清理资源
-    pub async fn cleanup(&self) {
-        // 取消任何正在进行的请求
-        let _ = self.cancel_stream().await;
-    }
 }
 
 impl Default for LLMHandler {
@@ -521,7 +649,14 @@ impl Default for LLMHandler {
 #[async_trait::async_trait]
 impl ModuleHandler for LLMHandler {
     fn module_type(&self) -> ModuleType {
-        ModuleType::Llm
+        ModuleType::LLM
+    }
+    
+    fn capabilities(&self) -> ModuleCapabilities {
+        ModuleCapabilities::new(&protocol().request_types(), serde_json::json!({
+            "api_formats": ApiFormat::ALL,
+            "thinking_filter": true,
+        }))
     }
     
     async fn handle(&self, msg: &ModuleMessage) -> Result<Option<ServerResponse>, RouterError> {
@@ -530,36 +665,41 @@ impl ModuleHandler for LLMHandler {
         match msg.msg_type.as_str() {
             "stream_start" => {
                 // 解析配置
-                let config: StreamConfig = serde_json::from_value(msg.payload.clone())
-                    .map_err(|e| RouterError::ModuleError(format!("Invalid stream config: {}", e)))?;
+                let config: StreamConfig = msg.parse()?;
                 
                 // 开始流式请求
-                self.start_stream(config).await
-                    .map_err(|e| RouterError::ModuleError(e.to_string()))?;
+                self.start_stream(config, msg.id.clone()).await
+                    .map_err(ServerError::from)?;
                 
                 // 返回确认消息
-                Ok(Some(ServerResponse::new(
-                    ModuleType::Llm,
-                    "stream_started",
-                    serde_json::json!({}),
-                )))
+                Ok(Some(ServerResponse::from_payload(ModuleType::LLM, &StreamStarted {})))
             }
             "stream_cancel" => {
                 // 取消流式请求
+                let StreamCancelRequest {} = msg.parse()?;
                 self.cancel_stream().await
-                    .map_err(|e| RouterError::ModuleError(e.to_string()))?;
+                    .map_err(ServerError::from)?;
                 
-                Ok(Some(ServerResponse::new(
-                    ModuleType::Llm,
-                    "stream_cancelled",
-                    serde_json::json!({}),
-                )))
+                Ok(Some(ServerResponse::from_payload(ModuleType::LLM, &StreamCancelled {})))
             }
             _ => {
-                Err(RouterError::ModuleError(format!("Unknown LLM message type: {}", msg.msg_type)))
+                Err(RouterError::InvalidMessage(Msg::UnknownMessageType {
+                    module: ModuleType::LLM.to_string(),
+                    msg_type: msg.msg_type.clone(),
+                }))
             }
         }
     }
+    
+    async fn on_connect(&self, context: &ModuleContext) {
+        self.set_ws_sender(context.ws_sender.clone()).await;
+        self.set_locale(context.locale.clone()).await;
+    }
+    
+    async fn cleanup(&self) {
+        // 取消任何正在进行的请求
+        let _ = self.cancel_stream().await;
+    }
 }
 
 // ============================================================================
@@ -602,9 +742,72 @@ mod tests {
         assert!(config.request_id.is_none());
     }
     
+    #[test]
+    fn test_stream_message_ids() {
+        let ids = StreamIds {
+            request_id: Some("req-123".to_string()),
+            id: Some(RequestId::Number(5)),
+        };
+        let msg = StreamChunkMessage {
+            content: "hi".to_string(),
+            request_id: ids.request_id.clone(),
+        };
+        let json = serde_json::to_value(stream_event(&msg, &ids)).unwrap();
+        assert_eq!(json["module"], "llm");
+        assert_eq!(json["type"], "stream_chunk");
+        assert_eq!(json["id"], 5);
+        assert_eq!(json["request_id"], "req-123");
+        
+        // 未设置的 ID 不序列化
+        let msg = StreamCompleteMessage {
+            full_content: String::new(),
+            request_id: None,
+        };
+        let json = serde_json::to_value(stream_event(&msg, &StreamIds::default())).unwrap();
+        assert!(json.get("id").is_none());
+        assert!(json.get("request_id").is_none());
+    }
+    
+    #[test]
+    fn test_llm_error_codes() {
+        let error = ServerError::from(LLMError::HttpError {
+            status: 401,
+            message: "invalid api key".to_string(),
+            retry_after: None,
+        });
+        assert_eq!(error.code, ErrorCode::AuthFailed);
+        assert!(!error.retryable);
+        assert_eq!(error.details.status, Some(401));
+        
+        let error = ServerError::from(LLMError::HttpError {
+            status: 429,
+            message: "rate limited".to_string(),
+            retry_after: Some(Duration::from_secs(3)),
+        });
+        assert_eq!(error.code, ErrorCode::RateLimited);
+        assert!(error.retryable);
+        assert_eq!(error.details.retry_after_ms, Some(3000));
+        
+        let error = ServerError::from(LLMError::NetworkError("reset".to_string()));
+        assert_eq!(error.code, ErrorCode::NetworkError);
+        assert!(error.retryable);
+        
+        // 流式错误消息中错误字段与 id 并列
+        let msg = StreamErrorMessage {
+            error: ServerError::from(LLMError::Cancelled).to_payload(Locale::ZhCn),
+            request_id: None,
+        };
+        let ids = StreamIds { request_id: None, id: Some(RequestId::Number(1)) };
+        let json = serde_json::to_value(stream_event(&msg, &ids)).unwrap();
+        assert_eq!(json["code"], "CANCELLED");
+        assert_eq!(json["message"], "请求已取消");
+        assert_eq!(json["retryable"], false);
+        assert_eq!(json["id"], 1);
+    }
+    
     #[test]
     fn test_llm_handler_creation() {
         let handler = LLMHandler::new();
-        assert_eq!(handler.module_type(), ModuleType::Llm);
+        assert_eq!(handler.module_type(), ModuleType::LLM);
     }
 }
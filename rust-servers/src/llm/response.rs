@@ -1,22 +1,23 @@
 // LLM API 响应解析
 // 支持 Chat Completions API 和 Responses API 两种格式
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// API 格式类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ApiFormat {
     /// OpenAI Chat Completions API 格式
+    #[default]
     ChatCompletions,
     /// OpenAI Responses API 格式（用于推理模型）
     Responses,
 }
 
-impl Default for ApiFormat {
-    fn default() -> Self {
-        Self::ChatCompletions
-    }
+impl ApiFormat {
+    /// 所有支持的 API 格式
+    pub const ALL: [ApiFormat; 2] = [ApiFormat::ChatCompletions, ApiFormat::Responses];
 }
 
 // ============================================================================
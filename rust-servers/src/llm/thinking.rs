@@ -543,8 +543,8 @@ mod tests {
         assert!(content2.is_empty());
         // thinking2 应该包含 "incomplete"
         // 注意：thinking1 或 thinking2 中应该有一个包含 "incomplete"
-        let has_incomplete = thinking1.as_ref().map_or(false, |t| t.contains("incomplete"))
-            || thinking2.as_ref().map_or(false, |t| t.contains("incomplete"));
+        let has_incomplete = thinking1.as_ref().is_some_and(|t| t.contains("incomplete"))
+            || thinking2.as_ref().is_some_and(|t| t.contains("incomplete"));
         assert!(has_incomplete, "Expected 'incomplete' in thinking content");
     }
     
@@ -0,0 +1,553 @@
+// 统一日志
+// 分级日志、模块目标、可选 JSON 输出、可选滚动日志文件，以及敏感信息自动脱敏
+//
+// 各模块直接使用 log_error! / log_warn! / log_info! / log_debug! 宏，
+// 目标 (target) 取自调用处的模块路径，例如 `pty::registry`。
+// 日志级别可在运行时通过 utils 模块的 `set_log_level` 消息调整。
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 单个日志文件的默认最大大小 (10 MiB)
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// 默认保留的历史日志文件数
+pub const DEFAULT_MAX_FILES: usize = 3;
+
+/// 脱敏后的占位符
+const REDACTED: &str = "***";
+
+/// 需要脱敏的字段名 (不区分大小写，其后紧跟的值会被替换)
+const SENSITIVE_KEYS: &[&str] = &[
+    "authorization",
+    "api_key",
+    "api-key",
+    "apikey",
+    "access_key",
+    "app_key",
+    "secret_key",
+    "secret",
+    "password",
+    "token",
+];
+
+// ============================================================================
+// 日志宏
+// ============================================================================
+
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Error, module_path!(), format_args!($($arg)*))
+    };
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Warn, module_path!(), format_args!($($arg)*))
+    };
+}
+
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Info, module_path!(), format_args!($($arg)*))
+    };
+}
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Debug, module_path!(), format_args!($($arg)*))
+    };
+}
+
+// ============================================================================
+// 日志级别
+// ============================================================================
+
+/// 日志级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+impl Level {
+    /// 所有日志级别 (用于工具模块的能力描述)
+    #[cfg_attr(not(feature = "utils"), allow(dead_code))]
+    pub const ALL: &'static [Level] = &[Level::Error, Level::Warn, Level::Info, Level::Debug];
+
+    /// 默认级别 (调试构建为 debug，发布构建为 info)
+    pub fn default_level() -> Self {
+        if cfg!(debug_assertions) {
+            Level::Debug
+        } else {
+            Level::Info
+        }
+    }
+
+    /// 级别名称 (小写)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            _ => Level::Debug,
+        }
+    }
+}
+
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" | "warning" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            _ => Err(format!("未知的日志级别: {}", s)),
+        }
+    }
+}
+
+// ============================================================================
+// 日志配置
+// ============================================================================
+
+/// 日志配置
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    /// 日志级别
+    pub level: Level,
+    /// 以 JSON 行格式输出
+    pub json: bool,
+    /// 日志文件路径 (None 表示只输出到 stderr)
+    pub file: Option<PathBuf>,
+    /// 单个日志文件的最大大小，超出后滚动
+    pub max_file_size: u64,
+    /// 保留的历史日志文件数
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: Level::default_level(),
+            json: false,
+            file: None,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_files: DEFAULT_MAX_FILES,
+        }
+    }
+}
+
+// ============================================================================
+// 日志器
+// ============================================================================
+
+/// 当前日志级别 (运行时可调整)
+static LEVEL: AtomicU8 = AtomicU8::new(0);
+
+/// 全局日志器 (未初始化时以文本格式输出到 stderr)
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+struct Logger {
+    json: bool,
+    file: Option<Mutex<RotatingFile>>,
+}
+
+/// 初始化日志 (只能调用一次，之后的调用只更新日志级别)
+pub fn init(config: LogConfig) -> std::io::Result<()> {
+    set_level(config.level);
+
+    let file = match config.file {
+        Some(path) => Some(Mutex::new(RotatingFile::open(
+            path,
+            config.max_file_size,
+            config.max_files,
+        )?)),
+        None => None,
+    };
+
+    let _ = LOGGER.set(Logger {
+        json: config.json,
+        file,
+    });
+    Ok(())
+}
+
+/// 获取当前日志级别
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => Level::default_level(),
+        value => Level::from_u8(value),
+    }
+}
+
+/// 设置日志级别，返回之前的级别
+pub fn set_level(level: Level) -> Level {
+    let previous = self::level();
+    LEVEL.store(level as u8, Ordering::Relaxed);
+    previous
+}
+
+/// 指定级别是否会输出
+pub fn enabled(level: Level) -> bool {
+    level <= self::level()
+}
+
+/// 输出一条日志 (由日志宏调用)
+pub fn log(level: Level, module_path: &str, args: std::fmt::Arguments<'_>) {
+    if !enabled(level) {
+        return;
+    }
+
+    let target = target_from_module_path(module_path);
+    let message = redact(&args.to_string());
+    let timestamp = format_timestamp(SystemTime::now());
+
+    let Some(logger) = LOGGER.get() else {
+        eprintln!("{}", format_text(level, target, &message));
+        return;
+    };
+
+    if logger.json {
+        let line = format_json(&timestamp, level, target, &message);
+        eprintln!("{}", line);
+        logger.write_file(&line);
+    } else {
+        eprintln!("{}", format_text(level, target, &message));
+        logger.write_file(&format!("{} {}", timestamp, format_text(level, target, &message)));
+    }
+}
+
+impl Logger {
+    fn write_file(&self, line: &str) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                // 写日志文件失败时不能再记录日志，直接忽略
+                let _ = file.write_line(line);
+            }
+        }
+    }
+}
+
+/// 从模块路径得到日志目标 (去掉 crate 名)
+fn target_from_module_path(module_path: &str) -> &str {
+    match module_path.split_once("::") {
+        Some((_, rest)) => rest,
+        None => "main",
+    }
+}
+
+/// 文本格式: `[INFO] [pty::registry] 消息`
+fn format_text(level: Level, target: &str, message: &str) -> String {
+    format!("[{}] [{}] {}", level.as_str().to_ascii_uppercase(), target, message)
+}
+
+/// JSON 行格式
+fn format_json(timestamp: &str, level: Level, target: &str, message: &str) -> String {
+    serde_json::json!({
+        "ts": timestamp,
+        "level": level,
+        "target": target,
+        "message": message,
+    })
+    .to_string()
+}
+
+/// 格式化为 RFC 3339 UTC 时间，精确到毫秒
+fn format_timestamp(time: SystemTime) -> String {
+    let duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = duration.as_secs();
+    let (hour, minute, second) = ((secs / 3600) % 24, (secs / 60) % 60, secs % 60);
+
+    // 由 Unix 天数计算公历日期 (Howard Hinnant 的 civil_from_days 算法)
+    let days = (secs / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year, month, day, hour, minute, second, duration.subsec_millis()
+    )
+}
+
+// ============================================================================
+// 滚动日志文件
+// ============================================================================
+
+/// 按大小滚动的日志文件
+///
+/// 超出大小时 `server.log` → `server.log.1` → `server.log.2` ...，最多保留 `max_files` 个历史文件
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+            max_files,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if self.max_files > 0 {
+            let _ = fs::remove_file(self.rotated_path(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let _ = fs::rename(self.rotated_path(index), self.rotated_path(index + 1));
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+}
+
+// ============================================================================
+// 脱敏
+// ============================================================================
+
+/// 替换日志中的敏感信息
+///
+/// - 敏感字段 (`api_key`、`Authorization`、`token` 等) 后紧跟的值，支持 `key=v`、`key: v`、`"key":"v"` 等形式
+/// - `Authorization: Bearer <v>` 保留认证方案，只替换凭据
+/// - 形如 `sk-...` 的 API Key
+pub fn redact(message: &str) -> String {
+    let bytes = message.as_bytes();
+    let mut output = String::with_capacity(message.len());
+    let mut copied = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if let Some(value_start) = match_sensitive_key(bytes, i) {
+            let value_start = skip_auth_scheme(bytes, value_start);
+            let value_end = find_value_end(bytes, value_start);
+            if value_end > value_start {
+                output.push_str(&message[copied..value_start]);
+                output.push_str(REDACTED);
+                copied = value_end;
+                i = value_end;
+                continue;
+            }
+        }
+
+        if is_api_key_at(bytes, i) {
+            let value_end = find_value_end(bytes, i);
+            output.push_str(&message[copied..i]);
+            output.push_str("sk-");
+            output.push_str(REDACTED);
+            copied = value_end;
+            i = value_end;
+            continue;
+        }
+
+        i += 1;
+    }
+
+    output.push_str(&message[copied..]);
+    output
+}
+
+/// 检查 `i` 处是否为敏感字段名，是则返回其值的起始位置
+fn match_sensitive_key(bytes: &[u8], i: usize) -> Option<usize> {
+    let key = SENSITIVE_KEYS.iter().find(|key| {
+        bytes.len() >= i + key.len() && bytes[i..i + key.len()].eq_ignore_ascii_case(key.as_bytes())
+    })?;
+
+    // 字段名后至少跟一个分隔符，避免误伤 `max_tokens` 之类的名称
+    let mut j = i + key.len();
+    let start = j;
+    while j < bytes.len() && matches!(bytes[j], b'"' | b'\'' | b':' | b'=' | b' ' | b'\t') {
+        j += 1;
+    }
+    if j == start {
+        return None;
+    }
+
+    // Debug 格式的 Option 值
+    if bytes[j..].starts_with(b"Some(") {
+        j += "Some(".len();
+        while j < bytes.len() && matches!(bytes[j], b'"' | b'\'') {
+            j += 1;
+        }
+    }
+    Some(j)
+}
+
+/// 跳过 `Bearer ` / `Basic ` 认证方案
+fn skip_auth_scheme(bytes: &[u8], start: usize) -> usize {
+    for scheme in [&b"bearer "[..], &b"basic "[..]] {
+        if bytes.len() >= start + scheme.len()
+            && bytes[start..start + scheme.len()].eq_ignore_ascii_case(scheme)
+        {
+            return start + scheme.len();
+        }
+    }
+    start
+}
+
+/// 值的结束位置 (引号、分隔符或空白)
+fn find_value_end(bytes: &[u8], start: usize) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|b| {
+            matches!(
+                b,
+                b'"' | b'\'' | b',' | b'&' | b';' | b')' | b'}' | b']' | b' ' | b'\t' | b'\r' | b'\n'
+            )
+        })
+        .map_or(bytes.len(), |offset| start + offset)
+}
+
+/// 检查 `i` 处是否为 `sk-` 开头的 API Key
+fn is_api_key_at(bytes: &[u8], i: usize) -> bool {
+    const MIN_KEY_LEN: usize = 20;
+
+    let at_word_start = i == 0 || !bytes[i - 1].is_ascii_alphanumeric();
+    at_word_start
+        && bytes[i..].starts_with(b"sk-")
+        && bytes[i..]
+            .iter()
+            .take_while(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_'))
+            .count()
+            >= MIN_KEY_LEN
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_key_values() {
+        assert_eq!(redact("api_key=abc123&model=x"), "api_key=***&model=x");
+        assert_eq!(redact(r#"{"api_key":"abc123","model":"x"}"#), r#"{"api_key":"***","model":"x"}"#);
+        assert_eq!(redact("Authorization: Bearer abc.def"), "Authorization: Bearer ***");
+        assert_eq!(redact("x-api-key: abc123"), "x-api-key: ***");
+        assert_eq!(redact(r#"Config { api_key: Some("abc123") }"#), r#"Config { api_key: Some("***") }"#);
+        assert_eq!(redact("ws://127.0.0.1:1/?token=abc"), "ws://127.0.0.1:1/?token=***");
+    }
+
+    #[test]
+    fn test_redact_bare_api_keys() {
+        assert_eq!(redact("使用密钥 sk-abcdefghijklmnopqrstuvwxyz 请求"), "使用密钥 sk-*** 请求");
+        // 过短的不是 API Key
+        assert_eq!(redact("task-sk-1"), "task-sk-1");
+    }
+
+    #[test]
+    fn test_redact_keeps_unrelated_text() {
+        assert_eq!(redact("max_tokens=100, 令牌数量正常"), "max_tokens=100, 令牌数量正常");
+        assert_eq!(redact("session_id=3"), "session_id=3");
+    }
+
+    #[test]
+    fn test_level_parse_and_order() {
+        assert_eq!("WARN".parse::<Level>(), Ok(Level::Warn));
+        assert!("verbose".parse::<Level>().is_err());
+        assert!(Level::Error < Level::Debug);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(target_from_module_path("smart_workflow_server::pty::registry"), "pty::registry");
+        assert_eq!(target_from_module_path("smart_workflow_server"), "main");
+        assert_eq!(format_text(Level::Info, "pty", "hi"), "[INFO] [pty] hi");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_123)),
+            "2023-11-14T22:13:20.123Z"
+        );
+
+        let line: serde_json::Value = serde_json::from_str(&format_json("t", Level::Warn, "llm", "x")).unwrap();
+        assert_eq!(line["level"], "warn");
+        assert_eq!(line["target"], "llm");
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let dir = std::env::temp_dir().join(format!("sw-log-test-{}", std::process::id()));
+        let path = dir.join("server.log");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut file = RotatingFile::open(path.clone(), 16, 2).unwrap();
+        for line in ["aaaaaaaaaa", "bbbbbbbbbb", "cccccccccc", "dddddddddd"] {
+            file.write_line(line).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "dddddddddd\n");
+        assert_eq!(fs::read_to_string(file.rotated_path(1)).unwrap(), "cccccccccc\n");
+        assert_eq!(fs::read_to_string(file.rotated_path(2)).unwrap(), "bbbbbbbbbb\n");
+        assert!(!file.rotated_path(3).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,85 +1,93 @@
 // Unified Server Main Program
 // 统一的 Rust 后端服务器，提供 PTY、语音、LLM 流式处理、工具等功能
 
+// 日志宏需在其他模块之前引入
+#[macro_use]
+mod logging;
+
+mod auth;
+mod config;
+mod error;
+mod frame;
+mod i18n;
+mod lifecycle;
+mod metrics;
+mod outbound;
+mod protocol;
 mod server;
 mod router;
+mod transport;
+
+#[cfg(test)]
+mod testing;
 
-// 功能模块
+// 功能模块 (由 cargo feature 控制是否编译)
+#[cfg(feature = "pty")]
 pub mod pty;
+#[cfg(feature = "voice")]
 pub mod voice;
+#[cfg(feature = "llm")]
 pub mod llm;
+#[cfg(feature = "utils")]
 pub mod utils;
 
-use server::{Server, ServerConfig};
-use std::env;
+#[cfg(not(any(feature = "pty", feature = "voice", feature = "llm", feature = "utils")))]
+compile_error!("至少需要启用一个功能模块 feature: pty、voice、llm、utils");
 
-/// 日志宏
-macro_rules! log_info {
-    ($($arg:tt)*) => {
-        eprintln!("[INFO] {}", format!($($arg)*));
-    };
-}
+use std::time::Duration;
 
-macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        if cfg!(debug_assertions) {
-            eprintln!("[DEBUG] {}", format!($($arg)*));
-        }
-    };
-}
+use server::Server;
 
-/// 解析命令行参数
-fn parse_args() -> u16 {
-    let args: Vec<String> = env::args().collect();
-    let mut port: u16 = 0;
-    
-    let mut i = 1;
-    while i < args.len() {
-        match args[i].as_str() {
-            "-p" | "--port" => {
-                if i + 1 < args.len() {
-                    port = args[i + 1].parse().unwrap_or(0);
-                    i += 1;
-                }
-            }
-            arg if arg.starts_with("--port=") => {
-                port = arg.trim_start_matches("--port=").parse().unwrap_or(0);
-            }
-            "-h" | "--help" => {
-                eprintln!("Usage: smart-workflow-server [OPTIONS]");
-                eprintln!("Options:");
-                eprintln!("  -p, --port <PORT>  监听端口 (0 表示随机端口) [默认: 0]");
-                eprintln!("  -h, --help         显示帮助信息");
-                std::process::exit(0);
-            }
-            _ => {}
-        }
-        i += 1;
-    }
-    
-    port
-}
+/// 退出时等待阻塞任务结束的最长时间
+/// (后台进程仍持有 PTY 从端时，读取 PTY 输出的阻塞任务不会结束)
+const BLOCKING_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 解析命令行参数
-    let port = parse_args();
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    let result = runtime.block_on(run());
+    // 默认的 drop 会无限等待阻塞任务，改为限时关闭
+    runtime.shutdown_timeout(BLOCKING_SHUTDOWN_TIMEOUT);
+    result
+}
 
-    log_debug!("启动参数: port={}", port);
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    // 解析命令行参数和配置文件，生成服务器配置
+    // 日志尚未初始化，配置错误直接输出到 stderr
+    let (config, log_config) = match config::load() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    logging::init(log_config)?;
 
-    // 创建服务器配置
-    let config = ServerConfig { port };
+    log_debug!(
+        "启动参数: transport={:?}, bind={}, port={}, parent_pid={:?}, idle_timeout={:?}, modules={:?}",
+        config.transport, config.bind_address, config.port, config.parent_pid, config.idle_timeout, config.modules.enabled
+    );
 
     // 创建并启动服务器
     let server = Server::new(config);
-    let port = server.start().await?;
+    let address = server.start().await?;
 
     // 保持主线程运行
-    log_info!("Smart Workflow Server 已启动，监听端口: {}", port);
+    log_info!("Smart Workflow Server 已启动，监听: {}", address);
+    
+    // 等待退出信号 (SIGINT/SIGTERM/SIGHUP) 或内部关闭 (父进程退出、空闲超时)
+    let shutdown = server.shutdown_signal();
+    let reason = tokio::select! {
+        result = lifecycle::wait_for_signal() => {
+            let reason = lifecycle::ShutdownReason::Signal(result?);
+            shutdown.trigger(reason);
+            reason
+        }
+        reason = shutdown.wait() => reason,
+    };
+    log_info!("{}，正在关闭服务器...", reason);
     
-    // 等待 Ctrl+C 信号
-    tokio::signal::ctrl_c().await?;
-    log_info!("收到退出信号，正在关闭服务器...");
+    // 统一的优雅关闭: 通知客户端、清理各模块 (停止录音、取消 LLM 流)，终止并回收所有 PTY
+    server.shutdown().await;
 
     Ok(())
 }
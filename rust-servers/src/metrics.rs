@@ -0,0 +1,339 @@
+// 运行指标
+// 服务器范围内的消息计数、按错误码统计的错误数、进行中的任务数和延迟分布，
+// 供 utils 模块的 diagnostics 请求和 /healthz 使用
+//
+// 延迟只保留最近的样本 (滑动窗口)，百分位按窗口内的样本计算
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::error::ErrorCode;
+use crate::protocol::Payload;
+use crate::router::ModuleType;
+
+/// 每个延迟分布保留的样本数
+const LATENCY_WINDOW: usize = 512;
+
+// ============================================================================
+// 进行中的任务数
+// ============================================================================
+
+/// 进行中的任务数 (LLM 流、录音)
+#[derive(Debug, Clone, Default)]
+pub struct Gauge(Arc<AtomicUsize>);
+
+impl Gauge {
+    /// 登记一个任务，返回的守卫释放时自动注销
+    #[cfg_attr(not(any(feature = "voice", feature = "llm")), allow(dead_code))]
+    pub fn enter(&self) -> GaugeGuard {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        GaugeGuard(Arc::clone(&self.0))
+    }
+
+    /// 当前任务数
+    #[cfg_attr(not(any(feature = "voice", feature = "llm")), allow(dead_code))]
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 任务守卫
+#[derive(Debug)]
+pub struct GaugeGuard(Arc<AtomicUsize>);
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// ============================================================================
+// 延迟分布
+// ============================================================================
+
+/// 延迟分布 (最近 [`LATENCY_WINDOW`] 个样本)
+#[derive(Debug, Default)]
+struct LatencyWindow {
+    /// 累计样本数
+    count: u64,
+    /// 最近的样本 (毫秒)
+    samples: VecDeque<u64>,
+}
+
+impl LatencyWindow {
+    fn record(&mut self, latency: Duration) {
+        if self.samples.len() == LATENCY_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(latency.as_millis() as u64);
+        self.count += 1;
+    }
+
+    fn summary(&self) -> LatencySummary {
+        let mut sorted: Vec<u64> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let percentile = |p: usize| {
+            // 最近秩法: 第 ceil(p% × n) 个样本
+            let rank = (sorted.len() * p).div_ceil(100).max(1);
+            sorted.get(rank - 1).copied().unwrap_or(0)
+        };
+        LatencySummary {
+            count: self.count,
+            p50_ms: percentile(50),
+            p90_ms: percentile(90),
+            p99_ms: percentile(99),
+            max_ms: sorted.last().copied().unwrap_or(0),
+        }
+    }
+}
+
+/// 延迟分布摘要
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct LatencySummary {
+    /// 累计样本数
+    pub count: u64,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    /// 窗口内的最大值
+    pub max_ms: u64,
+}
+
+// ============================================================================
+// 模块计数
+// ============================================================================
+
+/// 单个模块的消息和错误计数
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ModuleCounters {
+    /// 处理的消息数
+    pub messages: u64,
+    /// 按错误码统计的错误数 (包括请求的错误响应和模块主动推送的错误事件)
+    pub errors: BTreeMap<ErrorCode, u64>,
+}
+
+// ============================================================================
+// 运行指标
+// ============================================================================
+
+/// 服务器范围的运行指标 (所有连接共享)
+#[derive(Debug)]
+pub struct Metrics {
+    /// 服务器启动时间
+    started: Instant,
+    /// 各模块的计数
+    modules: Mutex<BTreeMap<String, ModuleCounters>>,
+    /// 各 ASR 引擎的转录延迟
+    asr_latency: Mutex<BTreeMap<String, LatencyWindow>>,
+    /// LLM 首个 token 的延迟
+    llm_ttft: Mutex<LatencyWindow>,
+    /// 进行中的 LLM 流
+    #[cfg_attr(not(feature = "llm"), allow(dead_code))]
+    pub llm_streams: Gauge,
+    /// 进行中的录音
+    #[cfg_attr(not(feature = "voice"), allow(dead_code))]
+    pub recordings: Gauge,
+}
+
+impl Metrics {
+    /// 创建运行指标，从当前时间开始计算运行时长
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            modules: Mutex::new(BTreeMap::new()),
+            asr_latency: Mutex::new(BTreeMap::new()),
+            llm_ttft: Mutex::new(LatencyWindow::default()),
+            llm_streams: Gauge::default(),
+            recordings: Gauge::default(),
+        }
+    }
+
+    /// 运行时长
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// 记录一条消息的处理结果 (`error` 为失败时的错误码)
+    pub fn record_message(&self, module: &ModuleType, error: Option<ErrorCode>) {
+        let mut modules = self.modules.lock().unwrap();
+        let counters = modules.entry(module.to_string()).or_default();
+        counters.messages += 1;
+        if let Some(code) = error {
+            *counters.errors.entry(code).or_default() += 1;
+        }
+    }
+
+    /// 记录一个不对应请求响应的错误 (解析错误、模块主动推送的错误事件)
+    pub fn record_error(&self, module: &ModuleType, code: ErrorCode) {
+        let mut modules = self.modules.lock().unwrap();
+        *modules.entry(module.to_string()).or_default().errors.entry(code).or_default() += 1;
+    }
+
+    /// 记录一次 ASR 转录的延迟
+    #[cfg_attr(not(feature = "voice"), allow(dead_code))]
+    pub fn record_asr_latency(&self, engine: &str, latency: Duration) {
+        self.asr_latency.lock().unwrap().entry(engine.to_string()).or_default().record(latency);
+    }
+
+    /// 记录一次 LLM 请求的首 token 延迟
+    #[cfg_attr(not(feature = "llm"), allow(dead_code))]
+    pub fn record_llm_ttft(&self, latency: Duration) {
+        self.llm_ttft.lock().unwrap().record(latency);
+    }
+
+    /// 各模块的计数
+    pub fn module_counters(&self) -> BTreeMap<String, ModuleCounters> {
+        self.modules.lock().unwrap().clone()
+    }
+
+    /// 各 ASR 引擎的转录延迟
+    #[cfg_attr(not(feature = "voice"), allow(dead_code))]
+    pub fn asr_latency(&self) -> BTreeMap<String, LatencySummary> {
+        self.asr_latency.lock().unwrap()
+            .iter()
+            .map(|(engine, window)| (engine.clone(), window.summary()))
+            .collect()
+    }
+
+    /// LLM 首 token 延迟
+    #[cfg_attr(not(feature = "llm"), allow(dead_code))]
+    pub fn llm_ttft(&self) -> LatencySummary {
+        self.llm_ttft.lock().unwrap().summary()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// 诊断信息
+// ============================================================================
+
+/// 诊断信息 (utils 模块 `diagnostics` 请求的响应，只包含已编译模块的状态)
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Diagnostics {
+    /// 服务器版本
+    pub server_version: &'static str,
+    /// 协议版本
+    pub protocol_version: u32,
+    /// 服务器进程 PID
+    pub pid: u32,
+    /// 运行时长 (毫秒)
+    pub uptime_ms: u64,
+    /// 活动连接数
+    pub connections: usize,
+    /// 各模块的消息和错误计数
+    pub modules: BTreeMap<String, ModuleCounters>,
+    /// PTY 会话
+    #[cfg(feature = "pty")]
+    pub pty: PtyDiagnostics,
+    /// 录音和转录延迟
+    #[cfg(feature = "voice")]
+    pub voice: VoiceDiagnostics,
+    /// LLM 流和首 token 延迟
+    #[cfg(feature = "llm")]
+    pub llm: LlmDiagnostics,
+}
+
+impl Payload for Diagnostics {
+    const TYPE: &'static str = "diagnostics";
+}
+
+/// PTY 诊断信息
+#[cfg(feature = "pty")]
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PtyDiagnostics {
+    /// 所有会话 (包括宽限期内未连接的会话)
+    pub sessions: Vec<crate::pty::SessionInfo>,
+}
+
+/// 语音诊断信息
+#[cfg(feature = "voice")]
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct VoiceDiagnostics {
+    /// 进行中的录音数
+    pub active_recordings: usize,
+    /// 各 ASR 引擎的转录延迟
+    pub asr_latency: BTreeMap<String, LatencySummary>,
+}
+
+/// LLM 诊断信息
+#[cfg(feature = "llm")]
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct LlmDiagnostics {
+    /// 进行中的流数
+    pub active_streams: usize,
+    /// 首 token 延迟
+    pub time_to_first_token: LatencySummary,
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gauge() {
+        let gauge = Gauge::default();
+        let first = gauge.enter();
+        let second = gauge.enter();
+        assert_eq!(gauge.get(), 2);
+        drop(first);
+        assert_eq!(gauge.get(), 1);
+        drop(second);
+        assert_eq!(gauge.get(), 0);
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let mut window = LatencyWindow::default();
+        assert_eq!(window.summary().p50_ms, 0);
+
+        for ms in 1..=100 {
+            window.record(Duration::from_millis(ms));
+        }
+        let summary = window.summary();
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.p50_ms, 50);
+        assert_eq!(summary.p90_ms, 90);
+        assert_eq!(summary.p99_ms, 99);
+        assert_eq!(summary.max_ms, 100);
+
+        // 只保留最近的样本
+        for _ in 0..LATENCY_WINDOW {
+            window.record(Duration::from_millis(7));
+        }
+        let summary = window.summary();
+        assert_eq!(summary.count, 100 + LATENCY_WINDOW as u64);
+        assert_eq!(summary.max_ms, 7);
+    }
+
+    #[test]
+    fn test_module_counters() {
+        let metrics = Metrics::new();
+        let module = ModuleType::UTILS;
+        metrics.record_message(&module, None);
+        metrics.record_message(&module, Some(ErrorCode::ModuleError));
+        metrics.record_error(&module, ErrorCode::ParseError);
+
+        let counters = &metrics.module_counters()["utils"];
+        assert_eq!(counters.messages, 2);
+        assert_eq!(counters.errors[&ErrorCode::ModuleError], 1);
+        assert_eq!(counters.errors[&ErrorCode::ParseError], 1);
+        assert_eq!(
+            serde_json::to_value(counters).unwrap(),
+            serde_json::json!({ "messages": 2, "errors": { "MODULE_ERROR": 1, "PARSE_ERROR": 1 } })
+        );
+    }
+}
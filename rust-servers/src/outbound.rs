@@ -0,0 +1,332 @@
+// 出站消息队列
+// 每个连接一个写任务，按优先级从有界队列中取出消息写入传输，
+// 生产者 (PTY 读取、LLM 流、语音转发) 不再直接争用传输的锁
+//
+// 背压策略:
+// - 控制消息 (请求响应、错误、关闭通知) 最先发送
+// - 流式数据 (LLM 流、语音事件) 和批量输出 (PTY 输出) 队列满时生产者等待，PTY 读取因此暂停
+// - 可合并的消息 (音量/波形) 只保留最新一条，过期未发出的直接丢弃
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures_util::SinkExt;
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::transport::MessageSink;
+
+/// 控制消息队列容量
+pub const CONTROL_QUEUE_SIZE: usize = 64;
+
+/// 流式数据队列容量
+pub const STREAM_QUEUE_SIZE: usize = 256;
+
+/// 批量输出队列容量 (PTY 输出每块最多 8 KiB)
+pub const BULK_QUEUE_SIZE: usize = 64;
+
+/// 关闭时等待写任务发送剩余消息的最长时间
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+// ============================================================================
+// 优先级
+// ============================================================================
+
+/// 消息优先级 (从高到低)
+///
+/// 同一优先级内保持发送顺序，因此需要相对有序的一组消息 (如 LLM 的数据块和完成/错误消息)
+/// 必须使用同一优先级
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// 控制消息: 请求响应、错误、关闭通知、Pong
+    Control,
+    /// 流式数据: 模块主动推送的事件序列 (LLM 流、录音状态、转录进度和结果)，按顺序发送
+    Stream,
+    /// 批量输出: PTY 输出，队列满时阻塞生产者
+    Bulk,
+}
+
+/// 连接已关闭，消息无法再发送
+#[derive(Debug, Error)]
+#[error("连接已关闭")]
+pub struct OutboundClosed;
+
+// ============================================================================
+// 合并槽
+// ============================================================================
+
+/// 可合并消息的最新值
+struct LatestEntry {
+    message: Message,
+    queued_at: Instant,
+    max_age: Option<Duration>,
+}
+
+/// 按 key 只保留最新一条消息
+#[derive(Default)]
+struct LatestSlots {
+    entries: Mutex<HashMap<&'static str, LatestEntry>>,
+    notify: Notify,
+}
+
+impl LatestSlots {
+    fn put(&self, key: &'static str, entry: LatestEntry) {
+        self.entries.lock().unwrap().insert(key, entry);
+        self.notify.notify_one();
+    }
+
+    /// 取出全部未过期的消息 (过期的直接丢弃)
+    fn take(&self) -> Vec<Message> {
+        let entries = std::mem::take(&mut *self.entries.lock().unwrap());
+        entries
+            .into_iter()
+            .filter_map(|(key, entry)| match entry.max_age {
+                Some(max_age) if entry.queued_at.elapsed() > max_age => {
+                    log_debug!("丢弃过期消息: {}", key);
+                    None
+                }
+                _ => Some(entry.message),
+            })
+            .collect()
+    }
+}
+
+// ============================================================================
+// 出站发送器
+// ============================================================================
+
+/// 连接的出站发送器
+///
+/// 可随意克隆并在各模块间共享，所有消息由同一个写任务按优先级写出
+#[derive(Clone)]
+pub struct Outbound {
+    control: mpsc::Sender<Message>,
+    stream: mpsc::Sender<Message>,
+    bulk: mpsc::Sender<Message>,
+    latest: Arc<LatestSlots>,
+}
+
+/// 写任务句柄 (连接结束时用于等待剩余消息发送完毕)
+///
+/// 丢弃句柄等同于 `finish(false)` 但不等待
+pub struct OutboundWriter {
+    task: JoinHandle<()>,
+    finish: oneshot::Sender<bool>,
+}
+
+impl Outbound {
+    fn queue(&self, priority: Priority) -> &mpsc::Sender<Message> {
+        match priority {
+            Priority::Control => &self.control,
+            Priority::Stream => &self.stream,
+            Priority::Bulk => &self.bulk,
+        }
+    }
+
+    /// 创建发送器并启动写任务
+    pub fn spawn(sink: MessageSink) -> (Self, OutboundWriter) {
+        let (control, control_rx) = mpsc::channel(CONTROL_QUEUE_SIZE);
+        let (stream, stream_rx) = mpsc::channel(STREAM_QUEUE_SIZE);
+        let (bulk, bulk_rx) = mpsc::channel(BULK_QUEUE_SIZE);
+        let latest = Arc::new(LatestSlots::default());
+        let (finish, finish_rx) = oneshot::channel();
+
+        let task = tokio::spawn(run_writer(
+            sink,
+            control_rx,
+            stream_rx,
+            bulk_rx,
+            Arc::clone(&latest),
+            finish_rx,
+        ));
+
+        let outbound = Self { control, stream, bulk, latest };
+        (outbound, OutboundWriter { task, finish })
+    }
+
+    /// 按优先级发送消息，队列满时等待
+    pub async fn send(&self, priority: Priority, message: Message) -> Result<(), OutboundClosed> {
+        self.queue(priority).send(message).await.map_err(|_| OutboundClosed)
+    }
+
+    /// 按优先级发送文本消息
+    pub async fn send_text(&self, priority: Priority, text: String) -> Result<(), OutboundClosed> {
+        self.send(priority, Message::Text(text.into())).await
+    }
+
+    /// 尝试发送文本消息，不会阻塞；队列已满时丢弃该消息
+    pub fn try_send_text(&self, priority: Priority, text: String) -> Result<(), OutboundClosed> {
+        match self.queue(priority).try_send(Message::Text(text.into())) {
+            Ok(()) => Ok(()),
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                log_debug!("{:?} 队列已满，丢弃消息", priority);
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => Err(OutboundClosed),
+        }
+    }
+
+    /// 合并发送: 同一 `key` 只保留最新一条，不会阻塞
+    ///
+    /// 设置 `max_age` 时，排队超过该时间仍未发出的消息会被丢弃 (如过期的波形帧)
+    pub fn send_latest(
+        &self,
+        key: &'static str,
+        text: String,
+        max_age: Option<Duration>,
+    ) -> Result<(), OutboundClosed> {
+        if self.control.is_closed() {
+            return Err(OutboundClosed);
+        }
+        self.latest.put(key, LatestEntry {
+            message: Message::Text(text.into()),
+            queued_at: Instant::now(),
+            max_age,
+        });
+        Ok(())
+    }
+}
+
+impl OutboundWriter {
+    /// 发送剩余消息后结束写任务
+    ///
+    /// `close_frame` 为 true 时最后发送关闭帧 (服务器主动关闭连接)
+    pub async fn finish(self, close_frame: bool) {
+        let _ = self.finish.send(close_frame);
+
+        let mut task = self.task;
+        if tokio::time::timeout(FLUSH_TIMEOUT, &mut task).await.is_err() {
+            log_warn!("发送剩余消息超时，丢弃未发送的消息");
+            task.abort();
+        }
+    }
+}
+
+/// 写任务: 按优先级取出消息写入传输
+///
+/// 收到结束请求后发送完已排队的消息再退出；写入失败时立即退出，
+/// 队列随之关闭，生产者的发送会返回 [`OutboundClosed`]
+async fn run_writer(
+    mut sink: MessageSink,
+    mut control: mpsc::Receiver<Message>,
+    mut stream: mpsc::Receiver<Message>,
+    mut bulk: mpsc::Receiver<Message>,
+    latest: Arc<LatestSlots>,
+    mut finish: oneshot::Receiver<bool>,
+) {
+    let close_frame = loop {
+        let messages = tokio::select! {
+            biased;
+            Some(message) = control.recv() => vec![message],
+            Some(message) = stream.recv() => vec![message],
+            _ = latest.notify.notified() => latest.take(),
+            Some(message) = bulk.recv() => vec![message],
+            // 只有所有队列都为空时才会走到这里
+            close_frame = &mut finish => break close_frame.unwrap_or(false),
+        };
+
+        for message in messages {
+            if let Err(e) = sink.send(message).await {
+                log_debug!("写入连接失败: {}", e);
+                return;
+            }
+        }
+    };
+
+    if close_frame {
+        let _ = sink.send(Message::Close(None)).await;
+    }
+    let _ = sink.flush().await;
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::tungstenite::Error as WsError;
+
+    /// 写入内存通道 (容量为 `capacity`) 的出站发送器
+    fn channel_outbound(capacity: usize) -> (Outbound, OutboundWriter, mpsc::Receiver<Message>) {
+        let (tx, rx) = mpsc::channel::<Message>(capacity);
+        let sink = futures_util::sink::unfold(tx, |tx, message: Message| async move {
+            tx.send(message).await.map_err(|_| WsError::ConnectionClosed)?;
+            Ok::<_, WsError>(tx)
+        });
+        let (outbound, writer) = Outbound::spawn(Box::pin(sink));
+        (outbound, writer, rx)
+    }
+
+    /// 读取写任务结束前发出的全部文本消息
+    async fn received_texts(rx: &mut mpsc::Receiver<Message>) -> Vec<String> {
+        let mut texts = Vec::new();
+        while let Some(message) = rx.recv().await {
+            texts.push(message.to_text().unwrap().to_string());
+        }
+        texts
+    }
+
+    #[tokio::test]
+    async fn test_control_before_bulk() {
+        let (outbound, writer, mut rx) = channel_outbound(16);
+
+        // 写任务尚未运行时排队，运行后按优先级发送
+        outbound.send_text(Priority::Bulk, "bulk".into()).await.unwrap();
+        outbound.send_text(Priority::Stream, "stream 1".into()).await.unwrap();
+        outbound.send_text(Priority::Control, "control".into()).await.unwrap();
+        outbound.send_text(Priority::Stream, "stream 2".into()).await.unwrap();
+        writer.finish(false).await;
+
+        assert_eq!(received_texts(&mut rx).await, vec!["control", "stream 1", "stream 2", "bulk"]);
+    }
+
+    #[tokio::test]
+    async fn test_latest_coalesced_and_expired() {
+        let (outbound, writer, mut rx) = channel_outbound(16);
+
+        for level in 0..5 {
+            outbound.send_latest("audio_level", format!("level {}", level), None).unwrap();
+        }
+        outbound.send_latest("stale", "stale".into(), Some(Duration::ZERO)).unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        writer.finish(false).await;
+
+        assert_eq!(received_texts(&mut rx).await, vec!["level 4"]);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_backpressure() {
+        // 连接不读取数据: 批量队列满后生产者等待
+        let (blocked, _blocked_writer, _blocked_rx) = channel_outbound(1);
+        let fill = async {
+            for _ in 0..BULK_QUEUE_SIZE * 2 {
+                blocked.send(Priority::Bulk, Message::Binary(vec![0].into())).await.unwrap();
+            }
+        };
+        assert!(tokio::time::timeout(Duration::from_millis(50), fill).await.is_err());
+
+        // 非阻塞发送直接丢弃
+        assert!(blocked.try_send_text(Priority::Bulk, "dropped".into()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_close_frame_and_closed_queue() {
+        let (outbound, writer, mut rx) = channel_outbound(16);
+        outbound.send_text(Priority::Stream, "last".into()).await.unwrap();
+        writer.finish(true).await;
+
+        assert_eq!(rx.recv().await.unwrap().to_text().unwrap(), "last");
+        assert!(matches!(rx.recv().await, Some(Message::Close(None))));
+        assert!(rx.recv().await.is_none());
+
+        // 写任务结束后发送失败
+        assert!(outbound.send_text(Priority::Control, "x".into()).await.is_err());
+        assert!(outbound.try_send_text(Priority::Stream, "x".into()).is_err());
+        assert!(outbound.send_latest("audio_level", "x".into(), None).is_err());
+    }
+}
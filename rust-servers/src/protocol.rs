@@ -0,0 +1,236 @@
+// 协议描述
+// 请求和服务器消息的负载均为带 JSON Schema 的类型，各模块在 BUILTIN_MODULES 中登记自己的消息，
+// `--print-schema` 据此导出整个协议的 JSON Schema，供插件生成 TypeScript 类型并校验两端是否一致
+//
+// 导出的 schema 只包含已编译的模块
+
+use schemars::generate::SchemaSettings;
+use schemars::{JsonSchema, Schema, SchemaGenerator};
+
+use crate::error::ErrorPayload;
+use crate::router::{
+    HelloRequest, HelloResponse, ModuleType, RequestId, ShuttingDownNotice, BUILTIN_MODULES,
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+
+// ============================================================================
+// 消息负载
+// ============================================================================
+
+/// 消息负载 (消息中除 `module`、`type`、`id` 以外的字段)
+pub trait Payload: JsonSchema {
+    /// 消息类型 (`type` 字段)
+    const TYPE: &'static str;
+}
+
+/// 单条消息: 消息类型和负载的 schema
+#[derive(Clone, Copy)]
+struct MessageSpec {
+    msg_type: &'static str,
+    schema: fn(&mut SchemaGenerator) -> Schema,
+}
+
+impl MessageSpec {
+    fn of<P: Payload>() -> Self {
+        Self {
+            msg_type: P::TYPE,
+            schema: |generator| generator.subschema_for::<P>(),
+        }
+    }
+}
+
+/// 模块的协议: 客户端可发送的请求和服务器发送的消息
+///
+/// 路由器直接处理的消息 (`hello`、`error`) 不需要登记
+#[derive(Default)]
+pub struct ModuleProtocol {
+    requests: Vec<MessageSpec>,
+    events: Vec<MessageSpec>,
+}
+
+impl ModuleProtocol {
+    /// 创建空的模块协议
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记客户端请求
+    pub fn request<P: Payload>(mut self) -> Self {
+        self.requests.push(MessageSpec::of::<P>());
+        self
+    }
+
+    /// 登记服务器消息 (请求的响应和主动推送的事件)
+    pub fn event<P: Payload>(mut self) -> Self {
+        self.events.push(MessageSpec::of::<P>());
+        self
+    }
+
+    /// 客户端请求的消息类型 (用于能力描述)
+    pub fn request_types(&self) -> Vec<&'static str> {
+        self.requests.iter().map(|spec| spec.msg_type).collect()
+    }
+}
+
+// ============================================================================
+// Schema 导出
+// ============================================================================
+
+/// 导出整个协议的 JSON Schema (draft 2020-12)
+///
+/// `$defs` 中的 `ClientMessage` 和 `ServerMessage` 分别是客户端和服务器所有消息的 `oneOf`，
+/// 每条消息由 `module`、`type`、可选的 `id` 和负载 (`allOf` 引用负载类型) 组成
+pub fn schema() -> serde_json::Value {
+    let mut generator = SchemaSettings::draft2020_12().into_generator();
+    let modules: Vec<&str> = BUILTIN_MODULES.iter().map(|builtin| builtin.name.as_str()).collect();
+
+    // 路由器直接处理的消息 (可发送到任意模块)，解析错误和关闭通知来自 utils 模块
+    let mut client = vec![envelope(&mut generator, &modules, MessageSpec::of::<HelloRequest>())];
+    let mut server = vec![
+        envelope(&mut generator, &modules, MessageSpec::of::<HelloResponse>()),
+        envelope(&mut generator, &modules, MessageSpec::of::<ErrorPayload>()),
+        envelope(&mut generator, &[ModuleType::UTILS.as_str()], MessageSpec::of::<ShuttingDownNotice>()),
+    ];
+
+    for builtin in BUILTIN_MODULES {
+        let protocol = (builtin.protocol)();
+        let module = [builtin.name.as_str()];
+        client.extend(protocol.requests.into_iter().map(|spec| envelope(&mut generator, &module, spec)));
+        server.extend(protocol.events.into_iter().map(|spec| envelope(&mut generator, &module, spec)));
+    }
+
+    let mut definitions = generator.take_definitions(true);
+    definitions.insert("ClientMessage".to_string(), serde_json::json!({
+        "description": "客户端发送的消息",
+        "oneOf": client,
+    }));
+    definitions.insert("ServerMessage".to_string(), serde_json::json!({
+        "description": "服务器发送的消息 (请求的响应和主动推送的事件)",
+        "oneOf": server,
+    }));
+
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "SmartWorkflowProtocol",
+        "description": "Smart Workflow Server 消息协议",
+        "x-server-version": env!("CARGO_PKG_VERSION"),
+        "x-protocol-version": PROTOCOL_VERSION,
+        "x-min-protocol-version": MIN_PROTOCOL_VERSION,
+        "oneOf": [
+            { "$ref": "#/$defs/ClientMessage" },
+            { "$ref": "#/$defs/ServerMessage" },
+        ],
+        "$defs": definitions,
+    })
+}
+
+/// 单条消息的 schema: 消息头 (`module`、`type`、`id`) 加负载
+fn envelope(generator: &mut SchemaGenerator, modules: &[&str], spec: MessageSpec) -> serde_json::Value {
+    let module = match modules {
+        [module] => serde_json::json!({ "const": module }),
+        _ => serde_json::json!({ "enum": modules }),
+    };
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "module": module,
+            "type": { "const": spec.msg_type },
+            "id": generator.subschema_for::<RequestId>(),
+        },
+        "required": ["module", "type"],
+        "allOf": [(spec.schema)(generator)],
+    })
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 收集 schema 中的所有 `$ref`
+    fn collect_refs(value: &serde_json::Value, refs: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(serde_json::Value::String(reference)) = map.get("$ref") {
+                    refs.push(reference.clone());
+                }
+                map.values().for_each(|v| collect_refs(v, refs));
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {}
+        }
+    }
+
+    /// 列出指定方向的所有 (module, type)
+    fn messages(schema: &serde_json::Value, direction: &str) -> Vec<(String, String)> {
+        let mut messages = Vec::new();
+        for message in schema["$defs"][direction]["oneOf"].as_array().unwrap() {
+            let msg_type = message["properties"]["type"]["const"].as_str().unwrap().to_string();
+            let module = &message["properties"]["module"];
+            let modules = match module.get("const") {
+                Some(name) => vec![name.clone()],
+                None => module["enum"].as_array().unwrap().clone(),
+            };
+            for name in modules {
+                messages.push((name.as_str().unwrap().to_string(), msg_type.clone()));
+            }
+        }
+        messages
+    }
+
+    #[test]
+    fn test_schema_refs_resolve() {
+        let schema = schema();
+        let mut refs = Vec::new();
+        collect_refs(&schema, &mut refs);
+        assert!(!refs.is_empty());
+        for reference in refs {
+            let name = reference.strip_prefix("#/$defs/").unwrap();
+            assert!(schema["$defs"].get(name).is_some(), "未定义的引用: {}", reference);
+        }
+    }
+
+    #[test]
+    fn test_schema_covers_module_messages() {
+        let schema = schema();
+        assert_eq!(schema["x-protocol-version"], PROTOCOL_VERSION);
+
+        let client = messages(&schema, "ClientMessage");
+        let server = messages(&schema, "ServerMessage");
+        let state = crate::router::ModuleState::default();
+        let config = crate::router::ModulesConfig::default();
+        for builtin in BUILTIN_MODULES {
+            let module = builtin.name.to_string();
+            assert!(client.contains(&(module.clone(), "hello".to_string())));
+            assert!(server.contains(&(module.clone(), "error".to_string())));
+
+            // 能力描述中的消息类型都有对应的 schema
+            let handler = (builtin.create)(&state, &config);
+            for msg_type in handler.capabilities().message_types {
+                assert!(client.contains(&(module.clone(), msg_type.to_string())), "{}/{}", module, msg_type);
+            }
+        }
+        assert!(server.contains(&("utils".to_string(), "server_shutting_down".to_string())));
+    }
+
+    #[cfg(feature = "pty")]
+    #[test]
+    fn test_schema_matches_serialized_payload() {
+        use crate::pty::InitComplete;
+
+        let schema = schema();
+        let payload = serde_json::to_value(InitComplete {
+            success: true,
+            session_id: 1,
+            session_token: "token".to_string(),
+        })
+        .unwrap();
+        let properties = schema["$defs"]["InitComplete"]["properties"].as_object().unwrap();
+        for field in payload.as_object().unwrap().keys() {
+            assert!(properties.contains_key(field), "schema 缺少字段: {}", field);
+        }
+    }
+}
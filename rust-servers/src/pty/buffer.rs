@@ -0,0 +1,102 @@
+// PTY 输出环形缓冲区
+// 保留会话最近的输出，客户端重新连接时回放
+
+use std::collections::VecDeque;
+
+/// 默认缓冲区容量 (256 KiB)
+pub const DEFAULT_OUTPUT_BUFFER_SIZE: usize = 256 * 1024;
+
+/// PTY 输出环形缓冲区
+///
+/// 超出容量时丢弃最早的数据。`offset` 为会话启动以来输出的总字节数，
+/// 客户端重新连接时可据此只回放尚未收到的部分。
+pub struct OutputBuffer {
+    data: VecDeque<u8>,
+    capacity: usize,
+    /// 累计写入的字节数
+    total_written: u64,
+}
+
+impl OutputBuffer {
+    /// 创建指定容量的缓冲区
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: VecDeque::with_capacity(capacity.min(DEFAULT_OUTPUT_BUFFER_SIZE)),
+            capacity,
+            total_written: 0,
+        }
+    }
+
+    /// 追加输出
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.total_written += bytes.len() as u64;
+
+        if bytes.len() >= self.capacity {
+            self.data.clear();
+            self.data.extend(&bytes[bytes.len() - self.capacity..]);
+            return;
+        }
+
+        let overflow = (self.data.len() + bytes.len()).saturating_sub(self.capacity);
+        self.data.drain(..overflow);
+        self.data.extend(bytes);
+    }
+
+    /// 累计写入的字节数
+    pub fn total_written(&self) -> u64 {
+        self.total_written
+    }
+
+    /// 获取从 `offset` 开始的输出
+    ///
+    /// `offset` 早于缓冲区中最早的数据时，返回缓冲区的全部内容
+    pub fn since(&self, offset: u64) -> Vec<u8> {
+        let start = self.total_written - self.data.len() as u64;
+        let skip = offset.saturating_sub(start).min(self.data.len() as u64) as usize;
+        self.data.range(skip..).copied().collect()
+    }
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_within_capacity() {
+        let mut buffer = OutputBuffer::new(16);
+        buffer.push(b"hello ");
+        buffer.push(b"world");
+
+        assert_eq!(buffer.since(0), b"hello world");
+        assert_eq!(buffer.total_written(), 11);
+    }
+
+    #[test]
+    fn test_push_drops_oldest() {
+        let mut buffer = OutputBuffer::new(8);
+        buffer.push(b"abcdef");
+        buffer.push(b"ghij");
+        assert_eq!(buffer.since(0), b"cdefghij");
+
+        // 单次写入超过容量时只保留末尾
+        buffer.push(b"0123456789");
+        assert_eq!(buffer.since(0), b"23456789");
+        assert_eq!(buffer.total_written(), 20);
+    }
+
+    #[test]
+    fn test_since_offset() {
+        let mut buffer = OutputBuffer::new(8);
+        buffer.push(b"abcdefghij");
+
+        // 缓冲区保存的是偏移 2..10
+        assert_eq!(buffer.since(5), b"fghij");
+        assert_eq!(buffer.since(1), b"cdefghij");
+        assert!(buffer.since(10).is_empty());
+        assert!(buffer.since(99).is_empty());
+    }
+}
@@ -0,0 +1,433 @@
+// Shell Integration 标记解析
+// 从 PTY 输出中提取注入脚本 (或 shell 自身) 发出的 OSC 序列:
+// - OSC 7 `file://host/path`: 当前工作目录
+// - OSC 133 `A` / `C;cmdline_url=...` / `D;exit_code`: 提示符开始、命令开始执行、命令结束
+// 输出仍原样发送给客户端，这里只做旁路解析，并据此跟踪会话的工作目录和命令
+
+use std::time::Instant;
+
+use super::{CommandFinishedMessage, CommandStartedMessage, CwdChangedMessage, CwdSource, SessionId};
+
+/// OSC 序列的最大长度，超过时丢弃该序列 (避免未终止的序列占用内存)
+const MAX_OSC_LEN: usize = 4096;
+
+// ============================================================================
+// 标记解析
+// ============================================================================
+
+/// Shell Integration 标记
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShellMark {
+    /// 当前工作目录 (OSC 7)
+    Cwd(String),
+    /// 提示符开始 (OSC 133;A)
+    PromptStart,
+    /// 命令开始执行 (OSC 133;C)
+    CommandStart { command: Option<String> },
+    /// 命令结束 (OSC 133;D)
+    CommandEnd { exit_code: Option<i32> },
+}
+
+/// 解析器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// 普通输出
+    Ground,
+    /// 收到 ESC
+    Escape,
+    /// OSC 序列内
+    Osc,
+    /// OSC 序列内收到 ESC (可能是 ST 终止符 `ESC \`)
+    OscEscape,
+}
+
+/// OSC 标记解析器
+///
+/// 逐字节解析，序列可以跨越多次读取
+pub struct MarkParser {
+    state: State,
+    /// 当前 OSC 序列的内容 (不含 `ESC ]` 和终止符)
+    osc: Vec<u8>,
+    /// 当前 OSC 序列超长，结束时丢弃
+    overflow: bool,
+}
+
+impl MarkParser {
+    /// 创建解析器
+    pub fn new() -> Self {
+        Self {
+            state: State::Ground,
+            osc: Vec::new(),
+            overflow: false,
+        }
+    }
+
+    /// 解析一段输出
+    ///
+    /// 返回本段中结束的标记，以及标记之后第一个字节在 `data` 中的位置
+    pub fn feed(&mut self, data: &[u8]) -> Vec<(usize, ShellMark)> {
+        let mut marks = Vec::new();
+
+        for (i, &byte) in data.iter().enumerate() {
+            match self.state {
+                State::Ground => {
+                    if byte == 0x1b {
+                        self.state = State::Escape;
+                    }
+                }
+                State::Escape => self.escape(byte),
+                State::Osc => match byte {
+                    // BEL 终止
+                    0x07 => {
+                        if let Some(mark) = self.finish() {
+                            marks.push((i + 1, mark));
+                        }
+                    }
+                    0x1b => self.state = State::OscEscape,
+                    // CAN / SUB 取消序列
+                    0x18 | 0x1a => self.state = State::Ground,
+                    _ => {
+                        if self.osc.len() < MAX_OSC_LEN {
+                            self.osc.push(byte);
+                        } else {
+                            self.overflow = true;
+                        }
+                    }
+                },
+                State::OscEscape => {
+                    if byte == b'\\' {
+                        // ST 终止
+                        if let Some(mark) = self.finish() {
+                            marks.push((i + 1, mark));
+                        }
+                    } else {
+                        // 序列被新的转义序列打断
+                        self.escape(byte);
+                    }
+                }
+            }
+        }
+
+        marks
+    }
+
+    /// 处理 ESC 之后的字节
+    fn escape(&mut self, byte: u8) {
+        self.state = match byte {
+            b']' => {
+                self.osc.clear();
+                self.overflow = false;
+                State::Osc
+            }
+            0x1b => State::Escape,
+            _ => State::Ground,
+        };
+    }
+
+    /// 结束当前 OSC 序列并解析
+    fn finish(&mut self) -> Option<ShellMark> {
+        self.state = State::Ground;
+        if self.overflow {
+            return None;
+        }
+        parse_osc(&String::from_utf8_lossy(&self.osc))
+    }
+}
+
+impl Default for MarkParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 解析 OSC 序列内容 (不关心的序列返回 None)
+fn parse_osc(osc: &str) -> Option<ShellMark> {
+    let (command, params) = osc.split_once(';')?;
+    match command {
+        "7" => parse_file_url(params).map(ShellMark::Cwd),
+        "133" => {
+            let mut params = params.split(';');
+            match params.next()? {
+                "A" => Some(ShellMark::PromptStart),
+                "C" => {
+                    let command = params
+                        .find_map(|param| param.strip_prefix("cmdline_url="))
+                        .map(|command| percent_decode(command).trim().to_string())
+                        .filter(|command| !command.is_empty());
+                    Some(ShellMark::CommandStart { command })
+                }
+                "D" => {
+                    let exit_code = params.next().and_then(|code| code.trim().parse().ok());
+                    Some(ShellMark::CommandEnd { exit_code })
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// 从 `file://host/path` 中提取路径
+fn parse_file_url(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    Some(percent_decode(path))
+}
+
+/// 百分号解码 (无效的转义原样保留)
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = s.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// ============================================================================
+// 会话状态跟踪
+// ============================================================================
+
+/// Shell 状态事件
+#[derive(Debug)]
+pub enum ShellEvent {
+    CwdChanged(CwdChangedMessage),
+    CommandStarted(CommandStartedMessage),
+    CommandFinished(CommandFinishedMessage),
+}
+
+/// 正在执行的命令
+struct RunningCommand {
+    command: Option<String>,
+    cwd: Option<String>,
+    started_at: Instant,
+}
+
+/// Shell 状态跟踪器
+///
+/// 根据 Shell Integration 标记跟踪工作目录和命令生命周期，生成 `cwd_changed`、
+/// `command_started`、`command_finished` 事件。
+/// 重复的开始标记和没有对应开始的结束标记被忽略 (自带 OSC 133 的 shell 会与注入脚本重复发送)。
+/// 收到 OSC 7 之前，每段输出后从 shell 进程读取工作目录 (仅 Linux)
+pub struct ShellTracker {
+    session_id: SessionId,
+    /// shell 进程 PID
+    pid: Option<u32>,
+    parser: MarkParser,
+    /// 当前工作目录及其来源
+    cwd: Option<(String, CwdSource)>,
+    running: Option<RunningCommand>,
+}
+
+impl ShellTracker {
+    /// 创建会话的状态跟踪器
+    pub fn new(session_id: SessionId, pid: Option<u32>) -> Self {
+        Self {
+            session_id,
+            pid,
+            parser: MarkParser::new(),
+            cwd: None,
+            running: None,
+        }
+    }
+
+    /// 解析一段输出
+    ///
+    /// `offset` 为这段输出之前的累计输出字节数，用于计算事件在输出流中的位置
+    pub fn feed(&mut self, data: &[u8], offset: u64) -> Vec<ShellEvent> {
+        let mut events = Vec::new();
+
+        for (end, mark) in self.parser.feed(data) {
+            let output_offset = offset + end as u64;
+            match mark {
+                ShellMark::Cwd(cwd) => self.update_cwd(cwd, CwdSource::Shell, &mut events),
+                ShellMark::PromptStart => {}
+                ShellMark::CommandStart { command } => {
+                    if self.running.is_some() {
+                        continue;
+                    }
+                    let cwd = self.cwd.as_ref().map(|(cwd, _)| cwd.clone());
+                    events.push(ShellEvent::CommandStarted(CommandStartedMessage {
+                        session_id: self.session_id,
+                        command: command.clone(),
+                        cwd: cwd.clone(),
+                        output_offset,
+                    }));
+                    self.running = Some(RunningCommand {
+                        command,
+                        cwd,
+                        started_at: Instant::now(),
+                    });
+                }
+                ShellMark::CommandEnd { exit_code } => {
+                    let Some(running) = self.running.take() else {
+                        continue;
+                    };
+                    events.push(ShellEvent::CommandFinished(CommandFinishedMessage {
+                        session_id: self.session_id,
+                        command: running.command,
+                        cwd: running.cwd,
+                        exit_code,
+                        duration_ms: running.started_at.elapsed().as_millis() as u64,
+                        output_offset,
+                    }));
+                }
+            }
+        }
+
+        // shell 未上报工作目录时从进程读取
+        let reported = matches!(self.cwd, Some((_, CwdSource::Shell)));
+        if !reported {
+            if let Some(cwd) = self.pid.and_then(process_cwd) {
+                self.update_cwd(cwd, CwdSource::Process, &mut events);
+            }
+        }
+
+        events
+    }
+
+    /// 更新工作目录，路径或来源变化时生成 `cwd_changed` 事件
+    fn update_cwd(&mut self, cwd: String, source: CwdSource, events: &mut Vec<ShellEvent>) {
+        if self.cwd.as_ref().is_some_and(|(current, current_source)| *current == cwd && *current_source == source) {
+            return;
+        }
+        events.push(ShellEvent::CwdChanged(CwdChangedMessage {
+            session_id: self.session_id,
+            cwd: cwd.clone(),
+            source,
+        }));
+        self.cwd = Some((cwd, source));
+    }
+}
+
+/// 读取进程的工作目录 (仅 Linux 支持)
+pub fn process_cwd(pid: u32) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_link(format!("/proc/{}/cwd", pid))
+            .ok()
+            .map(|path| path.to_string_lossy().into_owned())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid; // 避免未使用警告
+        None
+    }
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_marks() {
+        let mut parser = MarkParser::new();
+        let data = b"\x1b]7;file://host/tmp/a%20b\x1b\\$ \x1b]133;A\x07ls\r\n\x1b]133;C;cmdline_url=ls%20-la\x07out\x1b]133;D;2\x07";
+        let marks: Vec<ShellMark> = parser.feed(data).into_iter().map(|(_, mark)| mark).collect();
+        assert_eq!(marks, vec![
+            ShellMark::Cwd("/tmp/a b".into()),
+            ShellMark::PromptStart,
+            ShellMark::CommandStart { command: Some("ls -la".into()) },
+            ShellMark::CommandEnd { exit_code: Some(2) },
+        ]);
+
+        // 颜色等其他转义序列和未知的 OSC 序列被忽略
+        assert!(parser.feed(b"\x1b[31mred\x1b[0m\x1b]0;title\x07\x1b]133;B\x07").is_empty());
+    }
+
+    #[test]
+    fn test_parse_split_sequence() {
+        let mut parser = MarkParser::new();
+        assert!(parser.feed(b"out\x1b]13").is_empty());
+        assert!(parser.feed(b"3;D").is_empty());
+        assert_eq!(parser.feed(b";0\x1b").len(), 0);
+        assert_eq!(parser.feed(b"\\rest"), vec![(1, ShellMark::CommandEnd { exit_code: Some(0) })]);
+    }
+
+    #[test]
+    fn test_parse_overflow_and_interrupted() {
+        let mut parser = MarkParser::new();
+        let mut data = b"\x1b]133;C;cmdline_url=".to_vec();
+        data.extend(std::iter::repeat_n(b'x', MAX_OSC_LEN));
+        data.push(0x07);
+        assert!(parser.feed(&data).is_empty());
+
+        // 被新序列打断的 OSC 不产生标记，新序列正常解析
+        assert_eq!(
+            parser.feed(b"\x1b]133;A\x1b[0m\x1b]133;D\x07"),
+            vec![(19, ShellMark::CommandEnd { exit_code: None })]
+        );
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%3Bb%0Ac"), "a;b\nc");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%e4%b8%ad"), "%zz中");
+    }
+
+    #[test]
+    fn test_command_tracker() {
+        let mut tracker = ShellTracker::new(7, None);
+
+        // 没有对应开始的结束标记被忽略
+        assert!(tracker.feed(b"\x1b]133;D;0\x07", 0).is_empty());
+
+        let data = b"\x1b]7;file://h/work\x07\x1b]133;C;cmdline_url=make\x07\x1b]133;C\x07";
+        let events = tracker.feed(data, 100);
+        let [ShellEvent::CwdChanged(cwd), ShellEvent::CommandStarted(started)] = events.as_slice() else {
+            panic!("expected cwd_changed and command_started: {:?}", events)
+        };
+        assert_eq!(cwd.cwd, "/work");
+        assert_eq!(cwd.source, CwdSource::Shell);
+        assert_eq!(started.session_id, 7);
+        assert_eq!(started.command.as_deref(), Some("make"));
+        assert_eq!(started.cwd.as_deref(), Some("/work"));
+        assert_eq!(started.output_offset, 100 + 43);
+
+        // 工作目录未变化时不重复上报
+        let events = tracker.feed(b"done\x1b]133;D;1\x07\x1b]7;file://h/work\x07", 200);
+        let [ShellEvent::CommandFinished(finished)] = events.as_slice() else {
+            panic!("expected command_finished: {:?}", events)
+        };
+        assert_eq!(finished.command.as_deref(), Some("make"));
+        assert_eq!(finished.cwd.as_deref(), Some("/work"));
+        assert_eq!(finished.exit_code, Some(1));
+        assert_eq!(finished.output_offset, 200 + 14);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_cwd_fallback() {
+        let expected = std::env::current_dir().unwrap().to_string_lossy().into_owned();
+        assert_eq!(process_cwd(std::process::id()).as_deref(), Some(expected.as_str()));
+
+        let mut tracker = ShellTracker::new(1, Some(std::process::id()));
+        let events = tracker.feed(b"$ ", 0);
+        let [ShellEvent::CwdChanged(cwd)] = events.as_slice() else {
+            panic!("expected cwd_changed: {:?}", events)
+        };
+        assert_eq!(cwd.cwd, expected);
+        assert_eq!(cwd.source, CwdSource::Process);
+        assert!(tracker.feed(b"$ ", 2).is_empty());
+
+        // 收到 OSC 7 后以 shell 上报的为准
+        let events = tracker.feed(b"\x1b]7;file://h/remote\x07", 4);
+        assert!(matches!(events.as_slice(), [ShellEvent::CwdChanged(cwd)] if cwd.cwd == "/remote"));
+        assert!(tracker.feed(b"$ ", 30).is_empty());
+    }
+}
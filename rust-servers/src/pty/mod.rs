@@ -1,39 +1,617 @@
 // PTY 模块
 // 提供终端会话管理功能
 
+mod buffer;
+mod integration;
+mod registry;
+mod screen;
+mod search;
 mod session;
 mod shell;
 
+pub use buffer::{OutputBuffer, DEFAULT_OUTPUT_BUFFER_SIZE};
+pub use registry::{ConnectionId, DetachConfig, SessionInfo, SessionRegistry, DEFAULT_GRACE_PERIOD};
+pub use screen::DEFAULT_SCREEN_HISTORY;
+pub use search::DEFAULT_SEARCH_BUFFER_SIZE;
 pub use session::{PtySession, PtyReader, PtyWriter};
-pub use shell::{get_shell_by_type, get_shell_integration_script, get_default_shell};
+pub use shell::{get_shell_by_type, get_shell_integration_script, get_default_shell, SUPPORTED_SHELL_TYPES, SHELL_INTEGRATION_TYPES};
 
-use crate::router::{ModuleHandler, ModuleMessage, ModuleType, RouterError, ServerResponse};
-use crate::server::WsSender;
+use crate::error::{ErrorCode, ServerError};
+use crate::frame::{self, BinarySender};
+use crate::i18n::Msg;
+use crate::protocol::{ModuleProtocol, Payload};
+use crate::router::{ModuleCapabilities, ModuleContext, ModuleHandler, ModuleMessage, ModuleType, RequestId, RouterError, ServerResponse};
+use crate::server::{send_response, WsSender};
+use registry::SessionHandle;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use tokio::sync::Mutex as TokioMutex;
-use tokio_tungstenite::tungstenite::Message;
-use futures_util::SinkExt;
 
-/// 日志宏
-macro_rules! log_info {
-    ($($arg:tt)*) => {
-        eprintln!("[INFO] [PTY] {}", format!($($arg)*));
-    };
+/// PTY 会话 ID
+pub type SessionId = u32;
+
+/// PTY 模块配置（来自命令行 / 配置文件）
+#[derive(Debug, Clone, Default)]
+pub struct PtyConfig {
+    /// init 未指定 shell_type 时使用的 shell 类型 (同 shell_type 取值，如 zsh、custom:/path)
+    pub default_shell: Option<String>,
+    /// init 未指定 shell_args 时使用的启动参数
+    pub default_shell_args: Option<Vec<String>>,
+    /// 会话保活配置 (宽限期、输出缓冲区大小、回滚历史行数、检索缓冲区大小)
+    pub detach: DetachConfig,
+}
+
+// ============================================================================
+// 消息类型定义
+// ============================================================================
+
+/// 终端尺寸 (init 请求和 resize_complete 响应共用)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TerminalSize {
+    /// 列数
+    #[serde(default = "default_cols")]
+    pub cols: u16,
+    /// 行数
+    #[serde(default = "default_rows")]
+    pub rows: u16,
+    /// 终端区域宽度 (像素，0 表示未知)
+    #[serde(default)]
+    pub pixel_width: u16,
+    /// 终端区域高度 (像素，0 表示未知)
+    #[serde(default)]
+    pub pixel_height: u16,
+}
+
+impl Default for TerminalSize {
+    fn default() -> Self {
+        Self {
+            cols: default_cols(),
+            rows: default_rows(),
+            pixel_width: 0,
+            pixel_height: 0,
+        }
+    }
+}
+
+fn default_cols() -> u16 {
+    80
+}
+
+fn default_rows() -> u16 {
+    24
+}
+
+/// init 请求: 创建 PTY 会话
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct InitRequest {
+    /// Shell 类型 (如 zsh、powershell、custom:/bin/fish)，未指定时使用服务器默认 shell
+    #[serde(default)]
+    pub shell_type: Option<String>,
+    /// Shell 启动参数
+    #[serde(default)]
+    pub shell_args: Option<Vec<String>>,
+    /// 工作目录
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// 额外的环境变量
+    #[serde(default)]
+    pub env: Option<HashMap<String, String>>,
+    /// 初始终端尺寸 (未指定时为 80x24)，在 shell 启动前生效
+    #[serde(flatten)]
+    pub size: TerminalSize,
+}
+
+impl Payload for InitRequest {
+    const TYPE: &'static str = "init";
+}
+
+/// reattach 请求: 凭会话令牌接管已有会话
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ReattachRequest {
+    /// init / reattach 响应中的会话令牌
+    pub session_token: String,
+    /// 客户端已收到的输出字节数，之后缓存的输出会被回放
+    #[serde(default)]
+    pub offset: Option<u64>,
+}
+
+impl Payload for ReattachRequest {
+    const TYPE: &'static str = "reattach";
+}
+
+/// input 请求: 写入终端输入
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct InputRequest {
+    /// 目标会话 (未指定时使用最近创建的会话)
+    #[serde(default)]
+    pub session_id: Option<SessionId>,
+    /// 输入数据
+    pub data: InputData,
 }
 
-macro_rules! log_error {
-    ($($arg:tt)*) => {
-        eprintln!("[ERROR] [PTY] {}", format!($($arg)*));
-    };
+impl Payload for InputRequest {
+    const TYPE: &'static str = "input";
 }
 
-macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        if cfg!(debug_assertions) {
-            eprintln!("[DEBUG] [PTY] {}", format!($($arg)*));
+/// 终端输入数据: 文本或字节数组
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum InputData {
+    /// UTF-8 文本
+    Text(String),
+    /// 原始字节
+    Bytes(Vec<u8>),
+}
+
+impl InputData {
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            InputData::Text(text) => text.into_bytes(),
+            InputData::Bytes(bytes) => bytes,
         }
-    };
+    }
+}
+
+/// resize 请求: 调整终端尺寸
+///
+/// 列数和行数必须指定，缺少时请求无效 (不回退为默认尺寸)
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ResizeRequest {
+    /// 目标会话 (未指定时使用最近创建的会话)
+    #[serde(default)]
+    pub session_id: Option<SessionId>,
+    /// 列数
+    pub cols: u16,
+    /// 行数
+    pub rows: u16,
+    /// 终端区域宽度 (像素，0 表示未知)
+    #[serde(default)]
+    pub pixel_width: u16,
+    /// 终端区域高度 (像素，0 表示未知)
+    #[serde(default)]
+    pub pixel_height: u16,
+}
+
+impl ResizeRequest {
+    /// 目标尺寸
+    pub fn size(&self) -> TerminalSize {
+        TerminalSize {
+            cols: self.cols,
+            rows: self.rows,
+            pixel_width: self.pixel_width,
+            pixel_height: self.pixel_height,
+        }
+    }
+}
+
+impl Payload for ResizeRequest {
+    const TYPE: &'static str = "resize";
+}
+
+/// env 请求 (只记录日志，环境变量在 init 时设置)
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct EnvRequest {
+    /// 目标会话
+    #[serde(default)]
+    pub session_id: Option<SessionId>,
+    /// 工作目录
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// 环境变量
+    #[serde(default)]
+    pub env: Option<HashMap<String, String>>,
+}
+
+impl Payload for EnvRequest {
+    const TYPE: &'static str = "env";
+}
+
+/// kill 请求: 终止会话
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct KillRequest {
+    /// 目标会话 (未指定时使用最近创建的会话)
+    #[serde(default)]
+    pub session_id: Option<SessionId>,
+}
+
+impl Payload for KillRequest {
+    const TYPE: &'static str = "kill";
+}
+
+/// init 响应 (先于该会话的输出发送)
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct InitComplete {
+    /// 始终为 true (失败时返回错误消息)
+    pub success: bool,
+    /// 会话 ID (二进制帧的流 ID)
+    pub session_id: SessionId,
+    /// 会话令牌 (连接断开后凭此 reattach)
+    pub session_token: String,
+}
+
+impl Payload for InitComplete {
+    const TYPE: &'static str = "init_complete";
+}
+
+/// reattach 响应 (先于回放的输出发送)
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ReattachComplete {
+    /// 始终为 true (失败时返回错误消息)
+    pub success: bool,
+    /// 会话 ID
+    pub session_id: SessionId,
+    /// 会话令牌
+    pub session_token: String,
+}
+
+impl Payload for ReattachComplete {
+    const TYPE: &'static str = "reattach_complete";
+}
+
+/// kill 响应
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct KillComplete {
+    /// 已终止的会话 ID
+    pub session_id: SessionId,
+}
+
+impl Payload for KillComplete {
+    const TYPE: &'static str = "kill_complete";
+}
+
+/// resize 响应
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ResizeComplete {
+    /// 会话 ID
+    pub session_id: SessionId,
+    /// 调整后 PTY 实际生效的尺寸
+    #[serde(flatten)]
+    pub size: TerminalSize,
+}
+
+impl Payload for ResizeComplete {
+    const TYPE: &'static str = "resize_complete";
+}
+
+/// get_cwd 请求: 查询会话的当前工作目录
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetCwdRequest {
+    /// 目标会话 (未指定时使用最近创建的会话)
+    #[serde(default)]
+    pub session_id: Option<SessionId>,
+}
+
+impl Payload for GetCwdRequest {
+    const TYPE: &'static str = "get_cwd";
+}
+
+/// 工作目录来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CwdSource {
+    /// Shell Integration 上报 (OSC 7)
+    Shell,
+    /// 从 shell 进程读取 (Linux `/proc/<pid>/cwd`)
+    Process,
+}
+
+/// 工作目录变化通知
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CwdChangedMessage {
+    /// 会话 ID
+    pub session_id: SessionId,
+    /// 当前工作目录
+    pub cwd: String,
+    /// 工作目录来源
+    pub source: CwdSource,
+}
+
+impl Payload for CwdChangedMessage {
+    const TYPE: &'static str = "cwd_changed";
+}
+
+/// get_cwd 响应
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CwdResponse {
+    /// 会话 ID
+    pub session_id: SessionId,
+    /// 当前工作目录 (未知时省略)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// 工作目录来源 (未知时省略)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<CwdSource>,
+}
+
+impl Payload for CwdResponse {
+    const TYPE: &'static str = "cwd";
+}
+
+/// 屏幕内容格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenFormat {
+    /// 纯文本 (省略行尾空白)
+    #[default]
+    Text,
+    /// 带 SGR 颜色和属性的文本
+    Ansi,
+    /// 带内联样式的 HTML
+    Html,
+}
+
+/// snapshot 请求: 获取会话的可见屏幕
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SnapshotRequest {
+    /// 目标会话 (未指定时使用最近创建的会话)
+    #[serde(default)]
+    pub session_id: Option<SessionId>,
+    /// 内容格式 (默认为纯文本)
+    #[serde(default)]
+    pub format: ScreenFormat,
+}
+
+impl Payload for SnapshotRequest {
+    const TYPE: &'static str = "snapshot";
+}
+
+/// 光标位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CursorPosition {
+    /// 行 (从 0 开始)
+    pub row: usize,
+    /// 列 (从 0 开始)
+    pub col: usize,
+}
+
+/// snapshot 响应
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SnapshotResponse {
+    /// 会话 ID
+    pub session_id: SessionId,
+    /// 内容格式
+    pub format: ScreenFormat,
+    /// 屏幕内容: 文本按行以 `\n` 连接，ANSI 以 `\r\n` 连接，HTML 为 `<pre>` 片段
+    pub content: String,
+    /// 列数
+    pub cols: usize,
+    /// 行数
+    pub rows: usize,
+    /// 光标位置 (光标隐藏时省略)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<CursorPosition>,
+    /// 是否处于备用屏幕 (全屏程序运行时，此时没有回滚历史)
+    pub alternate_screen: bool,
+    /// 回滚历史行数 (scrollback_range 的范围)
+    pub history_lines: usize,
+    /// 快照对应的输出位置 (累计字节数，可作为 reattach 的 offset 接续实时输出)
+    pub output_offset: u64,
+}
+
+impl Payload for SnapshotResponse {
+    const TYPE: &'static str = "screen_snapshot";
+}
+
+/// scrollback_range 请求: 获取回滚历史中的行
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ScrollbackRangeRequest {
+    /// 目标会话 (未指定时使用最近创建的会话)
+    #[serde(default)]
+    pub session_id: Option<SessionId>,
+    /// 起始行 (0 为最早保留的一行)
+    #[serde(default)]
+    pub start: usize,
+    /// 行数 (默认 100，最多 1000)
+    #[serde(default = "default_scrollback_count")]
+    pub count: usize,
+    /// 内容格式 (默认为纯文本)
+    #[serde(default)]
+    pub format: ScreenFormat,
+}
+
+impl Payload for ScrollbackRangeRequest {
+    const TYPE: &'static str = "scrollback_range";
+}
+
+/// scrollback_range 默认行数
+fn default_scrollback_count() -> usize {
+    100
+}
+
+/// scrollback_range 单次最多返回的行数
+const MAX_SCROLLBACK_COUNT: usize = 1000;
+
+/// scrollback_range 响应
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ScrollbackRangeResponse {
+    /// 会话 ID
+    pub session_id: SessionId,
+    /// 内容格式
+    pub format: ScreenFormat,
+    /// 第一行的位置
+    pub start: usize,
+    /// 各行内容 (超出历史范围的部分被省略；HTML 格式的每行是不含 `<pre>` 的片段)
+    pub lines: Vec<String>,
+    /// 当前回滚历史行数 (历史已满时，新输出会使最早的行被丢弃、位置前移)
+    pub history_lines: usize,
+}
+
+impl Payload for ScrollbackRangeResponse {
+    const TYPE: &'static str = "scrollback_lines";
+}
+
+/// search 请求: 检索会话的历史输出
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchRequest {
+    /// 目标会话 (未指定时使用最近创建的会话)
+    #[serde(default)]
+    pub session_id: Option<SessionId>,
+    /// 检索内容
+    pub query: String,
+    /// 是否按正则表达式匹配 (默认按字面文本)
+    #[serde(default)]
+    pub regex: bool,
+    /// 是否忽略大小写
+    #[serde(default)]
+    pub ignore_case: bool,
+    /// 每个匹配行前后附带的上下文行数 (默认 2，最多 10)
+    #[serde(default = "default_search_context")]
+    pub context: usize,
+    /// 最多返回的匹配行数 (默认 100，最多 1000)
+    #[serde(default = "default_search_max_results")]
+    pub max_results: usize,
+}
+
+impl Payload for SearchRequest {
+    const TYPE: &'static str = "search";
+}
+
+fn default_search_context() -> usize {
+    2
+}
+
+fn default_search_max_results() -> usize {
+    100
+}
+
+/// search 的上下文行数上限
+const MAX_SEARCH_CONTEXT: usize = 10;
+
+/// search 单次最多返回的匹配行数
+const MAX_SEARCH_RESULTS: usize = 1000;
+
+/// 匹配在行中的位置 (字符偏移，不含 `end`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SearchRange {
+    /// 起始字符
+    pub start: usize,
+    /// 结束字符
+    pub end: usize,
+}
+
+/// 匹配的行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SearchMatch {
+    /// 行号 (会话开始以来的行序号，从 0 开始)
+    pub line: u64,
+    /// 行内容 (已去除控制序列)
+    pub text: String,
+    /// 行中所有匹配的位置
+    pub ranges: Vec<SearchRange>,
+    /// 之前的上下文行
+    pub before: Vec<String>,
+    /// 之后的上下文行
+    pub after: Vec<String>,
+}
+
+/// search 响应
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SearchResponse {
+    /// 会话 ID
+    pub session_id: SessionId,
+    /// 匹配的行 (按行号升序)
+    pub matches: Vec<SearchMatch>,
+    /// 是否有更早的匹配因超过 max_results 未返回
+    pub truncated: bool,
+    /// 检索缓冲区中最早一行的行号 (更早的输出已超出缓冲区上限)
+    pub first_line: u64,
+    /// 检索缓冲区中的行数
+    pub line_count: usize,
+}
+
+impl Payload for SearchResponse {
+    const TYPE: &'static str = "search_results";
+}
+
+/// 命令开始执行 (需要 Shell Integration 或 shell 自带的 OSC 133 标记)
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CommandStartedMessage {
+    /// 会话 ID
+    pub session_id: SessionId,
+    /// 命令行 (shell 未上报时省略)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// 执行命令时的工作目录 (shell 未上报时省略)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// 命令输出在会话输出流中的起始位置 (累计字节数，与 reattach 的 offset 一致)
+    pub output_offset: u64,
+}
+
+impl Payload for CommandStartedMessage {
+    const TYPE: &'static str = "command_started";
+}
+
+/// 命令执行结束
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CommandFinishedMessage {
+    /// 会话 ID
+    pub session_id: SessionId,
+    /// 命令行 (shell 未上报时省略)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// 执行命令时的工作目录 (shell 未上报时省略)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// 退出码 (shell 未上报时省略)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// 执行耗时 (毫秒，按服务器收到开始、结束标记的时间计算)
+    pub duration_ms: u64,
+    /// 命令输出在会话输出流中的结束位置
+    pub output_offset: u64,
+}
+
+impl Payload for CommandFinishedMessage {
+    const TYPE: &'static str = "command_finished";
+}
+
+/// 会话结束通知
+///
+/// shell 进程退出或会话被终止时发送给会话当前归属的连接，排在该会话的全部输出之后；
+/// 发送前会话已从注册表中移除
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ExitMessage {
+    /// 已结束的会话 ID
+    pub session_id: SessionId,
+    /// 退出码 (被信号终止时为 1)
+    pub code: u32,
+    /// 终止进程的信号描述，如 `Killed` (正常退出时省略)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+}
+
+impl Payload for ExitMessage {
+    const TYPE: &'static str = "exit";
+}
+
+/// PTY 模块的请求和服务器消息
+pub fn protocol() -> ModuleProtocol {
+    ModuleProtocol::new()
+        .request::<InitRequest>()
+        .request::<ReattachRequest>()
+        .request::<InputRequest>()
+        .request::<ResizeRequest>()
+        .request::<EnvRequest>()
+        .request::<KillRequest>()
+        .request::<GetCwdRequest>()
+        .request::<SnapshotRequest>()
+        .request::<ScrollbackRangeRequest>()
+        .request::<SearchRequest>()
+        .event::<InitComplete>()
+        .event::<ReattachComplete>()
+        .event::<ResizeComplete>()
+        .event::<KillComplete>()
+        .event::<CwdResponse>()
+        .event::<CwdChangedMessage>()
+        .event::<SnapshotResponse>()
+        .event::<ScrollbackRangeResponse>()
+        .event::<SearchResponse>()
+        .event::<CommandStartedMessage>()
+        .event::<CommandFinishedMessage>()
+        .event::<ExitMessage>()
 }
 
 // ============================================================================
@@ -42,32 +620,51 @@ macro_rules! log_debug {
 
 /// PTY 模块处理器
 /// 
-/// 管理 PTY 会话的生命周期，处理终端相关的消息
+/// 处理终端相关的消息。会话登记在服务器范围的 [`SessionRegistry`] 中，
+/// 每个连接只能操作归属自己的会话；连接断开后会话进入宽限期，可通过 `reattach` 接管。
+/// 一个连接可以同时持有多个会话，消息通过 `session_id` 指定目标会话；
+/// 未指定 `session_id` 时使用最近创建的会话 (兼容单会话客户端)。
 pub struct PtyHandler {
-    /// 当前 PTY 会话 (每个连接一个会话)
-    session: TokioMutex<Option<Arc<TokioMutex<PtySession>>>>,
-    /// PTY 读取器
-    reader: TokioMutex<Option<Arc<Mutex<PtyReader>>>>,
-    /// PTY 写入器
-    writer: TokioMutex<Option<Arc<Mutex<PtyWriter>>>>,
-    /// WebSocket 发送器 (用于发送 PTY 输出)
+    /// 会话注册表
+    registry: Arc<SessionRegistry>,
+    /// 当前连接 ID
+    connection_id: ConnectionId,
+    /// 默认会话 (最近创建或接管的会话)
+    default_session: TokioMutex<Option<SessionId>>,
+    /// WebSocket 发送器 (用于发送 JSON 响应)
     ws_sender: TokioMutex<Option<WsSender>>,
-    /// 读取任务句柄
-    read_task: TokioMutex<Option<tokio::task::JoinHandle<()>>>,
-    /// Shell 类型 (用于 Shell Integration)
-    shell_type: TokioMutex<Option<String>>,
+    /// 二进制发送器 (用于发送 PTY 输出，流 ID 为 session_id)
+    binary_sender: TokioMutex<Option<BinarySender>>,
+    /// 默认 shell 类型
+    default_shell: Option<String>,
+    /// 默认 shell 启动参数
+    default_shell_args: Option<Vec<String>>,
 }
 
 impl PtyHandler {
-    /// 创建新的 PTY 处理器
+    /// 创建新的 PTY 处理器 (使用独立的会话注册表)
     pub fn new() -> Self {
+        Self::with_registry(Arc::new(SessionRegistry::default()))
+    }
+    
+    /// 使用共享的会话注册表创建 PTY 处理器
+    pub fn with_registry(registry: Arc<SessionRegistry>) -> Self {
+        Self::with_config(registry, &PtyConfig::default())
+    }
+    
+    /// 使用共享的会话注册表和模块配置创建 PTY 处理器
+    /// 
+    /// `config.detach` 由注册表的创建方使用，这里只取 shell 默认值。
+    pub fn with_config(registry: Arc<SessionRegistry>, config: &PtyConfig) -> Self {
+        let connection_id = registry.next_connection_id();
         Self {
-            session: TokioMutex::new(None),
-            reader: TokioMutex::new(None),
-            writer: TokioMutex::new(None),
+            registry,
+            connection_id,
+            default_session: TokioMutex::new(None),
             ws_sender: TokioMutex::new(None),
-            read_task: TokioMutex::new(None),
-            shell_type: TokioMutex::new(None),
+            binary_sender: TokioMutex::new(None),
+            default_shell: config.default_shell.clone(),
+            default_shell_args: config.default_shell_args.clone(),
         }
     }
     
@@ -77,242 +674,168 @@ impl PtyHandler {
         *ws_sender = Some(sender);
     }
     
+    /// 设置二进制发送器
+    pub async fn set_binary_sender(&self, sender: BinarySender) {
+        let mut binary_sender = self.binary_sender.lock().await;
+        *binary_sender = Some(sender);
+    }
+    
+    /// 获取发送器
+    async fn senders(&self) -> Result<(WsSender, BinarySender), RouterError> {
+        let ws_sender = self.ws_sender.lock().await.clone()
+            .ok_or_else(|| RouterError::module(Msg::ConnectionNotReady))?;
+        let binary_sender = self.binary_sender.lock().await.clone()
+            .ok_or_else(|| RouterError::module(Msg::ConnectionNotReady))?;
+        Ok((ws_sender, binary_sender))
+    }
+    
     /// 处理 init 消息 - 创建 PTY 会话
     async fn handle_init(
         &self,
-        shell_type: Option<String>,
-        shell_args: Option<Vec<String>>,
-        cwd: Option<String>,
-        env: Option<HashMap<String, String>>,
+        id: Option<RequestId>,
+        request: InitRequest,
     ) -> Result<Option<ServerResponse>, RouterError> {
-        log_info!("初始化 PTY 会话: shell_type={:?}, cwd={:?}", shell_type, cwd);
+        let InitRequest { shell_type, shell_args, cwd, env, size } = request;
+        let shell_type = shell_type.or_else(|| self.default_shell.clone());
+        let shell_args = shell_args.or_else(|| self.default_shell_args.clone());
+        log_info!(
+            "初始化 PTY 会话: shell_type={:?}, cwd={:?}, {}x{}",
+            shell_type, cwd, size.cols, size.rows
+        );
         
-        // 创建 PTY 会话
-        let (pty_session, pty_reader, pty_writer) = PtySession::new(
-            80,
-            24,
-            shell_type.as_deref(),
-            shell_args.as_ref().map(|v| v.as_slice()),
-            cwd.as_deref(),
-            env.as_ref(),
-        ).map_err(|e| RouterError::ModuleError(format!("创建 PTY 会话失败: {}", e)))?;
+        let (ws_sender, binary_sender) = self.senders().await?;
         
-        // 保存会话和读写器
-        let pty_session = Arc::new(TokioMutex::new(pty_session));
-        let pty_reader = Arc::new(Mutex::new(pty_reader));
-        let pty_writer = Arc::new(Mutex::new(pty_writer));
+        // 创建 PTY 会话 (附加到连接前输出先进入缓冲区)
+        let handle = self.registry
+            .create(self.connection_id, size, shell_type, shell_args, cwd, env)
+            .await?;
+        let session_id = handle.id();
+        *self.default_session.lock().await = Some(session_id);
         
-        {
-            let mut session = self.session.lock().await;
-            *session = Some(Arc::clone(&pty_session));
-        }
-        {
-            let mut reader = self.reader.lock().await;
-            *reader = Some(Arc::clone(&pty_reader));
-        }
-        {
-            let mut writer = self.writer.lock().await;
-            *writer = Some(Arc::clone(&pty_writer));
-        }
-        {
-            let mut st = self.shell_type.lock().await;
-            *st = shell_type.clone();
-        }
+        log_info!("PTY 会话创建成功: session_id={}", session_id);
         
-        // 启动 PTY 输出读取任务
-        self.start_read_task().await?;
+        // 先发送 init_complete，保证客户端在收到该会话的输出前已知道 session_id
+        let response = ServerResponse::from_payload(ModuleType::PTY, &InitComplete {
+            success: true,
+            session_id,
+            session_token: handle.token().to_string(),
+        }).with_id(id);
+        send_response(&ws_sender, &response).await
+            .map_err(|e| RouterError::module(Msg::SendFailed { error: e.to_string() }))?;
         
-        log_info!("PTY 会话创建成功");
+        // 开始向连接发送输出
+        self.registry.attach(&handle, self.connection_id, binary_sender, None).await?;
         
-        // 返回成功响应
-        Ok(Some(ServerResponse::new(
-            ModuleType::Pty,
-            "init_complete",
-            serde_json::json!({
-                "success": true
-            }),
-        )))
+        Ok(None)
     }
     
-    /// 启动 PTY 输出读取任务
-    async fn start_read_task(&self) -> Result<(), RouterError> {
-        let reader = {
-            let reader_guard = self.reader.lock().await;
-            reader_guard.clone()
-        };
-        
-        let ws_sender = {
-            let ws_sender_guard = self.ws_sender.lock().await;
-            ws_sender_guard.clone()
-        };
-        
-        let writer = {
-            let writer_guard = self.writer.lock().await;
-            writer_guard.clone()
-        };
+    /// 处理 reattach 消息 - 凭会话令牌接管已有会话
+    /// 
+    /// 回放 `offset` (客户端已收到的输出字节数) 之后缓存的输出，然后恢复实时输出
+    async fn handle_reattach(
+        &self,
+        id: Option<RequestId>,
+        session_token: &str,
+        offset: Option<u64>,
+    ) -> Result<Option<ServerResponse>, RouterError> {
+        let (ws_sender, binary_sender) = self.senders().await?;
         
-        let shell_type = {
-            let st = self.shell_type.lock().await;
-            st.clone()
-        };
+        let handle = self.registry.find_by_token(session_token).await
+            .ok_or_else(|| ServerError::new(ErrorCode::SessionNotFound, Msg::SessionTokenInvalid))?;
+        let session_id = handle.id();
         
-        let reader = reader.ok_or_else(|| RouterError::ModuleError("PTY reader not initialized".to_string()))?;
-        let ws_sender = ws_sender.ok_or_else(|| RouterError::ModuleError("WebSocket sender not set".to_string()))?;
+        log_info!("重新连接 PTY 会话: session_id={}, offset={:?}", session_id, offset);
         
-        // 启动读取任务
-        let task = tokio::spawn(async move {
-            let mut first_output = true;
-            
-            loop {
-                // 在阻塞任务中读取 PTY 输出
-                let reader_clone = Arc::clone(&reader);
-                let result = tokio::task::spawn_blocking(move || -> Result<(Vec<u8>, usize), String> {
-                    let mut reader = reader_clone.lock().unwrap();
-                    let mut local_buf = vec![0u8; 8192];
-                    match reader.read(&mut local_buf) {
-                        Ok(n) => Ok((local_buf, n)),
-                        Err(e) => Err(e.to_string()),
-                    }
-                }).await;
-                
-                match result {
-                    Ok(Ok((data, n))) if n > 0 => {
-                        log_debug!("读取 PTY 输出: {} 字节", n);
-                        
-                        // 构建带 module 字段的响应
-                        // 对于二进制数据，我们直接发送，TypeScript 端会根据连接上下文处理
-                        let mut sender = ws_sender.lock().await;
-                        if let Err(e) = sender.send(Message::Binary(data[..n].to_vec().into())).await {
-                            log_error!("发送 PTY 输出失败: {}", e);
-                            break;
-                        }
-                        drop(sender);
-                        
-                        // 首次输出后注入 Shell Integration 脚本
-                        if first_output {
-                            first_output = false;
-                            if let Some(ref st) = shell_type {
-                                if let Some(script) = get_shell_integration_script(st) {
-                                    if let Some(ref writer) = writer {
-                                        let mut w = writer.lock().unwrap();
-                                        if let Err(e) = w.write(script.as_bytes()) {
-                                            log_error!("发送 Shell Integration 脚本失败: {}", e);
-                                        } else {
-                                            log_debug!("Shell Integration 脚本已发送");
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Ok(Ok(_)) => {
-                        // EOF
-                        log_info!("PTY 输出结束");
-                        break;
-                    }
-                    Ok(Err(e)) => {
-                        log_error!("PTY 输出读取错误: {}", e);
-                        break;
-                    }
-                    Err(e) => {
-                        log_error!("PTY 读取任务错误: {}", e);
-                        break;
-                    }
-                }
-            }
-        });
+        let response = ServerResponse::from_payload(ModuleType::PTY, &ReattachComplete {
+            success: true,
+            session_id,
+            session_token: handle.token().to_string(),
+        }).with_id(id);
+        send_response(&ws_sender, &response).await
+            .map_err(|e| RouterError::module(Msg::SendFailed { error: e.to_string() }))?;
         
-        // 保存任务句柄
-        let mut read_task = self.read_task.lock().await;
-        *read_task = Some(task);
+        self.registry.attach(&handle, self.connection_id, binary_sender, offset).await?;
+        *self.default_session.lock().await = Some(session_id);
         
-        Ok(())
+        Ok(None)
     }
     
-    /// 处理 resize 消息 - 调整终端尺寸
-    async fn handle_resize(&self, cols: u16, rows: u16) -> Result<Option<ServerResponse>, RouterError> {
-        log_info!("调整终端尺寸: {}x{}", cols, rows);
-        
-        let session = {
-            let session_guard = self.session.lock().await;
-            session_guard.clone()
-        };
-        
-        if let Some(session) = session {
-            let mut pty = session.lock().await;
-            pty.resize(cols, rows)
-                .map_err(|e| RouterError::ModuleError(format!("调整终端尺寸失败: {}", e)))?;
-        } else {
-            return Err(RouterError::ModuleError("PTY 会话未初始化".to_string()));
+    /// 解析目标会话 ID
+    /// 
+    /// 未指定 `session_id` 时返回默认会话 (最近创建的会话)
+    async fn resolve_session_id(&self, session_id: Option<SessionId>) -> Result<SessionId, RouterError> {
+        match session_id {
+            Some(id) => Ok(id),
+            None => self.default_session.lock().await
+                .ok_or_else(|| ServerError::new(ErrorCode::SessionNotFound, Msg::SessionNotInitialized).into()),
         }
-        
-        Ok(None) // resize 不需要响应
     }
     
-    /// 写入数据到 PTY
-    pub async fn write_data(&self, data: &[u8]) -> Result<(), RouterError> {
-        let writer = {
-            let writer_guard = self.writer.lock().await;
-            writer_guard.clone()
-        };
+    /// 查找归属当前连接的会话
+    async fn get_session(&self, session_id: Option<SessionId>) -> Result<Arc<SessionHandle>, RouterError> {
+        let session_id = self.resolve_session_id(session_id).await?;
         
-        if let Some(writer) = writer {
-            let mut w = writer.lock().unwrap();
-            w.write(data)
-                .map_err(|e| RouterError::ModuleError(format!("写入 PTY 失败: {}", e)))?;
-        } else {
-            return Err(RouterError::ModuleError("PTY writer 未初始化".to_string()));
+        match self.registry.get(session_id).await {
+            Some(handle) if handle.is_owned_by(self.connection_id).await => Ok(handle),
+            _ => Err(ServerError::new(ErrorCode::SessionNotFound, Msg::SessionNotFound { session_id }).into()),
         }
-        
-        Ok(())
     }
     
-    /// 终止 PTY 会话
-    pub async fn kill(&self) -> Result<(), RouterError> {
-        log_info!("终止 PTY 会话");
-        
-        // 终止 PTY 进程
-        let session = {
-            let session_guard = self.session.lock().await;
-            session_guard.clone()
-        };
-        
-        if let Some(session) = session {
-            let mut pty = session.lock().await;
-            let _ = pty.kill();
-        }
+    /// 处理 resize 消息 - 调整终端尺寸
+    ///
+    /// 响应中返回 PTY 实际生效的尺寸
+    async fn handle_resize(
+        &self,
+        session_id: Option<SessionId>,
+        size: TerminalSize,
+    ) -> Result<Option<ServerResponse>, RouterError> {
+        log_info!("调整终端尺寸: session_id={:?}, {}x{}", session_id, size.cols, size.rows);
         
-        // 等待读取任务结束
-        let task = {
-            let mut read_task = self.read_task.lock().await;
-            read_task.take()
-        };
+        let handle = self.get_session(session_id).await?;
+        let size = handle.resize(size).await?;
         
-        if let Some(task) = task {
-            let _ = task.await;
-        }
+        Ok(Some(ServerResponse::from_payload(ModuleType::PTY, &ResizeComplete {
+            session_id: handle.id(),
+            size,
+        })))
+    }
+    
+    /// 写入数据到 PTY
+    /// 
+    /// 未指定 `session_id` 时写入默认会话
+    pub async fn write_data(&self, session_id: Option<SessionId>, data: &[u8]) -> Result<(), RouterError> {
+        let handle = self.get_session(session_id).await?;
+        handle.write(data)
+    }
+    
+    /// 终止单个 PTY 会话
+    pub async fn kill(&self, session_id: Option<SessionId>) -> Result<SessionId, RouterError> {
+        let handle = self.get_session(session_id).await?;
+        let session_id = handle.id();
         
-        // 清理状态
-        {
-            let mut session = self.session.lock().await;
-            *session = None;
-        }
-        {
-            let mut reader = self.reader.lock().await;
-            *reader = None;
-        }
         {
-            let mut writer = self.writer.lock().await;
-            *writer = None;
+            let mut default = self.default_session.lock().await;
+            if *default == Some(session_id) {
+                *default = None;
+            }
         }
         
-        Ok(())
+        self.registry.kill(session_id).await;
+        
+        Ok(session_id)
+    }
+    
+    /// 断开当前连接的所有 PTY 会话
+    /// 
+    /// 会话在宽限期内保持运行，可通过 `reattach` 重新连接
+    pub async fn detach_all(&self) {
+        *self.default_session.lock().await = None;
+        self.registry.detach_owner(self.connection_id).await;
     }
     
-    /// 检查会话是否已初始化
-    pub async fn is_initialized(&self) -> bool {
-        let session = self.session.lock().await;
-        session.is_some()
+    /// 检查当前连接是否存在活动会话
+    pub async fn has_sessions(&self) -> bool {
+        !self.registry.owned_by(self.connection_id).await.is_empty()
     }
 }
 
@@ -325,7 +848,24 @@ impl Default for PtyHandler {
 #[async_trait::async_trait]
 impl ModuleHandler for PtyHandler {
     fn module_type(&self) -> ModuleType {
-        ModuleType::Pty
+        ModuleType::PTY
+    }
+    
+    fn binary_channel(&self) -> Option<u8> {
+        Some(frame::PTY_CHANNEL)
+    }
+    
+    fn capabilities(&self) -> ModuleCapabilities {
+        ModuleCapabilities::new(&protocol().request_types(), serde_json::json!({
+            "shell_types": SUPPORTED_SHELL_TYPES,
+            "custom_shell": true,
+            "shell_integration": SHELL_INTEGRATION_TYPES,
+            "binary_input": true,
+            "binary_output": true,
+            "reattach": true,
+            "snapshot_formats": ["text", "ansi", "html"],
+            "search": true,
+        }))
     }
     
     async fn handle(&self, msg: &ModuleMessage) -> Result<Option<ServerResponse>, RouterError> {
@@ -333,30 +873,102 @@ impl ModuleHandler for PtyHandler {
         
         match msg.msg_type.as_str() {
             "init" => {
-                let shell_type: Option<String> = msg.get_field("shell_type");
-                let shell_args: Option<Vec<String>> = msg.get_field("shell_args");
-                let cwd: Option<String> = msg.get_field("cwd");
-                let env: Option<HashMap<String, String>> = msg.get_field("env");
-                
-                self.handle_init(shell_type, shell_args, cwd, env).await
+                let request: InitRequest = msg.parse()?;
+                self.handle_init(msg.id.clone(), request).await
+            }
+            "reattach" => {
+                let request: ReattachRequest = msg.parse()?;
+                self.handle_reattach(msg.id.clone(), &request.session_token, request.offset).await
+            }
+            "input" => {
+                let request: InputRequest = msg.parse()?;
+                self.write_data(request.session_id, &request.data.into_bytes()).await?;
+                Ok(None)
             }
             "resize" => {
-                let cols: u16 = msg.get_field("cols").unwrap_or(80);
-                let rows: u16 = msg.get_field("rows").unwrap_or(24);
-                
-                self.handle_resize(cols, rows).await
+                let request: ResizeRequest = msg.parse()?;
+                self.handle_resize(request.session_id, request.size()).await
             }
             "env" => {
                 // env 命令在原实现中只是记录日志，实际环境变量在 init 时设置
-                let cwd: Option<String> = msg.get_field("cwd");
-                let env: Option<HashMap<String, String>> = msg.get_field("env");
-                log_info!("收到 env 命令: cwd={:?}, env={:?}", cwd, env);
+                let request: EnvRequest = msg.parse()?;
+                log_info!(
+                    "收到 env 命令: session_id={:?}, cwd={:?}, env={:?}",
+                    request.session_id, request.cwd, request.env
+                );
                 Ok(None)
             }
+            "get_cwd" => {
+                let request: GetCwdRequest = msg.parse()?;
+                let handle = self.get_session(request.session_id).await?;
+                let (cwd, source) = handle.cwd().unzip();
+                
+                Ok(Some(ServerResponse::from_payload(ModuleType::PTY, &CwdResponse {
+                    session_id: handle.id(),
+                    cwd,
+                    source,
+                })))
+            }
+            "snapshot" => {
+                let request: SnapshotRequest = msg.parse()?;
+                let handle = self.get_session(request.session_id).await?;
+                
+                Ok(Some(ServerResponse::from_payload(ModuleType::PTY, &handle.snapshot(request.format).await)))
+            }
+            "scrollback_range" => {
+                let request: ScrollbackRangeRequest = msg.parse()?;
+                let handle = self.get_session(request.session_id).await?;
+                let response = handle
+                    .scrollback_range(request.start, request.count.min(MAX_SCROLLBACK_COUNT), request.format)
+                    .await;
+                
+                Ok(Some(ServerResponse::from_payload(ModuleType::PTY, &response)))
+            }
+            "search" => {
+                let request: SearchRequest = msg.parse()?;
+                let pattern = search::build_pattern(&request.query, request.regex, request.ignore_case)
+                    .map_err(|error| RouterError::InvalidMessage(Msg::InvalidField { field: "query", error }))?;
+                let handle = self.get_session(request.session_id).await?;
+                let response = handle.search(
+                    &pattern,
+                    request.context.min(MAX_SEARCH_CONTEXT),
+                    request.max_results.min(MAX_SEARCH_RESULTS),
+                ).await;
+                
+                Ok(Some(ServerResponse::from_payload(ModuleType::PTY, &response)))
+            }
+            "kill" => {
+                let request: KillRequest = msg.parse()?;
+                let session_id = self.kill(request.session_id).await?;
+                
+                Ok(Some(ServerResponse::from_payload(ModuleType::PTY, &KillComplete { session_id })))
+            }
             _ => {
                 log_debug!("未知的 PTY 消息类型: {}", msg.msg_type);
-                Err(RouterError::ModuleError(format!("未知的 PTY 消息类型: {}", msg.msg_type)))
+                Err(RouterError::InvalidMessage(Msg::UnknownMessageType {
+                    module: ModuleType::PTY.to_string(),
+                    msg_type: msg.msg_type.clone(),
+                }))
             }
         }
     }
+    
+    async fn handle_binary(&self, stream_id: Option<u32>, data: &[u8]) -> Result<(), RouterError> {
+        // 流 ID 即 session_id；流 ID 为 0 或旧版未分帧的数据写入默认会话
+        self.write_data(stream_id.filter(|&id| id != 0), data).await
+    }
+    
+    async fn accepts_raw_text(&self) -> bool {
+        self.has_sessions().await
+    }
+    
+    async fn on_connect(&self, context: &ModuleContext) {
+        self.set_binary_sender(context.binary_sender.clone()).await;
+        self.set_ws_sender(context.ws_sender.clone()).await;
+    }
+    
+    async fn on_disconnect(&self) {
+        // 宽限期内可通过 reattach 重新连接
+        self.detach_all().await;
+    }
 }
@@ -0,0 +1,646 @@
+// PTY 会话注册表
+// 会话在服务器范围内登记，不随 WebSocket 连接销毁:
+// 连接断开后会话进入宽限期并继续缓存输出，客户端可凭会话令牌重新连接
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use portable_pty::ExitStatus;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tokio::sync::Mutex as TokioMutex;
+
+use super::buffer::{OutputBuffer, DEFAULT_OUTPUT_BUFFER_SIZE};
+use super::integration::{process_cwd, ShellEvent, ShellTracker};
+use super::screen::{Screen, DEFAULT_SCREEN_HISTORY};
+use super::search::{SearchBuffer, DEFAULT_SEARCH_BUFFER_SIZE};
+use super::{
+    get_shell_integration_script, CursorPosition, CwdSource, ExitMessage, PtyReader, PtySession, PtyWriter, ScreenFormat,
+    ScrollbackRangeResponse, SearchResponse, SessionId, SnapshotResponse, TerminalSize,
+};
+use crate::error::{ErrorCode, ServerError};
+use crate::frame::{self, BinarySender};
+use crate::i18n::Msg;
+use crate::protocol::Payload;
+use crate::router::{ModuleType, RouterError, ServerResponse};
+
+/// 连接 ID (每个 WebSocket 连接唯一)
+pub type ConnectionId = u64;
+
+/// 会话令牌字节长度
+const SESSION_TOKEN_BYTES: usize = 16;
+
+/// 默认断线宽限期
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// 输出结束后等待 shell 进程退出的最长时间 (超时后强制终止)
+const EXIT_WAIT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 等待 shell 进程退出时的轮询间隔
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 终止会话后等待输出读取结束的最长时间 (超时后中止读取任务)
+const READ_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+// ============================================================================
+// 配置
+// ============================================================================
+
+/// 会话保活配置
+#[derive(Debug, Clone)]
+pub struct DetachConfig {
+    /// 连接断开后会话的保留时间 (为 0 时立即终止)
+    pub grace_period: Duration,
+    /// 每个会话的输出缓冲区大小 (字节)
+    pub buffer_size: usize,
+    /// 每个会话虚拟终端的回滚历史行数
+    pub screen_history: usize,
+    /// 每个会话检索缓冲区的大小 (字节)
+    pub search_buffer_size: usize,
+}
+
+impl Default for DetachConfig {
+    fn default() -> Self {
+        Self {
+            grace_period: DEFAULT_GRACE_PERIOD,
+            buffer_size: DEFAULT_OUTPUT_BUFFER_SIZE,
+            screen_history: DEFAULT_SCREEN_HISTORY,
+            search_buffer_size: DEFAULT_SEARCH_BUFFER_SIZE,
+        }
+    }
+}
+
+// ============================================================================
+// 会话句柄
+// ============================================================================
+
+/// 会话概况 (诊断信息)
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SessionInfo {
+    /// 会话 ID
+    pub session_id: SessionId,
+    /// shell 进程 PID
+    pub pid: Option<u32>,
+    /// 是否附加到连接 (false 表示处于断线宽限期)
+    pub attached: bool,
+}
+
+/// 会话输出状态 (归属连接、发送器、缓冲区、虚拟终端和检索缓冲区在同一把锁下更新，
+/// 保证回放与实时输出不重复、快照与输出位置一致)
+struct SessionOutput {
+    /// 当前归属的连接 (None 表示已断开，处于宽限期)
+    owner: Option<ConnectionId>,
+    /// 当前连接的二进制发送器
+    sender: Option<BinarySender>,
+    /// 最近输出
+    buffer: OutputBuffer,
+    /// 虚拟终端 (屏幕和回滚历史)
+    screen: Screen,
+    /// 去除控制序列的历史输出 (供检索)
+    search: SearchBuffer,
+    /// 每次附加/断开时递增，用于作废过期的宽限期计时和过期发送器的清理
+    generation: u64,
+}
+
+/// 单个 PTY 会话的运行状态
+pub struct SessionHandle {
+    /// 会话 ID
+    id: SessionId,
+    /// 会话令牌 (重新连接时使用)
+    token: String,
+    /// shell 进程 PID
+    pid: Option<u32>,
+    /// PTY 会话
+    session: TokioMutex<PtySession>,
+    /// PTY 写入器
+    writer: Arc<Mutex<PtyWriter>>,
+    /// 读取任务句柄
+    read_task: TokioMutex<Option<tokio::task::JoinHandle<()>>>,
+    /// 输出状态
+    output: TokioMutex<SessionOutput>,
+    /// 最近跟踪到的工作目录 (由读取任务更新)
+    cwd: Mutex<Option<(String, CwdSource)>>,
+}
+
+impl SessionHandle {
+    /// 获取会话 ID
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    /// 获取会话令牌
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// 当前工作目录及其来源
+    ///
+    /// 尚未跟踪到时直接从 shell 进程读取 (仅 Linux)
+    pub fn cwd(&self) -> Option<(String, CwdSource)> {
+        if let Some(cwd) = self.cwd.lock().unwrap().clone() {
+            return Some(cwd);
+        }
+        self.pid.and_then(process_cwd).map(|cwd| (cwd, CwdSource::Process))
+    }
+
+    /// 检查会话是否归属指定连接
+    pub async fn is_owned_by(&self, owner: ConnectionId) -> bool {
+        self.output.lock().await.owner == Some(owner)
+    }
+
+    /// 写入数据到 PTY
+    pub fn write(&self, data: &[u8]) -> Result<(), RouterError> {
+        let mut w = self.writer.lock().unwrap();
+        w.write(data)
+            .map_err(|e| ServerError::new(ErrorCode::PtyIoError, Msg::PtyWriteFailed { error: e.to_string() }).into())
+    }
+
+    /// 调整终端尺寸 (虚拟终端随之调整)，返回实际生效的尺寸
+    pub async fn resize(&self, size: TerminalSize) -> Result<TerminalSize, RouterError> {
+        let size = {
+            let mut pty = self.session.lock().await;
+            pty.resize(size)
+                .map_err(|e| ServerError::new(ErrorCode::PtyIoError, Msg::PtyResizeFailed { error: e.to_string() }))?
+        };
+        self.output.lock().await.screen.resize(size);
+        Ok(size)
+    }
+
+    /// 可见屏幕的快照
+    pub async fn snapshot(&self, format: ScreenFormat) -> SnapshotResponse {
+        let output = self.output.lock().await;
+        let screen = &output.screen;
+        SnapshotResponse {
+            session_id: self.id,
+            format,
+            content: screen.render(format),
+            cols: screen.columns(),
+            rows: screen.screen_lines(),
+            cursor: screen.cursor().map(|(row, col)| CursorPosition { row, col }),
+            alternate_screen: screen.alternate_screen(),
+            history_lines: screen.history_size(),
+            output_offset: output.buffer.total_written(),
+        }
+    }
+
+    /// 检索历史输出
+    pub async fn search(&self, pattern: &Regex, context: usize, max_results: usize) -> SearchResponse {
+        let output = self.output.lock().await;
+        let (matches, truncated) = output.search.search(pattern, context, max_results);
+        SearchResponse {
+            session_id: self.id,
+            matches,
+            truncated,
+            first_line: output.search.first_line(),
+            line_count: output.search.line_count(),
+        }
+    }
+
+    /// 回滚历史中从 `start` 开始的至多 `count` 行
+    pub async fn scrollback_range(&self, start: usize, count: usize, format: ScreenFormat) -> ScrollbackRangeResponse {
+        let output = self.output.lock().await;
+        ScrollbackRangeResponse {
+            session_id: self.id,
+            format,
+            start,
+            lines: output.screen.history_lines(start, count, format),
+            history_lines: output.screen.history_size(),
+        }
+    }
+
+    /// 终止会话进程并回收，再等待读取任务结束
+    ///
+    /// shell 的后台进程可能仍持有 PTY 从端，读取任务迟迟读不到 EOF；
+    /// 超过 [`READ_DRAIN_TIMEOUT`] 后中止读取任务，由此处发送会话结束通知
+    async fn terminate(&self) {
+        let status = {
+            let mut pty = self.session.lock().await;
+            pty.kill().await
+        };
+
+        let task = self.read_task.lock().await.take();
+        let Some(mut task) = task else {
+            return;
+        };
+        if tokio::time::timeout(READ_DRAIN_TIMEOUT, &mut task).await.is_err() {
+            task.abort();
+            log_info!("PTY 输出未结束 (后台进程仍在运行)，停止读取: session_id={}", self.id);
+            match status {
+                Ok(status) => self.notify_exit(&status).await,
+                Err(e) => log_error!("回收 PTY 子进程失败: session_id={}, {}", self.id, e),
+            }
+        }
+    }
+
+    /// 等待 shell 进程退出并回收，返回退出状态
+    ///
+    /// 输出结束时进程通常已经退出；超过 [`EXIT_WAIT_TIMEOUT`] 仍未退出时强制终止。
+    /// 轮询而不是阻塞等待，避免占用会话锁导致 [`terminate`](Self::terminate) 无法终止进程
+    async fn wait_exit(&self) -> Option<ExitStatus> {
+        let deadline = tokio::time::Instant::now() + EXIT_WAIT_TIMEOUT;
+        loop {
+            let result = {
+                let mut pty = self.session.lock().await;
+                match pty.try_wait().map_err(|e| e.to_string()) {
+                    Ok(None) if tokio::time::Instant::now() >= deadline => {
+                        pty.kill().await.map(Some).map_err(|e| e.to_string())
+                    }
+                    result => result,
+                }
+            };
+            match result {
+                Ok(Some(status)) => return Some(status),
+                Ok(None) => tokio::time::sleep(EXIT_POLL_INTERVAL).await,
+                Err(e) => {
+                    log_error!("回收 PTY 子进程失败: session_id={}, {}", self.id, e);
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// 向归属连接发送会话结束通知 (会话处于宽限期时不发送)
+    async fn notify_exit(&self, status: &ExitStatus) {
+        let exit = ExitMessage {
+            session_id: self.id,
+            code: status.exit_code(),
+            signal: status.signal().map(str::to_string),
+        };
+        let sender = self.output.lock().await.sender.clone();
+        if let Some(sender) = sender {
+            send_event(&sender, &exit).await;
+        }
+    }
+}
+
+// ============================================================================
+// 会话注册表
+// ============================================================================
+
+/// PTY 会话注册表 (服务器范围共享)
+pub struct SessionRegistry {
+    /// 所有会话 (包括处于宽限期的会话)
+    sessions: TokioMutex<HashMap<SessionId, Arc<SessionHandle>>>,
+    /// 下一个会话 ID
+    next_session_id: AtomicU32,
+    /// 下一个连接 ID
+    next_connection_id: AtomicU64,
+    /// 保活配置
+    config: DetachConfig,
+}
+
+impl SessionRegistry {
+    /// 创建会话注册表
+    pub fn new(config: DetachConfig) -> Self {
+        Self {
+            sessions: TokioMutex::new(HashMap::new()),
+            next_session_id: AtomicU32::new(1),
+            next_connection_id: AtomicU64::new(1),
+            config,
+        }
+    }
+
+    /// 分配新的连接 ID
+    pub fn next_connection_id(&self) -> ConnectionId {
+        self.next_connection_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// 创建 PTY 会话
+    ///
+    /// 会话创建后即开始缓存输出，调用 [`attach`](Self::attach) 后才开始向连接发送
+    pub async fn create(
+        self: &Arc<Self>,
+        owner: ConnectionId,
+        size: TerminalSize,
+        shell_type: Option<String>,
+        shell_args: Option<Vec<String>>,
+        cwd: Option<String>,
+        env: Option<HashMap<String, String>>,
+    ) -> Result<Arc<SessionHandle>, RouterError> {
+        let (pty_session, pty_reader, pty_writer) = PtySession::new(
+            size,
+            shell_type.as_deref(),
+            shell_args.as_deref(),
+            cwd.as_deref(),
+            env.as_ref(),
+        ).map_err(|e| ServerError::new(ErrorCode::PtySpawnFailed, Msg::PtySpawnFailed { error: e.to_string() }))?;
+
+        // 按实际启动的程序选择脚本 (包括默认 shell 和 custom: 路径)
+        let integration = get_shell_integration_script(pty_session.program());
+
+        let id = self.next_session_id.fetch_add(1, Ordering::Relaxed);
+        let handle = Arc::new(SessionHandle {
+            id,
+            token: generate_session_token(),
+            pid: pty_session.process_id(),
+            session: TokioMutex::new(pty_session),
+            writer: Arc::new(Mutex::new(pty_writer)),
+            read_task: TokioMutex::new(None),
+            output: TokioMutex::new(SessionOutput {
+                owner: Some(owner),
+                sender: None,
+                buffer: OutputBuffer::new(self.config.buffer_size),
+                screen: Screen::new(size, self.config.screen_history),
+                search: SearchBuffer::new(self.config.search_buffer_size),
+                generation: 0,
+            }),
+            cwd: Mutex::new(None),
+        });
+
+        self.sessions.lock().await.insert(id, Arc::clone(&handle));
+
+        let task = self.start_read_task(&handle, pty_reader, integration);
+        *handle.read_task.lock().await = Some(task);
+
+        Ok(handle)
+    }
+
+    /// 获取会话
+    pub async fn get(&self, id: SessionId) -> Option<Arc<SessionHandle>> {
+        self.sessions.lock().await.get(&id).cloned()
+    }
+
+    /// 根据会话令牌查找会话
+    pub async fn find_by_token(&self, token: &str) -> Option<Arc<SessionHandle>> {
+        self.sessions.lock().await.values()
+            .find(|handle| handle.token == token)
+            .cloned()
+    }
+
+    /// 获取归属指定连接的会话 ID
+    pub async fn owned_by(&self, owner: ConnectionId) -> Vec<SessionId> {
+        let handles: Vec<Arc<SessionHandle>> = self.sessions.lock().await.values().cloned().collect();
+        let mut ids = Vec::new();
+        for handle in handles {
+            if handle.is_owned_by(owner).await {
+                ids.push(handle.id);
+            }
+        }
+        ids
+    }
+
+    /// 将会话附加到连接
+    ///
+    /// 先回放 `offset` 之后缓存的输出，再切换为实时发送。
+    /// 会话原先归属其他连接时，由新连接接管。
+    ///
+    /// 回放在释放锁之后发送，期间的新输出只写入缓冲区，回放完后补发，
+    /// 直到没有新输出时才设置发送器，保证回放与实时输出按顺序且不重复
+    pub async fn attach(
+        &self,
+        handle: &SessionHandle,
+        owner: ConnectionId,
+        sender: BinarySender,
+        offset: Option<u64>,
+    ) -> Result<(), RouterError> {
+        let generation = {
+            let mut output = handle.output.lock().await;
+            // 作废进行中的宽限期计时，原连接停止接收输出
+            output.generation += 1;
+            output.owner = Some(owner);
+            output.sender = None;
+            output.generation
+        };
+
+        let mut offset = offset.unwrap_or(0);
+        loop {
+            let replay = {
+                let mut output = handle.output.lock().await;
+                if output.generation != generation {
+                    // 回放期间会话已被其他连接接管或已断开
+                    return Ok(());
+                }
+                let replay = output.buffer.since(offset);
+                if replay.is_empty() {
+                    output.sender = Some(sender);
+                    return Ok(());
+                }
+                offset = output.buffer.total_written();
+                replay
+            };
+
+            log_debug!("回放 PTY 输出: session_id={}, {} 字节", handle.id, replay.len());
+            sender.send(frame::PTY_CHANNEL, handle.id, &replay).await
+                .map_err(|e| RouterError::module(Msg::PtyReplayFailed { error: e.to_string() }))?;
+        }
+    }
+
+    /// 断开连接的所有会话
+    ///
+    /// 会话保留宽限期后仍未重新连接则终止；宽限期为 0 时立即终止
+    pub async fn detach_owner(self: &Arc<Self>, owner: ConnectionId) {
+        for id in self.owned_by(owner).await {
+            if self.config.grace_period.is_zero() {
+                self.kill(id).await;
+                continue;
+            }
+
+            let Some(handle) = self.get(id).await else {
+                continue;
+            };
+
+            let generation = {
+                let mut output = handle.output.lock().await;
+                output.owner = None;
+                output.sender = None;
+                output.generation += 1;
+                output.generation
+            };
+
+            log_info!(
+                "PTY 会话已断开，保留 {} 秒: session_id={}",
+                self.config.grace_period.as_secs(), id
+            );
+
+            let registry = Arc::clone(self);
+            tokio::spawn(async move {
+                tokio::time::sleep(registry.config.grace_period).await;
+
+                let expired = {
+                    let output = handle.output.lock().await;
+                    output.owner.is_none() && output.generation == generation
+                };
+                if expired {
+                    log_info!("PTY 会话宽限期已过: session_id={}", id);
+                    registry.kill(id).await;
+                }
+            });
+        }
+    }
+
+    /// 所有会话的概况 (按会话 ID 排序)
+    #[cfg_attr(not(feature = "utils"), allow(dead_code))]
+    pub async fn sessions(&self) -> Vec<SessionInfo> {
+        let mut handles: Vec<Arc<SessionHandle>> = self.sessions.lock().await.values().cloned().collect();
+        handles.sort_by_key(|handle| handle.id);
+        let mut sessions = Vec::with_capacity(handles.len());
+        for handle in handles {
+            sessions.push(SessionInfo {
+                session_id: handle.id,
+                pid: handle.pid,
+                attached: handle.output.lock().await.owner.is_some(),
+            });
+        }
+        sessions
+    }
+
+    /// 终止会话
+    ///
+    /// 返回 false 表示会话不存在
+    pub async fn kill(&self, id: SessionId) -> bool {
+        // 先从注册表中移除，避免读取任务结束时等待锁
+        let Some(handle) = self.sessions.lock().await.remove(&id) else {
+            return false;
+        };
+
+        log_info!("终止 PTY 会话: session_id={}", id);
+        handle.terminate().await;
+        true
+    }
+
+    /// 终止所有会话 (服务器关闭时调用，各会话并发终止)
+    pub async fn kill_all(&self) {
+        let ids: Vec<SessionId> = self.sessions.lock().await.keys().copied().collect();
+        futures_util::future::join_all(ids.into_iter().map(|id| self.kill(id))).await;
+    }
+
+    /// 启动 PTY 输出读取任务
+    fn start_read_task(
+        self: &Arc<Self>,
+        handle: &Arc<SessionHandle>,
+        reader: PtyReader,
+        integration: Option<&'static str>,
+    ) -> tokio::task::JoinHandle<()> {
+        let reader = Arc::new(Mutex::new(reader));
+        let handle = Arc::clone(handle);
+        let registry = Arc::clone(self);
+
+        tokio::spawn(async move {
+            let session_id = handle.id;
+            let mut first_output = true;
+            let mut shell = ShellTracker::new(session_id, handle.pid);
+
+            loop {
+                // 在阻塞任务中读取 PTY 输出
+                let reader_clone = Arc::clone(&reader);
+                let result = tokio::task::spawn_blocking(move || -> Result<(Vec<u8>, usize), String> {
+                    let mut reader = reader_clone.lock().unwrap();
+                    let mut local_buf = vec![0u8; 8192];
+                    match reader.read(&mut local_buf) {
+                        Ok(n) => Ok((local_buf, n)),
+                        Err(e) => Err(e.to_string()),
+                    }
+                }).await;
+
+                match result {
+                    Ok(Ok((data, n))) if n > 0 => {
+                        log_debug!("读取 PTY 输出: session_id={}, {} 字节", session_id, n);
+
+                        // 先写入缓冲区、虚拟终端和检索缓冲区，已附加连接时再实时发送
+                        // 发送在释放锁之后进行: 客户端停止读取时发送会阻塞，
+                        // 不能因此卡住附加、快照、检索等其他需要该锁的操作
+                        let (events, target) = {
+                            let mut output = handle.output.lock().await;
+                            let events = shell.feed(&data[..n], output.buffer.total_written());
+                            output.buffer.push(&data[..n]);
+                            output.screen.feed(&data[..n]);
+                            output.search.feed(&data[..n]);
+                            let target = output.sender.clone().map(|sender| (sender, output.generation));
+                            (events, target)
+                        };
+                        for event in &events {
+                            if let ShellEvent::CwdChanged(changed) = event {
+                                *handle.cwd.lock().unwrap() = Some((changed.cwd.clone(), changed.source));
+                            }
+                        }
+
+                        // 以 session_id 作为流 ID，客户端据此分发到对应终端
+                        if let Some((sender, generation)) = target {
+                            if let Err(e) = sender.send(frame::PTY_CHANNEL, session_id, &data[..n]).await {
+                                // 连接已断开，继续缓存输出直到重新连接或宽限期结束
+                                // 期间已重新附加到其他连接时保留新的发送器
+                                log_error!("发送 PTY 输出失败: {}", e);
+                                let mut output = handle.output.lock().await;
+                                if output.generation == generation {
+                                    output.sender = None;
+                                }
+                            } else {
+                                // Shell 事件排在包含对应标记的输出之后
+                                for event in events {
+                                    match event {
+                                        ShellEvent::CwdChanged(changed) => send_event(&sender, &changed).await,
+                                        ShellEvent::CommandStarted(started) => send_event(&sender, &started).await,
+                                        ShellEvent::CommandFinished(finished) => send_event(&sender, &finished).await,
+                                    }
+                                }
+                            }
+                        }
+
+                        // 首次输出后注入 Shell Integration 脚本
+                        if first_output {
+                            first_output = false;
+                            if let Some(script) = integration {
+                                if let Err(e) = handle.write(script.as_bytes()) {
+                                    log_error!("发送 Shell Integration 脚本失败: {}", e);
+                                } else {
+                                    log_debug!("Shell Integration 脚本已发送");
+                                }
+                            }
+                        }
+                    }
+                    Ok(Ok(_)) => {
+                        // EOF
+                        log_info!("PTY 输出结束: session_id={}", session_id);
+                        break;
+                    }
+                    Ok(Err(e)) => {
+                        log_error!("PTY 输出读取错误: {}", e);
+                        break;
+                    }
+                    Err(e) => {
+                        log_error!("PTY 读取任务错误: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            // 会话结束: 回收子进程，从注册表中移除后再通知客户端，
+            // 客户端收到 exit 后该会话已不可用
+            let status = handle.wait_exit().await;
+            registry.sessions.lock().await.remove(&session_id);
+            if let Some(status) = status {
+                log_info!("PTY 会话已结束: session_id={}, {}", session_id, status);
+                handle.notify_exit(&status).await;
+            }
+        })
+    }
+}
+
+impl Default for SessionRegistry {
+    fn default() -> Self {
+        Self::new(DetachConfig::default())
+    }
+}
+
+/// 向连接发送 PTY 模块的事件 (与输出同一队列，排在之前的输出之后)
+async fn send_event<P: Payload + Serialize>(sender: &BinarySender, payload: &P) {
+    let text = match serde_json::to_string(&ServerResponse::from_payload(ModuleType::PTY, payload)) {
+        Ok(text) => text,
+        Err(e) => {
+            log_error!("序列化 PTY 事件失败: {}, {}", P::TYPE, e);
+            return;
+        }
+    };
+    if let Err(e) = sender.send_text(text).await {
+        log_debug!("发送 PTY 事件失败: {}, {}", P::TYPE, e);
+    }
+}
+
+/// 生成随机会话令牌
+fn generate_session_token() -> String {
+    let bytes: [u8; SESSION_TOKEN_BYTES] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,503 @@
+// PTY 虚拟终端
+// 会话输出经过 VT 解析器维护屏幕网格和回滚历史 (基于 alacritty_terminal)，
+// 用于屏幕快照和历史行查询；备用屏幕、宽字符和调整尺寸时的重排由终端实现处理
+
+use alacritty_terminal::event::VoidListener;
+use alacritty_terminal::grid::{Dimensions, Row};
+use alacritty_terminal::index::{Column, Line};
+use alacritty_terminal::term::cell::{Cell, Flags};
+use alacritty_terminal::term::{Config, TermMode};
+use alacritty_terminal::vte::ansi::{Color, NamedColor, Processor};
+use alacritty_terminal::Term;
+
+use super::{ScreenFormat, TerminalSize};
+
+/// 默认回滚历史行数
+pub const DEFAULT_SCREEN_HISTORY: usize = 10_000;
+
+/// 影响显示的单元格属性 (其余标志只用于布局)
+const STYLE_FLAGS: Flags = Flags::BOLD
+    .union(Flags::DIM)
+    .union(Flags::ITALIC)
+    .union(Flags::ALL_UNDERLINES)
+    .union(Flags::INVERSE)
+    .union(Flags::HIDDEN)
+    .union(Flags::STRIKEOUT);
+
+/// HTML 默认前景色
+const HTML_FOREGROUND: &str = "#e5e5e5";
+
+/// HTML 默认背景色
+const HTML_BACKGROUND: &str = "#000000";
+
+/// 16 色调色板 (xterm 默认值)
+const PALETTE: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00), (0xcd, 0x00, 0x00), (0x00, 0xcd, 0x00), (0xcd, 0xcd, 0x00),
+    (0x00, 0x00, 0xee), (0xcd, 0x00, 0xcd), (0x00, 0xcd, 0xcd), (0xe5, 0xe5, 0xe5),
+    (0x7f, 0x7f, 0x7f), (0xff, 0x00, 0x00), (0x00, 0xff, 0x00), (0xff, 0xff, 0x00),
+    (0x5c, 0x5c, 0xff), (0xff, 0x00, 0xff), (0x00, 0xff, 0xff), (0xff, 0xff, 0xff),
+];
+
+// ============================================================================
+// 虚拟终端
+// ============================================================================
+
+/// 终端网格尺寸
+struct GridSize {
+    columns: usize,
+    screen_lines: usize,
+}
+
+impl From<TerminalSize> for GridSize {
+    fn from(size: TerminalSize) -> Self {
+        Self {
+            columns: usize::from(size.cols.max(1)),
+            screen_lines: usize::from(size.rows.max(1)),
+        }
+    }
+}
+
+impl Dimensions for GridSize {
+    fn total_lines(&self) -> usize {
+        self.screen_lines
+    }
+
+    fn screen_lines(&self) -> usize {
+        self.screen_lines
+    }
+
+    fn columns(&self) -> usize {
+        self.columns
+    }
+}
+
+/// 单个会话的虚拟终端
+///
+/// 只解析输出，不回应终端查询 (由客户端的终端负责)
+pub struct Screen {
+    term: Term<VoidListener>,
+    parser: Processor,
+}
+
+impl Screen {
+    /// 创建虚拟终端，`history` 为回滚历史行数
+    pub fn new(size: TerminalSize, history: usize) -> Self {
+        let config = Config {
+            scrolling_history: history,
+            ..Config::default()
+        };
+        Self {
+            term: Term::new(config, &GridSize::from(size), VoidListener),
+            parser: Processor::new(),
+        }
+    }
+
+    /// 解析一段输出
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.parser.advance(&mut self.term, bytes);
+    }
+
+    /// 调整尺寸 (主屏幕按新列数重排换行)
+    pub fn resize(&mut self, size: TerminalSize) {
+        self.term.resize(GridSize::from(size));
+    }
+
+    /// 列数
+    pub fn columns(&self) -> usize {
+        self.term.grid().columns()
+    }
+
+    /// 行数
+    pub fn screen_lines(&self) -> usize {
+        self.term.grid().screen_lines()
+    }
+
+    /// 是否处于备用屏幕 (全屏程序运行时)
+    pub fn alternate_screen(&self) -> bool {
+        self.term.mode().contains(TermMode::ALT_SCREEN)
+    }
+
+    /// 当前屏幕的回滚历史行数 (备用屏幕没有历史)
+    pub fn history_size(&self) -> usize {
+        self.term.grid().history_size()
+    }
+
+    /// 光标位置 (行、列，从 0 开始)，光标隐藏时为 None
+    pub fn cursor(&self) -> Option<(usize, usize)> {
+        if !self.term.mode().contains(TermMode::SHOW_CURSOR) {
+            return None;
+        }
+        let point = self.term.grid().cursor.point;
+        Some((point.line.0.max(0) as usize, point.column.0))
+    }
+
+    /// 渲染可见屏幕
+    ///
+    /// 文本按行以 `\n` 连接；ANSI 以 `\r\n` 连接，可直接写入终端；
+    /// HTML 为带默认前景、背景色的 `<pre>` 片段
+    pub fn render(&self, format: ScreenFormat) -> String {
+        let lines: Vec<String> = (0..self.screen_lines())
+            .map(|line| self.render_line(Line(line as i32), format))
+            .collect();
+        match format {
+            ScreenFormat::Text => lines.join("\n"),
+            ScreenFormat::Ansi => lines.join("\r\n"),
+            ScreenFormat::Html => format!(
+                "<pre style=\"color:{};background:{}\">{}</pre>",
+                HTML_FOREGROUND, HTML_BACKGROUND, lines.join("\n"),
+            ),
+        }
+    }
+
+    /// 渲染回滚历史中的行，`start` 为 0 表示最早的一行
+    ///
+    /// 超出历史范围的部分被忽略；HTML 格式的每行是不含 `<pre>` 的片段
+    pub fn history_lines(&self, start: usize, count: usize, format: ScreenFormat) -> Vec<String> {
+        let history = self.history_size();
+        let end = start.saturating_add(count).min(history);
+        (start.min(end)..end)
+            .map(|index| self.render_line(Line(index as i32 - history as i32), format))
+            .collect()
+    }
+
+    /// 渲染一行
+    fn render_line(&self, line: Line, format: ScreenFormat) -> String {
+        let row = &self.term.grid()[line];
+        match format {
+            ScreenFormat::Text => {
+                let mut text = String::new();
+                for (_, cell) in visible_cells(row) {
+                    push_cell_text(&mut text, cell);
+                }
+                text.truncate(text.trim_end_matches(' ').len());
+                text
+            }
+            ScreenFormat::Ansi => render_styled(row, |out, style, text| {
+                let sgr = style.sgr();
+                if !sgr.is_empty() {
+                    out.push_str(&format!("\x1b[{}m", sgr));
+                }
+                out.push_str(text);
+                if !sgr.is_empty() {
+                    out.push_str("\x1b[0m");
+                }
+            }),
+            ScreenFormat::Html => render_styled(row, |out, style, text| {
+                let text = escape_html(text);
+                match style.css() {
+                    Some(css) => out.push_str(&format!("<span style=\"{}\">{}</span>", css, text)),
+                    None => out.push_str(&text),
+                }
+            }),
+        }
+    }
+}
+
+// ============================================================================
+// 渲染
+// ============================================================================
+
+/// 单元格的显示样式
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Style {
+    fg: Color,
+    bg: Color,
+    flags: Flags,
+}
+
+impl Style {
+    fn of(cell: &Cell) -> Self {
+        Self {
+            fg: cell.fg,
+            bg: cell.bg,
+            flags: cell.flags & STYLE_FLAGS,
+        }
+    }
+
+    /// 是否为默认样式
+    fn is_default(&self) -> bool {
+        *self == Self::of(&Cell::default())
+    }
+
+    /// 空白单元格是否可见 (有背景色、反显或下划线)
+    fn shows_blank(&self) -> bool {
+        self.bg != Color::Named(NamedColor::Background)
+            || self.flags.intersects(Flags::INVERSE | Flags::ALL_UNDERLINES | Flags::STRIKEOUT)
+    }
+
+    /// SGR 参数 (默认样式为空)
+    fn sgr(&self) -> String {
+        let mut params: Vec<String> = Vec::new();
+        for (flag, code) in [
+            (Flags::BOLD, "1"),
+            (Flags::DIM, "2"),
+            (Flags::ITALIC, "3"),
+            (Flags::ALL_UNDERLINES, "4"),
+            (Flags::INVERSE, "7"),
+            (Flags::HIDDEN, "8"),
+            (Flags::STRIKEOUT, "9"),
+        ] {
+            if self.flags.intersects(flag) {
+                params.push(code.to_string());
+            }
+        }
+        params.extend(sgr_color(self.fg, true));
+        params.extend(sgr_color(self.bg, false));
+        params.join(";")
+    }
+
+    /// CSS 样式 (默认样式为 None)
+    fn css(&self) -> Option<String> {
+        if self.is_default() {
+            return None;
+        }
+        let mut fg = css_color(self.fg).unwrap_or_else(|| HTML_FOREGROUND.to_string());
+        let mut bg = css_color(self.bg);
+        if self.flags.contains(Flags::INVERSE) {
+            let inverted = bg.unwrap_or_else(|| HTML_BACKGROUND.to_string());
+            bg = Some(fg);
+            fg = inverted;
+        }
+
+        let mut css = Vec::new();
+        if self.fg != Color::Named(NamedColor::Foreground) || self.flags.contains(Flags::INVERSE) {
+            css.push(format!("color:{}", fg));
+        }
+        if let Some(bg) = bg {
+            css.push(format!("background:{}", bg));
+        }
+        if self.flags.contains(Flags::BOLD) {
+            css.push("font-weight:bold".to_string());
+        }
+        if self.flags.contains(Flags::DIM) {
+            css.push("opacity:0.5".to_string());
+        }
+        if self.flags.contains(Flags::ITALIC) {
+            css.push("font-style:italic".to_string());
+        }
+        let decorations: Vec<&str> = [
+            (self.flags.intersects(Flags::ALL_UNDERLINES), "underline"),
+            (self.flags.contains(Flags::STRIKEOUT), "line-through"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, decoration)| enabled.then_some(decoration))
+        .collect();
+        if !decorations.is_empty() {
+            css.push(format!("text-decoration:{}", decorations.join(" ")));
+        }
+        if self.flags.contains(Flags::HIDDEN) {
+            css.push("visibility:hidden".to_string());
+        }
+        Some(css.join(";"))
+    }
+}
+
+/// 一行中需要输出的单元格 (跳过宽字符占位)
+fn visible_cells(row: &Row<Cell>) -> impl Iterator<Item = (usize, &Cell)> {
+    (0..row.len())
+        .map(move |column| (column, &row[Column(column)]))
+        .filter(|(_, cell)| !cell.flags.intersects(Flags::WIDE_CHAR_SPACER | Flags::LEADING_WIDE_CHAR_SPACER))
+}
+
+/// 追加单元格的字符 (包括组合字符)
+fn push_cell_text(out: &mut String, cell: &Cell) {
+    // 制表符起始处保存的是 '\t'，按空格显示以保持列对齐
+    out.push(if cell.c == '\t' { ' ' } else { cell.c });
+    if let Some(zerowidth) = cell.zerowidth() {
+        out.extend(zerowidth);
+    }
+}
+
+/// 按样式分段渲染一行，行尾不可见的空白被省略
+fn render_styled(row: &Row<Cell>, mut push_segment: impl FnMut(&mut String, &Style, &str)) -> String {
+    let cells: Vec<(usize, &Cell)> = visible_cells(row).collect();
+    let end = cells
+        .iter()
+        .rposition(|(_, cell)| cell.c != ' ' || Style::of(cell).shows_blank())
+        .map_or(0, |index| index + 1);
+
+    let mut out = String::new();
+    let mut segment = String::new();
+    let mut current: Option<Style> = None;
+    for (_, cell) in &cells[..end] {
+        let style = Style::of(cell);
+        if current.is_some_and(|current| current != style) {
+            push_segment(&mut out, current.as_ref().unwrap(), &segment);
+            segment.clear();
+        }
+        current = Some(style);
+        push_cell_text(&mut segment, cell);
+    }
+    if let Some(style) = current {
+        push_segment(&mut out, &style, &segment);
+    }
+    out
+}
+
+/// 命名颜色在 16 色调色板中的索引 (默认前景、背景色为 None)
+fn named_index(color: NamedColor) -> Option<u8> {
+    let index = color as usize;
+    if index < 16 {
+        Some(index as u8)
+    } else if (NamedColor::DimBlack as usize..=NamedColor::DimWhite as usize).contains(&index) {
+        Some((index - NamedColor::DimBlack as usize) as u8)
+    } else {
+        None
+    }
+}
+
+/// 颜色的 SGR 参数 (默认颜色为 None)
+fn sgr_color(color: Color, foreground: bool) -> Option<String> {
+    let base = if foreground { 38 } else { 48 };
+    match color {
+        Color::Named(named) => named_index(named).map(|index| match index {
+            0..=7 => (base - 8 + index as u32).to_string(),
+            _ => (base + 52 + index as u32 - 8).to_string(),
+        }),
+        Color::Indexed(index) => Some(format!("{};5;{}", base, index)),
+        Color::Spec(rgb) => Some(format!("{};2;{};{};{}", base, rgb.r, rgb.g, rgb.b)),
+    }
+}
+
+/// 颜色的 CSS 值 (默认颜色为 None)
+fn css_color(color: Color) -> Option<String> {
+    let (r, g, b) = match color {
+        Color::Named(named) => PALETTE[usize::from(named_index(named)?)],
+        Color::Indexed(index) => indexed_rgb(index),
+        Color::Spec(rgb) => (rgb.r, rgb.g, rgb.b),
+    };
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
+/// 256 色调色板中的颜色
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => PALETTE[usize::from(index)],
+        16..=231 => {
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+            (level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// 转义 HTML 特殊字符
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(cols: u16, rows: u16, history: usize) -> Screen {
+        Screen::new(TerminalSize { cols, rows, ..TerminalSize::default() }, history)
+    }
+
+    fn text(screen: &Screen) -> Vec<String> {
+        screen.render(ScreenFormat::Text).split('\n').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let mut screen = screen(20, 3, 100);
+        screen.feed(b"hello\r\n\x1b[31mred\x1b[0m world  ");
+        assert_eq!(text(&screen), vec!["hello", "red world", ""]);
+        assert_eq!(screen.cursor(), Some((1, 11)));
+        assert!(!screen.alternate_screen());
+
+        screen.feed(b"\x1b[?25l");
+        assert_eq!(screen.cursor(), None);
+    }
+
+    #[test]
+    fn test_wide_chars() {
+        let mut screen = screen(5, 2, 100);
+        // 第三个宽字符放不下，换到下一行
+        screen.feed("中文字a".as_bytes());
+        assert_eq!(text(&screen), vec!["中文", "字a"]);
+        assert_eq!(screen.cursor(), Some((1, 3)));
+    }
+
+    #[test]
+    fn test_alternate_screen() {
+        let mut screen = screen(20, 3, 100);
+        screen.feed(b"$ vim\r\n");
+        screen.feed(b"\x1b[?1049h\x1b[H\x1b[2Jediting");
+        assert!(screen.alternate_screen());
+        assert_eq!(text(&screen), vec!["editing", "", ""]);
+
+        screen.feed(b"\x1b[?1049l");
+        assert!(!screen.alternate_screen());
+        assert_eq!(text(&screen), vec!["$ vim", "", ""]);
+    }
+
+    #[test]
+    fn test_history_lines() {
+        let mut screen = screen(10, 2, 3);
+        for i in 1..=6 {
+            screen.feed(format!("line{}\r\n", i).as_bytes());
+        }
+        // 可见 line6 和空行，历史最多保留 3 行
+        assert_eq!(screen.history_size(), 3);
+        assert_eq!(screen.history_lines(0, 10, ScreenFormat::Text), vec!["line3", "line4", "line5"]);
+        assert_eq!(screen.history_lines(1, 1, ScreenFormat::Text), vec!["line4"]);
+        assert!(screen.history_lines(5, 1, ScreenFormat::Text).is_empty());
+    }
+
+    #[test]
+    fn test_resize_reflow() {
+        let mut screen = screen(10, 3, 100);
+        screen.feed(b"abcdefghij12345");
+        assert_eq!(text(&screen), vec!["abcdefghij", "12345", ""]);
+
+        screen.resize(TerminalSize { cols: 20, rows: 3, ..TerminalSize::default() });
+        assert_eq!(screen.columns(), 20);
+        assert_eq!(text(&screen)[0], "abcdefghij12345");
+
+        // 变窄后重排出的行被推入历史，光标所在行保持可见
+        screen.resize(TerminalSize { cols: 5, rows: 3, ..TerminalSize::default() });
+        assert_eq!(screen.history_lines(0, 10, ScreenFormat::Text), vec!["abcde", "fghij"]);
+        assert_eq!(text(&screen), vec!["12345", "", ""]);
+    }
+
+    #[test]
+    fn test_ansi_format() {
+        let mut screen = screen(20, 1, 0);
+        screen.feed(b"\x1b[1;31mred\x1b[0m \x1b[38;5;208mx\x1b[48;2;1;2;3my");
+        assert_eq!(
+            screen.render(ScreenFormat::Ansi),
+            "\x1b[1;31mred\x1b[0m \x1b[38;5;208mx\x1b[0m\x1b[38;5;208;48;2;1;2;3my\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn test_html_format() {
+        let mut screen = screen(20, 2, 0);
+        screen.feed(b"<a&b>\r\n\x1b[7minv\x1b[0m \x1b[94mblue");
+        assert_eq!(
+            screen.render(ScreenFormat::Html),
+            "<pre style=\"color:#e5e5e5;background:#000000\">&lt;a&amp;b&gt;\n\
+             <span style=\"color:#000000;background:#e5e5e5\">inv</span> \
+             <span style=\"color:#5c5cff\">blue</span></pre>",
+        );
+    }
+}
@@ -0,0 +1,396 @@
+// PTY 输出检索
+// 输出去除控制序列后按行保存在按字节计上限的缓冲区中，供 search 请求检索；
+// 缓冲区在服务器端随会话维护，与客户端是否显示该终端无关
+
+use std::collections::VecDeque;
+
+use alacritty_terminal::vte::{Params, Parser, Perform};
+use regex::{Regex, RegexBuilder};
+
+use super::{SearchMatch, SearchRange};
+
+/// 默认检索缓冲区大小 (1 MiB)
+pub const DEFAULT_SEARCH_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// 单行最多保存的字符数 (超出时折行，避免没有换行的输出无限增长)
+const MAX_LINE_CHARS: usize = 4096;
+
+/// 编译的正则表达式大小上限
+const REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+
+// ============================================================================
+// 行缓冲区
+// ============================================================================
+
+/// 已完成的行 (按字节计上限，超出时丢弃最早的行)
+struct Lines {
+    lines: VecDeque<String>,
+    /// 最早一行的行号
+    first_line: u64,
+    /// 已保存的字节数 (每行计入换行符)
+    bytes: usize,
+    /// 字节数上限
+    max_bytes: usize,
+}
+
+impl Lines {
+    fn push(&mut self, line: String) {
+        self.bytes += line.len() + 1;
+        self.lines.push_back(line);
+        while self.bytes > self.max_bytes {
+            let Some(oldest) = self.lines.pop_front() else {
+                break;
+            };
+            self.bytes -= oldest.len() + 1;
+            self.first_line += 1;
+        }
+    }
+
+    /// 丢弃所有行 (行号继续递增)
+    fn clear(&mut self) {
+        self.first_line += self.lines.len() as u64;
+        self.lines.clear();
+        self.bytes = 0;
+    }
+}
+
+/// 输出解析状态 (VT 解析器的回调)
+///
+/// 只处理影响行内容的控制: 换行、回车、退格、光标左右移动和行内擦除；
+/// 备用屏幕 (全屏程序) 的输出不保存，终端重置 (RIS) 时与屏幕一样清空已保存的输出
+struct LineWriter {
+    lines: Lines,
+    /// 当前行 (尚未换行)
+    current: Vec<char>,
+    /// 当前行中的光标列
+    cursor: usize,
+    /// 是否处于备用屏幕
+    alternate: bool,
+}
+
+impl LineWriter {
+    /// 结束当前行
+    fn commit(&mut self) {
+        let line: String = self.current.drain(..).collect();
+        self.lines.push(line.trim_end().to_string());
+        self.cursor = 0;
+    }
+
+    /// 在光标处写入字符
+    fn put(&mut self, c: char) {
+        if self.cursor >= MAX_LINE_CHARS {
+            self.commit();
+        }
+        if self.cursor < self.current.len() {
+            self.current[self.cursor] = c;
+        } else {
+            self.current.resize(self.cursor, ' ');
+            self.current.push(c);
+        }
+        self.cursor += 1;
+    }
+}
+
+/// CSI 序列的第一个参数 (缺省或为 0 时取 `default`)
+fn first_param(params: &Params, default: usize) -> usize {
+    match params.iter().next().and_then(|param| param.first()) {
+        Some(&value) if value > 0 => usize::from(value),
+        _ => default,
+    }
+}
+
+impl Perform for LineWriter {
+    fn print(&mut self, c: char) {
+        if !self.alternate {
+            self.put(c);
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        if self.alternate {
+            return;
+        }
+        match byte {
+            b'\n' => self.commit(),
+            b'\r' => self.cursor = 0,
+            0x08 => self.cursor = self.cursor.saturating_sub(1),
+            b'\t' => self.put('\t'),
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
+        if intermediates == b"?" {
+            // 进入 / 离开备用屏幕
+            let alternate = params.iter().any(|param| matches!(param.first(), Some(47 | 1047 | 1049)));
+            match action {
+                'h' if alternate => self.alternate = true,
+                'l' if alternate => self.alternate = false,
+                _ => {}
+            }
+            return;
+        }
+        if self.alternate || !intermediates.is_empty() {
+            return;
+        }
+        match action {
+            'C' => self.cursor += first_param(params, 1),
+            'D' => self.cursor = self.cursor.saturating_sub(first_param(params, 1)),
+            'G' => self.cursor = first_param(params, 1) - 1,
+            'K' => match params.iter().next().and_then(|param| param.first()).copied().unwrap_or(0) {
+                0 => self.current.truncate(self.cursor),
+                1 => {
+                    let end = (self.cursor + 1).min(self.current.len());
+                    self.current[..end].fill(' ');
+                }
+                2 => self.current.clear(),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        // RIS (`ESC c`): Shell Integration 注入后以此清除回显的脚本
+        if intermediates.is_empty() && byte == b'c' {
+            self.lines.clear();
+            self.current.clear();
+            self.cursor = 0;
+            self.alternate = false;
+        }
+    }
+}
+
+// ============================================================================
+// 检索缓冲区
+// ============================================================================
+
+/// 单个会话的检索缓冲区
+pub struct SearchBuffer {
+    parser: Parser,
+    writer: LineWriter,
+}
+
+impl SearchBuffer {
+    /// 创建检索缓冲区，`max_bytes` 为保存的字节数上限
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            parser: Parser::new(),
+            writer: LineWriter {
+                lines: Lines {
+                    lines: VecDeque::new(),
+                    first_line: 0,
+                    bytes: 0,
+                    max_bytes,
+                },
+                current: Vec::new(),
+                cursor: 0,
+                alternate: false,
+            },
+        }
+    }
+
+    /// 解析一段输出
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.parser.advance(&mut self.writer, bytes);
+    }
+
+    /// 最早保存的行号
+    pub fn first_line(&self) -> u64 {
+        self.writer.lines.first_line
+    }
+
+    /// 保存的行数 (包括尚未换行的当前行)
+    pub fn line_count(&self) -> usize {
+        self.writer.lines.lines.len() + usize::from(!self.writer.current.is_empty())
+    }
+
+    /// 尚未换行的当前行
+    fn current_line(&self) -> String {
+        self.writer.current.iter().collect::<String>().trim_end().to_string()
+    }
+
+    /// 检索匹配的行
+    ///
+    /// 从最新的行向前查找，最多返回 `max_results` 行 (按行号升序)，
+    /// 第二个返回值表示是否还有更早的匹配未返回
+    pub fn search(&self, pattern: &Regex, context: usize, max_results: usize) -> (Vec<SearchMatch>, bool) {
+        let current = self.current_line();
+        let lines: Vec<&str> = self.writer.lines.lines.iter()
+            .map(String::as_str)
+            .chain((!self.writer.current.is_empty()).then_some(current.as_str()))
+            .collect();
+
+        let mut matches = Vec::new();
+        let mut truncated = false;
+        for (index, text) in lines.iter().enumerate().rev() {
+            let ranges = match_ranges(pattern, text);
+            if ranges.is_empty() {
+                continue;
+            }
+            if matches.len() == max_results {
+                truncated = true;
+                break;
+            }
+            matches.push(SearchMatch {
+                line: self.first_line() + index as u64,
+                text: text.to_string(),
+                ranges,
+                before: lines[index.saturating_sub(context)..index].iter().map(|line| line.to_string()).collect(),
+                after: lines[index + 1..(index + 1 + context).min(lines.len())].iter().map(|line| line.to_string()).collect(),
+            });
+        }
+        matches.reverse();
+        (matches, truncated)
+    }
+}
+
+/// 编译检索模式
+///
+/// 非正则模式按字面文本匹配
+pub fn build_pattern(query: &str, regex: bool, ignore_case: bool) -> Result<Regex, String> {
+    if query.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    let pattern = if regex { query.to_string() } else { regex::escape(query) };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// 行中所有非空匹配的位置 (字符偏移)
+fn match_ranges(pattern: &Regex, text: &str) -> Vec<SearchRange> {
+    pattern
+        .find_iter(text)
+        .filter(|found| !found.is_empty())
+        .map(|found| SearchRange {
+            start: text[..found.start()].chars().count(),
+            end: text[..found.end()].chars().count(),
+        })
+        .collect()
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(buffer: &SearchBuffer) -> Vec<String> {
+        let mut lines: Vec<String> = buffer.writer.lines.lines.iter().cloned().collect();
+        if !buffer.writer.current.is_empty() {
+            lines.push(buffer.current_line());
+        }
+        lines
+    }
+
+    #[test]
+    fn test_strip_control_sequences() {
+        let mut buffer = SearchBuffer::new(1024);
+        buffer.feed(b"\x1b[1;31merror\x1b[0m: failed\r\n");
+        buffer.feed(b"\x1b]0;title\x07\x1b]133;A\x1b\\$ ls  \r\n");
+        // 进度条用回车覆盖同一行
+        buffer.feed(b"progress 10%\rprogress 100%\r\n");
+        buffer.feed(b"abc\x08\x08X\r\n");
+        buffer.feed(b"old text\r\x1b[Knew\r\n");
+        // 多字节字符跨越两段输出
+        let text = "中文".as_bytes();
+        buffer.feed(&text[..4]);
+        buffer.feed(&text[4..]);
+        assert_eq!(lines(&buffer), vec!["error: failed", "$ ls", "progress 100%", "aXc", "new", "中文"]);
+    }
+
+    #[test]
+    fn test_alternate_screen_skipped() {
+        let mut buffer = SearchBuffer::new(1024);
+        buffer.feed(b"$ vim\r\n\x1b[?1049h\x1b[Hediting\r\n~\r\n\x1b[?1049l$ done\r\n");
+        assert_eq!(lines(&buffer), vec!["$ vim", "$ done"]);
+    }
+
+    #[test]
+    fn test_reset_clears_lines() {
+        let mut buffer = SearchBuffer::new(1024);
+        // Shell Integration 脚本的回显 (被 readline 折行) 之后是 RIS
+        buffer.feed(b"$ \x1b[?2004l eval '__sw_cwd(){ printf \"\\e]7;file://%s%s\"; };__sw_prompt(){ \r\n");
+        buffer.feed(b"__sw_ready=1;}' 2>/dev/null;__sw_cwd;printf '\\ec'\r\n");
+        buffer.feed(b"\x1b]7;file://host/tmp\x1b\\\x1bc\x1b]133;A\x07$ ls\r\nfile\r\n");
+        assert_eq!(lines(&buffer), vec!["$ ls", "file"]);
+        // 行号不回退
+        assert_eq!(buffer.first_line(), 2);
+
+        let pattern = build_pattern("__sw_", false, false).unwrap();
+        assert!(buffer.search(&pattern, 0, 10).0.is_empty());
+    }
+
+    #[test]
+    fn test_byte_bound() {
+        // 每行 6 字节 (含换行)，上限 20 字节保留最近 3 行
+        let mut buffer = SearchBuffer::new(20);
+        for i in 0..10 {
+            buffer.feed(format!("line{}\n", i).as_bytes());
+        }
+        assert_eq!(buffer.first_line(), 7);
+        assert_eq!(lines(&buffer), vec!["line7", "line8", "line9"]);
+    }
+
+    #[test]
+    fn test_long_line_wrapped() {
+        let mut buffer = SearchBuffer::new(DEFAULT_SEARCH_BUFFER_SIZE);
+        buffer.feed("x".repeat(MAX_LINE_CHARS + 10).as_bytes());
+        assert_eq!(buffer.line_count(), 2);
+        assert_eq!(lines(&buffer)[1], "x".repeat(10));
+    }
+
+    #[test]
+    fn test_search() {
+        let mut buffer = SearchBuffer::new(1024);
+        buffer.feed(b"build\nError: one\nok\nwarning\nERROR: two error\nprompt$ ");
+
+        let pattern = build_pattern("error", false, false).unwrap();
+        let (matches, truncated) = buffer.search(&pattern, 1, 10);
+        assert!(!truncated);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, 4);
+        assert_eq!(matches[0].ranges, vec![SearchRange { start: 11, end: 16 }]);
+        assert_eq!(matches[0].before, vec!["warning"]);
+        assert_eq!(matches[0].after, vec!["prompt$"]);
+
+        let pattern = build_pattern("error", false, true).unwrap();
+        let (matches, _) = buffer.search(&pattern, 0, 10);
+        assert_eq!(matches.iter().map(|m| m.line).collect::<Vec<_>>(), vec![1, 4]);
+        assert_eq!(matches[1].ranges.len(), 2);
+        assert!(matches[0].before.is_empty());
+
+        // 超过上限时保留最近的匹配
+        let (matches, truncated) = buffer.search(&pattern, 0, 1);
+        assert!(truncated);
+        assert_eq!(matches[0].line, 4);
+
+        let pattern = build_pattern(r"^(ok|warn\w+)$", true, false).unwrap();
+        let (matches, _) = buffer.search(&pattern, 0, 10);
+        assert_eq!(matches.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(), vec!["ok", "warning"]);
+
+        // 字面模式不解释正则元字符
+        let pattern = build_pattern("$", false, false).unwrap();
+        let (matches, _) = buffer.search(&pattern, 0, 10);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].ranges, vec![SearchRange { start: 6, end: 7 }]);
+    }
+
+    #[test]
+    fn test_build_pattern_errors() {
+        assert!(build_pattern("", false, false).is_err());
+        assert!(build_pattern("(", true, false).is_err());
+        assert!(build_pattern("(", false, false).is_ok());
+    }
+
+    #[test]
+    fn test_match_ranges_use_chars() {
+        let pattern = build_pattern("文件", false, false).unwrap();
+        assert_eq!(match_ranges(&pattern, "打开文件失败"), vec![SearchRange { start: 2, end: 4 }]);
+    }
+}
@@ -1,13 +1,19 @@
 // PTY 会话管理
 
-use portable_pty::{native_pty_system, Child, MasterPty, PtySize};
+use portable_pty::{native_pty_system, Child, ExitStatus, MasterPty, PtySize};
 use std::io::{Read, Write};
 use std::sync::{Arc, Mutex};
 
+use super::TerminalSize;
+
 /// PTY 会话
 pub struct PtySession {
     master: Box<dyn MasterPty + Send>,
     child: Arc<Mutex<Box<dyn Child + Send + Sync>>>,
+    /// 子进程 PID
+    pid: Option<u32>,
+    /// 实际启动的 shell 程序 (如 `/bin/zsh`)
+    program: String,
 }
 
 /// PTY 读取器 (独立，无需锁)
@@ -24,15 +30,13 @@ impl PtySession {
     /// 创建新的 PTY 会话，返回 (session, reader, writer)
     /// 
     /// # 参数
-    /// - `cols`: 终端列数
-    /// - `rows`: 终端行数
+    /// - `size`: 终端尺寸 (创建 PTY 时设置，shell 启动时即为该尺寸)
     /// - `shell_type`: 可选的 shell 类型 (cmd, powershell, wsl, bash, zsh, custom:/path)
     /// - `shell_args`: 可选的 shell 启动参数
     /// - `cwd`: 可选的工作目录
     /// - `env`: 可选的环境变量
     pub fn new(
-        cols: u16, 
-        rows: u16, 
+        size: TerminalSize,
         shell_type: Option<&str>,
         shell_args: Option<&[String]>,
         cwd: Option<&str>,
@@ -42,15 +46,11 @@ impl PtySession {
         let pty_system = native_pty_system();
         
         // 创建 PTY 对
-        let pair = pty_system.openpty(PtySize {
-            rows,
-            cols,
-            pixel_width: 0,
-            pixel_height: 0,
-        })?;
+        let pair = pty_system.openpty(size.into())?;
         
         // 根据 shell 类型获取命令
         let mut cmd = super::shell::get_shell_by_type(shell_type);
+        let program = super::shell::shell_program(&cmd);
         
         // 添加启动参数
         if let Some(args) = shell_args {
@@ -112,29 +112,89 @@ impl PtySession {
         
         let session = Self {
             master: pair.master,
+            pid: child.process_id(),
             child: Arc::new(Mutex::new(child)),
+            program,
         };
         
         Ok((session, reader, writer))
     }
 
-    /// 调整 PTY 尺寸
-    pub fn resize(&mut self, cols: u16, rows: u16) -> Result<(), Box<dyn std::error::Error>> {
-        self.master.resize(PtySize {
-            rows,
-            cols,
-            pixel_width: 0,
-            pixel_height: 0,
-        })?;
-        Ok(())
+    /// 子进程 PID (平台不支持时为 None)
+    pub fn process_id(&self) -> Option<u32> {
+        self.pid
+    }
+
+    /// 实际启动的 shell 程序 (`shell_type` 未指定时为默认 shell)
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// 调整 PTY 尺寸，返回实际生效的尺寸
+    pub fn resize(&mut self, size: TerminalSize) -> Result<TerminalSize, Box<dyn std::error::Error>> {
+        self.master.resize(size.into())?;
+        Ok(self.master.get_size()?.into())
     }
     
-    /// 终止子进程
-    pub fn kill(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Ok(mut child) = self.child.lock() {
-            child.kill()?;
+    /// 检查子进程是否已退出 (不阻塞)，已退出时回收并返回退出状态
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>, Box<dyn std::error::Error>> {
+        let mut child = self.child.lock().map_err(|e| e.to_string())?;
+        Ok(child.try_wait()?)
+    }
+
+    /// 终止子进程及其进程组并回收 (避免留下僵尸进程)，返回退出状态
+    ///
+    /// portable-pty 的终止 (SIGHUP 后轮询等待) 和回收都会阻塞线程，在阻塞线程池中执行
+    pub async fn kill(&mut self) -> Result<ExitStatus, Box<dyn std::error::Error + Send + Sync>> {
+        let child = Arc::clone(&self.child);
+        let pid = self.pid;
+        tokio::task::spawn_blocking(move || {
+            let mut child = child.lock().map_err(|e| e.to_string())?;
+            // 回收后进程 ID 可能被复用，只在回收前向进程组发送信号
+            if child.try_wait()?.is_none() {
+                hangup_process_group(pid);
+            }
+            // 进程可能已自行退出，忽略终止错误，仍然回收
+            let _ = child.kill();
+            Ok(child.wait()?)
+        }).await?
+    }
+}
+
+/// 向 shell 所在的进程组发送 SIGHUP
+///
+/// shell 在新会话中启动，进程组 ID 即其 PID；同组的管道和未启用作业控制时的后台进程随之结束
+#[cfg(unix)]
+fn hangup_process_group(pid: Option<u32>) {
+    if let Some(pid) = pid {
+        // SAFETY: killpg 只发送信号，不涉及内存
+        unsafe { libc::killpg(pid as libc::pid_t, libc::SIGHUP) };
+    }
+}
+
+/// Windows 没有进程组信号，由终止 shell 进程结束会话
+#[cfg(not(unix))]
+fn hangup_process_group(_pid: Option<u32>) {}
+
+impl From<TerminalSize> for PtySize {
+    fn from(size: TerminalSize) -> Self {
+        PtySize {
+            rows: size.rows,
+            cols: size.cols,
+            pixel_width: size.pixel_width,
+            pixel_height: size.pixel_height,
+        }
+    }
+}
+
+impl From<PtySize> for TerminalSize {
+    fn from(size: PtySize) -> Self {
+        TerminalSize {
+            cols: size.cols,
+            rows: size.rows,
+            pixel_width: size.pixel_width,
+            pixel_height: size.pixel_height,
         }
-        Ok(())
     }
 }
 
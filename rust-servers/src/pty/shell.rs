@@ -2,38 +2,68 @@
 
 use portable_pty::CommandBuilder;
 
-/// Shell Integration 脚本 (通过 PTY 注入)
-/// 使用空格前缀防止命令进入历史记录，使用重定向隐藏输出
-/// 注意: bash/zsh 默认配置不记录以空格开头的命令
-/// 仅在 Unix 平台使用，Windows 依赖前端 prompt 解析
+// Shell Integration 脚本 (通过 PTY 注入)
+// 使用空格前缀防止命令进入历史记录，使用重定向隐藏输出
+// 注意: bash/zsh 默认配置不记录以空格开头的命令
+// 仅在 Unix 平台使用，Windows 依赖前端 prompt 解析
+//
+// 脚本发出的标记 (由 integration.rs 解析):
+// - OSC 7 `file://host/path`: 工作目录
+// - OSC 133 `A`: 提示符开始
+// - OSC 133 `C;cmdline_url=...`: 命令开始执行，命令行中的 `%`、`;`、换行经百分号编码
+// - OSC 133 `D;exit_code`: 命令结束
 
-// Bash: 定义函数并设置 PROMPT_COMMAND，静默执行
+// Bash: PROMPT_COMMAND 首尾分别记录退出码和输出提示符标记，DEBUG trap 在命令执行前输出命令行 (fc 取整行)
+// 与用户原有的 PROMPT_COMMAND 以换行分隔，原值以 `;` 结尾时 (如 pyenv-virtualenv) 不会产生 `;;` 语法错误
 #[cfg(not(windows))]
-const SHELL_INTEGRATION_BASH: &str = " eval '__sw_cwd(){ printf \"\\e]7;file://%s%s\\e\\\\\" \"${HOSTNAME:-localhost}\" \"$PWD\";};PROMPT_COMMAND=\"__sw_cwd${PROMPT_COMMAND:+;$PROMPT_COMMAND}\"' 2>/dev/null;__sw_cwd;printf '\\ec'\n";
+const SHELL_INTEGRATION_BASH: &str = " eval 'printf -v __sw_nl \"\\n\";__sw_cwd(){ printf \"\\e]7;file://%s%s\\e\\\\\" \"${HOSTNAME:-localhost}\" \"$PWD\";};__sw_status(){ __sw_ec=$?;};__sw_prompt(){ [ -n \"$__sw_run\" ] && printf \"\\e]133;D;%s\\a\" \"$__sw_ec\";__sw_run=;__sw_cwd;printf \"\\e]133;A\\a\";__sw_ready=1;};__sw_preexec(){ [ -n \"$__sw_ready\" ] || return;__sw_ready=;[ \"$BASH_COMMAND\" = __sw_status ] && return;__sw_run=1;local c;c=$(fc -ln -0 2>/dev/null);c=${c:-$BASH_COMMAND};c=${c//[%]/%25};c=${c//[;]/%3B};c=${c//$__sw_nl/%0A};printf \"\\e]133;C;cmdline_url=%s\\a\" \"$c\";};trap __sw_preexec DEBUG;PROMPT_COMMAND=\"__sw_status$__sw_nl${PROMPT_COMMAND:+$PROMPT_COMMAND$__sw_nl}__sw_prompt\"' 2>/dev/null;__sw_cwd;printf '\\ec'\n";
 
-// Zsh: 使用 precmd hook，静默执行
+// Zsh: 使用 precmd / preexec / chpwd hook
 #[cfg(not(windows))]
-const SHELL_INTEGRATION_ZSH: &str = " eval '__sw_cwd(){ printf \"\\e]7;file://%s%s\\e\\\\\" \"${HOST:-localhost}\" \"$PWD\";};autoload -Uz add-zsh-hook;add-zsh-hook precmd __sw_cwd;add-zsh-hook chpwd __sw_cwd' 2>/dev/null;__sw_cwd;printf '\\ec'\n";
+const SHELL_INTEGRATION_ZSH: &str = " eval 'printf -v __sw_nl \"\\n\";__sw_cwd(){ printf \"\\e]7;file://%s%s\\e\\\\\" \"${HOST:-localhost}\" \"$PWD\";};__sw_precmd(){ local ec=$?;[ -n \"$__sw_run\" ] && printf \"\\e]133;D;%s\\a\" \"$ec\";__sw_run=;__sw_cwd;printf \"\\e]133;A\\a\";};__sw_preexec(){ __sw_run=1;local c=${1//\\%/%25};c=${c//;/%3B};c=${c//$__sw_nl/%0A};printf \"\\e]133;C;cmdline_url=%s\\a\" \"$c\";};autoload -Uz add-zsh-hook;add-zsh-hook precmd __sw_precmd;add-zsh-hook preexec __sw_preexec;add-zsh-hook chpwd __sw_cwd' 2>/dev/null;__sw_cwd;printf '\\ec'\n";
 
-// Fish: 使用事件监听器
+// Fish: 使用 fish_prompt / fish_preexec / fish_postexec 事件
 #[cfg(not(windows))]
-const SHELL_INTEGRATION_FISH: &str = " eval 'function __sw_cwd --on-variable PWD; printf \"\\e]7;file://%s%s\\e\\\\\" (hostname) $PWD; end' 2>/dev/null;__sw_cwd;printf '\\ec'\n";
+const SHELL_INTEGRATION_FISH: &str = " eval 'function __sw_cwd --on-variable PWD; printf \"\\e]7;file://%s%s\\e\\\\\" (hostname) $PWD; end;function __sw_prompt --on-event fish_prompt; printf \"\\e]133;A\\a\"; end;function __sw_preexec --on-event fish_preexec; printf \"\\e]133;C;cmdline_url=%s\\a\" (string escape --style=url -- $argv); end;function __sw_postexec --on-event fish_postexec; printf \"\\e]133;D;%s\\a\" $status; end' 2>/dev/null;__sw_cwd;printf '\\ec'\n";
+
+/// 当前平台支持的 shell 类型
+/// 
+/// 另外支持 `custom:/path/to/shell` 形式的自定义 shell
+#[cfg(windows)]
+pub const SUPPORTED_SHELL_TYPES: &[&str] = &["cmd", "powershell", "wsl", "gitbash", "bash", "zsh"];
+
+/// 当前平台支持的 shell 类型
+/// 
+/// 另外支持 `custom:/path/to/shell` 形式的自定义 shell
+#[cfg(not(windows))]
+pub const SUPPORTED_SHELL_TYPES: &[&str] = &["bash", "zsh", "fish"];
+
+/// 注入 Shell Integration 脚本的 shell 类型
+#[cfg(windows)]
+pub const SHELL_INTEGRATION_TYPES: &[&str] = &[];
+
+/// 注入 Shell Integration 脚本的 shell (按实际启动程序的文件名匹配，
+/// 包括默认 shell 和 `custom:` 路径)
+#[cfg(not(windows))]
+pub const SHELL_INTEGRATION_TYPES: &[&str] = &["bash", "zsh", "fish"];
 
 /// 获取 Shell Integration 脚本
 /// 
+/// `program` 为实际启动的 shell 程序 (如 `/usr/bin/fish`)，按文件名选择脚本。
 /// 注意: Windows 平台的 shell 不使用 Shell Integration，依赖前端 prompt 解析
-pub fn get_shell_integration_script(shell_type: &str) -> Option<&'static str> {
+pub fn get_shell_integration_script(program: &str) -> Option<&'static str> {
     // Windows 平台不注入脚本
     #[cfg(windows)]
     {
-        let _ = shell_type; // 避免未使用警告
+        let _ = program; // 避免未使用警告
         None
     }
     
     // Unix 平台使用 Shell Integration
     #[cfg(not(windows))]
     {
-        match shell_type {
+        let name = std::path::Path::new(program).file_name().and_then(|name| name.to_str()).unwrap_or(program);
+        match name {
             "bash" => Some(SHELL_INTEGRATION_BASH),
             "zsh" => Some(SHELL_INTEGRATION_ZSH),
             "fish" => Some(SHELL_INTEGRATION_FISH),
@@ -85,6 +115,7 @@ pub fn get_shell_by_type(shell_type: Option<&str>) -> CommandBuilder {
         }
         Some("bash") => CommandBuilder::new("bash"),
         Some("zsh") => CommandBuilder::new("zsh"),
+        Some("fish") => CommandBuilder::new("fish"),
         Some(custom) if custom.starts_with("custom:") => {
             // 自定义 shell 路径，格式: "custom:/path/to/shell"
             let path = &custom[7..]; // 移除 "custom:" 前缀
@@ -94,6 +125,14 @@ pub fn get_shell_by_type(shell_type: Option<&str>) -> CommandBuilder {
     }
 }
 
+/// 获取 Shell 命令实际启动的程序
+pub fn shell_program(cmd: &CommandBuilder) -> String {
+    cmd.get_argv()
+        .first()
+        .map(|program| program.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
 /// 获取默认 Shell 命令
 pub fn get_default_shell() -> CommandBuilder {
     #[cfg(windows)]
@@ -177,10 +216,8 @@ mod tests {
 
     #[test]
     fn test_get_default_shell() {
-        // 只测试函数能成功返回，不检查具体内容
-        // 因为 CommandBuilder 没有提供公开 API 来获取程序路径
-        let _shell = get_default_shell();
-        // 如果能到达这里，函数工作正常
+        let shell = get_default_shell();
+        assert!(!shell_program(&shell).is_empty());
     }
     
     #[test]
@@ -207,6 +244,11 @@ mod tests {
         // 测试不会 panic
     }
     
+    #[test]
+    fn test_get_shell_by_type_fish() {
+        assert_eq!(shell_program(&get_shell_by_type(Some("fish"))), "fish");
+    }
+    
     #[test]
     fn test_get_shell_by_type_custom() {
         let _cmd = get_shell_by_type(Some("custom:/bin/sh"));
@@ -224,4 +266,17 @@ mod tests {
         let _cmd = get_shell_by_type(Some("unknown_shell"));
         // 未知类型应该返回默认 shell
     }
+    
+    #[test]
+    #[cfg(not(windows))]
+    fn test_shell_integration_script_by_program() {
+        assert_eq!(get_shell_integration_script("bash"), Some(SHELL_INTEGRATION_BASH));
+        assert_eq!(get_shell_integration_script("/bin/zsh"), Some(SHELL_INTEGRATION_ZSH));
+        assert_eq!(get_shell_integration_script("/usr/bin/fish"), Some(SHELL_INTEGRATION_FISH));
+        assert_eq!(get_shell_integration_script("/bin/sh"), None);
+
+        // custom: 路径按实际程序选择脚本
+        let custom = get_shell_by_type(Some("custom:/usr/local/bin/fish"));
+        assert_eq!(get_shell_integration_script(&shell_program(&custom)), Some(SHELL_INTEGRATION_FISH));
+    }
 }
@@ -1,61 +1,107 @@
 // 消息路由器
-// 根据 module 字段将消息分发到对应的功能模块
+// 根据 module 字段将消息分发到按名称注册的功能模块
 
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+use crate::error::{ErrorCode, ServerError};
+use crate::frame::{self, BinarySender};
+use crate::i18n::{Locale, Msg, SharedLocale};
+use crate::lifecycle::ConnectionTracker;
+use crate::metrics::{Diagnostics, Metrics};
+use crate::protocol::{ModuleProtocol, Payload};
 use crate::server::WsSender;
 
-/// 日志宏
-macro_rules! log_info {
-    ($($arg:tt)*) => {
-        eprintln!("[INFO] {}", format!($($arg)*));
-    };
-}
+// ============================================================================
+// 协议版本
+// ============================================================================
 
-#[allow(unused_macros)]
-macro_rules! log_error {
-    ($($arg:tt)*) => {
-        eprintln!("[ERROR] {}", format!($($arg)*));
-    };
-}
+/// 协议版本
+///
+/// 消息格式发生不兼容变更时递增
+pub const PROTOCOL_VERSION: u32 = 1;
 
-macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        if cfg!(debug_assertions) {
-            eprintln!("[DEBUG] {}", format!($($arg)*));
-        }
-    };
-}
+/// 服务器仍兼容的最低客户端协议版本
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// 握手消息类型 (由路由器直接应答，可发送到任意模块)
+pub const HELLO_MESSAGE_TYPE: &str = "hello";
+
+/// 服务器关闭广播消息类型 (服务器主动推送，不对应任何请求)
+pub const SHUTTING_DOWN_MESSAGE_TYPE: &str = "server_shutting_down";
 
 // ============================================================================
 // 模块类型和消息定义
 // ============================================================================
 
-/// 模块类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum ModuleType {
+/// 模块名称 (消息中的 `module` 字段)
+///
+/// 模块在路由器中按名称注册，名称不限于内置模块
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ModuleType(Cow<'static, str>);
+
+impl ModuleType {
     /// PTY 终端模块
-    Pty,
+    #[cfg(feature = "pty")]
+    pub const PTY: ModuleType = ModuleType::new("pty");
     /// 语音模块
-    Voice,
+    #[cfg(feature = "voice")]
+    pub const VOICE: ModuleType = ModuleType::new("voice");
     /// LLM 流式处理模块
-    Llm,
-    /// 工具模块
-    Utils,
+    #[cfg(feature = "llm")]
+    pub const LLM: ModuleType = ModuleType::new("llm");
+    /// 工具模块 (也用于不属于任何模块的服务器消息，如解析错误和关闭通知，未编译时同样可用)
+    pub const UTILS: ModuleType = ModuleType::new("utils");
+    
+    /// 创建模块名称
+    pub const fn new(name: &'static str) -> Self {
+        Self(Cow::Borrowed(name))
+    }
+    
+    /// 获取名称字符串
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::str::FromStr for ModuleType {
+    type Err = String;
+    
+    /// 解析内置模块名称 (用于命令行和配置文件)，只接受已编译的模块
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BUILTIN_MODULES
+            .iter()
+            .find(|builtin| builtin.name.as_str() == s)
+            .map(|builtin| builtin.name.clone())
+            .ok_or_else(|| format!("未知模块或未编译: {}", s))
+    }
 }
 
 impl std::fmt::Display for ModuleType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ModuleType::Pty => write!(f, "pty"),
-            ModuleType::Voice => write!(f, "voice"),
-            ModuleType::Llm => write!(f, "llm"),
-            ModuleType::Utils => write!(f, "utils"),
-        }
+        f.write_str(&self.0)
     }
 }
 
+/// 请求 ID (消息顶层的 `id` 字段，字符串或整数)
+///
+/// 服务器在该请求产生的响应、流式事件和错误中原样返回，客户端据此匹配请求
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum RequestId {
+    /// 整数 ID
+    Number(i64),
+    /// 字符串 ID
+    String(String),
+}
+
 /// 统一消息格式
 /// 
 /// 所有客户端消息必须包含 `module` 字段来指定目标模块
@@ -66,6 +112,9 @@ pub struct ModuleMessage {
     /// 消息类型
     #[serde(rename = "type")]
     pub msg_type: String,
+    /// 请求 ID (可选)
+    #[serde(default)]
+    pub id: Option<RequestId>,
     /// 消息负载 (保留原始 JSON 以便各模块解析)
     #[serde(flatten)]
     pub payload: serde_json::Value,
@@ -79,9 +128,38 @@ impl ModuleMessage {
     }
     
     /// 获取负载中的字段值
+    #[allow(dead_code)]
     pub fn get_field<T: serde::de::DeserializeOwned>(&self, field: &str) -> Option<T> {
         self.payload.get(field).and_then(|v| serde_json::from_value(v.clone()).ok())
     }
+    
+    /// 将负载解析为请求类型，字段缺失或类型不符时返回错误
+    pub fn parse<P: Payload + DeserializeOwned>(&self) -> Result<P, RouterError> {
+        serde_json::from_value(self.payload.clone()).map_err(|e| {
+            RouterError::InvalidMessage(Msg::InvalidRequest { msg_type: P::TYPE, error: e.to_string() })
+        })
+    }
+}
+
+/// 模块能力描述
+///
+/// 用于 `hello` 握手响应，客户端据此判断服务器支持的功能
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ModuleCapabilities {
+    /// 支持的消息类型
+    pub message_types: Vec<&'static str>,
+    /// 功能详情 (各模块自定义，如 API 格式、ASR 供应商、shell 类型)
+    pub features: serde_json::Value,
+}
+
+impl ModuleCapabilities {
+    /// 创建模块能力描述
+    pub fn new(message_types: &[&'static str], features: serde_json::Value) -> Self {
+        Self {
+            message_types: message_types.to_vec(),
+            features,
+        }
+    }
 }
 
 /// 服务器响应消息
@@ -92,6 +170,9 @@ pub struct ServerResponse {
     /// 消息类型
     #[serde(rename = "type")]
     pub msg_type: String,
+    /// 对应请求的 ID (请求未指定时省略)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<RequestId>,
     /// 响应负载
     #[serde(flatten)]
     pub payload: serde_json::Value,
@@ -104,23 +185,90 @@ impl ServerResponse {
         Self {
             module,
             msg_type: msg_type.to_string(),
+            id: None,
             payload,
         }
     }
     
-    /// 创建错误响应
-    pub fn error(module: ModuleType, code: &str, message: &str) -> Self {
-        Self {
-            module,
-            msg_type: "error".to_string(),
-            payload: serde_json::json!({
-                "code": code,
-                "message": message
-            }),
+    /// 由消息负载创建服务器响应
+    pub fn from_payload<P: Payload + Serialize>(module: ModuleType, payload: &P) -> Self {
+        Self::new(module, P::TYPE, serde_json::to_value(payload).unwrap_or_default())
+    }
+    
+    /// 创建错误响应 (按连接语言渲染错误描述)
+    pub fn error(module: ModuleType, error: &ServerError, locale: Locale) -> Self {
+        Self::from_payload(module, &error.to_payload(locale))
+    }
+    
+    /// 附加请求 ID (已有 ID 时保留原值)
+    pub fn with_id(mut self, id: Option<RequestId>) -> Self {
+        if self.id.is_none() {
+            self.id = id;
         }
+        self
     }
 }
 
+// ============================================================================
+// 握手和服务器通知
+// ============================================================================
+
+/// 握手请求 (`hello`，可发送到任意模块)
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct HelloRequest {
+    /// 客户端协议版本
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    /// 客户端是否支持二进制分帧
+    #[serde(default)]
+    pub binary_frames: Option<bool>,
+    /// 客户端语言 (如 `en`、`zh-CN`)，不支持的语言保持原设置
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+impl Payload for HelloRequest {
+    const TYPE: &'static str = HELLO_MESSAGE_TYPE;
+}
+
+/// 握手响应: 服务器版本、协议版本和已启用模块的能力
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct HelloResponse {
+    /// 服务器版本
+    pub server_version: &'static str,
+    /// 服务器协议版本
+    pub protocol_version: u32,
+    /// 服务器兼容的最低客户端协议版本
+    pub min_protocol_version: u32,
+    /// 客户端协议版本是否兼容 (客户端未声明版本时为 true)
+    pub compatible: bool,
+    /// 本连接是否已启用二进制分帧
+    pub binary_frames: bool,
+    /// 本连接的语言
+    pub locale: Locale,
+    /// 支持的语言
+    pub locales: &'static [Locale],
+    /// 已启用的模块及其能力
+    pub modules: BTreeMap<String, ModuleCapabilities>,
+}
+
+impl Payload for HelloResponse {
+    const TYPE: &'static str = HELLO_MESSAGE_TYPE;
+}
+
+/// 服务器关闭通知 (由 utils 模块发出，不对应任何请求)
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ShuttingDownNotice {
+    /// 关闭原因: `signal`、`requested`、`parent_exited`、`idle_timeout`、`stdin_closed`
+    pub reason: &'static str,
+    /// 按连接语言渲染的关闭原因
+    pub message: String,
+}
+
+impl Payload for ShuttingDownNotice {
+    const TYPE: &'static str = SHUTTING_DOWN_MESSAGE_TYPE;
+}
+
 // ============================================================================
 // 路由器错误
 // ============================================================================
@@ -130,43 +278,274 @@ impl ServerResponse {
 pub enum RouterError {
     /// 未知模块
     #[error("Unknown module: {0}")]
-    #[allow(dead_code)]
     UnknownModule(String),
     
     /// 无效的消息格式
     #[error("Invalid message format: {0}")]
-    #[allow(dead_code)]
-    InvalidMessage(String),
+    InvalidMessage(Msg),
     
-    /// 模块处理错误
+    /// 模块处理错误 (第三方模块的错误描述，按原文返回；内置模块使用 `RouterError::module`)
     #[error("Module error: {0}")]
+    #[allow(dead_code)]
     ModuleError(String),
     
+    /// 模块已被服务器配置禁用
+    #[error("Module disabled: {0}")]
+    ModuleDisabled(ModuleType),
+    
     /// JSON 序列化/反序列化错误
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
+    
+    /// 带错误码和详情的模块错误
+    #[error("{0}")]
+    Server(#[from] ServerError),
+}
+
+impl RouterError {
+    /// 内置模块的模块错误 (错误描述取自消息目录)
+    #[cfg_attr(not(any(feature = "pty", feature = "voice", feature = "utils")), allow(dead_code))]
+    pub fn module(message: Msg) -> Self {
+        ServerError::new(ErrorCode::ModuleError, message).into()
+    }
+    
+    /// 转为带错误码的错误
+    pub fn to_server_error(&self) -> ServerError {
+        match self {
+            RouterError::UnknownModule(m) => {
+                ServerError::new(ErrorCode::UnknownModule, Msg::UnknownModule { module: m.clone() })
+            }
+            RouterError::InvalidMessage(m) => ServerError::new(ErrorCode::InvalidMessage, m.clone()),
+            RouterError::ModuleError(m) => ServerError::new(ErrorCode::ModuleError, Msg::Text(m.clone())),
+            RouterError::ModuleDisabled(m) => {
+                ServerError::new(ErrorCode::ModuleDisabled, Msg::ModuleDisabled { module: m.to_string() })
+            }
+            RouterError::JsonError(e) => {
+                ServerError::new(ErrorCode::JsonError, Msg::JsonError { error: e.to_string() })
+            }
+            RouterError::Server(e) => e.clone(),
+        }
+    }
+}
+
+// ============================================================================
+// 模块配置
+// ============================================================================
+
+/// 各功能模块的配置 (由命令行 / 配置文件生成，传入每个连接的处理器)
+#[derive(Debug, Clone)]
+pub struct ModulesConfig {
+    /// 启用的模块
+    pub enabled: Vec<ModuleType>,
+    /// PTY 模块配置
+    #[cfg(feature = "pty")]
+    pub pty: crate::pty::PtyConfig,
+    /// LLM 模块配置
+    #[cfg(feature = "llm")]
+    pub llm: crate::llm::LlmConfig,
+    /// ASR 请求重试配置
+    #[cfg(feature = "voice")]
+    pub asr_retry: crate::voice::asr::RetryConfig,
+}
+
+impl Default for ModulesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: BUILTIN_MODULES.iter().map(|builtin| builtin.name.clone()).collect(),
+            #[cfg(feature = "pty")]
+            pty: Default::default(),
+            #[cfg(feature = "llm")]
+            llm: Default::default(),
+            #[cfg(feature = "voice")]
+            asr_retry: Default::default(),
+        }
+    }
+}
+
+/// 服务器范围内各模块共享的状态 (跨连接保留)
+#[derive(Clone, Default)]
+pub struct ModuleState {
+    /// PTY 会话注册表 (连接断开后会话在宽限期内保留)
+    #[cfg(feature = "pty")]
+    pub pty_registry: Arc<crate::pty::SessionRegistry>,
+    /// 运行指标
+    pub metrics: Arc<Metrics>,
+    /// 活动连接计数 (空闲超时和诊断信息共用)
+    pub connections: Arc<ConnectionTracker>,
+}
+
+impl ModuleState {
+    /// 按模块配置创建共享状态
+    pub fn new(config: &ModulesConfig) -> Self {
+        let _ = config;
+        Self {
+            #[cfg(feature = "pty")]
+            pty_registry: Arc::new(crate::pty::SessionRegistry::new(config.pty.detach.clone())),
+            metrics: Arc::new(Metrics::new()),
+            connections: Arc::new(ConnectionTracker::new()),
+        }
+    }
+    
+    /// 诊断信息: 运行时长、版本、连接数、各模块的运行状态和计数
+    ///
+    /// 只包含已编译模块的状态
+    #[cfg_attr(not(feature = "utils"), allow(dead_code))]
+    pub async fn diagnostics(&self) -> Diagnostics {
+        let metrics = &self.metrics;
+        Diagnostics {
+            server_version: env!("CARGO_PKG_VERSION"),
+            protocol_version: PROTOCOL_VERSION,
+            pid: std::process::id(),
+            uptime_ms: metrics.uptime().as_millis() as u64,
+            connections: self.connections.active(),
+            modules: metrics.module_counters(),
+            #[cfg(feature = "pty")]
+            pty: crate::metrics::PtyDiagnostics {
+                sessions: self.pty_registry.sessions().await,
+            },
+            #[cfg(feature = "voice")]
+            voice: crate::metrics::VoiceDiagnostics {
+                active_recordings: metrics.recordings.get(),
+                asr_latency: metrics.asr_latency(),
+            },
+            #[cfg(feature = "llm")]
+            llm: crate::metrics::LlmDiagnostics {
+                active_streams: metrics.llm_streams.get(),
+                time_to_first_token: metrics.llm_ttft(),
+            },
+        }
+    }
+    
+    /// 服务器关闭时释放共享资源 (终止所有 PTY 会话)
+    pub async fn shutdown(&self) {
+        #[cfg(feature = "pty")]
+        self.pty_registry.kill_all().await;
+    }
 }
 
 // ============================================================================
 // 模块处理器 trait
 // ============================================================================
 
+/// 连接上下文 (连接建立时传给各模块)
+#[derive(Clone)]
+pub struct ModuleContext {
+    /// 文本消息发送器
+    pub ws_sender: WsSender,
+    /// 二进制数据发送器 (按连接协商的分帧模式发送)
+    #[cfg_attr(not(feature = "pty"), allow(dead_code))]
+    pub binary_sender: BinarySender,
+    /// 连接语言 (渲染模块主动推送的错误消息)
+    #[cfg_attr(not(any(feature = "voice", feature = "llm")), allow(dead_code))]
+    pub locale: SharedLocale,
+}
+
 /// 模块处理器 trait
 /// 
-/// 各功能模块需要实现此 trait 来处理消息
+/// 各功能模块需要实现此 trait 来处理消息，并注册到路由器。
+/// 每个连接创建一组新的处理器，生命周期钩子按 `on_connect` → 消息处理 → `on_disconnect` → `cleanup` 顺序调用。
 #[async_trait::async_trait]
 pub trait ModuleHandler: Send + Sync {
-    /// 获取模块类型
-    #[allow(dead_code)]
+    /// 获取模块名称
     fn module_type(&self) -> ModuleType;
     
+    /// 二进制帧通道 ID (见 [`crate::frame`])，不接收二进制帧的模块返回 None
+    fn binary_channel(&self) -> Option<u8> {
+        None
+    }
+    
+    /// 获取模块能力描述 (支持的消息类型和功能详情)
+    fn capabilities(&self) -> ModuleCapabilities;
+    
     /// 处理消息
     /// 
     /// 返回 Some(response) 表示需要发送响应
     /// 返回 None 表示无需响应（如异步处理）
     async fn handle(&self, msg: &ModuleMessage) -> Result<Option<ServerResponse>, RouterError>;
+    
+    /// 处理二进制数据
+    /// 
+    /// `stream_id` 为帧头中的流 ID，旧版未分帧的数据为 None。
+    /// 默认不支持二进制数据。
+    async fn handle_binary(&self, stream_id: Option<u32>, data: &[u8]) -> Result<(), RouterError> {
+        let _ = (stream_id, data);
+        Err(ServerError::new(
+            ErrorCode::ModuleError,
+            Msg::BinaryNotSupported { module: self.module_type().to_string() },
+        ).into())
+    }
+    
+    /// 是否接收无法解析为模块消息的纯文本 (旧版客户端的 PTY 输入)
+    ///
+    /// 仅对旧版通道 ([`frame::LEGACY_CHANNEL`]) 的模块生效
+    async fn accepts_raw_text(&self) -> bool {
+        false
+    }
+    
+    /// 连接建立后调用，保存本连接的发送器
+    async fn on_connect(&self, context: &ModuleContext) {
+        let _ = context;
+    }
+    
+    /// 连接断开后立即调用 (如断开 PTY 会话以便重新连接)
+    async fn on_disconnect(&self) {}
+    
+    /// 释放模块资源 (停止录音、取消请求)
+    ///
+    /// 在 `on_disconnect` 之后与其他模块并行执行，超过截止时间将不再等待
+    async fn cleanup(&self) {}
 }
 
+// ============================================================================
+// 内置模块
+// ============================================================================
+
+/// 内置模块的名称和构造函数
+pub struct BuiltinModule {
+    /// 模块名称
+    pub name: ModuleType,
+    /// 为新连接创建处理器
+    pub create: fn(&ModuleState, &ModulesConfig) -> Box<dyn ModuleHandler>,
+    /// 模块的请求和服务器消息 (用于能力描述和 `--print-schema`)
+    pub protocol: fn() -> ModuleProtocol,
+}
+
+/// 已编译的内置模块 (按注册顺序)
+///
+/// 新增模块只需实现 [`ModuleHandler`]、描述其消息，并在此登记 (可用 cargo feature 控制是否编译)
+pub const BUILTIN_MODULES: &[BuiltinModule] = &[
+    #[cfg(feature = "pty")]
+    BuiltinModule {
+        name: ModuleType::PTY,
+        create: |state, config| {
+            Box::new(crate::pty::PtyHandler::with_config(Arc::clone(&state.pty_registry), &config.pty))
+        },
+        protocol: crate::pty::protocol,
+    },
+    #[cfg(feature = "voice")]
+    BuiltinModule {
+        name: ModuleType::VOICE,
+        create: |state, config| {
+            Box::new(crate::voice::VoiceHandler::with_config(config.asr_retry.clone(), Arc::clone(&state.metrics)))
+        },
+        protocol: crate::voice::protocol,
+    },
+    #[cfg(feature = "llm")]
+    BuiltinModule {
+        name: ModuleType::LLM,
+        create: |state, config| {
+            Box::new(crate::llm::LLMHandler::with_config(&config.llm, Arc::clone(&state.metrics)))
+        },
+        protocol: crate::llm::protocol,
+    },
+    #[cfg(feature = "utils")]
+    BuiltinModule {
+        name: ModuleType::UTILS,
+        create: |state, _| Box::new(crate::utils::UtilsHandler::with_state(state.clone())),
+        protocol: crate::utils::protocol,
+    },
+];
+
 // ============================================================================
 // 消息路由器
 // ============================================================================
@@ -175,53 +554,115 @@ pub trait ModuleHandler: Send + Sync {
 /// 
 /// 负责将消息路由到对应的功能模块
 pub struct MessageRouter {
-    // PTY 模块处理器
-    pty_handler: crate::pty::PtyHandler,
-    // Voice 模块处理器
-    voice_handler: crate::voice::VoiceHandler,
-    // LLM 模块处理器
-    llm_handler: crate::llm::LLMHandler,
-    // Utils 模块处理器
-    utils_handler: crate::utils::UtilsHandler,
+    // 已注册的模块处理器 (按注册顺序)
+    handlers: Vec<Box<dyn ModuleHandler>>,
+    // 被配置禁用的内置模块 (用于区分 MODULE_DISABLED 和 UNKNOWN_MODULE)
+    disabled_modules: Vec<ModuleType>,
+    // 是否启用二进制分帧 (由 hello 握手协商)
+    binary_frames: Arc<AtomicBool>,
+    // 连接语言 (由 hello 握手协商)
+    locale: SharedLocale,
+    // 运行指标 (服务器范围共享)
+    metrics: Arc<Metrics>,
 }
 
 impl MessageRouter {
     /// 创建新的消息路由器
     pub fn new() -> Self {
-        Self {
-            pty_handler: crate::pty::PtyHandler::new(),
-            voice_handler: crate::voice::VoiceHandler::new(),
-            llm_handler: crate::llm::LLMHandler::new(),
-            utils_handler: crate::utils::UtilsHandler::new(),
+        Self::with_config(&ModuleState::default(), &ModulesConfig::default())
+    }
+    
+    /// 使用共享状态和模块配置创建消息路由器
+    ///
+    /// 注册配置中启用的内置模块
+    pub fn with_config(state: &ModuleState, config: &ModulesConfig) -> Self {
+        let mut router = Self {
+            handlers: Vec::new(),
+            disabled_modules: Vec::new(),
+            binary_frames: Arc::new(AtomicBool::new(false)),
+            locale: SharedLocale::default(),
+            metrics: Arc::clone(&state.metrics),
+        };
+        for builtin in BUILTIN_MODULES {
+            if config.enabled.contains(&builtin.name) {
+                router.register((builtin.create)(state, config));
+            } else {
+                router.disabled_modules.push(builtin.name.clone());
+            }
         }
+        router
     }
     
-    /// 设置 WebSocket 发送器 (用于 PTY 输出、Voice 消息、LLM 流式响应等)
-    pub async fn set_ws_sender(&self, sender: WsSender) {
-        self.pty_handler.set_ws_sender(sender.clone()).await;
-        self.voice_handler.set_ws_sender(sender.clone()).await;
-        self.llm_handler.set_ws_sender(sender.clone()).await;
-        self.utils_handler.set_ws_sender(sender).await;
+    /// 注册模块处理器
+    ///
+    /// 模块名称和二进制通道 ID 必须唯一
+    pub fn register(&mut self, handler: Box<dyn ModuleHandler>) {
+        let module = handler.module_type();
+        assert!(self.handler(&module).is_none(), "模块重复注册: {}", module);
+        if let Some(channel) = handler.binary_channel() {
+            assert!(self.channel_handler(channel).is_err(), "二进制通道重复注册: {}", channel);
+        }
+        
+        log_debug!("注册模块: {}", module);
+        self.disabled_modules.retain(|disabled| *disabled != module);
+        self.handlers.push(handler);
     }
     
-    /// 获取 PTY 处理器引用 (用于写入数据)
-    pub fn pty_handler(&self) -> &crate::pty::PtyHandler {
-        &self.pty_handler
+    /// 查找已注册的模块处理器
+    fn handler(&self, module: &ModuleType) -> Option<&dyn ModuleHandler> {
+        self.handlers
+            .iter()
+            .find(|handler| handler.module_type() == *module)
+            .map(|handler| handler.as_ref())
     }
     
-    /// 获取 Voice 处理器引用
-    pub fn voice_handler(&self) -> &crate::voice::VoiceHandler {
-        &self.voice_handler
+    /// 查找启用的模块处理器，模块不存在或被禁用时返回对应错误
+    fn enabled_handler(&self, module: &ModuleType) -> Result<&dyn ModuleHandler, RouterError> {
+        self.handler(module).ok_or_else(|| {
+            if self.disabled_modules.contains(module) {
+                RouterError::ModuleDisabled(module.clone())
+            } else {
+                RouterError::UnknownModule(module.to_string())
+            }
+        })
     }
     
-    /// 获取 LLM 处理器引用
-    pub fn llm_handler(&self) -> &crate::llm::LLMHandler {
-        &self.llm_handler
+    /// 查找接收指定二进制通道的模块处理器
+    fn channel_handler(&self, channel: u8) -> Result<&dyn ModuleHandler, RouterError> {
+        self.handlers
+            .iter()
+            .find(|handler| handler.binary_channel() == Some(channel))
+            .map(|handler| handler.as_ref())
+            .ok_or_else(|| RouterError::UnknownModule(format!("channel {}", channel)))
     }
     
-    /// 获取 Utils 处理器引用
-    pub fn utils_handler(&self) -> &crate::utils::UtilsHandler {
-        &self.utils_handler
+    /// 连接建立: 将本连接的发送器交给各模块 (用于 PTY 输出、Voice 消息、LLM 流式响应等)
+    pub async fn on_connect(&self, ws_sender: WsSender) {
+        let context = ModuleContext {
+            binary_sender: BinarySender::new(ws_sender.clone(), Arc::clone(&self.binary_frames)),
+            ws_sender,
+            locale: self.locale.clone(),
+        };
+        for handler in &self.handlers {
+            handler.on_connect(&context).await;
+        }
+    }
+    
+    /// 连接断开: 依次通知各模块
+    pub async fn on_disconnect(&self) {
+        for handler in &self.handlers {
+            handler.on_disconnect().await;
+        }
+    }
+    
+    /// 并行清理各模块资源，每个模块有独立的截止时间
+    pub async fn cleanup(&self, deadline: Duration) {
+        futures_util::future::join_all(self.handlers.iter().map(|handler| async move {
+            if tokio::time::timeout(deadline, handler.cleanup()).await.is_err() {
+                log_error!("{} 模块清理超时 ({} 秒)", handler.module_type(), deadline.as_secs());
+            }
+        }))
+        .await;
     }
     
     /// 解析消息并提取模块类型
@@ -230,6 +671,9 @@ impl MessageRouter {
     pub fn parse_message(&self, text: &str) -> Result<ModuleMessage, RouterError> {
         // 首先尝试解析为 ModuleMessage
         let msg: ModuleMessage = serde_json::from_str(text)?;
+        if !self.is_module_implemented(&msg.module) {
+            return Err(RouterError::UnknownModule(msg.module.to_string()));
+        }
         
         log_debug!("解析消息: module={}, type={}", msg.module, msg.msg_type);
         
@@ -239,75 +683,151 @@ impl MessageRouter {
     /// 尝试从原始 JSON 中解析模块类型
     /// 
     /// 用于在消息解析失败时提取模块信息以便返回正确的错误响应
-    #[allow(dead_code)]
     pub fn try_parse_module(&self, text: &str) -> Option<ModuleType> {
-        if let Ok(value) = serde_json::from_str::<serde_json::Value>(text) {
-            if let Some(module_str) = value.get("module").and_then(|v| v.as_str()) {
-                return match module_str {
-                    "pty" => Some(ModuleType::Pty),
-                    "voice" => Some(ModuleType::Voice),
-                    "llm" => Some(ModuleType::Llm),
-                    "utils" => Some(ModuleType::Utils),
-                    _ => None,
-                };
-            }
-        }
-        None
+        let value = serde_json::from_str::<serde_json::Value>(text).ok()?;
+        let module = ModuleType(Cow::Owned(value.get("module")?.as_str()?.to_string()));
+        self.is_module_implemented(&module).then_some(module)
+    }
+    
+    /// 尝试从原始 JSON 中解析请求 ID
+    /// 
+    /// 用于在消息解析失败时仍能将错误响应关联到请求
+    pub fn try_parse_id(&self, text: &str) -> Option<RequestId> {
+        let value = serde_json::from_str::<serde_json::Value>(text).ok()?;
+        serde_json::from_value(value.get("id")?.clone()).ok()
     }
     
     /// 路由消息到对应模块
     /// 
-    /// 返回模块处理结果或错误响应
+    /// 返回模块处理结果或错误响应，响应中附带请求的 `id`
     /// 
     pub async fn route(&self, msg: ModuleMessage) -> Result<Option<ServerResponse>, RouterError> {
         log_info!("路由消息到模块: {}, 类型: {}", msg.module, msg.msg_type);
         
-        match msg.module {
-            ModuleType::Pty => {
-                // PTY 模块处理
-                log_debug!("PTY 模块消息: {}", msg.msg_type);
-                self.pty_handler.handle(&msg).await
-            }
-            ModuleType::Voice => {
-                // Voice 模块处理
-                log_debug!("Voice 模块消息: {}", msg.msg_type);
-                self.voice_handler.handle(&msg).await
-            }
-            ModuleType::Llm => {
-                // LLM 模块处理
-                log_debug!("LLM 模块消息: {}", msg.msg_type);
-                self.llm_handler.handle(&msg).await
-            }
-            ModuleType::Utils => {
-                // Utils 模块处理
-                log_debug!("Utils 模块消息: {}", msg.msg_type);
-                self.utils_handler.handle(&msg).await
+        let result = if msg.msg_type == HELLO_MESSAGE_TYPE {
+            // 握手消息由路由器直接应答
+            self.handle_hello(&msg).map(Some)
+        } else {
+            match self.enabled_handler(&msg.module) {
+                Ok(handler) => {
+                    log_debug!("{} 模块消息: {}", msg.module, msg.msg_type);
+                    handler.handle(&msg).await
+                }
+                Err(e) => Err(e),
             }
+        };
+        self.metrics.record_message(&msg.module, result.as_ref().err().map(|e| e.to_server_error().code));
+        
+        Ok(result?.map(|response| response.with_id(msg.id)))
+    }
+    
+    /// 路由二进制消息
+    /// 
+    /// 分帧模式下按帧头的通道 ID 分发到对应模块；旧版模式下整帧作为 PTY 输入
+    pub async fn route_binary(&self, data: &[u8]) -> Result<(), RouterError> {
+        if !self.binary_frames_enabled() {
+            log_debug!("旧版二进制数据: {} 字节", data.len());
+            return self.channel_handler(frame::LEGACY_CHANNEL)?.handle_binary(None, data).await;
         }
+        
+        let frame = frame::decode(data)?;
+        log_debug!(
+            "二进制帧: channel={}, stream_id={}, {} 字节",
+            frame.channel, frame.stream_id, frame.payload.len()
+        );
+        
+        self.channel_handler(frame.channel)?
+            .handle_binary(Some(frame.stream_id), frame.payload)
+            .await
     }
     
-    /// 创建错误响应
+    /// 路由无法解析为模块消息的文本
     /// 
-    pub fn create_error_response(&self, module: ModuleType, error: &RouterError) -> ServerResponse {
-        let (code, message) = match error {
-            RouterError::UnknownModule(m) => ("UNKNOWN_MODULE", format!("未知模块: {}", m)),
-            RouterError::InvalidMessage(m) => ("INVALID_MESSAGE", format!("无效消息: {}", m)),
-            RouterError::ModuleError(m) => ("MODULE_ERROR", m.clone()),
-            RouterError::JsonError(e) => ("JSON_ERROR", format!("JSON 错误: {}", e)),
+    /// 兼容旧客户端直接发送纯文本作为 PTY 输入。返回 false 表示没有可写入的 PTY 会话。
+    pub async fn route_raw_text(&self, text: &str) -> Result<bool, RouterError> {
+        let Ok(handler) = self.channel_handler(frame::LEGACY_CHANNEL) else {
+            return Ok(false);
         };
+        if !handler.accepts_raw_text().await {
+            return Ok(false);
+        }
         
-        ServerResponse::error(module, code, &message)
+        log_debug!("将文本作为 {} 输入: {} 字节", handler.module_type(), text.len());
+        handler.handle_binary(None, text.as_bytes()).await?;
+        Ok(true)
     }
     
-    /// 检查模块是否已实现
-    #[allow(dead_code)]
-    pub fn is_module_implemented(&self, module: ModuleType) -> bool {
-        match module {
-            ModuleType::Pty => true,    // PTY 模块已实现
-            ModuleType::Voice => true,  // Voice 模块已实现
-            ModuleType::Llm => true,    // LLM 模块已实现
-            ModuleType::Utils => true,  // Utils 模块已实现
+    /// 是否已启用二进制分帧
+    pub fn binary_frames_enabled(&self) -> bool {
+        self.binary_frames.load(Ordering::Relaxed)
+    }
+    
+    /// 处理握手消息
+    /// 
+    /// 返回服务器版本、协议版本以及各模块支持的消息类型和功能。
+    /// 客户端可在 `protocol_version` 字段中声明自己的协议版本，服务器据此返回 `compatible`。
+    pub fn handle_hello(&self, msg: &ModuleMessage) -> Result<ServerResponse, RouterError> {
+        let request: HelloRequest = msg.parse()?;
+        let compatible = request.protocol_version
+            .is_none_or(|v| (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&v));
+        
+        log_info!("客户端握手: protocol_version={:?}, compatible={}", request.protocol_version, compatible);
+        
+        // 客户端声明支持二进制分帧后，本连接的二进制消息改用分帧格式
+        if request.binary_frames == Some(true) {
+            self.binary_frames.store(true, Ordering::Relaxed);
+        }
+        
+        // 客户端声明语言后，本连接的错误和状态消息改用该语言 (不支持的语言保持原设置)
+        if let Some(tag) = request.locale {
+            match tag.parse::<Locale>() {
+                Ok(locale) => self.locale.set(locale),
+                Err(e) => log_warn!("{}", e),
+            }
         }
+        
+        // 只列出启用的模块
+        let modules = self.handlers
+            .iter()
+            .map(|handler| (handler.module_type().to_string(), handler.capabilities()))
+            .collect();
+        
+        Ok(ServerResponse::from_payload(msg.module.clone(), &HelloResponse {
+            server_version: env!("CARGO_PKG_VERSION"),
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            compatible,
+            binary_frames: self.binary_frames_enabled(),
+            locale: self.locale(),
+            locales: Locale::ALL,
+            modules,
+        }))
+    }
+    
+    /// 记录不经过 [`route`](Self::route) 的错误 (如消息解析错误)
+    pub fn record_error(&self, module: &ModuleType, code: ErrorCode) {
+        self.metrics.record_error(module, code);
+    }
+    
+    /// 本连接的语言
+    pub fn locale(&self) -> Locale {
+        self.locale.get()
+    }
+    
+    /// 创建错误响应 (按连接语言渲染)
+    /// 
+    pub fn create_error_response(&self, module: ModuleType, error: &RouterError) -> ServerResponse {
+        ServerResponse::error(module, &error.to_server_error(), self.locale())
+    }
+    
+    /// 检查模块是否已启用 (已注册处理器)
+    pub fn is_module_enabled(&self, module: &ModuleType) -> bool {
+        self.handler(module).is_some()
+    }
+    
+    /// 检查模块是否存在 (已注册，或是被配置禁用的内置模块)
+    pub fn is_module_implemented(&self, module: &ModuleType) -> bool {
+        self.is_module_enabled(module) || self.disabled_modules.contains(module)
     }
 }
 
@@ -326,12 +846,13 @@ mod tests {
     use super::*;
     
     #[test]
+    #[cfg(feature = "pty")]
     fn test_parse_pty_message() {
         let router = MessageRouter::new();
         let json = r#"{"module": "pty", "type": "init", "shell_type": "powershell"}"#;
         
         let msg = router.parse_message(json).unwrap();
-        assert_eq!(msg.module, ModuleType::Pty);
+        assert_eq!(msg.module, ModuleType::PTY);
         assert_eq!(msg.msg_type, "init");
         
         // 测试获取负载字段
@@ -340,32 +861,35 @@ mod tests {
     }
     
     #[test]
+    #[cfg(feature = "voice")]
     fn test_parse_voice_message() {
         let router = MessageRouter::new();
         let json = r#"{"module": "voice", "type": "start_recording", "mode": "press"}"#;
         
         let msg = router.parse_message(json).unwrap();
-        assert_eq!(msg.module, ModuleType::Voice);
+        assert_eq!(msg.module, ModuleType::VOICE);
         assert_eq!(msg.msg_type, "start_recording");
     }
     
     #[test]
+    #[cfg(feature = "llm")]
     fn test_parse_llm_message() {
         let router = MessageRouter::new();
         let json = r#"{"module": "llm", "type": "stream_start", "endpoint": "https://api.example.com"}"#;
         
         let msg = router.parse_message(json).unwrap();
-        assert_eq!(msg.module, ModuleType::Llm);
+        assert_eq!(msg.module, ModuleType::LLM);
         assert_eq!(msg.msg_type, "stream_start");
     }
     
     #[test]
+    #[cfg(feature = "utils")]
     fn test_parse_utils_message() {
         let router = MessageRouter::new();
         let json = r#"{"module": "utils", "type": "detect_language", "text": "Hello world"}"#;
         
         let msg = router.parse_message(json).unwrap();
-        assert_eq!(msg.module, ModuleType::Utils);
+        assert_eq!(msg.module, ModuleType::UTILS);
         assert_eq!(msg.msg_type, "detect_language");
     }
     
@@ -391,10 +915,14 @@ mod tests {
     fn test_try_parse_module_valid() {
         let router = MessageRouter::new();
         
-        assert_eq!(router.try_parse_module(r#"{"module": "pty"}"#), Some(ModuleType::Pty));
-        assert_eq!(router.try_parse_module(r#"{"module": "voice"}"#), Some(ModuleType::Voice));
-        assert_eq!(router.try_parse_module(r#"{"module": "llm"}"#), Some(ModuleType::Llm));
-        assert_eq!(router.try_parse_module(r#"{"module": "utils"}"#), Some(ModuleType::Utils));
+        #[cfg(feature = "pty")]
+        assert_eq!(router.try_parse_module(r#"{"module": "pty"}"#), Some(ModuleType::PTY));
+        #[cfg(feature = "voice")]
+        assert_eq!(router.try_parse_module(r#"{"module": "voice"}"#), Some(ModuleType::VOICE));
+        #[cfg(feature = "llm")]
+        assert_eq!(router.try_parse_module(r#"{"module": "llm"}"#), Some(ModuleType::LLM));
+        #[cfg(feature = "utils")]
+        assert_eq!(router.try_parse_module(r#"{"module": "utils"}"#), Some(ModuleType::UTILS));
     }
     
     #[test]
@@ -413,41 +941,46 @@ mod tests {
     
     #[test]
     fn test_server_response_error() {
-        let response = ServerResponse::error(ModuleType::Pty, "TEST_ERROR", "Test error message");
+        let error = ServerError::new(ErrorCode::InternalError, Msg::Text("Test error message".into()));
+        let response = ServerResponse::error(ModuleType::new("pty"), &error, Locale::En);
         
-        assert_eq!(response.module, ModuleType::Pty);
+        assert_eq!(response.module.as_str(), "pty");
         assert_eq!(response.msg_type, "error");
         
         let payload = response.payload.as_object().unwrap();
-        assert_eq!(payload.get("code").unwrap().as_str().unwrap(), "TEST_ERROR");
+        assert_eq!(payload.get("code").unwrap().as_str().unwrap(), "INTERNAL_ERROR");
         assert_eq!(payload.get("message").unwrap().as_str().unwrap(), "Test error message");
+        assert_eq!(payload.get("retryable").unwrap().as_bool(), Some(false));
     }
     
     #[test]
     fn test_server_response_new() {
         let payload = serde_json::json!({"key": "value"});
-        let response = ServerResponse::new(ModuleType::Voice, "test_type", payload);
+        let response = ServerResponse::new(ModuleType::new("voice"), "test_type", payload);
         
-        assert_eq!(response.module, ModuleType::Voice);
+        assert_eq!(response.module.as_str(), "voice");
         assert_eq!(response.msg_type, "test_type");
         assert_eq!(response.payload.get("key").unwrap().as_str().unwrap(), "value");
     }
     
     #[test]
     fn test_module_type_display() {
-        assert_eq!(format!("{}", ModuleType::Pty), "pty");
-        assert_eq!(format!("{}", ModuleType::Voice), "voice");
-        assert_eq!(format!("{}", ModuleType::Llm), "llm");
-        assert_eq!(format!("{}", ModuleType::Utils), "utils");
+        #[cfg(feature = "pty")]
+        assert_eq!(format!("{}", ModuleType::PTY), "pty");
+        #[cfg(feature = "voice")]
+        assert_eq!(format!("{}", ModuleType::VOICE), "voice");
+        #[cfg(feature = "llm")]
+        assert_eq!(format!("{}", ModuleType::LLM), "llm");
+        assert_eq!(format!("{}", ModuleType::UTILS), "utils");
     }
     
     #[test]
     fn test_create_error_response_unknown_module() {
         let router = MessageRouter::new();
         let error = RouterError::UnknownModule("test_module".to_string());
-        let response = router.create_error_response(ModuleType::Utils, &error);
+        let response = router.create_error_response(ModuleType::UTILS, &error);
         
-        assert_eq!(response.module, ModuleType::Utils);
+        assert_eq!(response.module, ModuleType::UTILS);
         assert_eq!(response.msg_type, "error");
         
         let payload = response.payload.as_object().unwrap();
@@ -459,9 +992,9 @@ mod tests {
     fn test_create_error_response_module_error() {
         let router = MessageRouter::new();
         let error = RouterError::ModuleError("Something went wrong".to_string());
-        let response = router.create_error_response(ModuleType::Llm, &error);
+        let response = router.create_error_response(ModuleType::new("llm"), &error);
         
-        assert_eq!(response.module, ModuleType::Llm);
+        assert_eq!(response.module.as_str(), "llm");
         assert_eq!(response.msg_type, "error");
         
         let payload = response.payload.as_object().unwrap();
@@ -469,31 +1002,49 @@ mod tests {
         assert_eq!(payload.get("message").unwrap().as_str().unwrap(), "Something went wrong");
     }
     
+    #[test]
+    fn test_create_error_response_server_error() {
+        let router = MessageRouter::new();
+        let error = RouterError::from(ServerError::http(429, Msg::Text("Too many requests".into())).with_provider("qwen"));
+        let response = router.create_error_response(ModuleType::UTILS, &error);
+        
+        assert_eq!(response.payload["code"], "RATE_LIMITED");
+        assert_eq!(response.payload["message"], "Too many requests");
+        assert_eq!(response.payload["retryable"], true);
+        assert_eq!(response.payload["details"]["status"], 429);
+        assert_eq!(response.payload["details"]["provider"], "qwen");
+    }
+    
     #[tokio::test]
+    #[cfg(feature = "utils")]
     async fn test_utils_module_is_implemented() {
         let router = MessageRouter::new();
-        assert!(router.is_module_implemented(ModuleType::Utils));
+        assert!(router.is_module_implemented(&ModuleType::UTILS));
     }
     
     #[tokio::test]
+    #[cfg(feature = "llm")]
     async fn test_llm_module_is_implemented() {
         let router = MessageRouter::new();
-        assert!(router.is_module_implemented(ModuleType::Llm));
+        assert!(router.is_module_implemented(&ModuleType::LLM));
     }
     
     #[tokio::test]
+    #[cfg(feature = "pty")]
     async fn test_pty_module_is_implemented() {
         let router = MessageRouter::new();
-        assert!(router.is_module_implemented(ModuleType::Pty));
+        assert!(router.is_module_implemented(&ModuleType::PTY));
     }
     
     #[tokio::test]
+    #[cfg(feature = "voice")]
     async fn test_voice_module_is_implemented() {
         let router = MessageRouter::new();
-        assert!(router.is_module_implemented(ModuleType::Voice));
+        assert!(router.is_module_implemented(&ModuleType::VOICE));
     }
     
     #[test]
+    #[cfg(feature = "pty")]
     fn test_module_message_get_field() {
         let router = MessageRouter::new();
         let json = r#"{"module": "pty", "type": "init", "cols": 80, "rows": 24, "shell_type": "bash"}"#;
@@ -515,15 +1066,358 @@ mod tests {
         assert_eq!(missing, None);
     }
     
+    #[tokio::test]
+    #[cfg(feature = "utils")]
+    async fn test_hello_lists_modules_and_message_types() {
+        let router = MessageRouter::new();
+        let msg = router.parse_message(r#"{"module": "utils", "type": "hello"}"#).unwrap();
+        
+        let response = router.route(msg).await.unwrap().unwrap();
+        assert_eq!(response.module, ModuleType::UTILS);
+        assert_eq!(response.msg_type, "hello");
+        
+        let payload = &response.payload;
+        assert_eq!(payload["server_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(payload["protocol_version"], PROTOCOL_VERSION);
+        assert_eq!(payload["compatible"], true);
+        
+        let modules = payload["modules"].as_object().unwrap();
+        for builtin in BUILTIN_MODULES {
+            assert!(modules.contains_key(builtin.name.as_str()), "missing module {}", builtin.name);
+        }
+        
+        #[cfg(feature = "llm")]
+        {
+            let llm_types = modules["llm"]["message_types"].as_array().unwrap();
+            assert!(llm_types.iter().any(|t| t == "stream_start"));
+            
+            let api_formats = modules["llm"]["features"]["api_formats"].as_array().unwrap();
+            assert!(api_formats.iter().any(|f| f == "responses"));
+        }
+        
+        #[cfg(feature = "voice")]
+        {
+            let providers = modules["voice"]["features"]["asr_providers"].as_array().unwrap();
+            assert!(providers.iter().any(|p| p["provider"] == "sensevoice"));
+        }
+        
+        #[cfg(feature = "pty")]
+        assert!(modules["pty"]["features"]["shell_types"].is_array());
+    }
+    
+    #[tokio::test]
+    #[cfg(feature = "pty")]
+    async fn test_hello_protocol_compatibility() {
+        let router = MessageRouter::new();
+        
+        let json = format!(r#"{{"module": "pty", "type": "hello", "protocol_version": {}}}"#, PROTOCOL_VERSION);
+        let response = router.route(router.parse_message(&json).unwrap()).await.unwrap().unwrap();
+        assert_eq!(response.module, ModuleType::PTY);
+        assert_eq!(response.payload["compatible"], true);
+        
+        let json = format!(r#"{{"module": "pty", "type": "hello", "protocol_version": {}}}"#, PROTOCOL_VERSION + 1);
+        let response = router.route(router.parse_message(&json).unwrap()).await.unwrap().unwrap();
+        assert_eq!(response.payload["compatible"], false);
+    }
+    
+    #[tokio::test]
+    #[cfg(feature = "utils")]
+    async fn test_hello_negotiates_binary_frames() {
+        let router = MessageRouter::new();
+        assert!(!router.binary_frames_enabled());
+        
+        let msg = router.parse_message(r#"{"module": "utils", "type": "hello"}"#).unwrap();
+        let response = router.route(msg).await.unwrap().unwrap();
+        assert_eq!(response.payload["binary_frames"], false);
+        
+        let msg = router.parse_message(r#"{"module": "utils", "type": "hello", "binary_frames": true}"#).unwrap();
+        let response = router.route(msg).await.unwrap().unwrap();
+        assert_eq!(response.payload["binary_frames"], true);
+        assert!(router.binary_frames_enabled());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "utils")]
+    async fn test_hello_negotiates_locale() {
+        let router = MessageRouter::new();
+        let error = RouterError::UnknownModule("x".to_string());
+        assert_eq!(router.create_error_response(ModuleType::UTILS, &error).payload["message"], "Unknown module: x");
+
+        let msg = router.parse_message(r#"{"module": "utils", "type": "hello", "locale": "zh-CN"}"#).unwrap();
+        let response = router.route(msg).await.unwrap().unwrap();
+        assert_eq!(response.payload["locale"], "zh-CN");
+        assert_eq!(response.payload["locales"], serde_json::json!(["en", "zh-CN"]));
+
+        // 错误码不变，描述改用协商的语言
+        let payload = router.create_error_response(ModuleType::UTILS, &error).payload;
+        assert_eq!(payload["code"], "UNKNOWN_MODULE");
+        assert_eq!(payload["message"], "未知模块: x");
+
+        // 不支持的语言保持原设置
+        let msg = router.parse_message(r#"{"module": "utils", "type": "hello", "locale": "fr"}"#).unwrap();
+        let response = router.route(msg).await.unwrap().unwrap();
+        assert_eq!(response.payload["locale"], "zh-CN");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "utils")]
+    async fn test_hello_without_locale() {
+        let router = MessageRouter::new();
+        let error = RouterError::UnknownModule("x".to_string());
+
+        // 未声明语言的连接使用英语
+        let msg = router.parse_message(r#"{"module": "utils", "type": "hello"}"#).unwrap();
+        let response = router.route(msg).await.unwrap().unwrap();
+        assert_eq!(response.payload["locale"], "en");
+        assert_eq!(router.create_error_response(ModuleType::UTILS, &error).payload["message"], "Unknown module: x");
+
+        // 再次握手未声明语言时保持已协商的语言
+        let msg = router.parse_message(r#"{"module": "utils", "type": "hello", "locale": "zh-CN"}"#).unwrap();
+        router.route(msg).await.unwrap();
+        let msg = router.parse_message(r#"{"module": "utils", "type": "hello"}"#).unwrap();
+        let response = router.route(msg).await.unwrap().unwrap();
+        assert_eq!(response.payload["locale"], "zh-CN");
+        assert_eq!(router.create_error_response(ModuleType::UTILS, &error).payload["message"], "未知模块: x");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "utils")]
+    async fn test_route_records_metrics() {
+        let state = ModuleState::default();
+        let router = MessageRouter::with_config(&state, &ModulesConfig::default());
+
+        let msg = router.parse_message(r#"{"module": "utils", "type": "hello"}"#).unwrap();
+        router.route(msg).await.unwrap();
+        let msg = router.parse_message(r#"{"module": "utils", "type": "unknown"}"#).unwrap();
+        assert!(router.route(msg).await.is_err());
+        router.record_error(&ModuleType::UTILS, ErrorCode::ParseError);
+
+        let counters = &state.metrics.module_counters()["utils"];
+        assert_eq!(counters.messages, 2);
+        assert_eq!(counters.errors[&ErrorCode::InvalidMessage], 1);
+        assert_eq!(counters.errors[&ErrorCode::ParseError], 1);
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "pty", feature = "utils"))]
+    async fn test_disabled_module_rejected() {
+        let config = ModulesConfig {
+            enabled: vec![ModuleType::UTILS],
+            ..Default::default()
+        };
+        let router = MessageRouter::with_config(&ModuleState::default(), &config);
+        assert!(router.is_module_enabled(&ModuleType::UTILS));
+        assert!(!router.is_module_enabled(&ModuleType::PTY));
+        
+        // 禁用的模块返回 MODULE_DISABLED
+        let msg = router.parse_message(r#"{"module": "pty", "type": "init"}"#).unwrap();
+        let error = router.route(msg).await.unwrap_err();
+        assert!(matches!(error, RouterError::ModuleDisabled(ref m) if *m == ModuleType::PTY));
+        let response = router.create_error_response(ModuleType::PTY, &error);
+        assert_eq!(response.payload["code"], "MODULE_DISABLED");
+        assert!(router.route_binary(b"ls\r").await.is_err());
+        
+        // hello 仍可发送到任意模块，但只列出启用的模块
+        let msg = router.parse_message(r#"{"module": "pty", "type": "hello"}"#).unwrap();
+        let response = router.route(msg).await.unwrap().unwrap();
+        let modules = response.payload["modules"].as_object().unwrap();
+        assert_eq!(modules.keys().collect::<Vec<_>>(), vec!["utils"]);
+    }
+    
+    #[test]
+    fn test_module_type_from_str() {
+        for builtin in BUILTIN_MODULES {
+            assert_eq!(builtin.name.to_string().parse::<ModuleType>().as_ref(), Ok(&builtin.name));
+        }
+        assert!("unknown".parse::<ModuleType>().is_err());
+    }
+    
+    #[tokio::test]
+    #[cfg(feature = "pty")]
+    async fn test_route_binary() {
+        let router = MessageRouter::new();
+        
+        // 旧版模式: 没有 PTY 会话时写入失败
+        assert!(router.route_binary(b"ls\r").await.is_err());
+        assert!(!router.route_raw_text("ls\r").await.unwrap());
+        
+        router.binary_frames.store(true, Ordering::Relaxed);
+        
+        // 分帧模式: 帧头无效
+        assert!(matches!(router.route_binary(&[1, 0]).await, Err(RouterError::InvalidMessage(_))));
+        assert!(matches!(router.route_binary(&[0, 0, 0, 0, 1]).await, Err(RouterError::UnknownModule(_))));
+        
+        // 未被模块声明的通道
+        let data = frame::encode(3, 1, b"data");
+        assert!(matches!(router.route_binary(&data).await, Err(RouterError::UnknownModule(_))));
+        
+        // 不存在的 PTY 会话
+        let data = frame::encode(frame::PTY_CHANNEL, 42, b"ls\r");
+        assert!(router.route_binary(&data).await.is_err());
+    }
+    
+    #[test]
+    fn test_builtin_binary_channels() {
+        // 通道 ID 是协议的一部分，不随注册顺序变化
+        let router = MessageRouter::new();
+        #[cfg(feature = "pty")]
+        assert_eq!(router.channel_handler(frame::PTY_CHANNEL).unwrap().module_type(), ModuleType::PTY);
+        assert_eq!(frame::LEGACY_CHANNEL, frame::PTY_CHANNEL);
+        
+        // 其余模块暂不接收二进制帧，保留的通道 ID 未被占用
+        for channel in [2, 3, 4] {
+            assert!(router.channel_handler(channel).is_err());
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_modules_reflect_build() {
+        // 只注册并在 hello 中列出已编译的模块
+        let router = MessageRouter::new();
+        let hello = ModuleMessage {
+            module: ModuleType::UTILS,
+            msg_type: HELLO_MESSAGE_TYPE.to_string(),
+            id: None,
+            payload: serde_json::json!({}),
+        };
+        let response = router.route(hello).await.unwrap().unwrap();
+        let modules = response.payload["modules"].as_object().unwrap();
+        let names: Vec<&str> = BUILTIN_MODULES.iter().map(|builtin| builtin.name.as_str()).collect();
+        assert_eq!(modules.len(), names.len());
+        
+        for (name, built) in [
+            ("pty", cfg!(feature = "pty")),
+            ("voice", cfg!(feature = "voice")),
+            ("llm", cfg!(feature = "llm")),
+            ("utils", cfg!(feature = "utils")),
+        ] {
+            assert_eq!(names.contains(&name), built, "module {}", name);
+            assert_eq!(router.is_module_enabled(&ModuleType::new(name)), built, "module {}", name);
+            assert_eq!(name.parse::<ModuleType>().is_ok(), built, "module {}", name);
+        }
+        
+        // 未编译 PTY 时旧版输入无处可写
+        if !cfg!(feature = "pty") {
+            assert!(router.route_binary(b"ls\r").await.is_err());
+            assert!(!router.route_raw_text("ls\r").await.unwrap());
+        }
+    }
+    
+    /// 记录生命周期钩子调用的测试模块
+    #[derive(Default)]
+    struct EchoHandler {
+        events: Arc<std::sync::Mutex<Vec<&'static str>>>,
+    }
+    
+    #[async_trait::async_trait]
+    impl ModuleHandler for EchoHandler {
+        fn module_type(&self) -> ModuleType {
+            ModuleType::new("echo")
+        }
+        
+        fn capabilities(&self) -> ModuleCapabilities {
+            ModuleCapabilities::new(&["echo"], serde_json::Value::Null)
+        }
+        
+        async fn handle(&self, msg: &ModuleMessage) -> Result<Option<ServerResponse>, RouterError> {
+            Ok(Some(ServerResponse::new(self.module_type(), "echo", msg.payload.clone())))
+        }
+        
+        async fn on_connect(&self, _context: &ModuleContext) {
+            self.events.lock().unwrap().push("connect");
+        }
+        
+        async fn on_disconnect(&self) {
+            self.events.lock().unwrap().push("disconnect");
+        }
+        
+        async fn cleanup(&self) {
+            self.events.lock().unwrap().push("cleanup");
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_register_custom_module() {
+        let mut router = MessageRouter::new();
+        let handler = EchoHandler::default();
+        let events = Arc::clone(&handler.events);
+        router.register(Box::new(handler));
+        
+        // 注册后即可解析和路由，hello 中列出
+        let msg = router.parse_message(r#"{"module": "echo", "type": "echo", "text": "hi"}"#).unwrap();
+        let response = router.route(msg).await.unwrap().unwrap();
+        assert_eq!(response.module.as_str(), "echo");
+        assert_eq!(response.payload["text"], "hi");
+        assert_eq!(router.try_parse_module(r#"{"module": "echo"}"#), Some(ModuleType::new("echo")));
+        
+        let msg = router.parse_message(r#"{"module": "echo", "type": "hello"}"#).unwrap();
+        let response = router.route(msg).await.unwrap().unwrap();
+        assert!(response.payload["modules"]["echo"]["message_types"].is_array());
+        
+        // 生命周期钩子
+        use futures_util::SinkExt;
+        let sink = futures_util::sink::drain().sink_map_err(|never| match never {});
+        let (sender, _writer) = crate::outbound::Outbound::spawn(Box::pin(sink));
+        router.on_connect(sender).await;
+        router.on_disconnect().await;
+        router.cleanup(Duration::from_secs(1)).await;
+        assert_eq!(*events.lock().unwrap(), vec!["connect", "disconnect", "cleanup"]);
+    }
+    
+    #[tokio::test]
+    async fn test_request_id_echoed() {
+        let mut router = MessageRouter::new();
+        router.register(Box::new(EchoHandler::default()));
+        
+        // 字符串和整数 ID 原样回传，不出现在 payload 中
+        let msg = router.parse_message(r#"{"module": "echo", "type": "echo", "id": "req-1"}"#).unwrap();
+        assert_eq!(msg.id, Some(RequestId::String("req-1".to_string())));
+        let response = router.route(msg).await.unwrap().unwrap();
+        let json: serde_json::Value = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["id"], "req-1");
+        assert!(response.payload.get("id").is_none());
+        
+        let msg = router.parse_message(r#"{"module": "echo", "type": "hello", "id": 7}"#).unwrap();
+        let response = router.route(msg).await.unwrap().unwrap();
+        assert_eq!(response.id, Some(RequestId::Number(7)));
+        
+        // 未指定 ID 时响应中不包含 id 字段
+        let msg = router.parse_message(r#"{"module": "echo", "type": "echo"}"#).unwrap();
+        let response = router.route(msg).await.unwrap().unwrap();
+        let json: serde_json::Value = serde_json::to_value(&response).unwrap();
+        assert!(json.get("id").is_none());
+        
+        // 解析失败时仍可恢复 ID 用于错误响应
+        assert_eq!(router.try_parse_id(r#"{"module": "nope", "id": 3}"#), Some(RequestId::Number(3)));
+        assert_eq!(router.try_parse_id(r#"{"module": "echo", "id": [1]}"#), None);
+        assert_eq!(router.try_parse_id("not json"), None);
+        let error = router.parse_message(r#"{"module": "nope", "type": "x", "id": 3}"#).unwrap_err();
+        let response = router.create_error_response(ModuleType::UTILS, &error)
+            .with_id(router.try_parse_id(r#"{"id": 3}"#));
+        assert_eq!(serde_json::to_value(&response).unwrap()["id"], 3);
+    }
+    
+    #[test]
+    #[cfg(feature = "utils")]
+    #[should_panic(expected = "模块重复注册")]
+    fn test_register_duplicate_module() {
+        let mut router = MessageRouter::new();
+        router.register(Box::new(crate::utils::UtilsHandler::new()));
+    }
+    
     #[test]
     fn test_module_type_serialization() {
         // 测试序列化
-        let pty = ModuleType::Pty;
+        let pty = ModuleType::new("pty");
         let json = serde_json::to_string(&pty).unwrap();
         assert_eq!(json, r#""pty""#);
         
         // 测试反序列化
         let deserialized: ModuleType = serde_json::from_str(r#""voice""#).unwrap();
-        assert_eq!(deserialized, ModuleType::Voice);
+        assert_eq!(deserialized.as_str(), "voice");
+        
+        // 名称不限于内置模块
+        let custom: ModuleType = serde_json::from_str(r#""custom""#).unwrap();
+        assert_eq!(custom.as_str(), "custom");
     }
 }
@@ -1,85 +1,308 @@
 // WebSocket 服务器实现
 // 统一的 WebSocket 服务器，处理所有模块的消息
 
-use tokio::net::TcpListener;
-use tokio_tungstenite::{accept_async, tungstenite::Message};
-use futures_util::{StreamExt, SinkExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{accept_async, accept_hdr_async, tungstenite::Message};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use futures_util::StreamExt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
-use tokio::sync::Mutex as TokioMutex;
+use std::time::Duration;
 
-use crate::router::{MessageRouter, ModuleType, RouterError, ServerResponse};
-
-/// 日志宏
-macro_rules! log_info {
-    ($($arg:tt)*) => {
-        eprintln!("[INFO] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_error {
-    ($($arg:tt)*) => {
-        eprintln!("[ERROR] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        if cfg!(debug_assertions) {
-            eprintln!("[DEBUG] {}", format!($($arg)*));
-        }
-    };
-}
+use crate::auth::AuthToken;
+use crate::error::{ErrorCode, ServerError};
+use crate::i18n::{Locale, Msg};
+use crate::lifecycle::{self, ConnectionTracker, Shutdown};
+use crate::router::{MessageRouter, ModuleState, ModulesConfig, ModuleType, RouterError, ServerResponse, ShuttingDownNotice};
+use crate::outbound::{Outbound, Priority};
+use crate::transport::{self, MessageSink, MessageStream, StdioFormat, Transport};
 
 // ============================================================================
 // 服务器配置和实现
 // ============================================================================
 
+/// 关闭时等待连接完成清理的最长时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 关闭时释放模块共享资源 (终止 PTY 会话) 的最长时间
+const MODULE_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 单个模块清理的最长时间 (需小于 SHUTDOWN_TIMEOUT)
+const MODULE_CLEANUP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 健康检查请求的读取超时
+const HEALTHZ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 健康检查请求头的最大字节数
+const HEALTHZ_MAX_REQUEST: usize = 8 * 1024;
+
+/// 默认监听地址 (仅本机)
+pub const DEFAULT_BIND_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
 /// WebSocket 服务器配置
+#[derive(Debug, Clone)]
 pub struct ServerConfig {
+    /// 传输方式 (TCP / Unix 套接字 / stdio)
+    pub transport: Transport,
+    /// 监听地址
+    pub bind_address: IpAddr,
+    /// 监听端口 (0 表示随机端口)
     pub port: u16,
+    /// 父进程 PID (父进程退出后自动关闭服务器)
+    pub parent_pid: Option<u32>,
+    /// 空闲超时 (没有连接超过该时间后自动关闭服务器)
+    pub idle_timeout: Option<Duration>,
+    /// 在 TCP 端口上应答 `GET /healthz` (无需令牌，不升级到 WebSocket)
+    pub healthz: bool,
+    /// 各功能模块的配置
+    pub modules: ModulesConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            transport: Transport::Tcp,
+            bind_address: DEFAULT_BIND_ADDRESS,
+            port: 0,
+            parent_pid: None,
+            idle_timeout: None,
+            healthz: false,
+            modules: ModulesConfig::default(),
+        }
+    }
 }
 
 /// WebSocket 服务器
 pub struct Server {
     config: ServerConfig,
+    /// 本次启动的认证令牌
+    auth_token: Arc<AuthToken>,
+    /// 模块共享状态 (所有连接共享，如 PTY 会话可在重新连接后继续使用)
+    module_state: ModuleState,
+    /// 模块配置 (每个连接的处理器据此创建)
+    modules: Arc<ModulesConfig>,
+    /// 关闭信号
+    shutdown: Shutdown,
+    /// 活动连接计数
+    connections: Arc<ConnectionTracker>,
 }
 
 impl Server {
     pub fn new(config: ServerConfig) -> Self {
-        Self { config }
+        let module_state = ModuleState::new(&config.modules);
+        let modules = Arc::new(config.modules.clone());
+        let connections = Arc::clone(&module_state.connections);
+        
+        Self {
+            config,
+            auth_token: Arc::new(AuthToken::generate()),
+            module_state,
+            modules,
+            shutdown: Shutdown::new(),
+            connections,
+        }
+    }
+    
+    /// 本次启动的认证令牌 (测试客户端据此连接)
+    #[cfg(test)]
+    pub fn auth_token(&self) -> &str {
+        self.auth_token.as_str()
+    }
+    
+    /// 获取关闭信号 (用于从外部触发关闭)
+    pub fn shutdown_signal(&self) -> Shutdown {
+        self.shutdown.clone()
     }
 
     /// 启动服务器
-    pub async fn start(&self) -> Result<u16, Box<dyn std::error::Error>> {
-        let addr = format!("127.0.0.1:{}", self.config.port);
-        let listener = TcpListener::bind(&addr).await?;
+    /// 
+    /// 按配置的传输方式开始接受连接，返回监听地址的描述
+    pub async fn start(&self) -> Result<String, Box<dyn std::error::Error>> {
+        // 父进程监控和空闲超时
+        if let Some(pid) = self.config.parent_pid {
+            lifecycle::spawn_parent_watchdog(pid, self.shutdown.clone());
+        }
+        if let Some(timeout) = self.config.idle_timeout {
+            lifecycle::spawn_idle_watchdog(timeout, Arc::clone(&self.connections), self.shutdown.clone());
+        }
+
+        match &self.config.transport {
+            Transport::Tcp => self.start_tcp().await,
+            Transport::Unix(path) => self.start_unix(path).await,
+            Transport::Stdio(format) => self.start_stdio(*format),
+        }
+    }
+    
+    /// 连接上下文 (每个连接持有一份)
+    fn context(&self) -> ConnectionContext {
+        ConnectionContext {
+            module_state: self.module_state.clone(),
+            modules: Arc::clone(&self.modules),
+            shutdown: self.shutdown.clone(),
+        }
+    }
+    
+    /// 在 TCP 端口上提供 WebSocket 服务，握手时校验认证令牌
+    async fn start_tcp(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let addr = SocketAddr::new(self.config.bind_address, self.config.port);
+        if !addr.ip().is_loopback() {
+            log_warn!("监听非本机地址 {}，请确保网络环境可信", addr.ip());
+        }
+        let listener = TcpListener::bind(addr).await?;
         let local_addr = listener.local_addr()?;
         let port = local_addr.port();
 
         log_info!("服务器绑定到 {}", local_addr);
 
         // 输出端口信息到 stdout (JSON 格式)
-        // TypeScript 端会解析这个 JSON 来获取端口号
+        // TypeScript 端会解析这个 JSON 来获取端口号和认证令牌
         println!(
-            r#"{{"port": {}, "pid": {}}}"#,
+            r#"{{"port": {}, "pid": {}, "token": "{}"}}"#,
             port,
-            std::process::id()
+            std::process::id(),
+            self.auth_token.as_str()
         );
 
-        // 主循环：接受 WebSocket 连接
+        // 主循环：接受 WebSocket 连接，关闭后停止接受
+        let auth_token = Arc::clone(&self.auth_token);
+        let context = self.context();
+        let connections = Arc::clone(&self.connections);
+        let healthz = self.config.healthz;
         tokio::spawn(async move {
             log_info!("正在监听 WebSocket 连接...");
-            while let Ok((stream, addr)) = listener.accept().await {
+            loop {
+                let (stream, addr) = tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(_) => break,
+                    },
+                    _ = context.shutdown.cancelled() => break,
+                };
+                
                 log_debug!("接受来自 {} 的连接", addr);
+                let auth_token = Some(Arc::clone(&auth_token));
+                let context = context.clone();
+                let connections = Arc::clone(&connections);
                 tokio::spawn(async move {
-                    if let Err(e) = handle_connection(stream).await {
+                    let mut stream = stream;
+                    // 健康检查请求直接应答，不计入活动连接
+                    if healthz {
+                        match serve_healthz(&mut stream, &context).await {
+                            Ok(true) => return,
+                            Ok(false) => {}
+                            Err(e) => {
+                                log_debug!("健康检查请求处理失败: {}", e);
+                                return;
+                            }
+                        }
+                    }
+                    let guard = connections.track();
+                    if let Err(e) = handle_websocket(stream, auth_token, context).await {
                         log_error!("连接处理错误: {}", e);
                     }
+                    drop(guard);
                 });
             }
+            log_info!("停止接受新连接");
         });
 
-        Ok(port)
+        Ok(local_addr.to_string())
+    }
+    
+    /// 在 Unix 套接字上提供 WebSocket 服务
+    /// 
+    /// 套接字文件权限为 0600，只有同一用户的进程可以连接，因此不再校验认证令牌
+    #[cfg(unix)]
+    async fn start_unix(&self, path: &std::path::Path) -> Result<String, Box<dyn std::error::Error>> {
+        let listener = bind_unix_socket(path)?;
+        
+        log_info!("服务器绑定到 Unix 套接字 {}", path.display());
+        
+        // 输出套接字信息到 stdout (JSON 格式)
+        println!(
+            "{}",
+            serde_json::json!({ "socket": path, "pid": std::process::id() })
+        );
+        
+        let context = self.context();
+        let connections = Arc::clone(&self.connections);
+        tokio::spawn(async move {
+            log_info!("正在监听 Unix 套接字连接...");
+            loop {
+                let stream = tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => stream,
+                        Err(_) => break,
+                    },
+                    _ = context.shutdown.cancelled() => break,
+                };
+                
+                log_debug!("接受 Unix 套接字连接");
+                let context = context.clone();
+                let guard = connections.track();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_websocket(stream, None, context).await {
+                        log_error!("连接处理错误: {}", e);
+                    }
+                    drop(guard);
+                });
+            }
+            log_info!("停止接受新连接");
+        });
+        
+        Ok(path.display().to_string())
+    }
+    
+    /// Unix 套接字仅在 Unix 平台可用
+    #[cfg(not(unix))]
+    async fn start_unix(&self, _path: &std::path::Path) -> Result<String, Box<dyn std::error::Error>> {
+        Err("Unix 套接字传输仅在 Unix 平台可用".into())
+    }
+    
+    /// 通过标准输入/输出提供服务
+    /// 
+    /// 只有一个连接 (父进程)，标准输入关闭后服务器随之关闭
+    fn start_stdio(&self, format: StdioFormat) -> Result<String, Box<dyn std::error::Error>> {
+        let (sender, receiver) = transport::stdio(format)?;
+        
+        log_info!("通过 stdio 提供服务 (格式: {})", format);
+        
+        let context = self.context();
+        let guard = self.connections.track();
+        tokio::spawn(async move {
+            let shutdown = context.shutdown.clone();
+            if let Err(e) = handle_connection(sender, receiver, context).await {
+                log_error!("连接处理错误: {}", e);
+            }
+            drop(guard);
+            shutdown.trigger(lifecycle::ShutdownReason::StdinClosed);
+        });
+        
+        Ok(format!("stdio ({})", format))
+    }
+    
+    /// 优雅关闭
+    /// 
+    /// 触发关闭信号，等待所有连接完成清理 (停止录音、取消 LLM 流)，然后终止所有 PTY 会话
+    pub async fn shutdown(&self) {
+        // 已由信号或监控任务触发时保留原有原因
+        self.shutdown.trigger(lifecycle::ShutdownReason::Requested);
+        
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, self.connections.wait_idle()).await.is_err() {
+            log_error!("等待连接关闭超时，剩余 {} 个连接", self.connections.active());
+        }
+        
+        // 包括处于宽限期的 PTY 会话
+        if tokio::time::timeout(MODULE_SHUTDOWN_TIMEOUT, self.module_state.shutdown()).await.is_err() {
+            log_error!("释放模块资源超时");
+        }
+        
+        if let Transport::Unix(path) = &self.config.transport {
+            let _ = std::fs::remove_file(path);
+        }
+        
+        log_info!("服务器已关闭");
     }
 }
 
@@ -87,33 +310,194 @@ impl Server {
 // 连接处理
 // ============================================================================
 
-/// WebSocket 发送器类型别名
-pub type WsSender = Arc<TokioMutex<futures_util::stream::SplitSink<
-    tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
-    Message
->>>;
+/// 消息发送器类型别名 (连接的出站队列，各传输共用，历史原因沿用 WebSocket 的命名)
+pub type WsSender = Outbound;
 
-/// 处理单个 WebSocket 连接
-async fn handle_connection(
-    stream: tokio::net::TcpStream,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // 升级到 WebSocket
-    let ws_stream = accept_async(stream).await?;
+/// 连接共享的服务器状态
+#[derive(Clone)]
+struct ConnectionContext {
+    /// 模块共享状态
+    module_state: ModuleState,
+    /// 模块配置
+    modules: Arc<ModulesConfig>,
+    /// 关闭信号
+    shutdown: Shutdown,
+}
+
+/// 绑定 Unix 套接字并限制为仅属主可访问
+/// 
+/// 路径上残留的套接字文件 (上次异常退出遗留) 会被清理；仍有服务器在监听时返回错误
+#[cfg(unix)]
+fn bind_unix_socket(path: &std::path::Path) -> std::io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+    
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} 已存在且不是套接字文件", path.display()),
+            ));
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                format!("{} 已有服务器在监听", path.display()),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    
+    // 先在新建的仅属主可访问的临时目录中绑定并收紧权限，再移动到目标路径，
+    // 目标路径上出现的套接字始终为 0600，无需修改进程的 umask
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+    let staging = parent.join(format!(".smart-workflow-{}.sock.d", std::process::id()));
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("server.sock");
+    let result = tokio::net::UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&staging);
+    result
+}
+
+/// 应答 `GET /healthz` 请求
+///
+/// 先窥探请求行 (不消耗数据)，不是健康检查请求时返回 false，由调用方继续 WebSocket 握手。
+/// 服务器正在关闭时返回 503。
+async fn serve_healthz(stream: &mut TcpStream, context: &ConnectionContext) -> std::io::Result<bool> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    
+    const PREFIX: &[u8] = b"GET /healthz";
+    
+    // 请求行可能分多次到达，窥探到足够的字节或确定不匹配为止
+    let mut buf = [0u8; PREFIX.len() + 1];
+    let peeked = tokio::time::timeout(HEALTHZ_TIMEOUT, async {
+        loop {
+            let n = stream.peek(&mut buf).await?;
+            if n == 0 || n == buf.len() || !PREFIX.starts_with(&buf[..n.min(PREFIX.len())]) {
+                return Ok::<_, std::io::Error>(n);
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }).await;
+    let n = match peeked {
+        Ok(result) => result?,
+        // 窥探超时交给 WebSocket 握手处理
+        Err(_) => return Ok(false),
+    };
+    if n < buf.len() || &buf[..PREFIX.len()] != PREFIX || !matches!(buf[PREFIX.len()], b' ' | b'?') {
+        return Ok(false);
+    }
+    
+    // 读完请求头 (不关心内容)
+    let mut head = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < HEALTHZ_MAX_REQUEST {
+        let read = tokio::time::timeout(HEALTHZ_TIMEOUT, stream.read(&mut chunk)).await
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&chunk[..read]);
+    }
+    
+    let metrics = &context.module_state.metrics;
+    let shutting_down = context.shutdown.reason().is_some();
+    let body = serde_json::json!({
+        "status": if shutting_down { "shutting_down" } else { "ok" },
+        "server_version": env!("CARGO_PKG_VERSION"),
+        "uptime_ms": metrics.uptime().as_millis() as u64,
+        "connections": context.module_state.connections.active(),
+    }).to_string();
+    let status = if shutting_down { "503 Service Unavailable" } else { "200 OK" };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    log_debug!("已应答健康检查请求");
+    Ok(true)
+}
+
+/// 升级到 WebSocket 并处理连接 (TCP 和 Unix 套接字共用)
+/// 
+/// `auth_token` 为 None 时不校验令牌 (由传输本身控制访问)
+async fn handle_websocket<S>(
+    stream: S,
+    auth_token: Option<Arc<AuthToken>>,
+    context: ConnectionContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // 升级到 WebSocket，握手阶段校验令牌
+    // 令牌无效时返回 401 并关闭连接，不会创建 MessageRouter
+    let ws_stream = match auth_token {
+        Some(auth_token) => {
+            #[allow(clippy::result_large_err)]
+            let callback = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+                let result = auth_token.check_handshake(request, response);
+                if result.is_err() {
+                    log_error!("拒绝未认证的连接: {}", request.uri().path());
+                }
+                result
+            };
+            accept_hdr_async(stream, callback).await?
+        }
+        None => accept_async(stream).await?,
+    };
     
     log_info!("WebSocket 连接已建立");
     
     // 分离读写流
-    let (ws_sender, mut ws_receiver) = ws_stream.split();
-    let ws_sender: WsSender = Arc::new(TokioMutex::new(ws_sender));
+    let (ws_sender, ws_receiver) = ws_stream.split();
+    handle_connection(Box::pin(ws_sender), Box::pin(ws_receiver), context).await
+}
+
+/// 处理单个连接的消息，直到连接断开或服务器关闭
+async fn handle_connection(
+    sender: MessageSink,
+    mut ws_receiver: MessageStream,
+    context: ConnectionContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let ConnectionContext { module_state, modules, shutdown } = context;
+    // 所有出站消息经由写任务按优先级发送
+    let (ws_sender, writer) = Outbound::spawn(sender);
     
     // 创建消息路由器
-    let router = Arc::new(MessageRouter::new());
+    let router = Arc::new(MessageRouter::with_config(&module_state, &modules));
     
-    // 设置 WebSocket 发送器 (用于 PTY 输出)
-    router.set_ws_sender(Arc::clone(&ws_sender)).await;
+    // 将发送器交给各模块 (用于 PTY 输出、模块推送消息)
+    router.on_connect(ws_sender.clone()).await;
     
-    // 消息处理循环
-    while let Some(msg_result) = ws_receiver.next().await {
+    // 消息处理循环 (服务器关闭时退出)
+    let mut shutting_down = false;
+    loop {
+        let msg_result = tokio::select! {
+            msg = ws_receiver.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            _ = shutdown.cancelled() => {
+                log_info!("服务器正在关闭，断开连接");
+                // 先通知客户端，避免其将断开视为异常并立即重连
+                let reason = shutdown.reason().unwrap_or(lifecycle::ShutdownReason::Requested);
+                let notice = ServerResponse::from_payload(ModuleType::UTILS, &ShuttingDownNotice {
+                    reason: reason.code(),
+                    message: reason.message().render(router.locale()),
+                });
+                let _ = send_response(&ws_sender, &notice).await;
+                shutting_down = true;
+                break;
+            }
+        };
+        
         match msg_result {
             Ok(msg) => {
                 log_debug!("收到消息类型: {:?}", std::mem::discriminant(&msg));
@@ -130,12 +514,10 @@ async fn handle_connection(
                         }
                     }
                     Message::Binary(data) => {
-                        // 二进制数据 - 写入 PTY
+                        // 二进制数据 - 由路由器按帧头分发
                         log_debug!("收到二进制数据: {} 字节", data.len());
-                        if router.pty_handler().is_initialized().await {
-                            if let Err(e) = router.pty_handler().write_data(&data).await {
-                                log_error!("写入 PTY 失败: {}", e);
-                            }
+                        if let Err(e) = router.route_binary(&data).await {
+                            log_error!("二进制数据处理错误: {}", e);
                         }
                     }
                     Message::Close(_) => {
@@ -144,8 +526,7 @@ async fn handle_connection(
                     }
                     Message::Ping(data) => {
                         // 响应 Ping
-                        let mut sender = ws_sender.lock().await;
-                        sender.send(Message::Pong(data)).await?;
+                        ws_sender.send(Priority::Control, Message::Pong(data)).await?;
                     }
                     Message::Pong(_) => {
                         // 忽略 Pong
@@ -162,21 +543,16 @@ async fn handle_connection(
         }
     }
     
-    log_info!("WebSocket 连接已关闭");
-    
-    // 清理 PTY 会话
-    if router.pty_handler().is_initialized().await {
-        let _ = router.pty_handler().kill().await;
-    }
+    log_info!("连接已关闭");
     
-    // 清理 Voice 模块资源
-    router.voice_handler().cleanup().await;
+    // 通知各模块连接已断开 (PTY 会话在宽限期内可通过 reattach 重新连接)
+    router.on_disconnect().await;
     
-    // 清理 LLM 模块资源
-    router.llm_handler().cleanup().await;
+    // 并行清理各模块资源 (停止录音、取消 LLM 流)，每个模块有独立的截止时间
+    router.cleanup(MODULE_CLEANUP_TIMEOUT).await;
     
-    // 清理 Utils 模块资源
-    router.utils_handler().cleanup().await;
+    // 发送剩余消息，服务器关闭时由服务器发起关闭握手
+    writer.finish(shutting_down).await;
     
     Ok(())
 }
@@ -190,7 +566,8 @@ async fn handle_text_message(
     // 解析消息
     match router.parse_message(text) {
         Ok(msg) => {
-            let module = msg.module;
+            let module = msg.module.clone();
+            let id = msg.id.clone();
             
             // 路由消息到对应模块
             match router.route(msg).await {
@@ -205,27 +582,30 @@ async fn handle_text_message(
                 Err(e) => {
                     // 模块处理错误，发送错误响应
                     log_error!("模块处理错误: {}", e);
-                    let error_response = router.create_error_response(module, &e);
+                    let error_response = router.create_error_response(module, &e).with_id(id);
                     send_response(ws_sender, &error_response).await?;
                 }
             }
         }
         Err(e) => {
             // 消息解析错误 - 可能是纯文本输入 (用于 PTY)
-            // 如果 PTY 已初始化，将文本写入 PTY
-            if router.pty_handler().is_initialized().await {
-                log_debug!("将文本作为 PTY 输入: {} 字节", text.len());
-                if let Err(write_err) = router.pty_handler().write_data(text.as_bytes()).await {
+            // 如果 PTY 已初始化，由路由器将文本写入 PTY
+            match router.route_raw_text(text).await {
+                Ok(true) => {}
+                Err(write_err) => {
                     log_error!("写入 PTY 失败: {}", write_err);
                 }
-            } else {
-                // PTY 未初始化，返回解析错误
-                log_error!("消息解析错误: {}", e);
-                
-                // 尝试从原始 JSON 中提取 module 字段用于错误响应
-                let module = extract_module_from_json(text);
-                let error_response = create_parse_error_response(module, &e);
-                send_response(ws_sender, &error_response).await?;
+                Ok(false) => {
+                    // PTY 未初始化，返回解析错误
+                    log_error!("消息解析错误: {}", e);
+                    
+                    // 尝试从原始 JSON 中提取 module 字段用于错误响应 (默认 Utils 模块)
+                    let module = router.try_parse_module(text).unwrap_or(ModuleType::UTILS);
+                    router.record_error(&module, ErrorCode::ParseError);
+                    let error_response = create_parse_error_response(module, &e, router.locale())
+                        .with_id(router.try_parse_id(text));
+                    send_response(ws_sender, &error_response).await?;
+                }
             }
         }
     }
@@ -233,31 +613,13 @@ async fn handle_text_message(
     Ok(())
 }
 
-/// 从 JSON 中提取 module 字段
-fn extract_module_from_json(text: &str) -> ModuleType {
-    // 尝试解析 JSON 并提取 module 字段
-    if let Ok(value) = serde_json::from_str::<serde_json::Value>(text) {
-        if let Some(module_str) = value.get("module").and_then(|v| v.as_str()) {
-            match module_str {
-                "pty" => return ModuleType::Pty,
-                "voice" => return ModuleType::Voice,
-                "llm" => return ModuleType::Llm,
-                "utils" => return ModuleType::Utils,
-                _ => {}
-            }
-        }
-    }
-    
-    // 默认返回 Utils 模块（用于通用错误）
-    ModuleType::Utils
-}
-
 /// 创建解析错误响应
-fn create_parse_error_response(module: ModuleType, error: &RouterError) -> ServerResponse {
+fn create_parse_error_response(module: ModuleType, error: &RouterError, locale: Locale) -> ServerResponse {
+    let reason = Box::new(error.to_server_error().message);
     ServerResponse::error(
         module,
-        "PARSE_ERROR",
-        &format!("消息解析失败: {}", error)
+        &ServerError::new(ErrorCode::ParseError, Msg::ParseFailed { reason }),
+        locale,
     )
 }
 
@@ -267,8 +629,7 @@ pub async fn send_response(
     response: &ServerResponse,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let json = serde_json::to_string(response)?;
-    let mut sender = ws_sender.lock().await;
-    sender.send(Message::Text(json.into())).await?;
+    ws_sender.send_text(Priority::Control, json).await?;
     Ok(())
 }
 
@@ -278,8 +639,7 @@ pub async fn send_json(
     ws_sender: &WsSender,
     json: &str,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut sender = ws_sender.lock().await;
-    sender.send(Message::Text(json.to_string().into())).await?;
+    ws_sender.send_text(Priority::Control, json.to_string()).await?;
     Ok(())
 }
 
@@ -289,7 +649,37 @@ pub async fn send_binary(
     ws_sender: &WsSender,
     data: Vec<u8>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut sender = ws_sender.lock().await;
-    sender.send(Message::Binary(data.into())).await?;
+    ws_sender.send(Priority::Bulk, Message::Binary(data.into())).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_bind_unix_socket() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("sw-socket-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("server.sock");
+
+        // 套接字仅属主可访问，临时目录已清理
+        let listener = bind_unix_socket(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        tokio::net::UnixStream::connect(&path).await.unwrap();
+
+        // 仍有服务器在监听时拒绝绑定，残留的套接字文件被替换
+        assert!(bind_unix_socket(&path).is_err());
+        drop(listener);
+        let _listener = bind_unix_socket(&path).unwrap();
+        tokio::net::UnixStream::connect(&path).await.unwrap();
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
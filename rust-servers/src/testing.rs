@@ -0,0 +1,845 @@
+// 协议测试工具
+// 在随机端口上启动 Server，通过真实的 WebSocket 连接走完整的 handle_connection 流程，
+// 提供发送模块消息、等待类型化响应、读取二进制输出和模拟 HTTP 上游的辅助方法
+//
+// 仅在测试中编译
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::auth::TOKEN_HEADER;
+use crate::error::ErrorPayload;
+use crate::frame;
+use crate::protocol::Payload;
+use crate::router::{ModuleType, RequestId, BUILTIN_MODULES, HELLO_MESSAGE_TYPE, PROTOCOL_VERSION};
+use crate::server::{Server, ServerConfig};
+
+/// 等待单条消息的最长时间
+const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+
+// ============================================================================
+// 测试服务器
+// ============================================================================
+
+/// 在随机端口上运行的服务器
+pub struct TestServer {
+    server: Server,
+    address: String,
+}
+
+impl TestServer {
+    /// 使用默认配置启动服务器
+    pub async fn start() -> Self {
+        Self::with_config(ServerConfig::default()).await
+    }
+
+    /// 使用指定配置启动服务器 (端口和传输方式固定为随机 TCP 端口)
+    pub async fn with_config(config: ServerConfig) -> Self {
+        let config = ServerConfig { port: 0, transport: Default::default(), ..config };
+        let server = Server::new(config);
+        let address = server.start().await.expect("服务器启动失败");
+        Self { server, address }
+    }
+
+    /// 连接服务器并完成 hello 握手 (启用二进制分帧)
+    pub async fn connect(&self) -> TestClient {
+        let mut client = self.connect_raw().await;
+        let module = BUILTIN_MODULES[0].name.clone();
+        client.send(module.clone(), HELLO_MESSAGE_TYPE, serde_json::json!({
+            "protocol_version": PROTOCOL_VERSION,
+            "binary_frames": true,
+        })).await;
+        client.expect_json(&module, HELLO_MESSAGE_TYPE).await;
+        client
+    }
+
+    /// 连接服务器 (不握手，二进制输出为旧版格式)
+    pub async fn connect_raw(&self) -> TestClient {
+        let mut request = format!("ws://{}/", self.address)
+            .into_client_request()
+            .expect("无效的服务器地址");
+        request.headers_mut().insert(TOKEN_HEADER, self.server.auth_token().parse().unwrap());
+        let (ws, _) = tokio_tungstenite::connect_async(request).await.expect("连接服务器失败");
+        TestClient {
+            ws,
+            next_id: 0,
+            inbox: VecDeque::new(),
+            output: HashMap::new(),
+        }
+    }
+
+    /// 优雅关闭服务器
+    pub async fn shutdown(self) {
+        self.server.shutdown().await;
+    }
+}
+
+// ============================================================================
+// 测试客户端
+// ============================================================================
+
+/// WebSocket 测试客户端
+///
+/// 收到的 JSON 消息先放入收件箱，按模块和类型取走，因此不同类型消息的到达顺序不影响测试；
+/// 分帧的二进制消息按流 ID 累积 (PTY 输出)
+pub struct TestClient {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    /// 下一个请求 ID
+    next_id: i64,
+    /// 已收到但尚未取走的 JSON 消息
+    inbox: VecDeque<serde_json::Value>,
+    /// 按流 ID 累积的二进制负载
+    output: HashMap<u32, Vec<u8>>,
+}
+
+impl TestClient {
+    /// 发送模块消息，返回自动分配的请求 ID
+    pub async fn send(&mut self, module: ModuleType, msg_type: &str, payload: serde_json::Value) -> RequestId {
+        self.next_id += 1;
+        let id = RequestId::Number(self.next_id);
+        let mut message = match payload {
+            serde_json::Value::Object(map) => map,
+            serde_json::Value::Null => serde_json::Map::new(),
+            other => panic!("消息负载必须是对象: {}", other),
+        };
+        message.insert("module".to_string(), serde_json::json!(module));
+        message.insert("type".to_string(), serde_json::json!(msg_type));
+        message.insert("id".to_string(), serde_json::json!(id));
+        self.send_text(&serde_json::Value::Object(message).to_string()).await;
+        id
+    }
+
+    /// 发送原始文本消息
+    pub async fn send_text(&mut self, text: &str) {
+        self.ws.send(Message::Text(text.into())).await.expect("发送消息失败");
+    }
+
+    /// 发送分帧的二进制消息
+    #[cfg_attr(not(feature = "pty"), allow(dead_code))]
+    pub async fn send_binary(&mut self, channel: u8, stream_id: u32, payload: &[u8]) {
+        let data = frame::encode(channel, stream_id, payload);
+        self.ws.send(Message::Binary(data.into())).await.expect("发送消息失败");
+    }
+
+    /// 接收一条消息放入收件箱或输出缓冲区，超时或连接关闭时 panic
+    async fn recv(&mut self) {
+        let message = match tokio::time::timeout(RECV_TIMEOUT, self.ws.next()).await {
+            Ok(Some(Ok(message))) => message,
+            Ok(Some(Err(e))) => panic!("接收消息失败: {}", e),
+            Ok(None) => panic!("连接已关闭，未处理的消息: {:?}", self.inbox),
+            Err(_) => panic!("等待消息超时，未处理的消息: {:?}", self.inbox),
+        };
+        match message {
+            Message::Text(text) => {
+                let value = serde_json::from_str(&text).expect("服务器发送了无效的 JSON");
+                self.inbox.push_back(value);
+            }
+            Message::Binary(data) => {
+                let frame = frame::decode(&data).expect("服务器发送了无效的二进制帧");
+                self.output.entry(frame.stream_id).or_default().extend_from_slice(frame.payload);
+            }
+            Message::Close(_) => panic!("服务器关闭了连接，未处理的消息: {:?}", self.inbox),
+            _ => {}
+        }
+    }
+
+    /// 等待指定模块和类型的 JSON 消息，返回完整消息 (含 `module`、`type`、`id`)
+    pub async fn expect_json(&mut self, module: &ModuleType, msg_type: &str) -> serde_json::Value {
+        loop {
+            let found = self.inbox.iter().position(|message| {
+                message["module"] == module.as_str() && message["type"] == msg_type
+            });
+            if let Some(index) = found {
+                return self.inbox.remove(index).unwrap();
+            }
+            self.recv().await;
+        }
+    }
+
+    /// 等待指定模块的消息并解析为负载类型
+    pub async fn expect<P: Payload + DeserializeOwned>(&mut self, module: &ModuleType) -> P {
+        let message = self.expect_json(module, P::TYPE).await;
+        serde_json::from_value(message.clone())
+            .unwrap_or_else(|e| panic!("无法解析 {} 消息: {} ({})", P::TYPE, e, message))
+    }
+
+    /// 等待指定模块的错误消息
+    pub async fn expect_error(&mut self, module: &ModuleType) -> ErrorPayload {
+        self.expect::<ErrorPayload>(module).await
+    }
+
+    /// 读取流的二进制输出，直到包含 `needle`，返回并清空已累积的输出
+    #[cfg_attr(not(feature = "pty"), allow(dead_code))]
+    pub async fn read_output_until(&mut self, stream_id: u32, needle: &str) -> String {
+        loop {
+            if let Some(output) = self.output.get_mut(&stream_id) {
+                let text = String::from_utf8_lossy(output).into_owned();
+                if text.contains(needle) {
+                    output.clear();
+                    return text;
+                }
+            }
+            self.recv().await;
+        }
+    }
+
+    /// 关闭连接
+    pub async fn close(mut self) {
+        let _ = self.ws.close(None).await;
+    }
+}
+
+// ============================================================================
+// 模拟 HTTP 上游
+// ============================================================================
+
+/// 模拟 HTTP 上游的响应
+#[cfg_attr(not(feature = "llm"), allow(dead_code))]
+pub struct MockResponse {
+    /// HTTP 状态码
+    pub status: u16,
+    /// 额外的响应头
+    pub headers: Vec<(&'static str, String)>,
+    /// 响应体
+    pub body: String,
+}
+
+#[cfg_attr(not(feature = "llm"), allow(dead_code))]
+impl MockResponse {
+    /// SSE 响应: 每个事件为一行 `data:`
+    pub fn sse(events: &[&str]) -> Self {
+        let body = events.iter().map(|event| format!("data: {}\n\n", event)).collect();
+        Self {
+            status: 200,
+            headers: vec![("Content-Type", "text/event-stream".to_string())],
+            body,
+        }
+    }
+
+    /// 错误响应
+    pub fn error(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type", "application/json".to_string())],
+            body: body.to_string(),
+        }
+    }
+
+    /// 附加响应头
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+}
+
+/// 启动模拟 HTTP 上游，对每个请求返回相同的响应，返回其 URL
+#[cfg_attr(not(feature = "llm"), allow(dead_code))]
+pub async fn mock_http(response: MockResponse) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("模拟上游绑定失败");
+    let url = format!("http://{}/v1/chat/completions", listener.local_addr().unwrap());
+    let mut head = format!("HTTP/1.1 {} Mock\r\n", response.status);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", response.body.len()));
+    let raw = format!("{}{}", head, response.body);
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let raw = raw.clone();
+            tokio::spawn(async move {
+                if read_request(&mut stream).await.is_ok() {
+                    let _ = stream.write_all(raw.as_bytes()).await;
+                    let _ = stream.shutdown().await;
+                }
+            });
+        }
+    });
+    url
+}
+
+/// 读完一个 HTTP 请求 (请求头和 Content-Length 指定的请求体)
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<()> {
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&data[..end]).to_ascii_lowercase();
+            let length: usize = head.lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or(0);
+            if data.len() >= end + 4 + length {
+                return Ok(());
+            }
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        data.extend_from_slice(&chunk[..n]);
+    }
+}
+
+// ============================================================================
+// 端到端测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+
+    #[tokio::test]
+    async fn test_hello_round_trip() {
+        let server = TestServer::start().await;
+        let mut client = server.connect_raw().await;
+        let module = BUILTIN_MODULES[0].name.clone();
+
+        let id = client.send(module.clone(), HELLO_MESSAGE_TYPE, serde_json::json!({ "locale": "zh-CN" })).await;
+        let hello = client.expect_json(&module, HELLO_MESSAGE_TYPE).await;
+        assert_eq!(hello["id"], serde_json::json!(id));
+        assert_eq!(hello["protocol_version"], PROTOCOL_VERSION);
+        assert_eq!(hello["locale"], "zh-CN");
+        for builtin in BUILTIN_MODULES {
+            assert!(hello["modules"].get(builtin.name.as_str()).is_some(), "{}", builtin.name);
+        }
+
+        // 解析失败的消息返回按连接语言渲染的 PARSE_ERROR
+        client.send_text(r#"{"module": "utils", "id": 7"#).await;
+        let error = client.expect_error(&ModuleType::UTILS).await;
+        assert_eq!(error.code, ErrorCode::ParseError);
+        assert!(error.message.starts_with("消息解析失败"), "{}", error.message);
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(all(unix, feature = "pty"))]
+    #[tokio::test]
+    async fn test_pty_session() {
+        use crate::pty::{ExitMessage, InitComplete, KillComplete, ResizeComplete, TerminalSize};
+
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        let id = client.send(ModuleType::PTY, "init", serde_json::json!({
+            "shell_type": "custom:/bin/sh",
+            "cols": 120,
+            "rows": 40,
+        })).await;
+        let init = client.expect_json(&ModuleType::PTY, InitComplete::TYPE).await;
+        assert_eq!(init["id"], serde_json::json!(id));
+        let init: InitComplete = serde_json::from_value(init).unwrap();
+        assert!(init.success);
+        let session_id = init.session_id;
+
+        // 输入回显: 用算术展开区分命令回显和命令输出
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "echo e2e-$((40 + 2))\n" })).await;
+        client.read_output_until(session_id, "e2e-42").await;
+
+        // shell 启动时即为初始尺寸
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "stty size\n" })).await;
+        client.read_output_until(session_id, "40 120").await;
+
+        // 二进制帧输入
+        client.send_binary(frame::PTY_CHANNEL, session_id, b"echo bin-$((1 + 1))\n").await;
+        client.read_output_until(session_id, "bin-2").await;
+
+        client.send(ModuleType::PTY, "resize", serde_json::json!({
+            "session_id": session_id,
+            "cols": 100,
+            "rows": 30,
+            "pixel_width": 800,
+            "pixel_height": 600,
+        })).await;
+        let resize: ResizeComplete = client.expect(&ModuleType::PTY).await;
+        assert_eq!(resize.session_id, session_id);
+        assert_eq!(resize.size, TerminalSize { cols: 100, rows: 30, pixel_width: 800, pixel_height: 600 });
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "stty size\n" })).await;
+        client.read_output_until(session_id, "30 100").await;
+
+        // 缺少行数的 resize 无效，尺寸保持不变
+        client.send(ModuleType::PTY, "resize", serde_json::json!({ "session_id": session_id, "cols": 90 })).await;
+        assert_eq!(client.expect_error(&ModuleType::PTY).await.code, ErrorCode::InvalidMessage);
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "stty size\n" })).await;
+        client.read_output_until(session_id, "30 100").await;
+
+        client.send(ModuleType::PTY, "kill", serde_json::json!({ "session_id": session_id })).await;
+        let kill: KillComplete = client.expect(&ModuleType::PTY).await;
+        assert_eq!(kill.session_id, session_id);
+        let exit: ExitMessage = client.expect(&ModuleType::PTY).await;
+        assert_eq!(exit.session_id, session_id);
+        assert!(exit.signal.is_some());
+
+        // 会话已终止
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "session_id": session_id, "data": "x" })).await;
+        assert_eq!(client.expect_error(&ModuleType::PTY).await.code, ErrorCode::SessionNotFound);
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    /// 在会话中启动忽略 SIGHUP 的后台进程，返回其 PID (测试结束时需自行终止)
+    ///
+    /// 后台进程仍持有 PTY 从端，shell 终止后读取任务读不到 EOF
+    #[cfg(all(unix, feature = "pty"))]
+    async fn start_background_job(client: &mut TestClient, session_id: u32) -> i32 {
+        client.send(ModuleType::PTY, "input", serde_json::json!({
+            "session_id": session_id,
+            "data": "(trap '' HUP; exec sleep 30) & echo bg-pid-$((0 + $!))\n",
+        })).await;
+        let pattern = regex::Regex::new(r"bg-pid-(\d+)").unwrap();
+        let mut output = String::new();
+        loop {
+            output += &client.read_output_until(session_id, "\n").await;
+            if let Some(captures) = pattern.captures(&output) {
+                return captures[1].parse().unwrap();
+            }
+        }
+    }
+
+    #[cfg(all(unix, feature = "pty"))]
+    #[tokio::test]
+    async fn test_pty_kill_with_background_job() {
+        use crate::pty::{ExitMessage, InitComplete, KillComplete};
+
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        client.send(ModuleType::PTY, "init", serde_json::json!({ "shell_type": "custom:/bin/sh" })).await;
+        let init: InitComplete = client.expect(&ModuleType::PTY).await;
+
+        let pid = start_background_job(&mut client, init.session_id).await;
+
+        // shell 终止并回收后即回复，不等待后台进程
+        let started = std::time::Instant::now();
+        client.send(ModuleType::PTY, "kill", serde_json::json!({ "session_id": init.session_id })).await;
+        let kill: KillComplete = client.expect(&ModuleType::PTY).await;
+        assert_eq!(kill.session_id, init.session_id);
+        assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+        let exit: ExitMessage = client.expect(&ModuleType::PTY).await;
+        assert_eq!(exit.session_id, init.session_id);
+
+        unsafe { libc::kill(pid, libc::SIGKILL) };
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(all(unix, feature = "pty"))]
+    #[tokio::test]
+    async fn test_shutdown_with_background_job() {
+        use crate::pty::InitComplete;
+
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        let mut pids = Vec::new();
+        for _ in 0..2 {
+            client.send(ModuleType::PTY, "init", serde_json::json!({ "shell_type": "custom:/bin/sh" })).await;
+            let init: InitComplete = client.expect(&ModuleType::PTY).await;
+            pids.push(start_background_job(&mut client, init.session_id).await);
+        }
+
+        // 关闭不等待后台进程结束
+        let started = std::time::Instant::now();
+        client.close().await;
+        server.shutdown().await;
+        assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+
+        for pid in pids {
+            unsafe { libc::kill(pid, libc::SIGKILL) };
+        }
+    }
+
+    #[cfg(all(unix, feature = "pty"))]
+    #[tokio::test]
+    async fn test_pty_reattach() {
+        use crate::pty::{InitComplete, ReattachComplete};
+
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        client.send(ModuleType::PTY, "init", serde_json::json!({ "shell_type": "custom:/bin/sh" })).await;
+        let init: InitComplete = client.expect(&ModuleType::PTY).await;
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "echo before-$((1 + 1))\n" })).await;
+        client.read_output_until(init.session_id, "before-2").await;
+        client.close().await;
+
+        // 新连接凭令牌接管: 先回放缓存的输出，再接收实时输出
+        let mut client = server.connect().await;
+        client.send(ModuleType::PTY, "reattach", serde_json::json!({ "session_token": init.session_token })).await;
+        let reattach: ReattachComplete = client.expect(&ModuleType::PTY).await;
+        assert_eq!(reattach.session_id, init.session_id);
+        client.read_output_until(init.session_id, "before-2").await;
+
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "echo after-$((2 + 3))\n" })).await;
+        client.read_output_until(init.session_id, "after-5").await;
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(all(unix, feature = "pty"))]
+    #[tokio::test]
+    async fn test_pty_exit() {
+        use crate::pty::{ExitMessage, InitComplete};
+
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        client.send(ModuleType::PTY, "init", serde_json::json!({ "shell_type": "custom:/bin/sh" })).await;
+        let init: InitComplete = client.expect(&ModuleType::PTY).await;
+
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "echo bye-$((1 + 2)); exit 3\n" })).await;
+        client.read_output_until(init.session_id, "bye-3").await;
+        let exit: ExitMessage = client.expect(&ModuleType::PTY).await;
+        assert_eq!(exit.session_id, init.session_id);
+        assert_eq!(exit.code, 3);
+        assert_eq!(exit.signal, None);
+
+        // 退出通知发送前会话已清理
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "x" })).await;
+        assert_eq!(client.expect_error(&ModuleType::PTY).await.code, ErrorCode::SessionNotFound);
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(all(unix, feature = "pty"))]
+    #[tokio::test]
+    async fn test_pty_command_events() {
+        use crate::pty::{CommandFinishedMessage, CommandStartedMessage, CwdResponse, CwdSource, InitComplete};
+
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        client.send(ModuleType::PTY, "init", serde_json::json!({
+            "shell_type": "bash",
+            "shell_args": ["--norc"],
+            "cwd": "/",
+        })).await;
+        let init: InitComplete = client.expect(&ModuleType::PTY).await;
+
+        // 等待 Shell Integration 脚本生效 (注入后立即上报工作目录)
+        client.read_output_until(init.session_id, "\x1b]7;file://").await;
+
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "cd /tmp; (exit 3)\n" })).await;
+        let started: CommandStartedMessage = client.expect(&ModuleType::PTY).await;
+        assert_eq!(started.session_id, init.session_id);
+        assert_eq!(started.command.as_deref(), Some("cd /tmp; (exit 3)"));
+        assert_eq!(started.cwd.as_deref(), Some("/"));
+
+        let finished: CommandFinishedMessage = client.expect(&ModuleType::PTY).await;
+        assert_eq!(finished.command, started.command);
+        assert_eq!(finished.cwd.as_deref(), Some("/"));
+        assert_eq!(finished.exit_code, Some(3));
+        assert!(finished.output_offset >= started.output_offset);
+
+        client.send(ModuleType::PTY, "get_cwd", serde_json::json!({ "session_id": init.session_id })).await;
+        let cwd: CwdResponse = client.expect(&ModuleType::PTY).await;
+        assert_eq!(cwd.cwd.as_deref(), Some("/tmp"));
+        assert_eq!(cwd.source, Some(CwdSource::Shell));
+
+        // 空命令不产生事件，下一条命令的工作目录已更新
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "\ntrue\n" })).await;
+        let started: CommandStartedMessage = client.expect(&ModuleType::PTY).await;
+        assert_eq!(started.command.as_deref(), Some("true"));
+        assert_eq!(started.cwd.as_deref(), Some("/tmp"));
+        let finished: CommandFinishedMessage = client.expect(&ModuleType::PTY).await;
+        assert_eq!(finished.exit_code, Some(0));
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(all(unix, feature = "pty"))]
+    #[tokio::test]
+    async fn test_pty_command_events_with_prompt_command() {
+        use crate::pty::{CommandFinishedMessage, CommandStartedMessage, InitComplete};
+
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        // 用户的 PROMPT_COMMAND 以 `;` 结尾 (如 pyenv-virtualenv)
+        client.send(ModuleType::PTY, "init", serde_json::json!({
+            "shell_type": "bash",
+            "shell_args": ["--norc"],
+            "env": { "PROMPT_COMMAND": "__user_hook=1;" },
+        })).await;
+        let init: InitComplete = client.expect(&ModuleType::PTY).await;
+        client.read_output_until(init.session_id, "\x1b]7;file://").await;
+
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "false\n" })).await;
+        let started: CommandStartedMessage = client.expect(&ModuleType::PTY).await;
+        assert_eq!(started.command.as_deref(), Some("false"));
+        let finished: CommandFinishedMessage = client.expect(&ModuleType::PTY).await;
+        assert_eq!(finished.exit_code, Some(1));
+
+        // 用户的 PROMPT_COMMAND 仍然执行
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "echo hook-$__user_hook$((1 + 1))\n" })).await;
+        let output = client.read_output_until(init.session_id, "hook-12").await;
+        assert!(!output.contains("syntax error"), "{}", output);
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(all(target_os = "linux", feature = "pty"))]
+    #[tokio::test]
+    async fn test_pty_cwd_from_process() {
+        use crate::pty::{CwdChangedMessage, CwdResponse, CwdSource, InitComplete};
+
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        // /bin/sh 不注入 Shell Integration，从 /proc 读取工作目录
+        client.send(ModuleType::PTY, "init", serde_json::json!({ "shell_type": "custom:/bin/sh", "cwd": "/" })).await;
+        let init: InitComplete = client.expect(&ModuleType::PTY).await;
+
+        client.send(ModuleType::PTY, "get_cwd", serde_json::json!({})).await;
+        let cwd: CwdResponse = client.expect(&ModuleType::PTY).await;
+        assert_eq!(cwd.session_id, init.session_id);
+        assert_eq!(cwd.cwd.as_deref(), Some("/"));
+        assert_eq!(cwd.source, Some(CwdSource::Process));
+
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "cd /tmp\n" })).await;
+        loop {
+            let changed: CwdChangedMessage = client.expect(&ModuleType::PTY).await;
+            assert_eq!(changed.source, CwdSource::Process);
+            if changed.cwd == "/tmp" {
+                break;
+            }
+        }
+
+        client.send(ModuleType::PTY, "get_cwd", serde_json::json!({})).await;
+        let cwd: CwdResponse = client.expect(&ModuleType::PTY).await;
+        assert_eq!(cwd.cwd.as_deref(), Some("/tmp"));
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(all(unix, feature = "pty"))]
+    #[tokio::test]
+    async fn test_pty_snapshot() {
+        use crate::pty::{InitComplete, ScreenFormat, ScrollbackRangeResponse, SnapshotResponse};
+
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        client.send(ModuleType::PTY, "init", serde_json::json!({
+            "shell_type": "custom:/bin/sh",
+            "cols": 40,
+            "rows": 10,
+        })).await;
+        let init: InitComplete = client.expect(&ModuleType::PTY).await;
+
+        // 输出先于转发进入虚拟终端，收到输出后快照已包含该内容
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "seq 1 30; echo snap-$((6 * 7))\n" })).await;
+        client.read_output_until(init.session_id, "snap-42").await;
+
+        client.send(ModuleType::PTY, "snapshot", serde_json::json!({})).await;
+        let snapshot: SnapshotResponse = client.expect(&ModuleType::PTY).await;
+        assert_eq!(snapshot.session_id, init.session_id);
+        assert_eq!(snapshot.format, ScreenFormat::Text);
+        assert_eq!((snapshot.cols, snapshot.rows), (40, 10));
+        assert!(snapshot.content.lines().any(|line| line == "snap-42"));
+        assert!(!snapshot.alternate_screen);
+        assert!(snapshot.history_lines > 0);
+        assert!(snapshot.output_offset > 0);
+
+        client.send(ModuleType::PTY, "scrollback_range", serde_json::json!({
+            "start": 0,
+            "count": snapshot.history_lines + 10,
+        })).await;
+        let range: ScrollbackRangeResponse = client.expect(&ModuleType::PTY).await;
+        assert_eq!(range.lines.len(), snapshot.history_lines);
+        // 输入先于提示符回显时 seq 的第一行会与提示符同行
+        assert!(range.lines.iter().any(|line| line == "2"));
+
+        // 全屏程序切换到备用屏幕
+        client.send(ModuleType::PTY, "input", serde_json::json!({ "data": "printf '\\033[?1049hfull-%s' screen\n" })).await;
+        client.read_output_until(init.session_id, "full-screen").await;
+        client.send(ModuleType::PTY, "snapshot", serde_json::json!({ "format": "html" })).await;
+        let snapshot: SnapshotResponse = client.expect(&ModuleType::PTY).await;
+        assert!(snapshot.alternate_screen);
+        assert!(snapshot.content.starts_with("<pre"));
+        assert!(snapshot.content.contains("full-screen"));
+        assert_eq!(snapshot.history_lines, 0);
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(all(unix, feature = "pty"))]
+    #[tokio::test]
+    async fn test_pty_search() {
+        use crate::pty::{InitComplete, SearchResponse};
+
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        client.send(ModuleType::PTY, "init", serde_json::json!({ "shell_type": "custom:/bin/sh" })).await;
+        let first: InitComplete = client.expect(&ModuleType::PTY).await;
+        // 输出另起一行，避免提示符晚于输入回显时与输出同行
+        client.send(ModuleType::PTY, "input", serde_json::json!({
+            "data": "printf '\\n\\033[31mBuild %s\\033[0m\\nstep-%s\\n' FAILED $((1 + 1))\n",
+        })).await;
+        client.read_output_until(first.session_id, "step-2").await;
+
+        // 之后创建的会话成为默认会话，先前的会话仍可检索
+        client.send(ModuleType::PTY, "init", serde_json::json!({ "shell_type": "custom:/bin/sh" })).await;
+        let second: InitComplete = client.expect(&ModuleType::PTY).await;
+        assert_ne!(second.session_id, first.session_id);
+
+        client.send(ModuleType::PTY, "search", serde_json::json!({
+            "session_id": first.session_id,
+            "query": "build failed",
+            "ignore_case": true,
+            "context": 1,
+        })).await;
+        let results: SearchResponse = client.expect(&ModuleType::PTY).await;
+        assert_eq!(results.session_id, first.session_id);
+        assert!(!results.truncated);
+        assert_eq!(results.matches.len(), 1);
+        let found = &results.matches[0];
+        assert_eq!(found.text, "Build FAILED");
+        assert_eq!((found.ranges[0].start, found.ranges[0].end), (0, 12));
+        assert_eq!(found.after, vec!["step-2"]);
+
+        client.send(ModuleType::PTY, "search", serde_json::json!({
+            "session_id": first.session_id,
+            "query": r"^step-\d+$",
+            "regex": true,
+        })).await;
+        let results: SearchResponse = client.expect(&ModuleType::PTY).await;
+        assert_eq!(results.matches.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(), vec!["step-2"]);
+
+        client.send(ModuleType::PTY, "search", serde_json::json!({ "query": "(", "regex": true })).await;
+        assert_eq!(client.expect_error(&ModuleType::PTY).await.code, ErrorCode::InvalidMessage);
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(feature = "llm")]
+    #[tokio::test]
+    async fn test_llm_stream() {
+        use crate::llm::{StreamChunkMessage, StreamCompleteMessage, StreamStarted};
+
+        let endpoint = mock_http(MockResponse::sse(&[
+            r#"{"choices":[{"delta":{"content":"Hel"}}]}"#,
+            r#"{"choices":[{"delta":{"content":"lo"}}]}"#,
+            "[DONE]",
+        ])).await;
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        let id = client.send(ModuleType::LLM, "stream_start", serde_json::json!({
+            "endpoint": endpoint,
+            "body": "{}",
+            "request_id": "req-1",
+        })).await;
+        let StreamStarted {} = client.expect(&ModuleType::LLM).await;
+
+        let mut content = String::new();
+        for _ in 0..2 {
+            let chunk = client.expect_json(&ModuleType::LLM, StreamChunkMessage::TYPE).await;
+            assert_eq!(chunk["id"], serde_json::json!(id));
+            let chunk: StreamChunkMessage = serde_json::from_value(chunk).unwrap();
+            assert_eq!(chunk.request_id.as_deref(), Some("req-1"));
+            content.push_str(&chunk.content);
+        }
+        assert_eq!(content, "Hello");
+
+        let complete: StreamCompleteMessage = client.expect(&ModuleType::LLM).await;
+        assert_eq!(complete.full_content, "Hello");
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(feature = "llm")]
+    #[tokio::test]
+    async fn test_llm_stream_upstream_error() {
+        use crate::llm::StreamErrorMessage;
+
+        let endpoint = mock_http(
+            MockResponse::error(429, r#"{"error":"rate limited"}"#).with_header("Retry-After", "2"),
+        ).await;
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        client.send(ModuleType::LLM, "stream_start", serde_json::json!({ "endpoint": endpoint, "body": "{}" })).await;
+        let StreamErrorMessage { error, .. } = client.expect(&ModuleType::LLM).await;
+        assert_eq!(error.code, ErrorCode::RateLimited);
+        assert!(error.retryable);
+        let details = error.details.unwrap();
+        assert_eq!(details.status, Some(429));
+        assert_eq!(details.retry_after_ms, Some(2000));
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(feature = "voice")]
+    #[tokio::test]
+    async fn test_voice_rejects_requests_without_recording() {
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        // 配置不完整时在打开音频设备之前拒绝
+        client.send(ModuleType::VOICE, "start_recording", serde_json::json!({
+            "mode": "toggle",
+            "asr_config": {
+                "primary": { "provider": "qwen", "mode": "http" },
+                "enable_fallback": false,
+            },
+        })).await;
+        assert_eq!(client.expect_error(&ModuleType::VOICE).await.code, ErrorCode::InvalidConfig);
+
+        let id = client.send(ModuleType::VOICE, "stop_recording", serde_json::Value::Null).await;
+        let error = client.expect_json(&ModuleType::VOICE, ErrorPayload::TYPE).await;
+        assert_eq!(error["id"], serde_json::json!(id));
+        assert_eq!(error["code"], "INVALID_STATE");
+
+        client.close().await;
+        server.shutdown().await;
+    }
+
+    #[cfg(feature = "utils")]
+    #[tokio::test]
+    async fn test_utils_detect_language() {
+        use crate::utils::LanguageDetectedResponse;
+
+        let server = TestServer::start().await;
+        let mut client = server.connect().await;
+
+        client.send(ModuleType::UTILS, "detect_language", serde_json::json!({
+            "text": "这是一段用于测试语言检测的简体中文文本。",
+            "request_id": "lang-1",
+        })).await;
+        let detected: LanguageDetectedResponse = client.expect(&ModuleType::UTILS).await;
+        assert_eq!(detected.request_id, "lang-1");
+        assert_eq!(detected.language, "zh");
+        assert_eq!(detected.is_simplified, Some(true));
+
+        // 缺少字段的请求返回错误，并回传请求 ID
+        let id = client.send(ModuleType::UTILS, "detect_language", serde_json::json!({ "text": "hello" })).await;
+        let error = client.expect_json(&ModuleType::UTILS, ErrorPayload::TYPE).await;
+        assert_eq!(error["id"], serde_json::json!(id));
+        assert_eq!(error["code"], "INVALID_MESSAGE");
+
+        client.close().await;
+        server.shutdown().await;
+    }
+}
@@ -0,0 +1,345 @@
+// 传输层
+// 服务器可通过 TCP WebSocket、Unix 套接字 WebSocket 或 stdio 提供服务，
+// 各传输统一转换为 WebSocket `Message` 的收发流，上层协议 (ModuleMessage/ServerResponse) 完全一致
+//
+// stdio 消息格式:
+// - lines: 每行一条 JSON 文本消息；二进制消息编码为 `{"binary": "<base64>"}`
+// - length: 每条消息为 `[类型 u8][长度 u32 BE][负载]`，类型 1 为文本、2 为二进制
+
+use std::io::{self, Cursor, Read};
+use std::path::PathBuf;
+use std::pin::Pin;
+
+use base64::Engine as _;
+use futures_util::{Sink, Stream};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use tokio_util::io::StreamReader;
+
+/// 单条 stdio 消息的最大长度 (与 WebSocket 默认上限一致)
+pub const MAX_STDIO_MESSAGE_SIZE: usize = 64 << 20;
+
+/// lines 格式中二进制消息的字段名
+const BINARY_FIELD: &str = "binary";
+
+/// length 格式的消息类型: 文本
+const KIND_TEXT: u8 = 1;
+
+/// length 格式的消息类型: 二进制
+const KIND_BINARY: u8 = 2;
+
+/// 消息发送端 (各传输统一的类型)
+pub type MessageSink = Pin<Box<dyn Sink<Message, Error = WsError> + Send>>;
+
+/// 消息接收端 (各传输统一的类型)
+pub type MessageStream = Pin<Box<dyn Stream<Item = Result<Message, WsError>> + Send>>;
+
+// ============================================================================
+// 传输配置
+// ============================================================================
+
+/// 服务器传输方式
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Transport {
+    /// TCP 上的 WebSocket，握手时校验认证令牌
+    #[default]
+    Tcp,
+    /// Unix 套接字上的 WebSocket，由套接字文件权限 (仅属主可访问) 控制访问
+    Unix(PathBuf),
+    /// 标准输入/输出，只有父进程可以访问，不打开任何网络端口
+    Stdio(StdioFormat),
+}
+
+/// stdio 传输的消息格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StdioFormat {
+    /// 换行分隔的 JSON
+    #[default]
+    Lines,
+    /// 带长度前缀的消息
+    Length,
+}
+
+impl std::str::FromStr for StdioFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lines" => Ok(StdioFormat::Lines),
+            "length" => Ok(StdioFormat::Length),
+            _ => Err(format!("未知的 stdio 格式: {} (可选 lines、length)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for StdioFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StdioFormat::Lines => write!(f, "lines"),
+            StdioFormat::Length => write!(f, "length"),
+        }
+    }
+}
+
+// ============================================================================
+// stdio 传输
+// ============================================================================
+
+/// 从标准输入/输出创建消息收发端
+pub fn stdio(format: StdioFormat) -> io::Result<(MessageSink, MessageStream)> {
+    Ok((message_sink(tokio::io::stdout(), format), message_stream(stdin_reader()?, format)))
+}
+
+/// 标准输入读取器
+///
+/// tokio 的 stdin 在阻塞线程池中读取且无法取消，关闭服务器时运行时会一直等待输入；
+/// 这里改用独立线程读取，进程退出时无需等待该线程。
+fn stdin_reader() -> io::Result<impl AsyncRead + Send + Unpin + 'static> {
+    let (tx, rx) = mpsc::channel::<io::Result<Cursor<Vec<u8>>>>(16);
+    std::thread::Builder::new()
+        .name("stdin".to_string())
+        .spawn(move || {
+            let mut stdin = io::stdin().lock();
+            let mut buf = vec![0u8; 8192];
+            loop {
+                let chunk = match stdin.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => Ok(Cursor::new(buf[..n].to_vec())),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => Err(e),
+                };
+                let failed = chunk.is_err();
+                if tx.blocking_send(chunk).is_err() || failed {
+                    break;
+                }
+            }
+        })?;
+
+    let chunks = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+    Ok(StreamReader::new(Box::pin(chunks)))
+}
+
+/// 按 stdio 格式从字节流中读取消息
+pub fn message_stream<R>(reader: R, format: StdioFormat) -> MessageStream
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    let reader = BufReader::new(reader);
+    Box::pin(futures_util::stream::unfold(Some(reader), move |reader| async move {
+        let mut reader = reader?;
+        let result = match format {
+            StdioFormat::Lines => read_line_message(&mut reader).await,
+            StdioFormat::Length => read_length_message(&mut reader).await,
+        };
+        match result {
+            Ok(Some(message)) => Some((Ok(message), Some(reader))),
+            Ok(None) => None,
+            // 出错后结束流，避免在损坏的数据上继续解析
+            Err(e) => Some((Err(WsError::Io(e)), None)),
+        }
+    }))
+}
+
+/// 按 stdio 格式向字节流写入消息
+///
+/// 控制消息 (Ping/Pong/Close) 在 stdio 上没有意义，直接忽略
+pub fn message_sink<W>(writer: W, format: StdioFormat) -> MessageSink
+where
+    W: AsyncWrite + Send + Unpin + 'static,
+{
+    Box::pin(futures_util::sink::unfold(writer, move |mut writer, message: Message| async move {
+        write_message(&mut writer, format, message).await.map_err(WsError::Io)?;
+        Ok::<_, WsError>(writer)
+    }))
+}
+
+/// 读取一行消息 (跳过空行)，输入结束时返回 None
+///
+/// 最多读取 `MAX_STDIO_MESSAGE_SIZE + 1` 字节，超长的行不会整行读入内存
+async fn read_line_message<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> io::Result<Option<Message>> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let limit = MAX_STDIO_MESSAGE_SIZE as u64 + 1;
+        if (&mut *reader).take(limit).read_until(b'\n', &mut line).await? == 0 {
+            return Ok(None);
+        }
+        if line.len() > MAX_STDIO_MESSAGE_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "消息超过最大长度"));
+        }
+        let line = std::str::from_utf8(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let text = line.trim_end_matches(['\r', '\n']);
+        if !text.trim().is_empty() {
+            return decode_line(text).map(Some);
+        }
+    }
+}
+
+/// 解析一行消息，`{"binary": "<base64>"}` 为二进制消息，其余均为文本消息
+fn decode_line(text: &str) -> io::Result<Message> {
+    if let Ok(serde_json::Value::Object(object)) = serde_json::from_str::<serde_json::Value>(text) {
+        if object.len() == 1 {
+            if let Some(encoded) = object.get(BINARY_FIELD).and_then(|v| v.as_str()) {
+                let data = base64::engine::general_purpose::STANDARD
+                    .decode(encoded)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                return Ok(Message::Binary(data.into()));
+            }
+        }
+    }
+    Ok(Message::Text(text.to_string().into()))
+}
+
+/// 读取一条带长度前缀的消息，输入在消息边界结束时返回 None
+async fn read_length_message<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> io::Result<Option<Message>> {
+    let kind = match reader.read_u8().await {
+        Ok(kind) => kind,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let len = reader.read_u32().await? as usize;
+    if len > MAX_STDIO_MESSAGE_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("消息长度 {} 超过最大值", len)));
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+
+    match kind {
+        KIND_TEXT => String::from_utf8(payload)
+            .map(|text| Some(Message::Text(text.into())))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        KIND_BINARY => Ok(Some(Message::Binary(payload.into()))),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("未知的消息类型: {}", kind))),
+    }
+}
+
+/// 写入一条消息并刷新
+async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, format: StdioFormat, message: Message) -> io::Result<()> {
+    let (kind, payload) = match message {
+        Message::Text(text) => (KIND_TEXT, text.as_bytes().to_vec()),
+        Message::Binary(data) => (KIND_BINARY, data.to_vec()),
+        _ => return Ok(()),
+    };
+
+    match format {
+        StdioFormat::Lines => {
+            let mut line = if kind == KIND_BINARY {
+                let encoded = base64::engine::general_purpose::STANDARD.encode(&payload);
+                serde_json::json!({ BINARY_FIELD: encoded }).to_string().into_bytes()
+            } else {
+                payload
+            };
+            line.push(b'\n');
+            writer.write_all(&line).await?;
+        }
+        StdioFormat::Length => {
+            let len = u32::try_from(payload.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "消息过长"))?;
+            let mut frame = Vec::with_capacity(5 + payload.len());
+            frame.push(kind);
+            frame.extend_from_slice(&len.to_be_bytes());
+            frame.extend_from_slice(&payload);
+            writer.write_all(&frame).await?;
+        }
+    }
+    writer.flush().await
+}
+
+// ============================================================================
+// 测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{SinkExt, StreamExt};
+
+    /// 通过内存管道写入后再读出
+    async fn roundtrip(format: StdioFormat, messages: Vec<Message>) -> Vec<Message> {
+        let (client, server) = tokio::io::duplex(1024);
+        let mut sink = message_sink(client, format);
+        let mut stream = message_stream(server, format);
+        let count = messages.len();
+        for message in messages {
+            sink.send(message).await.unwrap();
+        }
+        let mut received = Vec::new();
+        for _ in 0..count {
+            received.push(stream.next().await.unwrap().unwrap());
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip() {
+        for format in [StdioFormat::Lines, StdioFormat::Length] {
+            let messages = vec![
+                Message::Text(r#"{"module":"utils","type":"hello"}"#.into()),
+                Message::Binary(vec![0, 1, 2, 0xff, b'\n'].into()),
+                Message::Text("多行\\n文本".into()),
+            ];
+            assert_eq!(roundtrip(format, messages.clone()).await, messages, "format {}", format);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_control_messages_ignored() {
+        let (client, server) = tokio::io::duplex(1024);
+        let mut sink = message_sink(client, StdioFormat::Lines);
+        sink.send(Message::Ping(vec![1].into())).await.unwrap();
+        sink.send(Message::Text("{}".into())).await.unwrap();
+        drop(sink);
+
+        let mut stream = message_stream(server, StdioFormat::Lines);
+        assert_eq!(stream.next().await.unwrap().unwrap(), Message::Text("{}".into()));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lines_format() {
+        let input = b"{\"module\":\"pty\",\"type\":\"init\"}\r\n\n{\"binary\":\"aGk=\"}\n{\"binary\":\"aGk=\",\"module\":\"pty\"}\n";
+        let mut stream = message_stream(&input[..], StdioFormat::Lines);
+        assert_eq!(stream.next().await.unwrap().unwrap(), Message::Text(r#"{"module":"pty","type":"init"}"#.into()));
+        assert_eq!(stream.next().await.unwrap().unwrap(), Message::Binary(b"hi".to_vec().into()));
+        // 带其他字段时仍是普通文本消息
+        assert!(matches!(stream.next().await.unwrap().unwrap(), Message::Text(_)));
+        assert!(stream.next().await.is_none());
+
+        let mut stream = message_stream(&b"{\"binary\":\"!!\"}\n"[..], StdioFormat::Lines);
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+
+        // 没有换行的超长输入读到最大长度即报错
+        let mut stream = message_stream(tokio::io::repeat(b'a'), StdioFormat::Lines);
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_length_format_errors() {
+        // 未知类型
+        let mut stream = message_stream(&[9, 0, 0, 0, 0][..], StdioFormat::Length);
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+
+        // 超过最大长度
+        let mut stream = message_stream(&[KIND_TEXT, 0xff, 0xff, 0xff, 0xff][..], StdioFormat::Length);
+        assert!(stream.next().await.unwrap().is_err());
+
+        // 负载不完整
+        let mut stream = message_stream(&[KIND_BINARY, 0, 0, 0, 4, 1, 2][..], StdioFormat::Length);
+        assert!(stream.next().await.unwrap().is_err());
+    }
+
+    #[test]
+    fn test_stdio_format_from_str() {
+        assert_eq!("lines".parse::<StdioFormat>(), Ok(StdioFormat::Lines));
+        assert_eq!("length".parse::<StdioFormat>(), Ok(StdioFormat::Length));
+        assert!("json".parse::<StdioFormat>().is_err());
+    }
+}
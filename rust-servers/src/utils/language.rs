@@ -4,15 +4,6 @@
 use serde::Serialize;
 use whatlang::{detect, Lang};
 
-/// 日志宏
-macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        if cfg!(debug_assertions) {
-            eprintln!("[DEBUG] {}", format!($($arg)*));
-        }
-    };
-}
-
 // ============================================================================
 // 语言检测结果
 // ============================================================================
@@ -93,9 +84,9 @@ impl LanguageDetector {
                 // 如果是中文，进一步区分简繁体
                 if lang == Lang::Cmn {
                     let is_simplified = self.is_simplified_chinese(text);
-                    LanguageDetectionResult::chinese(confidence as f64, is_simplified)
+                    LanguageDetectionResult::chinese(confidence, is_simplified)
                 } else {
-                    LanguageDetectionResult::new(&iso_code, confidence as f64)
+                    LanguageDetectionResult::new(&iso_code, confidence)
                 }
             }
             None => {
@@ -1,43 +1,27 @@
 // Utils 模块
-// 提供语言检测等通用工具功能
+// 提供语言检测、日志级别调整、诊断信息等通用工具功能
 
 pub mod language;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::Mutex as TokioMutex;
 
-use crate::router::{ModuleHandler, ModuleMessage, ModuleType, RouterError, ServerResponse};
+use crate::logging::{self, Level};
+use crate::i18n::Msg;
+use crate::metrics::Diagnostics;
+use crate::protocol::{ModuleProtocol, Payload};
+use crate::router::{ModuleCapabilities, ModuleContext, ModuleHandler, ModuleMessage, ModuleState, ModuleType, RouterError, ServerResponse};
 use crate::server::WsSender;
 use language::{LanguageDetector, LanguageDetectionResult};
 
-/// 日志宏
-macro_rules! log_info {
-    ($($arg:tt)*) => {
-        eprintln!("[INFO] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_error {
-    ($($arg:tt)*) => {
-        eprintln!("[ERROR] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        if cfg!(debug_assertions) {
-            eprintln!("[DEBUG] {}", format!($($arg)*));
-        }
-    };
-}
-
 // ============================================================================
 // 消息类型定义
 // ============================================================================
 
 /// 语言检测请求
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct DetectLanguageRequest {
     /// 要检测的文本
     pub text: String,
@@ -45,9 +29,31 @@ pub struct DetectLanguageRequest {
     pub request_id: String,
 }
 
+impl Payload for DetectLanguageRequest {
+    const TYPE: &'static str = "detect_language";
+}
+
+/// 设置日志级别请求
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SetLogLevelRequest {
+    /// 日志级别 (error, warn, info, debug，不区分大小写)
+    pub level: String,
+}
+
+impl Payload for SetLogLevelRequest {
+    const TYPE: &'static str = "set_log_level";
+}
+
+/// 诊断请求
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DiagnosticsRequest {}
+
+impl Payload for DiagnosticsRequest {
+    const TYPE: &'static str = Diagnostics::TYPE;
+}
 
 /// 语言检测响应
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct LanguageDetectedResponse {
     /// 请求 ID
     pub request_id: String,
@@ -72,6 +78,34 @@ impl LanguageDetectedResponse {
     }
 }
 
+impl Payload for LanguageDetectedResponse {
+    const TYPE: &'static str = "language_detected";
+}
+
+/// 设置日志级别响应
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct LogLevelSetResponse {
+    /// 新的日志级别
+    pub level: Level,
+    /// 调整前的日志级别
+    pub previous: Level,
+}
+
+impl Payload for LogLevelSetResponse {
+    const TYPE: &'static str = "log_level_set";
+}
+
+/// Utils 模块的请求和服务器消息
+pub fn protocol() -> ModuleProtocol {
+    ModuleProtocol::new()
+        .request::<DetectLanguageRequest>()
+        .request::<SetLogLevelRequest>()
+        .request::<DiagnosticsRequest>()
+        .event::<LanguageDetectedResponse>()
+        .event::<LogLevelSetResponse>()
+        .event::<Diagnostics>()
+}
+
 // ============================================================================
 // Utils 模块处理器
 // ============================================================================
@@ -84,14 +118,22 @@ pub struct UtilsHandler {
     detector: LanguageDetector,
     /// WebSocket 发送器
     ws_sender: Arc<TokioMutex<Option<WsSender>>>,
+    /// 服务器共享状态 (诊断信息)
+    state: ModuleState,
 }
 
 impl UtilsHandler {
     /// 创建新的 Utils 处理器
     pub fn new() -> Self {
+        Self::with_state(ModuleState::default())
+    }
+    
+    /// 使用服务器共享状态创建 Utils 处理器
+    pub fn with_state(state: ModuleState) -> Self {
         Self {
             detector: LanguageDetector::new(),
             ws_sender: Arc::new(TokioMutex::new(None)),
+            state,
         }
     }
     
@@ -107,8 +149,7 @@ impl UtilsHandler {
         msg: &ModuleMessage,
     ) -> Result<Option<ServerResponse>, RouterError> {
         // 解析请求
-        let request: DetectLanguageRequest = serde_json::from_value(msg.payload.clone())
-            .map_err(|e| RouterError::ModuleError(format!("Invalid detect_language request: {}", e)))?;
+        let request: DetectLanguageRequest = msg.parse()?;
         
         log_debug!("语言检测请求: request_id={}, text_len={}", 
             request.request_id, request.text.len());
@@ -123,20 +164,26 @@ impl UtilsHandler {
         
         // 构建响应
         let response = LanguageDetectedResponse::from_result(request.request_id, result);
-        let payload = serde_json::to_value(&response)
-            .map_err(|e| RouterError::ModuleError(format!("Failed to serialize response: {}", e)))?;
+        Ok(Some(ServerResponse::from_payload(ModuleType::UTILS, &response)))
+    }
+    
+    /// 处理设置日志级别请求 (运行时生效，无需重启)
+    fn handle_set_log_level(&self, msg: &ModuleMessage) -> Result<Option<ServerResponse>, RouterError> {
+        let request: SetLogLevelRequest = msg.parse()?;
+        let level: Level = request.level.parse()
+            .map_err(|error| RouterError::module(Msg::InvalidField { field: "level", error }))?;
         
-        Ok(Some(ServerResponse {
-            module: ModuleType::Utils,
-            msg_type: "language_detected".to_string(),
-            payload,
-        }))
+        let previous = logging::set_level(level);
+        log_info!("日志级别已调整: {} -> {}", previous, level);
+        
+        Ok(Some(ServerResponse::from_payload(ModuleType::UTILS, &LogLevelSetResponse { level, previous })))
     }
     
-    /// 清理资源
-    pub async fn cleanup(&self) {
-        log_debug!("Utils 模块清理资源");
-        // Utils 模块目前没有需要清理的资源
+    /// 处理诊断请求: 返回运行时长、连接数、各模块的运行状态和计数
+    async fn handle_diagnostics(&self, msg: &ModuleMessage) -> Result<Option<ServerResponse>, RouterError> {
+        let DiagnosticsRequest {} = msg.parse()?;
+        let diagnostics = self.state.diagnostics().await;
+        Ok(Some(ServerResponse::from_payload(ModuleType::UTILS, &diagnostics)))
     }
 }
 
@@ -153,7 +200,16 @@ impl Default for UtilsHandler {
 #[async_trait::async_trait]
 impl ModuleHandler for UtilsHandler {
     fn module_type(&self) -> ModuleType {
-        ModuleType::Utils
+        ModuleType::UTILS
+    }
+    
+    fn capabilities(&self) -> ModuleCapabilities {
+        ModuleCapabilities::new(&protocol().request_types(), serde_json::json!({
+            "language_detection": {
+                "chinese_variant": true,
+            },
+            "log_levels": Level::ALL,
+        }))
     }
     
     async fn handle(&self, msg: &ModuleMessage) -> Result<Option<ServerResponse>, RouterError> {
@@ -163,15 +219,30 @@ impl ModuleHandler for UtilsHandler {
             "detect_language" => {
                 self.handle_detect_language(msg).await
             }
+            "set_log_level" => {
+                self.handle_set_log_level(msg)
+            }
+            "diagnostics" => {
+                self.handle_diagnostics(msg).await
+            }
             _ => {
                 log_error!("未知的 Utils 消息类型: {}", msg.msg_type);
-                Err(RouterError::ModuleError(format!(
-                    "Unknown Utils message type: {}",
-                    msg.msg_type
-                )))
+                Err(RouterError::InvalidMessage(Msg::UnknownMessageType {
+                    module: ModuleType::UTILS.to_string(),
+                    msg_type: msg.msg_type.clone(),
+                }))
             }
         }
     }
+    
+    async fn on_connect(&self, context: &ModuleContext) {
+        self.set_ws_sender(context.ws_sender.clone()).await;
+    }
+    
+    async fn cleanup(&self) {
+        log_debug!("Utils 模块清理资源");
+        // Utils 模块目前没有需要清理的资源
+    }
 }
 
 
@@ -182,6 +253,8 @@ impl ModuleHandler for UtilsHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ErrorCode;
+    use crate::i18n::Locale;
     
     #[test]
     fn test_detect_language_request_deserialization() {
@@ -223,8 +296,9 @@ mod tests {
         
         // 创建测试消息
         let msg = ModuleMessage {
-            module: ModuleType::Utils,
+            module: ModuleType::UTILS,
             msg_type: "detect_language".to_string(),
+            id: None,
             payload: serde_json::json!({
                 "text": "Hello, this is a test message.",
                 "request_id": "test-789"
@@ -238,7 +312,7 @@ mod tests {
         assert!(response.is_some());
         
         let response = response.unwrap();
-        assert_eq!(response.module, ModuleType::Utils);
+        assert_eq!(response.module, ModuleType::UTILS);
         assert_eq!(response.msg_type, "language_detected");
         
         // 验证响应内容
@@ -252,19 +326,19 @@ mod tests {
         let handler = UtilsHandler::new();
         
         let msg = ModuleMessage {
-            module: ModuleType::Utils,
+            module: ModuleType::UTILS,
             msg_type: "unknown_type".to_string(),
+            id: None,
             payload: serde_json::json!({}),
         };
         
         let result = handler.handle(&msg).await;
         assert!(result.is_err());
         
-        if let Err(RouterError::ModuleError(msg)) = result {
-            assert!(msg.contains("Unknown Utils message type"));
-        } else {
-            panic!("Expected ModuleError");
-        }
+        let error = result.unwrap_err().to_server_error();
+        assert_eq!(error.code, ErrorCode::InvalidMessage);
+        assert_eq!(error.message.render(Locale::En), "Unknown utils message type: unknown_type");
+        assert_eq!(error.message.render(Locale::ZhCn), "未知的 utils 消息类型: unknown_type");
     }
     
     #[tokio::test]
@@ -273,8 +347,9 @@ mod tests {
         
         // 缺少必要字段的请求
         let msg = ModuleMessage {
-            module: ModuleType::Utils,
+            module: ModuleType::UTILS,
             msg_type: "detect_language".to_string(),
+            id: None,
             payload: serde_json::json!({
                 "text": "Hello"
                 // 缺少 request_id
@@ -284,4 +359,62 @@ mod tests {
         let result = handler.handle(&msg).await;
         assert!(result.is_err());
     }
+    
+    #[tokio::test]
+    async fn test_utils_handler_set_log_level() {
+        let handler = UtilsHandler::new();
+        
+        // 设置为当前级别，避免影响并行运行的其他测试
+        let current = logging::level();
+        let msg = ModuleMessage {
+            module: ModuleType::UTILS,
+            msg_type: "set_log_level".to_string(),
+            id: None,
+            payload: serde_json::json!({ "level": current.as_str().to_uppercase() }),
+        };
+        let response = handler.handle(&msg).await.unwrap().unwrap();
+        assert_eq!(response.msg_type, "log_level_set");
+        assert_eq!(response.payload["level"], current.as_str());
+        assert_eq!(response.payload["previous"], current.as_str());
+        
+        let msg = ModuleMessage {
+            module: ModuleType::UTILS,
+            msg_type: "set_log_level".to_string(),
+            id: None,
+            payload: serde_json::json!({ "level": "verbose" }),
+        };
+        assert!(matches!(
+            handler.handle(&msg).await,
+            Err(RouterError::Server(ref e)) if e.code == ErrorCode::ModuleError
+        ));
+        assert_eq!(logging::level(), current);
+    }
+    
+    #[tokio::test]
+    async fn test_utils_handler_diagnostics() {
+        let state = ModuleState::default();
+        let handler = UtilsHandler::with_state(state.clone());
+        state.metrics.record_message(&ModuleType::UTILS, Some(ErrorCode::ModuleError));
+        let _connection = state.connections.track();
+        
+        let msg = ModuleMessage {
+            module: ModuleType::UTILS,
+            msg_type: "diagnostics".to_string(),
+            id: None,
+            payload: serde_json::json!({}),
+        };
+        let response = handler.handle(&msg).await.unwrap().unwrap();
+        assert_eq!(response.msg_type, "diagnostics");
+        assert_eq!(response.payload["server_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(response.payload["pid"], std::process::id());
+        assert_eq!(response.payload["connections"], 1);
+        assert_eq!(response.payload["modules"]["utils"]["messages"], 1);
+        assert_eq!(response.payload["modules"]["utils"]["errors"]["MODULE_ERROR"], 1);
+        #[cfg(feature = "pty")]
+        assert_eq!(response.payload["pty"]["sessions"], serde_json::json!([]));
+        #[cfg(feature = "voice")]
+        assert_eq!(response.payload["voice"]["active_recordings"], 0);
+        #[cfg(feature = "llm")]
+        assert_eq!(response.payload["llm"]["time_to_first_token"]["count"], 0);
+    }
 }
@@ -43,29 +43,30 @@ impl FallbackStrategy {
         }
     }
     
-    pub fn from_config(config: &ASRConfig) -> Result<Self, ASRError> {
-        let primary = crate::voice::asr::create_engine(&config.primary)?;
+    pub fn from_config(config: &ASRConfig, retry_config: RetryConfig) -> Result<Self, ASRError> {
+        let primary = crate::voice::asr::create_engine(&config.primary, &retry_config)?;
         
         let fallback = if let Some(ref fallback_config) = config.fallback {
-            Some(crate::voice::asr::create_engine(fallback_config)?)
+            Some(crate::voice::asr::create_engine(fallback_config, &retry_config)?)
         } else {
             None
         };
         
-        Ok(Self::new(primary, fallback, config.enable_fallback))
+        Ok(Self::with_retry_config(primary, fallback, config.enable_fallback, retry_config))
     }
     
     pub async fn transcribe(&self, audio: &AudioData) -> Result<TranscriptionResult, ASRError> {
         let start_time = Instant::now();
         let mut primary_errors: Vec<String> = Vec::new();
+        let mut last_error: Option<ASRError> = None;
         
         for attempt in 0..=self.retry_config.max_retries {
             if attempt > 0 {
                 let delay = Duration::from_millis(
                     self.retry_config.base_delay_ms * (1 << (attempt - 1))
                 );
-                eprintln!(
-                    "[INFO] 主引擎重试 {}/{}, 等待 {}ms",
+                log_info!(
+                    "主引擎重试 {}/{}, 等待 {}ms",
                     attempt,
                     self.retry_config.max_retries,
                     delay.as_millis()
@@ -76,8 +77,8 @@ impl FallbackStrategy {
             match self.primary.transcribe(audio).await {
                 Ok(text) => {
                     let duration_ms = start_time.elapsed().as_millis() as u64;
-                    eprintln!(
-                        "[INFO] 主引擎 {} 转录成功 (尝试 {}), 耗时 {}ms",
+                    log_info!(
+                        "主引擎 {} 转录成功 (尝试 {}), 耗时 {}ms",
                         self.primary.name(),
                         attempt + 1,
                         duration_ms
@@ -90,14 +91,15 @@ impl FallbackStrategy {
                     ));
                 }
                 Err(e) => {
-                    eprintln!(
-                        "[WARN] 主引擎 {} 转录失败 (尝试 {}/{}): {}",
+                    log_warn!(
+                        "主引擎 {} 转录失败 (尝试 {}/{}): {}",
                         self.primary.name(),
                         attempt + 1,
                         self.retry_config.max_retries + 1,
                         e
                     );
                     primary_errors.push(e.to_string());
+                    last_error = Some(e);
                 }
             }
         }
@@ -105,12 +107,12 @@ impl FallbackStrategy {
         // 主引擎失败，尝试备用引擎
         if self.enable_fallback {
             if let Some(ref fallback) = self.fallback {
-                eprintln!("[INFO] 主引擎所有重试失败，尝试兜底引擎...");
+                log_info!("主引擎所有重试失败，尝试兜底引擎...");
                 match fallback.transcribe(audio).await {
                     Ok(text) => {
                         let duration_ms = start_time.elapsed().as_millis() as u64;
-                        eprintln!(
-                            "[INFO] 兜底引擎 {} 转录成功，耗时 {}ms",
+                        log_info!(
+                            "兜底引擎 {} 转录成功，耗时 {}ms",
                             fallback.name(),
                             duration_ms
                         );
@@ -125,6 +127,7 @@ impl FallbackStrategy {
                         return Err(ASRError::AllEnginesFailed {
                             primary_error: primary_errors.join("; "),
                             fallback_error: Some(fallback_error.to_string()),
+                            cause: last_error.map(Box::new),
                         });
                     }
                 }
@@ -134,6 +137,7 @@ impl FallbackStrategy {
         Err(ASRError::AllEnginesFailed {
             primary_error: primary_errors.join("; "),
             fallback_error: None,
+            cause: last_error.map(Box::new),
         })
     }
     
@@ -180,27 +184,29 @@ impl ParallelFallbackStrategy {
         let fallback_handle = if self.enable_fallback && self.fallback_config.is_some() {
             let fallback_config = self.fallback_config.clone().unwrap();
             let audio_clone = audio.clone();
+            let retry_config = self.retry_config.clone();
             
             Some(tokio::spawn(async move {
-                let engine = crate::voice::asr::create_engine(&fallback_config)?;
+                let engine = crate::voice::asr::create_engine(&fallback_config, &retry_config)?;
                 engine.transcribe(&audio_clone).await
             }))
         } else {
             None
         };
         
-        let primary_engine = crate::voice::asr::create_engine(&self.primary_config)?;
+        let primary_engine = crate::voice::asr::create_engine(&self.primary_config, &self.retry_config)?;
         let primary_name = primary_engine.name().to_string();
         
         let mut primary_errors: Vec<String> = Vec::new();
+        let mut last_error: Option<ASRError> = None;
         
         for attempt in 0..=self.retry_config.max_retries {
             if attempt > 0 {
                 let delay = Duration::from_millis(
                     self.retry_config.base_delay_ms * (1 << (attempt - 1))
                 );
-                eprintln!(
-                    "[INFO] 主引擎重试 {}/{}, 等待 {}ms",
+                log_info!(
+                    "主引擎重试 {}/{}, 等待 {}ms",
                     attempt,
                     self.retry_config.max_retries,
                     delay.as_millis()
@@ -211,8 +217,8 @@ impl ParallelFallbackStrategy {
             match primary_engine.transcribe(audio).await {
                 Ok(text) => {
                     let duration_ms = start_time.elapsed().as_millis() as u64;
-                    eprintln!(
-                        "[INFO] 主引擎 {} 转录成功 (尝试 {}), 耗时 {}ms",
+                    log_info!(
+                        "主引擎 {} 转录成功 (尝试 {}), 耗时 {}ms",
                         primary_name,
                         attempt + 1,
                         duration_ms
@@ -230,21 +236,22 @@ impl ParallelFallbackStrategy {
                     ));
                 }
                 Err(e) => {
-                    eprintln!(
-                        "[WARN] 主引擎 {} 转录失败 (尝试 {}/{}): {}",
+                    log_warn!(
+                        "主引擎 {} 转录失败 (尝试 {}/{}): {}",
                         primary_name,
                         attempt + 1,
                         self.retry_config.max_retries + 1,
                         e
                     );
                     primary_errors.push(e.to_string());
+                    last_error = Some(e);
                 }
             }
         }
         
         // 主引擎所有重试都失败，等待后台任务结果
         if let Some(handle) = fallback_handle {
-            eprintln!("[INFO] 主引擎所有重试失败，等待兜底引擎结果...");
+            log_info!("主引擎所有重试失败，等待兜底引擎结果...");
             
             match handle.await {
                 Ok(Ok(text)) => {
@@ -254,8 +261,8 @@ impl ParallelFallbackStrategy {
                         .map(|c| c.provider.to_string())
                         .unwrap_or_else(|| "fallback".to_string());
                     
-                    eprintln!(
-                        "[INFO] 兜底引擎 {} 转录成功，耗时 {}ms",
+                    log_info!(
+                        "兜底引擎 {} 转录成功，耗时 {}ms",
                         fallback_name,
                         duration_ms
                     );
@@ -271,12 +278,14 @@ impl ParallelFallbackStrategy {
                     return Err(ASRError::AllEnginesFailed {
                         primary_error: primary_errors.join("; "),
                         fallback_error: Some(fallback_error.to_string()),
+                        cause: last_error.map(Box::new),
                     });
                 }
                 Err(join_error) => {
                     return Err(ASRError::AllEnginesFailed {
                         primary_error: primary_errors.join("; "),
                         fallback_error: Some(format!("后台任务失败: {}", join_error)),
+                        cause: last_error.map(Box::new),
                     });
                 }
             }
@@ -285,6 +294,7 @@ impl ParallelFallbackStrategy {
         Err(ASRError::AllEnginesFailed {
             primary_error: primary_errors.join("; "),
             fallback_error: None,
+            cause: last_error.map(Box::new),
         })
     }
     
@@ -43,7 +43,7 @@ impl DoubaoHttpEngine {
         
         let audio_base64 = general_purpose::STANDARD.encode(&wav_data);
         
-        eprintln!("[INFO] 豆包 ASR: 音频数据大小 {} bytes", wav_data.len());
+        log_info!("豆包 ASR: 音频数据大小 {} bytes", wav_data.len());
         
         let request_body = serde_json::json!({
             "user": {
@@ -89,7 +89,7 @@ impl DoubaoHttpEngine {
             .and_then(|v| v.to_str().ok())
             .unwrap_or("");
         
-        eprintln!("[INFO] 豆包 ASR 响应: status_code={}, message={}", status_code, api_message);
+        log_info!("豆包 ASR 响应: status_code={}, message={}", status_code, api_message);
         
         if status_code != "20000000" {
             return match status_code {
@@ -110,7 +110,7 @@ impl DoubaoHttpEngine {
         let result: serde_json::Value = response.json().await
             .map_err(|e| ASRError::InternalError(format!("解析响应失败: {}", e)))?;
         
-        eprintln!("[DEBUG] 豆包 ASR 响应体: {}", serde_json::to_string_pretty(&result).unwrap_or_default());
+        log_debug!("豆包 ASR 响应体: {}", serde_json::to_string_pretty(&result).unwrap_or_default());
         
         let text = result["result"]["text"]
             .as_str()
@@ -154,12 +154,12 @@ impl ASREngine for DoubaoHttpEngine {
             match self.transcribe_once(audio).await {
                 Ok(text) => {
                     let duration = start_time.elapsed().as_millis() as u64;
-                    eprintln!("[INFO] 豆包 HTTP 转录成功，耗时 {}ms: {}", duration, text);
+                    log_info!("豆包 HTTP 转录成功，耗时 {}ms: {}", duration, text);
                     return Ok(text);
                 }
                 Err(e) => {
-                    eprintln!(
-                        "[WARN] 豆包 HTTP 转录失败 (尝试 {}/{}): {}",
+                    log_warn!(
+                        "豆包 HTTP 转录失败 (尝试 {}/{}): {}",
                         attempt + 1,
                         self.retry_config.max_retries + 1,
                         e
@@ -157,12 +157,12 @@ impl ASREngine for QwenHttpEngine {
             match self.transcribe_once(audio).await {
                 Ok(text) => {
                     let duration = start_time.elapsed().as_millis() as u64;
-                    eprintln!("[INFO] Qwen HTTP 转录成功，耗时 {}ms", duration);
+                    log_info!("Qwen HTTP 转录成功，耗时 {}ms", duration);
                     return Ok(text);
                 }
                 Err(e) => {
-                    eprintln!(
-                        "[WARN] Qwen HTTP 转录失败 (尝试 {}/{}): {}",
+                    log_warn!(
+                        "Qwen HTTP 转录失败 (尝试 {}/{}): {}",
                         attempt + 1,
                         self.retry_config.max_retries + 1,
                         e
@@ -45,7 +45,7 @@ impl SenseVoiceHttpEngine {
         let wav_data = audio.to_wav()
             .map_err(|e| ASRError::InvalidAudio(e.to_string()))?;
         
-        eprintln!("[INFO] SenseVoice ASR: 音频数据大小 {} bytes", wav_data.len());
+        log_info!("SenseVoice ASR: 音频数据大小 {} bytes", wav_data.len());
         
         let file_part = reqwest::multipart::Part::bytes(wav_data)
             .file_name("audio.wav")
@@ -102,7 +102,7 @@ impl SenseVoiceHttpEngine {
         let result: SenseVoiceResponse = response.json().await
             .map_err(|e| ASRError::InternalError(format!("解析响应失败: {}", e)))?;
         
-        eprintln!("[DEBUG] SenseVoice ASR 响应: text={}", result.text);
+        log_debug!("SenseVoice ASR 响应: text={}", result.text);
         
         let mut text = result.text;
         strip_trailing_punctuation(&mut text);
@@ -144,12 +144,12 @@ impl ASREngine for SenseVoiceHttpEngine {
             match self.transcribe_once(audio).await {
                 Ok(text) => {
                     let duration = start_time.elapsed().as_millis() as u64;
-                    eprintln!("[INFO] SenseVoice HTTP 转录成功，耗时 {}ms: {}", duration, text);
+                    log_info!("SenseVoice HTTP 转录成功，耗时 {}ms: {}", duration, text);
                     return Ok(text);
                 }
                 Err(e) => {
-                    eprintln!(
-                        "[WARN] SenseVoice HTTP 转录失败 (尝试 {}/{}): {}",
+                    log_warn!(
+                        "SenseVoice HTTP 转录失败 (尝试 {}/{}): {}",
                         attempt + 1,
                         self.retry_config.max_retries + 1,
                         e
@@ -2,6 +2,8 @@
 // 包含 ASR 引擎抽象层和各供应商实现
 
 use async_trait::async_trait;
+use crate::error::{ErrorCode, ServerError};
+use crate::i18n::Msg;
 use crate::voice::audio::AudioData;
 use crate::voice::config::{ASRProviderConfig, ASRProvider, ASRMode as ConfigASRMode};
 
@@ -53,6 +55,8 @@ pub enum ASRError {
     AllEnginesFailed {
         primary_error: String,
         fallback_error: Option<String>,
+        /// 主引擎最后一次失败的原因 (决定错误码)
+        cause: Option<Box<ASRError>>,
     },
     
     #[error("引擎未初始化")]
@@ -68,6 +72,51 @@ pub enum ASRError {
     InternalError(String),
 }
 
+impl From<ASRError> for ServerError {
+    fn from(error: ASRError) -> Self {
+        match error {
+            ASRError::NetworkError(error) => {
+                ServerError::new(ErrorCode::NetworkError, Msg::NetworkError { error })
+            }
+            ASRError::WebSocketError(error) => {
+                ServerError::new(ErrorCode::NetworkError, Msg::WebSocketError { error })
+            }
+            ASRError::AuthFailed { engine, message } => {
+                let msg = Msg::AsrAuthFailed { engine: engine.clone(), error: message };
+                ServerError::new(ErrorCode::AuthFailed, msg).with_provider(engine)
+            }
+            ASRError::QuotaExceeded { engine } => {
+                let msg = Msg::AsrQuotaExceeded { engine: engine.clone() };
+                ServerError::new(ErrorCode::RateLimited, msg).with_provider(engine)
+            }
+            ASRError::InvalidAudio(error) => {
+                ServerError::new(ErrorCode::InvalidAudio, Msg::AsrInvalidAudio { error })
+            }
+            ASRError::Timeout { timeout_ms } => {
+                ServerError::new(ErrorCode::Timeout, Msg::AsrTimeout { timeout_ms })
+            }
+            // 所有引擎都失败时按主引擎的失败原因分类，描述保留完整信息
+            ASRError::AllEnginesFailed { primary_error, fallback_error, cause } => {
+                let msg = Msg::AsrAllEnginesFailed { primary: primary_error, fallback: fallback_error };
+                match cause {
+                    Some(cause) => ServerError::from(*cause).with_message(msg),
+                    None => ServerError::new(ErrorCode::TranscriptionFailed, msg),
+                }
+            }
+            ASRError::UnsupportedOperation(detail) => {
+                ServerError::new(ErrorCode::InvalidConfig, Msg::AsrUnsupported { detail })
+            }
+            ASRError::ConfigError(detail) => {
+                ServerError::new(ErrorCode::InvalidConfig, Msg::InvalidConfig { detail })
+            }
+            ASRError::NotInitialized => ServerError::new(ErrorCode::InternalError, Msg::AsrNotInitialized),
+            ASRError::InternalError(detail) => {
+                ServerError::new(ErrorCode::InternalError, Msg::AsrInternal { detail })
+            }
+        }
+    }
+}
+
 // ============================================================================
 // ASR 模式
 // ============================================================================
@@ -168,6 +217,7 @@ pub trait RealtimeSession: Send {
 // 重试配置
 // ============================================================================
 
+/// ASR 请求重试配置
 #[derive(Debug, Clone)]
 pub struct RetryConfig {
     pub max_retries: u32,
@@ -241,7 +291,12 @@ impl EngineCredentials {
 }
 
 /// 创建 ASR 引擎
-pub fn create_engine(config: &ASRProviderConfig) -> Result<Box<dyn ASREngine>, ASRError> {
+/// 
+/// `retry_config` 用于 HTTP 模式引擎的请求超时和重试
+pub fn create_engine(
+    config: &ASRProviderConfig,
+    retry_config: &RetryConfig,
+) -> Result<Box<dyn ASREngine>, ASRError> {
     config.validate().map_err(|e| ASRError::ConfigError(e.to_string()))?;
     
     let engine_type = EngineType::from(config.provider.clone());
@@ -253,7 +308,7 @@ pub fn create_engine(config: &ASRProviderConfig) -> Result<Box<dyn ASREngine>, A
                 .ok_or_else(|| ASRError::ConfigError("缺少 dashscope_api_key".to_string()))?;
             
             match mode {
-                ASRMode::Http => Ok(Box::new(QwenHttpEngine::new(api_key))),
+                ASRMode::Http => Ok(Box::new(QwenHttpEngine::with_config(api_key, retry_config.clone()))),
                 ASRMode::Realtime => Ok(Box::new(QwenRealtimeEngine::new(api_key))),
             }
         }
@@ -264,14 +319,14 @@ pub fn create_engine(config: &ASRProviderConfig) -> Result<Box<dyn ASREngine>, A
                 .ok_or_else(|| ASRError::ConfigError("缺少 access_token".to_string()))?;
             
             match mode {
-                ASRMode::Http => Ok(Box::new(DoubaoHttpEngine::new(app_id, access_token))),
+                ASRMode::Http => Ok(Box::new(DoubaoHttpEngine::with_config(app_id, access_token, retry_config.clone()))),
                 ASRMode::Realtime => Ok(Box::new(DoubaoRealtimeEngine::new(app_id, access_token))),
             }
         }
         EngineType::SenseVoice => {
             let api_key = config.siliconflow_api_key.clone()
                 .ok_or_else(|| ASRError::ConfigError("缺少 siliconflow_api_key".to_string()))?;
-            Ok(Box::new(SenseVoiceHttpEngine::new(api_key)))
+            Ok(Box::new(SenseVoiceHttpEngine::with_config(api_key, retry_config.clone())))
         }
     }
 }
@@ -281,6 +336,7 @@ pub fn create_engine_by_type(
     engine_type: EngineType,
     credentials: EngineCredentials,
     mode: ASRMode,
+    retry_config: &RetryConfig,
 ) -> Result<Box<dyn ASREngine>, ASRError> {
     match engine_type {
         EngineType::Qwen => {
@@ -288,7 +344,7 @@ pub fn create_engine_by_type(
                 .ok_or_else(|| ASRError::ConfigError("缺少 API Key".to_string()))?;
             
             match mode {
-                ASRMode::Http => Ok(Box::new(QwenHttpEngine::new(api_key))),
+                ASRMode::Http => Ok(Box::new(QwenHttpEngine::with_config(api_key, retry_config.clone()))),
                 ASRMode::Realtime => Ok(Box::new(QwenRealtimeEngine::new(api_key))),
             }
         }
@@ -299,14 +355,14 @@ pub fn create_engine_by_type(
                 .ok_or_else(|| ASRError::ConfigError("缺少 access_token".to_string()))?;
             
             match mode {
-                ASRMode::Http => Ok(Box::new(DoubaoHttpEngine::new(app_id, access_token))),
+                ASRMode::Http => Ok(Box::new(DoubaoHttpEngine::with_config(app_id, access_token, retry_config.clone()))),
                 ASRMode::Realtime => Ok(Box::new(DoubaoRealtimeEngine::new(app_id, access_token))),
             }
         }
         EngineType::SenseVoice => {
             let api_key = credentials.api_key
                 .ok_or_else(|| ASRError::ConfigError("缺少 API Key".to_string()))?;
-            Ok(Box::new(SenseVoiceHttpEngine::new(api_key)))
+            Ok(Box::new(SenseVoiceHttpEngine::with_config(api_key, retry_config.clone())))
         }
     }
 }
@@ -17,7 +17,7 @@ use tokio_tungstenite::{
     WebSocketStream
 };
 
-use crate::voice::asr::{ASREngine, ASRError, ASRMode, RealtimeSession, RetryConfig};
+use crate::voice::asr::{ASREngine, ASRError, ASRMode, PartialResultCallback, RealtimeSession, RetryConfig};
 use crate::voice::audio::AudioData;
 
 const WEBSOCKET_URL: &str = "wss://openspeech.bytedance.com/api/v3/sauc/bigmodel_nostream";
@@ -77,7 +77,7 @@ enum SessionCommand {
 pub struct DoubaoRealtimeSession {
     cmd_sender: mpsc::Sender<SessionCommand>,
     result_receiver: Option<oneshot::Receiver<Result<String, ASRError>>>,
-    partial_callback: Option<Arc<Mutex<Box<dyn Fn(&str) + Send + 'static>>>>,
+    partial_callback: Option<Arc<Mutex<PartialResultCallback>>>,
 }
 
 impl DoubaoRealtimeSession {
@@ -85,7 +85,7 @@ impl DoubaoRealtimeSession {
         let websocket_key = generate_websocket_key();
         let request_id = generate_request_id();
         
-        eprintln!("[INFO] 创建豆包 Realtime WebSocket 连接");
+        log_info!("创建豆包 Realtime WebSocket 连接");
         
         let request = http::Request::builder()
             .uri(WEBSOCKET_URL)
@@ -104,7 +104,7 @@ impl DoubaoRealtimeSession {
         let (ws_stream, _) = connect_async(request).await
             .map_err(|e| ASRError::WebSocketError(format!("WebSocket 连接失败: {}", e)))?;
         
-        eprintln!("[INFO] 豆包 Realtime WebSocket 连接成功");
+        log_info!("豆包 Realtime WebSocket 连接成功");
         
         let (mut write, mut read) = ws_stream.split();
         
@@ -114,7 +114,7 @@ impl DoubaoRealtimeSession {
             "request": {"model_name": "bigmodel", "enable_itn": true, "enable_punc": true}
         });
         
-        eprintln!("[DEBUG] 豆包 Full Client Request: {}", serde_json::to_string_pretty(&config).unwrap_or_default());
+        log_debug!("豆包 Full Client Request: {}", serde_json::to_string_pretty(&config).unwrap_or_default());
         
         let msg = build_message(0x1, 0x1, 1, &serde_json::to_vec(&config)
             .map_err(|e| ASRError::InternalError(format!("序列化配置失败: {}", e)))?, 0x1)?;
@@ -122,25 +122,25 @@ impl DoubaoRealtimeSession {
         write.send(Message::Binary(msg.clone().into())).await
             .map_err(|e| ASRError::WebSocketError(format!("发送 Full Client Request 失败: {}", e)))?;
         
-        eprintln!("[DEBUG] 豆包 Full Client Request 已发送: {} bytes", msg.len());
+        log_debug!("豆包 Full Client Request 已发送: {} bytes", msg.len());
         
         if let Some(response) = read.next().await {
             match response {
                 Ok(Message::Binary(data)) => {
-                    eprintln!("[DEBUG] 豆包 Full Client Request 响应: {} bytes", data.len());
+                    log_debug!("豆包 Full Client Request 响应: {} bytes", data.len());
                     match parse_response(&data) {
                         Ok((text, _is_last)) => {
                             if !text.is_empty() {
-                                eprintln!("[DEBUG] 豆包初始响应包含文本（意外）: {}", text);
+                                log_debug!("豆包初始响应包含文本（意外）: {}", text);
                             }
                         }
                         Err(e) => {
-                            eprintln!("[DEBUG] 豆包初始响应（预期无文本）: {}", e);
+                            log_debug!("豆包初始响应（预期无文本）: {}", e);
                         }
                     }
                 }
                 Ok(other) => {
-                    eprintln!("[WARN] 豆包 Full Client Request 收到非二进制响应: {:?}", other);
+                    log_warn!("豆包 Full Client Request 收到非二进制响应: {:?}", other);
                 }
                 Err(e) => {
                     return Err(ASRError::WebSocketError(format!(
@@ -168,29 +168,29 @@ impl DoubaoRealtimeSession {
                             Ok(msg) => {
                                 let mut w = write_clone.lock().await;
                                 if let Err(e) = w.send(Message::Binary(msg.into())).await {
-                                    eprintln!("[ERROR] 豆包发送音频块失败: {}", e);
+                                    log_error!("豆包发送音频块失败: {}", e);
                                     break;
                                 }
                             }
                             Err(e) => {
-                                eprintln!("[ERROR] 豆包构建音频消息失败: {}", e);
+                                log_error!("豆包构建音频消息失败: {}", e);
                             }
                         }
                     }
                     SessionCommand::Finish => {
                         sequence += 1;
                         let last_seq = -sequence;
-                        eprintln!("[DEBUG] 豆包发送结束标志，sequence={}", last_seq);
+                        log_debug!("豆包发送结束标志，sequence={}", last_seq);
                         
                         match build_message(0x2, 0x3, last_seq, &[], 0x0) {
                             Ok(msg) => {
                                 let mut w = write_clone.lock().await;
                                 if let Err(e) = w.send(Message::Binary(msg.into())).await {
-                                    eprintln!("[ERROR] 豆包发送结束标志失败: {}", e);
+                                    log_error!("豆包发送结束标志失败: {}", e);
                                 }
                             }
                             Err(e) => {
-                                eprintln!("[ERROR] 豆包构建结束消息失败: {}", e);
+                                log_error!("豆包构建结束消息失败: {}", e);
                             }
                         }
                         break;
@@ -207,17 +207,17 @@ impl DoubaoRealtimeSession {
             while let Some(msg) = read.next().await {
                 match msg {
                     Ok(Message::Binary(data)) => {
-                        eprintln!("[DEBUG] 豆包 WebSocket 收到二进制消息: {} bytes", data.len());
+                        log_debug!("豆包 WebSocket 收到二进制消息: {} bytes", data.len());
                         match parse_response(&data) {
                             Ok((text, is_final)) => {
                                 if !text.is_empty() {
                                     accumulated_text = text.clone();
-                                    eprintln!("[DEBUG] 豆包累积文本: {}", accumulated_text);
+                                    log_debug!("豆包累积文本: {}", accumulated_text);
                                     let _ = partial_tx_clone.send(accumulated_text.clone()).await;
                                 }
                                 if is_final {
                                     let final_text = accumulated_text.clone();
-                                    eprintln!("[INFO] 豆包流式转录结果（最终包）: {}", final_text);
+                                    log_info!("豆包流式转录结果（最终包）: {}", final_text);
                                     if let Some(tx) = result_tx.take() {
                                         let _ = tx.send(Ok(final_text));
                                     }
@@ -225,19 +225,19 @@ impl DoubaoRealtimeSession {
                                 }
                             }
                             Err(e) => {
-                                eprintln!("[DEBUG] 豆包响应解析（非最终结果）: {}", e);
+                                log_debug!("豆包响应解析（非最终结果）: {}", e);
                             }
                         }
                     }
                     Ok(Message::Close(frame)) => {
-                        eprintln!("[WARN] 豆包 WebSocket 连接关闭: {:?}", frame);
+                        log_warn!("豆包 WebSocket 连接关闭: {:?}", frame);
                         if !accumulated_text.is_empty() {
-                            eprintln!("[INFO] 豆包连接关闭，返回累积文本: {}", accumulated_text);
+                            log_info!("豆包连接关闭，返回累积文本: {}", accumulated_text);
                             if let Some(tx) = result_tx.take() {
                                 let _ = tx.send(Ok(accumulated_text.clone()));
                             }
                         } else {
-                            eprintln!("[WARN] 豆包连接关闭，无转录结果");
+                            log_warn!("豆包连接关闭，无转录结果");
                             if let Some(tx) = result_tx.take() {
                                 let _ = tx.send(Err(ASRError::WebSocketError(
                                     "WebSocket 连接被关闭".to_string()
@@ -247,10 +247,10 @@ impl DoubaoRealtimeSession {
                         break;
                     }
                     Ok(other) => {
-                        eprintln!("[DEBUG] 豆包 WebSocket 收到其他消息类型: {:?}", other);
+                        log_debug!("豆包 WebSocket 收到其他消息类型: {:?}", other);
                     }
                     Err(e) => {
-                        eprintln!("[ERROR] 豆包 WebSocket 接收错误: {}", e);
+                        log_error!("豆包 WebSocket 接收错误: {}", e);
                         if let Some(tx) = result_tx.take() {
                             let _ = tx.send(Err(ASRError::WebSocketError(
                                 format!("WebSocket 错误: {}", e)
@@ -263,12 +263,12 @@ impl DoubaoRealtimeSession {
             
             if result_tx.is_some() {
                 if !accumulated_text.is_empty() {
-                    eprintln!("[INFO] 豆包连接结束，返回累积文本: {}", accumulated_text);
+                    log_info!("豆包连接结束，返回累积文本: {}", accumulated_text);
                     if let Some(tx) = result_tx.take() {
                         let _ = tx.send(Ok(accumulated_text));
                     }
                 } else {
-                    eprintln!("[WARN] 豆包连接结束，无转录结果");
+                    log_warn!("豆包连接结束，无转录结果");
                     if let Some(tx) = result_tx.take() {
                         let _ = tx.send(Err(ASRError::WebSocketError(
                             "WebSocket 连接结束，无转录结果".to_string()
@@ -276,10 +276,10 @@ impl DoubaoRealtimeSession {
                     }
                 }
             }
-            eprintln!("[DEBUG] 豆包 WebSocket 接收任务结束");
+            log_debug!("豆包 WebSocket 接收任务结束");
         });
         
-        let partial_callback: Option<Arc<Mutex<Box<dyn Fn(&str) + Send + 'static>>>> = None;
+        let partial_callback: Option<Arc<Mutex<PartialResultCallback>>> = None;
         let partial_callback_clone = partial_callback.clone();
         tokio::spawn(async move {
             while let Some(text) = partial_rx.recv().await {
@@ -386,8 +386,8 @@ fn parse_response(data: &[u8]) -> Result<(String, bool), ASRError> {
     let message_flags = data[1] & 0x0f;
     let compression = data[2] & 0x0f;
     
-    eprintln!(
-        "[DEBUG] 豆包响应 header: size={}, type={:#x}, flags={:#x}, compression={}",
+    log_debug!(
+        "豆包响应 header: size={}, type={:#x}, flags={:#x}, compression={}",
         header_size, message_type, message_flags, compression
     );
     
@@ -417,7 +417,7 @@ fn parse_response(data: &[u8]) -> Result<(String, bool), ASRError> {
             data[offset + 2],
             data[offset + 3],
         ]);
-        eprintln!("[DEBUG] 豆包响应 sequence: {}", sequence);
+        log_debug!("豆包响应 sequence: {}", sequence);
         offset += 4;
     }
     
@@ -452,7 +452,7 @@ fn parse_response(data: &[u8]) -> Result<(String, bool), ASRError> {
             .map_err(|e| ASRError::InternalError(format!("UTF-8 解码失败: {}", e)))?
     };
     
-    eprintln!("[DEBUG] 豆包响应 JSON: {}", json_str);
+    log_debug!("豆包响应 JSON: {}", json_str);
     
     let result: serde_json::Value = serde_json::from_str(&json_str)
         .map_err(|e| ASRError::InternalError(format!("JSON 解析失败: {}", e)))?;
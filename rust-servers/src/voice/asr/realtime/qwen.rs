@@ -15,7 +15,7 @@ use tokio_tungstenite::{
     WebSocketStream
 };
 
-use crate::voice::asr::{ASREngine, ASRError, ASRMode, RealtimeSession, RetryConfig};
+use crate::voice::asr::{ASREngine, ASRError, ASRMode, PartialResultCallback, RealtimeSession, RetryConfig};
 use crate::voice::audio::AudioData;
 
 const WEBSOCKET_URL: &str = "wss://dashscope.aliyuncs.com/api-ws/v1/realtime";
@@ -81,7 +81,7 @@ enum SessionCommand {
 pub struct QwenRealtimeSession {
     cmd_sender: mpsc::Sender<SessionCommand>,
     result_receiver: Option<oneshot::Receiver<Result<String, ASRError>>>,
-    partial_callback: Option<Arc<Mutex<Box<dyn Fn(&str) + Send + 'static>>>>,
+    partial_callback: Option<Arc<Mutex<PartialResultCallback>>>,
     #[allow(dead_code)]
     partial_sender: mpsc::Sender<String>,
 }
@@ -89,7 +89,7 @@ pub struct QwenRealtimeSession {
 impl QwenRealtimeSession {
     async fn connect(api_key: String, model: String) -> Result<Self, ASRError> {
         let url = format!("{}?model={}", WEBSOCKET_URL, model);
-        eprintln!("[INFO] 创建 Qwen Realtime WebSocket 连接: {}", url);
+        log_info!("创建 Qwen Realtime WebSocket 连接: {}", url);
         
         let request = http::Request::builder()
             .uri(&url)
@@ -106,7 +106,7 @@ impl QwenRealtimeSession {
         let (ws_stream, _) = connect_async(request).await
             .map_err(|e| ASRError::WebSocketError(format!("WebSocket 连接失败: {}", e)))?;
         
-        eprintln!("[INFO] Qwen Realtime WebSocket 连接成功");
+        log_info!("Qwen Realtime WebSocket 连接成功");
         
         let (mut write, mut read) = ws_stream.split();
         
@@ -127,7 +127,7 @@ impl QwenRealtimeSession {
         write.send(Message::Text(session_update.to_string().into())).await
             .map_err(|e| ASRError::WebSocketError(format!("发送 session.update 失败: {}", e)))?;
         
-        eprintln!("[INFO] 已发送 session.update 配置");
+        log_info!("已发送 session.update 配置");
         
         let (cmd_tx, mut cmd_rx) = mpsc::channel::<SessionCommand>(100);
         let (result_tx, result_rx) = oneshot::channel::<Result<String, ASRError>>();
@@ -149,7 +149,7 @@ impl QwenRealtimeSession {
                         
                         let mut w = write_clone.lock().await;
                         if let Err(e) = w.send(Message::Text(event.to_string().into())).await {
-                            eprintln!("[ERROR] 发送音频块失败: {}", e);
+                            log_error!("发送音频块失败: {}", e);
                             break;
                         }
                     }
@@ -161,9 +161,9 @@ impl QwenRealtimeSession {
                         
                         let mut w = write_clone.lock().await;
                         if let Err(e) = w.send(Message::Text(event.to_string().into())).await {
-                            eprintln!("[ERROR] 发送 commit 失败: {}", e);
+                            log_error!("发送 commit 失败: {}", e);
                         }
-                        eprintln!("[INFO] 已发送 input_audio_buffer.commit");
+                        log_info!("已发送 input_audio_buffer.commit");
                     }
                     SessionCommand::Close => {
                         let mut w = write_clone.lock().await;
@@ -189,16 +189,16 @@ impl QwenRealtimeSession {
                                 
                                 match event_type {
                                     "session.created" | "session.updated" => {
-                                        eprintln!("[INFO] 会话已创建/更新");
+                                        log_info!("会话已创建/更新");
                                     }
                                     "input_audio_buffer.committed" => {
-                                        eprintln!("[INFO] 音频缓冲区已提交");
+                                        log_info!("音频缓冲区已提交");
                                     }
                                     "conversation.item.input_audio_transcription.completed" => {
                                         if let Some(transcript) = data["transcript"].as_str() {
                                             final_text = transcript.to_string();
                                             has_result = true;
-                                            eprintln!("[INFO] 转录完成: {}", final_text);
+                                            log_info!("转录完成: {}", final_text);
                                         }
                                     }
                                     "response.audio_transcript.delta" => {
@@ -212,7 +212,7 @@ impl QwenRealtimeSession {
                                             final_text = transcript.to_string();
                                         }
                                         has_result = true;
-                                        eprintln!("[INFO] 转录完成: {}", final_text);
+                                        log_info!("转录完成: {}", final_text);
                                     }
                                     "response.done" => {
                                         has_result = true;
@@ -221,7 +221,7 @@ impl QwenRealtimeSession {
                                         let error_msg = data["error"]["message"]
                                             .as_str()
                                             .unwrap_or("未知错误");
-                                        eprintln!("[ERROR] API 错误: {}", error_msg);
+                                        log_error!("API 错误: {}", error_msg);
                                         if let Some(tx) = result_tx.take() {
                                             let _ = tx.send(Err(ASRError::WebSocketError(
                                                 format!("API 错误: {}", error_msg)
@@ -233,16 +233,16 @@ impl QwenRealtimeSession {
                                 }
                             }
                             Err(e) => {
-                                eprintln!("[WARN] 解析消息失败: {}", e);
+                                log_warn!("解析消息失败: {}", e);
                             }
                         }
                     }
                     Ok(Message::Close(_)) => {
-                        eprintln!("[INFO] WebSocket 连接关闭");
+                        log_info!("WebSocket 连接关闭");
                         break;
                     }
                     Err(e) => {
-                        eprintln!("[ERROR] WebSocket 错误: {}", e);
+                        log_error!("WebSocket 错误: {}", e);
                         if let Some(tx) = result_tx.take() {
                             let _ = tx.send(Err(ASRError::WebSocketError(
                                 format!("WebSocket 错误: {}", e)
@@ -269,7 +269,7 @@ impl QwenRealtimeSession {
             }
         });
         
-        let partial_callback: Option<Arc<Mutex<Box<dyn Fn(&str) + Send + 'static>>>> = None;
+        let partial_callback: Option<Arc<Mutex<PartialResultCallback>>> = None;
         let partial_callback_clone = partial_callback.clone();
         tokio::spawn(async move {
             while let Some(text) = partial_rx.recv().await {
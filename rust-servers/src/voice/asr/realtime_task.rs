@@ -4,34 +4,10 @@
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex, oneshot};
 
-use crate::voice::asr::{ASRError, TranscriptionResult, create_engine};
+use crate::voice::asr::{ASRError, RetryConfig, TranscriptionResult, create_engine};
 use crate::voice::audio::streaming::AudioChunkData;
 use crate::voice::config::ASRProviderConfig;
 
-macro_rules! log_info {
-    ($($arg:tt)*) => {
-        eprintln!("[INFO] [realtime_task] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        eprintln!("[DEBUG] [realtime_task] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_warn {
-    ($($arg:tt)*) => {
-        eprintln!("[WARN] [realtime_task] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_error {
-    ($($arg:tt)*) => {
-        eprintln!("[ERROR] [realtime_task] {}", format!($($arg)*));
-    };
-}
-
 /// 实时转录任务结果
 #[derive(Debug)]
 pub enum RealtimeTaskResult {
@@ -70,6 +46,7 @@ pub type PartialResultCallback = Box<dyn Fn(&str) + Send + 'static>;
 /// 实时转录任务
 pub struct RealtimeTranscriptionTask {
     asr_config: ASRProviderConfig,
+    retry_config: RetryConfig,
     chunk_receiver: mpsc::Receiver<AudioChunkData>,
     partial_callback: Arc<Mutex<Option<PartialResultCallback>>>,
     stop_receiver: Option<oneshot::Receiver<()>>,
@@ -78,6 +55,7 @@ pub struct RealtimeTranscriptionTask {
 impl RealtimeTranscriptionTask {
     pub fn new(
         asr_config: ASRProviderConfig,
+        retry_config: RetryConfig,
         chunk_receiver: mpsc::Receiver<AudioChunkData>,
         partial_callback: Option<PartialResultCallback>,
     ) -> (Self, oneshot::Sender<()>) {
@@ -85,6 +63,7 @@ impl RealtimeTranscriptionTask {
         
         let task = Self {
             asr_config,
+            retry_config,
             chunk_receiver,
             partial_callback: Arc::new(Mutex::new(partial_callback)),
             stop_receiver: Some(stop_rx),
@@ -118,7 +97,7 @@ impl RealtimeTranscriptionTask {
             self.asr_config.mode
         );
         
-        let engine = match create_engine(&self.asr_config) {
+        let engine = match create_engine(&self.asr_config, &self.retry_config) {
             Ok(e) => e,
             Err(e) => {
                 log_error!("创建 ASR 引擎失败: {}", e);
@@ -213,7 +192,7 @@ impl RealtimeTranscriptionTask {
                                 }
                             }
                             
-                            if chunk_count % 10 == 0 {
+                            if chunk_count.is_multiple_of(10) {
                                 log_debug!(
                                     "已发送 {} 个音频块，共 {} 样本",
                                     chunk_count,
@@ -1,32 +1,6 @@
 // 音频录制模块
 // 使用 cpal 实现跨平台音频采集，支持 Press/Toggle 录音模式
 
-macro_rules! log_info {
-    ($($arg:tt)*) => {
-        eprintln!("[INFO] [recorder] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        if cfg!(debug_assertions) {
-            eprintln!("[DEBUG] [recorder] {}", format!($($arg)*));
-        }
-    };
-}
-
-macro_rules! log_warn {
-    ($($arg:tt)*) => {
-        eprintln!("[WARN] [recorder] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_error {
-    ($($arg:tt)*) => {{
-        eprintln!("[ERROR] [recorder] {}", format!($($arg)*))
-    }};
-}
-
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::Stream;
 use std::sync::{Arc, Mutex};
@@ -246,6 +220,7 @@ impl AudioRecorder {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn handle_audio_callback(
         data: &[f32],
         audio_data: &Arc<Mutex<Vec<f32>>>,
@@ -265,7 +240,7 @@ impl AudioRecorder {
         let mut counter = callback_counter.lock().unwrap();
         *counter += 1;
 
-        if *counter % 2 == 0 {
+        if (*counter).is_multiple_of(2) {
             let raw_level = utils::calculate_rms(data);
             let mut current_smoothed = smoothed_level.lock().unwrap();
             *current_smoothed = utils::smooth_level(*current_smoothed, raw_level);
@@ -1,24 +1,6 @@
 // 流式音频录制模块
 // 支持边录音边发送 PCM 数据块，用于实时 ASR
 
-macro_rules! log_info {
-    ($($arg:tt)*) => {
-        eprintln!("[INFO] [streaming] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_warn {
-    ($($arg:tt)*) => {
-        eprintln!("[WARN] [streaming] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_error {
-    ($($arg:tt)*) => {{
-        eprintln!("[ERROR] [streaming] {}", format!($($arg)*))
-    }};
-}
-
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::Stream;
 use std::sync::{Arc, Mutex};
@@ -283,7 +265,7 @@ impl StreamingRecorder {
             let mut counter = callback_counter.lock().unwrap();
             *counter += 1;
 
-            if *counter % 2 == 0 {
+            if (*counter).is_multiple_of(2) {
                 let raw_level = utils::calculate_rms(&resampled);
                 let mut current_smoothed = smoothed_level.lock().unwrap();
                 *current_smoothed = utils::smooth_level(*current_smoothed, raw_level);
@@ -25,7 +25,7 @@ pub fn calculate_rms(samples: &[f32]) -> f32 {
     let normalized = (amplified * 3.0).min(1.0);
 
     if normalized > 0.0 {
-        ((normalized.ln() + 4.0) / 4.0).max(0.0).min(1.0)
+        ((normalized.ln() + 4.0) / 4.0).clamp(0.0, 1.0)
     } else {
         0.0
     }
@@ -6,21 +6,6 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
-/// 日志宏
-macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        if cfg!(debug_assertions) {
-            eprintln!("[DEBUG] [beep] {}", format!($($arg)*));
-        }
-    };
-}
-
-macro_rules! log_error {
-    ($($arg:tt)*) => {
-        eprintln!("[ERROR] [beep] {}", format!($($arg)*));
-    };
-}
-
 /// 提示音类型
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BeepType {
@@ -118,7 +103,7 @@ fn play_beep_blocking(beep_type: BeepType, volume: f32) -> Result<(), BeepError>
         .map_err(|e| BeepError::OutputStreamError(e.to_string()))?;
     
     let mixer = stream.mixer();
-    let sink = Sink::connect_new(&mixer);
+    let sink = Sink::connect_new(mixer);
 
     // 根据提示音类型生成不同的音调
     let source = match beep_type {
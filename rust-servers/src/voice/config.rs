@@ -1,10 +1,14 @@
 // 配置管理模块
 // 定义 ASR 供应商配置和相关数据结构
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::error::{ErrorCode, ServerError};
+use crate::i18n::Msg;
+
 /// ASR 供应商类型
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum ASRProvider {
     /// 阿里云 Qwen
@@ -16,6 +20,20 @@ pub enum ASRProvider {
     SenseVoice,
 }
 
+impl ASRProvider {
+    /// 所有支持的 ASR 供应商
+    pub const ALL: [ASRProvider; 3] = [ASRProvider::Qwen, ASRProvider::Doubao, ASRProvider::SenseVoice];
+    
+    /// 供应商支持的 ASR 模式
+    pub fn supported_modes(&self) -> &'static [ASRMode] {
+        match self {
+            ASRProvider::Qwen | ASRProvider::Doubao => &[ASRMode::Realtime, ASRMode::Http],
+            // SenseVoice 仅支持 HTTP 模式
+            ASRProvider::SenseVoice => &[ASRMode::Http],
+        }
+    }
+}
+
 impl std::fmt::Display for ASRProvider {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -27,7 +45,7 @@ impl std::fmt::Display for ASRProvider {
 }
 
 /// ASR 模式
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ASRMode {
     /// WebSocket 实时模式
@@ -46,7 +64,7 @@ impl std::fmt::Display for ASRMode {
 }
 
 /// ASR 供应商配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ASRProviderConfig {
     /// 供应商类型
     pub provider: ASRProvider,
@@ -113,37 +131,36 @@ impl ASRProviderConfig {
     pub fn validate(&self) -> Result<(), ConfigError> {
         match self.provider {
             ASRProvider::Qwen => {
-                if self.dashscope_api_key.as_ref().map_or(true, |k| k.is_empty()) {
+                if self.dashscope_api_key.as_ref().is_none_or(|k| k.is_empty()) {
                     return Err(ConfigError::MissingApiKey("dashscope_api_key".to_string()));
                 }
             }
             ASRProvider::Doubao => {
-                if self.app_id.as_ref().map_or(true, |k| k.is_empty()) {
+                if self.app_id.as_ref().is_none_or(|k| k.is_empty()) {
                     return Err(ConfigError::MissingApiKey("app_id".to_string()));
                 }
-                if self.access_token.as_ref().map_or(true, |k| k.is_empty()) {
+                if self.access_token.as_ref().is_none_or(|k| k.is_empty()) {
                     return Err(ConfigError::MissingApiKey("access_token".to_string()));
                 }
             }
             ASRProvider::SenseVoice => {
-                if self.siliconflow_api_key.as_ref().map_or(true, |k| k.is_empty()) {
+                if self.siliconflow_api_key.as_ref().is_none_or(|k| k.is_empty()) {
                     return Err(ConfigError::MissingApiKey("siliconflow_api_key".to_string()));
                 }
-                // SenseVoice 仅支持 HTTP 模式
-                if self.mode != ASRMode::Http {
-                    return Err(ConfigError::UnsupportedMode {
-                        provider: self.provider.to_string(),
-                        mode: self.mode.to_string(),
-                    });
-                }
             }
         }
+        if !self.provider.supported_modes().contains(&self.mode) {
+            return Err(ConfigError::UnsupportedMode {
+                provider: self.provider.to_string(),
+                mode: self.mode.to_string(),
+            });
+        }
         Ok(())
     }
 }
 
 /// 完整 ASR 配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ASRConfig {
     /// 主 ASR 引擎配置
     pub primary: ASRProviderConfig,
@@ -209,10 +226,37 @@ pub enum ConfigError {
     InvalidConfig(String),
 }
 
+impl From<ConfigError> for ServerError {
+    fn from(error: ConfigError) -> Self {
+        match error {
+            ConfigError::UnsupportedMode { provider, mode } => {
+                let msg = Msg::UnsupportedMode { provider: provider.clone(), mode };
+                ServerError::new(ErrorCode::InvalidConfig, msg).with_provider(provider)
+            }
+            ConfigError::MissingApiKey(key) => {
+                ServerError::new(ErrorCode::InvalidConfig, Msg::MissingApiKey { key })
+            }
+            ConfigError::InvalidConfig(detail) => {
+                ServerError::new(ErrorCode::InvalidConfig, Msg::InvalidConfig { detail })
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_config_error_code() {
+        let mut config = ASRProviderConfig::sensevoice("key".to_string());
+        config.mode = ASRMode::Realtime;
+        let error = ServerError::from(config.validate().unwrap_err());
+        assert_eq!(error.code, ErrorCode::InvalidConfig);
+        assert_eq!(error.details.provider.as_deref(), Some("sensevoice"));
+        assert!(!error.retryable);
+    }
+
     #[test]
     fn test_qwen_config_validation() {
         let config = ASRProviderConfig::qwen(ASRMode::Realtime, "test-key".to_string());
@@ -6,45 +6,34 @@ pub mod asr;
 pub mod beep;
 pub mod config;
 
-use crate::router::{ModuleHandler, ModuleMessage, ModuleType, RouterError, ServerResponse};
+use crate::error::{ErrorCode, ServerError};
+use crate::i18n::{Msg, SharedLocale};
+use crate::metrics::{GaugeGuard, Metrics};
+use crate::protocol::{ModuleProtocol, Payload};
+use crate::router::{ModuleCapabilities, ModuleContext, ModuleHandler, ModuleMessage, ModuleType, RequestId, RouterError, ServerResponse};
+use crate::outbound::Priority;
 use crate::server::WsSender;
-use futures_util::SinkExt;
-use std::time::Instant;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, oneshot, Mutex as TokioMutex};
 use tokio::task::JoinHandle;
 
 use audio::{AudioRecorder, RecordingMode as AudioRecordingMode, StreamingRecorder, AudioData};
-use asr::{ParallelFallbackStrategy, TranscriptionResult, ASRError, RealtimeTaskResult, RealtimeTranscriptionTask};
+use asr::{ParallelFallbackStrategy, TranscriptionResult, ASRError, PartialResultCallback, RealtimeTaskResult, RealtimeTranscriptionTask, RetryConfig};
 use beep::BeepPlayer;
-use config::{ASRConfig, ASRMode};
+use config::{ASRConfig, ASRMode, ASRProvider};
 
-/// 日志宏
-macro_rules! log_info {
-    ($($arg:tt)*) => {
-        eprintln!("[INFO] [Voice] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_error {
-    ($($arg:tt)*) => {
-        eprintln!("[ERROR] [Voice] {}", format!($($arg)*));
-    };
-}
-
-macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        if cfg!(debug_assertions) {
-            eprintln!("[DEBUG] [Voice] {}", format!($($arg)*));
-        }
-    };
-}
+/// 音量/波形消息的最长排队时间，超过后丢弃 (界面只需要最新的波形)
+const AUDIO_LEVEL_MAX_AGE: Duration = Duration::from_millis(200);
 
 // ============================================================================
 // 录音模式
 // ============================================================================
 
 /// 录音模式
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum RecordingMode {
     Press,  // 按住录音
@@ -65,7 +54,7 @@ impl From<RecordingMode> for AudioRecordingMode {
 // ============================================================================
 
 /// 录音状态
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum RecordingState {
     Started,
@@ -74,14 +63,112 @@ pub enum RecordingState {
 }
 
 // ============================================================================
-// 音频级别数据
+// 消息类型定义
 // ============================================================================
 
-/// 音频级别数据 (用于通过 channel 传递)
-#[derive(Debug, Clone)]
-struct AudioLevelData {
-    level: f32,
-    waveform: Vec<f32>,
+/// start_recording 请求
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct StartRecordingRequest {
+    /// 录音模式
+    pub mode: RecordingMode,
+    /// ASR 配置
+    pub asr_config: ASRConfig,
+}
+
+impl Payload for StartRecordingRequest {
+    const TYPE: &'static str = "start_recording";
+}
+
+/// stop_recording 请求: 停止录音并转录
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct StopRecordingRequest {}
+
+impl Payload for StopRecordingRequest {
+    const TYPE: &'static str = "stop_recording";
+}
+
+/// cancel_recording 请求: 取消录音，不转录
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CancelRecordingRequest {}
+
+impl Payload for CancelRecordingRequest {
+    const TYPE: &'static str = "cancel_recording";
+}
+
+/// update_config 请求: 更新 ASR 配置
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UpdateConfigRequest {
+    /// ASR 配置
+    pub asr_config: ASRConfig,
+}
+
+impl Payload for UpdateConfigRequest {
+    const TYPE: &'static str = "update_config";
+}
+
+/// 录音状态变化
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RecordingStateMessage {
+    /// 新的录音状态
+    pub state: RecordingState,
+}
+
+impl Payload for RecordingStateMessage {
+    const TYPE: &'static str = "recording_state";
+}
+
+/// 音量和波形 (录音期间持续推送，也用于通过 channel 传递)
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct AudioLevelMessage {
+    /// 当前音量 (0.0 - 1.0)
+    pub level: f32,
+    /// 波形采样
+    pub waveform: Vec<f32>,
+}
+
+impl Payload for AudioLevelMessage {
+    const TYPE: &'static str = "audio_level";
+}
+
+/// 实时转录的部分结果
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TranscriptionProgressMessage {
+    /// 目前为止识别的文本
+    pub partial_text: String,
+}
+
+impl Payload for TranscriptionProgressMessage {
+    const TYPE: &'static str = "transcription_progress";
+}
+
+/// 转录结果
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TranscriptionCompleteMessage {
+    /// 转录文本
+    pub text: String,
+    /// 使用的引擎
+    pub engine: String,
+    /// 是否使用了备用引擎
+    pub used_fallback: bool,
+    /// 转录耗时 (毫秒)
+    pub duration_ms: u64,
+}
+
+impl Payload for TranscriptionCompleteMessage {
+    const TYPE: &'static str = "transcription_complete";
+}
+
+/// Voice 模块的请求和服务器消息
+pub fn protocol() -> ModuleProtocol {
+    ModuleProtocol::new()
+        .request::<StartRecordingRequest>()
+        .request::<StopRecordingRequest>()
+        .request::<CancelRecordingRequest>()
+        .request::<UpdateConfigRequest>()
+        .event::<RecordingStateMessage>()
+        .event::<AudioLevelMessage>()
+        .event::<TranscriptionProgressMessage>()
+        .event::<TranscriptionCompleteMessage>()
 }
 
 // ============================================================================
@@ -96,6 +183,8 @@ struct ConnectionState {
     is_recording: bool,
     /// 录音模式
     recording_mode: Option<RecordingMode>,
+    /// 进行中录音的计数守卫 (诊断信息)
+    recording_guard: Option<GaugeGuard>,
     /// 录音开始时间
     recording_start_time: Option<Instant>,
     /// 音频录制器 (HTTP 模式)
@@ -109,7 +198,7 @@ struct ConnectionState {
     /// 提示音播放器
     beep_player: BeepPlayer,
     /// 音频级别发送器
-    audio_level_tx: Option<mpsc::UnboundedSender<AudioLevelData>>,
+    audio_level_tx: Option<mpsc::UnboundedSender<AudioLevelMessage>>,
 }
 
 impl ConnectionState {
@@ -118,6 +207,7 @@ impl ConnectionState {
             asr_config: None,
             is_recording: false,
             recording_mode: None,
+            recording_guard: None,
             recording_start_time: None,
             recorder: None,
             streaming_recorder: None,
@@ -141,14 +231,28 @@ pub struct VoiceHandler {
     state: TokioMutex<ConnectionState>,
     /// WebSocket 发送器
     ws_sender: TokioMutex<Option<WsSender>>,
+    /// 连接语言 (渲染主动推送的错误消息)
+    locale: TokioMutex<SharedLocale>,
+    /// ASR 请求重试配置
+    retry_config: RetryConfig,
+    /// 运行指标 (服务器范围共享)
+    metrics: Arc<Metrics>,
 }
 
 impl VoiceHandler {
     /// 创建新的 Voice 处理器
     pub fn new() -> Self {
+        Self::with_config(RetryConfig::default(), Arc::default())
+    }
+    
+    /// 使用指定的 ASR 重试配置和运行指标创建 Voice 处理器
+    pub fn with_config(retry_config: RetryConfig, metrics: Arc<Metrics>) -> Self {
         Self {
             state: TokioMutex::new(ConnectionState::new()),
             ws_sender: TokioMutex::new(None),
+            locale: TokioMutex::new(SharedLocale::default()),
+            retry_config,
+            metrics,
         }
     }
     
@@ -158,36 +262,58 @@ impl VoiceHandler {
         *ws_sender = Some(sender);
     }
     
+    /// 设置连接语言
+    pub async fn set_locale(&self, locale: SharedLocale) {
+        *self.locale.lock().await = locale;
+    }
+    
     /// 发送消息给客户端
-    async fn send_message(&self, msg_type: &str, payload: serde_json::Value) -> Result<(), RouterError> {
+    /// 
+    /// `id` 为触发该消息的请求 ID，设置时原样回传
+    async fn send_event<P: Payload + Serialize>(
+        &self,
+        id: Option<&RequestId>,
+        payload: &P,
+    ) -> Result<(), RouterError> {
         let ws_sender = self.ws_sender.lock().await;
         if let Some(ref sender) = *ws_sender {
-            let response = serde_json::json!({
-                "module": "voice",
-                "type": msg_type,
-            });
-            
-            // 合并 payload 到 response
-            let mut response = response.as_object().unwrap().clone();
-            if let serde_json::Value::Object(payload_obj) = payload {
-                for (k, v) in payload_obj {
-                    response.insert(k, v);
-                }
-            }
-            
-            let json = serde_json::to_string(&response)
-                .map_err(|e| RouterError::ModuleError(format!("JSON 序列化失败: {}", e)))?;
+            let json = encode_event(id, payload)
+                .map_err(|e| RouterError::module(Msg::SerializeFailed { error: e.to_string() }))?;
             
-            let mut sender = sender.lock().await;
-            sender.send(tokio_tungstenite::tungstenite::Message::Text(json.into())).await
-                .map_err(|e| RouterError::ModuleError(format!("发送消息失败: {}", e)))?;
+            // 与转录进度使用同一队列，保证录音状态和结果不会先于进度到达
+            sender.send_text(Priority::Stream, json).await
+                .map_err(|e| RouterError::module(Msg::SendFailed { error: e.to_string() }))?;
         }
         Ok(())
     }
+    
+    /// 发送错误消息给客户端 (按连接语言渲染)
+    async fn send_error(&self, id: Option<&RequestId>, error: &ServerError) -> Result<(), RouterError> {
+        self.metrics.record_error(&ModuleType::VOICE, error.code);
+        let locale = self.locale.lock().await.get();
+        self.send_event(id, &error.to_payload(locale)).await
+    }
+
+    /// 发送转录结果并记录转录延迟
+    async fn send_transcription(
+        &self,
+        id: Option<&RequestId>,
+        result: &TranscriptionResult,
+        used_fallback: bool,
+    ) -> Result<(), RouterError> {
+        self.metrics.record_asr_latency(&result.engine, Duration::from_millis(result.duration_ms));
+        self.send_event(id, &TranscriptionCompleteMessage {
+            text: result.text.clone(),
+            engine: result.engine.clone(),
+            used_fallback,
+            duration_ms: result.duration_ms,
+        }).await
+    }
 
     /// 处理开始录音命令
     async fn handle_start_recording(
         &self,
+        id: Option<RequestId>,
         mode: RecordingMode,
         asr_config: ASRConfig,
     ) -> Result<Option<ServerResponse>, RouterError> {
@@ -197,20 +323,24 @@ impl VoiceHandler {
         
         // 检查是否已在录音
         if state.is_recording {
-            return Err(RouterError::ModuleError("已在录音中".to_string()));
+            return Err(ServerError::new(ErrorCode::InvalidState, Msg::AlreadyRecording).into());
         }
         
+        // 配置不完整时立即拒绝，避免录音结束后才报错
+        asr_config.validate().map_err(ServerError::from)?;
+        
         // 更新状态
         state.asr_config = Some(asr_config.clone());
         state.is_recording = true;
         state.recording_mode = Some(mode.clone());
+        state.recording_guard = Some(self.metrics.recordings.enter());
         state.recording_start_time = Some(Instant::now());
         
         // 根据配置设置音频反馈
         state.beep_player.set_enabled(asr_config.enable_audio_feedback);
         
         // 创建音频级别 channel
-        let (audio_level_tx, mut audio_level_rx) = mpsc::unbounded_channel::<AudioLevelData>();
+        let (audio_level_tx, mut audio_level_rx) = mpsc::unbounded_channel::<AudioLevelMessage>();
         state.audio_level_tx = Some(audio_level_tx.clone());
         
         // 根据 ASR 模式选择录音器
@@ -221,37 +351,31 @@ impl VoiceHandler {
             
             // 创建流式录音器
             let mut streaming_recorder = StreamingRecorder::new()
-                .map_err(|e| RouterError::ModuleError(format!("创建流式录音器失败: {}", e)))?;
+                .map_err(|e| ServerError::new(ErrorCode::AudioDeviceError, Msg::CreateRecorderFailed { error: e.to_string() }))?;
             
             // 设置音频级别回调
             let tx = audio_level_tx.clone();
             streaming_recorder.set_level_callback(move |level, waveform| {
-                let _ = tx.send(AudioLevelData { level, waveform });
+                let _ = tx.send(AudioLevelMessage { level, waveform });
             });
             
             // 启动流式录音，获取音频块接收通道
             let chunk_rx = streaming_recorder.start_streaming(mode.clone().into())
-                .map_err(|e| RouterError::ModuleError(format!("启动流式录音失败: {}", e)))?;
+                .map_err(|e| ServerError::new(ErrorCode::AudioDeviceError, Msg::StartRecordingFailed { error: e.to_string() }))?;
             
             // 创建实时转录任务
             let primary_config = asr_config.primary.clone();
             let ws_sender = self.ws_sender.lock().await.clone();
             
             // 创建部分结果回调
-            let partial_callback: Option<Box<dyn Fn(&str) + Send + 'static>> = if let Some(sender) = ws_sender.clone() {
+            let partial_callback: Option<PartialResultCallback> = if let Some(sender) = ws_sender.clone() {
+                let id = id.clone();
                 Some(Box::new(move |text: &str| {
-                    let text_owned = text.to_string();
-                    let sender = sender.clone();
-                    tokio::spawn(async move {
-                        let msg = serde_json::json!({
-                            "module": "voice",
-                            "type": "transcription_progress",
-                            "partial_text": text_owned,
-                        });
-                        let json = serde_json::to_string(&msg).unwrap();
-                        let mut s = sender.lock().await;
-                        let _ = s.send(tokio_tungstenite::tungstenite::Message::Text(json.into())).await;
-                    });
+                    let progress = TranscriptionProgressMessage { partial_text: text.to_string() };
+                    // 回调不能等待，队列满时丢弃这条进度 (后续进度包含完整文本)
+                    if let Ok(json) = encode_event(id.as_ref(), &progress) {
+                        let _ = sender.try_send_text(Priority::Stream, json);
+                    }
                 }))
             } else {
                 None
@@ -260,6 +384,7 @@ impl VoiceHandler {
             // 创建实时转录任务
             let (task, stop_tx) = RealtimeTranscriptionTask::new(
                 primary_config,
+                self.retry_config.clone(),
                 chunk_rx,
                 partial_callback,
             );
@@ -278,17 +403,17 @@ impl VoiceHandler {
             
             // 创建普通录音器
             let mut recorder = AudioRecorder::new()
-                .map_err(|e| RouterError::ModuleError(format!("创建录音器失败: {}", e)))?;
+                .map_err(|e| ServerError::new(ErrorCode::AudioDeviceError, Msg::CreateRecorderFailed { error: e.to_string() }))?;
             
             // 设置音频级别回调
             let tx = audio_level_tx.clone();
             recorder.set_level_callback(move |level, waveform| {
-                let _ = tx.send(AudioLevelData { level, waveform });
+                let _ = tx.send(AudioLevelMessage { level, waveform });
             });
             
             // 启动录音
             recorder.start(mode.clone().into())
-                .map_err(|e| RouterError::ModuleError(format!("启动录音失败: {}", e)))?;
+                .map_err(|e| ServerError::new(ErrorCode::AudioDeviceError, Msg::StartRecordingFailed { error: e.to_string() }))?;
             
             state.recorder = Some(recorder);
         }
@@ -301,17 +426,14 @@ impl VoiceHandler {
         // 启动音频级别转发任务
         let ws_sender = self.ws_sender.lock().await.clone();
         if let Some(sender) = ws_sender {
+            let id = id.clone();
             tokio::spawn(async move {
                 while let Some(data) = audio_level_rx.recv().await {
-                    let msg = serde_json::json!({
-                        "module": "voice",
-                        "type": "audio_level",
-                        "level": data.level,
-                        "waveform": data.waveform,
-                    });
-                    let json = serde_json::to_string(&msg).unwrap();
-                    let mut s = sender.lock().await;
-                    if s.send(tokio_tungstenite::tungstenite::Message::Text(json.into())).await.is_err() {
+                    let Ok(json) = encode_event(id.as_ref(), &data) else {
+                        continue;
+                    };
+                    // 只保留最新的音量/波形，过期的直接丢弃
+                    if sender.send_latest(AudioLevelMessage::TYPE, json, Some(AUDIO_LEVEL_MAX_AGE)).is_err() {
                         break;
                     }
                 }
@@ -319,22 +441,20 @@ impl VoiceHandler {
         }
         
         // 发送录音开始状态
-        self.send_message("recording_state", serde_json::json!({
-            "state": "started"
-        })).await?;
+        self.send_event(id.as_ref(), &RecordingStateMessage { state: RecordingState::Started }).await?;
         
         Ok(None)
     }
 
     /// 处理停止录音命令
-    async fn handle_stop_recording(&self) -> Result<Option<ServerResponse>, RouterError> {
+    async fn handle_stop_recording(&self, id: Option<RequestId>) -> Result<Option<ServerResponse>, RouterError> {
         log_info!("收到停止录音命令");
         
         let mut state = self.state.lock().await;
         
         // 检查是否在录音
         if !state.is_recording {
-            return Err(RouterError::ModuleError("未在录音中".to_string()));
+            return Err(ServerError::new(ErrorCode::InvalidState, Msg::NotRecording).into());
         }
         
         // 播放结束提示音
@@ -345,7 +465,7 @@ impl VoiceHandler {
         
         // 获取 ASR 配置
         let asr_config = state.asr_config.clone()
-            .ok_or_else(|| RouterError::ModuleError("ASR 配置未设置".to_string()))?;
+            .ok_or_else(|| RouterError::module(Msg::AsrConfigMissing))?;
         
         // 检查是否是 realtime 模式
         let is_realtime_mode = state.streaming_recorder.is_some();
@@ -362,9 +482,9 @@ impl VoiceHandler {
             // 停止流式录音并获取完整音频数据 (用于回退)
             let audio_data = if let Some(ref mut streaming_recorder) = state.streaming_recorder {
                 streaming_recorder.stop_streaming()
-                    .map_err(|e| RouterError::ModuleError(format!("停止流式录音失败: {}", e)))?
+                    .map_err(|e| ServerError::new(ErrorCode::AudioDeviceError, Msg::StopRecordingFailed { error: e.to_string() }))?
             } else {
-                return Err(RouterError::ModuleError("流式录音器未初始化".to_string()));
+                return Err(RouterError::module(Msg::RecorderNotInitialized));
             };
             
             // 获取实时转录任务句柄
@@ -373,13 +493,12 @@ impl VoiceHandler {
             // 更新状态
             state.is_recording = false;
             state.recording_mode = None;
+            state.recording_guard = None;
             state.streaming_recorder = None;
             drop(state);
             
             // 发送录音停止状态
-            self.send_message("recording_state", serde_json::json!({
-                "state": "stopped"
-            })).await?;
+            self.send_event(id.as_ref(), &RecordingStateMessage { state: RecordingState::Stopped }).await?;
             
             // 等待实时转录任务完成
             let realtime_result = if let Some(task_handle) = realtime_task {
@@ -406,18 +525,13 @@ impl VoiceHandler {
                         &result.text
                     );
                     
-                    self.send_message("transcription_complete", serde_json::json!({
-                        "text": result.text,
-                        "engine": result.engine,
-                        "used_fallback": false,
-                        "duration_ms": result.duration_ms,
-                    })).await?;
+                    self.send_transcription(id.as_ref(), &result, false).await?;
                 }
                 Some(RealtimeTaskResult::Failed { error, engine_name, .. }) => {
                     log_error!("实时转录失败 ({}): {}，尝试回退到 HTTP 模式", engine_name, error);
                     
                     // 回退到 HTTP 模式
-                    let fallback_result = perform_fallback_transcription(&audio_data, &asr_config).await;
+                    let fallback_result = perform_fallback_transcription(&audio_data, &asr_config, &self.retry_config).await;
                     
                     match fallback_result {
                         Ok(result) => {
@@ -428,23 +542,17 @@ impl VoiceHandler {
                                 &result.text
                             );
                             
-                            self.send_message("transcription_complete", serde_json::json!({
-                                "text": result.text,
-                                "engine": result.engine,
-                                "used_fallback": true,
-                                "duration_ms": result.duration_ms,
-                            })).await?;
+                            self.send_transcription(id.as_ref(), &result, true).await?;
                         }
                         Err(fallback_error) => {
                             log_error!("HTTP 回退也失败: {}", fallback_error);
                             
-                            self.send_message("error", serde_json::json!({
-                                "code": "TRANSCRIPTION_FAILED",
-                                "message": format!(
-                                    "实时转录失败: {}; HTTP 回退也失败: {}",
-                                    error, fallback_error
-                                ),
-                            })).await?;
+                            let fallback = ServerError::from(fallback_error);
+                            let message = Msg::RealtimeFallbackFailed {
+                                realtime: Box::new(ServerError::from(error).message),
+                                fallback: Box::new(fallback.message.clone()),
+                            };
+                            self.send_error(id.as_ref(), &fallback.with_message(message)).await?;
                         }
                     }
                 }
@@ -452,7 +560,7 @@ impl VoiceHandler {
                     log_error!("实时转录任务异常，尝试回退到 HTTP 模式");
                     
                     // 回退到 HTTP 模式
-                    let fallback_result = perform_fallback_transcription(&audio_data, &asr_config).await;
+                    let fallback_result = perform_fallback_transcription(&audio_data, &asr_config, &self.retry_config).await;
                     
                     match fallback_result {
                         Ok(result) => {
@@ -463,23 +571,16 @@ impl VoiceHandler {
                                 &result.text
                             );
                             
-                            self.send_message("transcription_complete", serde_json::json!({
-                                "text": result.text,
-                                "engine": result.engine,
-                                "used_fallback": true,
-                                "duration_ms": result.duration_ms,
-                            })).await?;
+                            self.send_transcription(id.as_ref(), &result, true).await?;
                         }
                         Err(fallback_error) => {
                             log_error!("HTTP 回退也失败: {}", fallback_error);
                             
-                            self.send_message("error", serde_json::json!({
-                                "code": "TRANSCRIPTION_FAILED",
-                                "message": format!(
-                                    "实时转录任务异常; HTTP 回退也失败: {}",
-                                    fallback_error
-                                ),
-                            })).await?;
+                            let fallback = ServerError::from(fallback_error);
+                            let message = Msg::RealtimeTaskFallbackFailed {
+                                fallback: Box::new(fallback.message.clone()),
+                            };
+                            self.send_error(id.as_ref(), &fallback.with_message(message)).await?;
                         }
                     }
                 }
@@ -490,38 +591,37 @@ impl VoiceHandler {
             
             // 停止录音并获取音频数据
             let audio_data = if let Some(ref mut recorder) = state.recorder {
-                recorder.stop().map_err(|e| RouterError::ModuleError(format!("停止录音失败: {}", e)))?
+                recorder.stop().map_err(|e| ServerError::new(ErrorCode::AudioDeviceError, Msg::StopRecordingFailed { error: e.to_string() }))?
             } else {
-                return Err(RouterError::ModuleError("录音器未初始化".to_string()));
+                return Err(RouterError::module(Msg::RecorderNotInitialized));
             };
             
             // 更新状态
             state.is_recording = false;
             state.recording_mode = None;
+            state.recording_guard = None;
             state.recorder = None;
             drop(state);
             
             // 发送录音停止状态
-            self.send_message("recording_state", serde_json::json!({
-                "state": "stopped"
-            })).await?;
+            self.send_event(id.as_ref(), &RecordingStateMessage { state: RecordingState::Stopped }).await?;
             
             // 检查音频数据是否为空
             if audio_data.is_empty() {
                 log_info!("录音数据为空，跳过转录");
-                self.send_message("transcription_complete", serde_json::json!({
-                    "text": "",
-                    "engine": "none",
-                    "used_fallback": false,
-                    "duration_ms": 0,
-                })).await?;
+                self.send_event(id.as_ref(), &TranscriptionCompleteMessage {
+                    text: String::new(),
+                    engine: "none".to_string(),
+                    used_fallback: false,
+                    duration_ms: 0,
+                }).await?;
                 return Ok(None);
             }
             
             log_info!("开始 ASR 转录，音频时长: {}ms", audio_data.duration_ms);
             
             // 执行 ASR 转录
-            let transcription_result = perform_transcription(&audio_data, &asr_config).await;
+            let transcription_result = perform_transcription(&audio_data, &asr_config, &self.retry_config).await;
             
             match transcription_result {
                 Ok(result) => {
@@ -533,20 +633,12 @@ impl VoiceHandler {
                         &result.text
                     );
                     
-                    self.send_message("transcription_complete", serde_json::json!({
-                        "text": result.text,
-                        "engine": result.engine,
-                        "used_fallback": result.used_fallback,
-                        "duration_ms": result.duration_ms,
-                    })).await?;
+                    self.send_transcription(id.as_ref(), &result, result.used_fallback).await?;
                 }
                 Err(e) => {
                     log_error!("转录失败: {}", e);
                     
-                    self.send_message("error", serde_json::json!({
-                        "code": "TRANSCRIPTION_FAILED",
-                        "message": e.to_string(),
-                    })).await?;
+                    self.send_error(id.as_ref(), &ServerError::from(e)).await?;
                 }
             }
         }
@@ -555,14 +647,14 @@ impl VoiceHandler {
     }
 
     /// 处理取消录音命令
-    async fn handle_cancel_recording(&self) -> Result<Option<ServerResponse>, RouterError> {
+    async fn handle_cancel_recording(&self, id: Option<RequestId>) -> Result<Option<ServerResponse>, RouterError> {
         log_info!("收到取消录音命令");
         
         let mut state = self.state.lock().await;
         
         // 检查是否在录音
         if !state.is_recording {
-            return Err(RouterError::ModuleError("未在录音中".to_string()));
+            return Err(ServerError::new(ErrorCode::InvalidState, Msg::NotRecording).into());
         }
         
         // 关闭音频级别 channel
@@ -602,12 +694,11 @@ impl VoiceHandler {
         // 更新状态
         state.is_recording = false;
         state.recording_mode = None;
+        state.recording_guard = None;
         drop(state);
         
         // 发送录音取消状态
-        self.send_message("recording_state", serde_json::json!({
-            "state": "cancelled"
-        })).await?;
+        self.send_event(id.as_ref(), &RecordingStateMessage { state: RecordingState::Cancelled }).await?;
         
         Ok(None)
     }
@@ -629,37 +720,11 @@ impl VoiceHandler {
         let state = self.state.lock().await;
         state.is_recording
     }
-    
-    /// 清理资源
-    pub async fn cleanup(&self) {
-        let mut state = self.state.lock().await;
-        
-        if state.is_recording {
-            state.is_recording = false;
-            state.recording_mode = None;
-            log_info!("连接关闭，取消录音");
-        }
-        
-        // 取消实时转录任务
-        if let Some(stop_tx) = state.stop_signal.take() {
-            let _ = stop_tx.send(());
-        }
-        if let Some(task_handle) = state.realtime_task.take() {
-            task_handle.abort();
-        }
-        
-        // 取消录音
-        if let Some(ref mut streaming_recorder) = state.streaming_recorder {
-            streaming_recorder.cancel();
-        }
-        if let Some(ref mut recorder) = state.recorder {
-            recorder.cancel();
-        }
-        
-        state.streaming_recorder = None;
-        state.recorder = None;
-        state.audio_level_tx = None;
-    }
+}
+
+/// 编码 Voice 模块的消息 (附加触发该消息的请求 ID)
+fn encode_event<P: Payload + Serialize>(id: Option<&RequestId>, payload: &P) -> serde_json::Result<String> {
+    serde_json::to_string(&ServerResponse::from_payload(ModuleType::VOICE, payload).with_id(id.cloned()))
 }
 
 impl Default for VoiceHandler {
@@ -671,7 +736,23 @@ impl Default for VoiceHandler {
 #[async_trait::async_trait]
 impl ModuleHandler for VoiceHandler {
     fn module_type(&self) -> ModuleType {
-        ModuleType::Voice
+        ModuleType::VOICE
+    }
+    
+    fn capabilities(&self) -> ModuleCapabilities {
+        let providers: Vec<serde_json::Value> = ASRProvider::ALL
+            .iter()
+            .map(|provider| serde_json::json!({
+                "provider": provider,
+                "modes": provider.supported_modes(),
+            }))
+            .collect();
+        
+        ModuleCapabilities::new(&protocol().request_types(), serde_json::json!({
+            "asr_providers": providers,
+            "recording_modes": [RecordingMode::Press, RecordingMode::Toggle],
+            "fallback": true,
+        }))
     }
     
     async fn handle(&self, msg: &ModuleMessage) -> Result<Option<ServerResponse>, RouterError> {
@@ -679,31 +760,66 @@ impl ModuleHandler for VoiceHandler {
         
         match msg.msg_type.as_str() {
             "start_recording" => {
-                let mode: RecordingMode = msg.get_field("mode")
-                    .ok_or_else(|| RouterError::ModuleError("缺少 mode 字段".to_string()))?;
-                let asr_config: ASRConfig = msg.get_field("asr_config")
-                    .ok_or_else(|| RouterError::ModuleError("缺少 asr_config 字段".to_string()))?;
-                
-                self.handle_start_recording(mode, asr_config).await
+                let request: StartRecordingRequest = msg.parse()?;
+                self.handle_start_recording(msg.id.clone(), request.mode, request.asr_config).await
             }
             "stop_recording" => {
-                self.handle_stop_recording().await
+                let StopRecordingRequest {} = msg.parse()?;
+                self.handle_stop_recording(msg.id.clone()).await
             }
             "cancel_recording" => {
-                self.handle_cancel_recording().await
+                let CancelRecordingRequest {} = msg.parse()?;
+                self.handle_cancel_recording(msg.id.clone()).await
             }
             "update_config" => {
-                let asr_config: ASRConfig = msg.get_field("asr_config")
-                    .ok_or_else(|| RouterError::ModuleError("缺少 asr_config 字段".to_string()))?;
-                
-                self.handle_update_config(asr_config).await
+                let request: UpdateConfigRequest = msg.parse()?;
+                self.handle_update_config(request.asr_config).await
             }
             _ => {
                 log_debug!("未知的 Voice 消息类型: {}", msg.msg_type);
-                Err(RouterError::ModuleError(format!("未知的 Voice 消息类型: {}", msg.msg_type)))
+                Err(RouterError::InvalidMessage(Msg::UnknownMessageType {
+                    module: ModuleType::VOICE.to_string(),
+                    msg_type: msg.msg_type.clone(),
+                }))
             }
         }
     }
+    
+    async fn on_connect(&self, context: &ModuleContext) {
+        self.set_ws_sender(context.ws_sender.clone()).await;
+        self.set_locale(context.locale.clone()).await;
+    }
+    
+    async fn cleanup(&self) {
+        let mut state = self.state.lock().await;
+        
+        if state.is_recording {
+            state.is_recording = false;
+            state.recording_mode = None;
+            state.recording_guard = None;
+            log_info!("连接关闭，取消录音");
+        }
+        
+        // 取消实时转录任务
+        if let Some(stop_tx) = state.stop_signal.take() {
+            let _ = stop_tx.send(());
+        }
+        if let Some(task_handle) = state.realtime_task.take() {
+            task_handle.abort();
+        }
+        
+        // 取消录音
+        if let Some(ref mut streaming_recorder) = state.streaming_recorder {
+            streaming_recorder.cancel();
+        }
+        if let Some(ref mut recorder) = state.recorder {
+            recorder.cancel();
+        }
+        
+        state.streaming_recorder = None;
+        state.recorder = None;
+        state.audio_level_tx = None;
+    }
 }
 
 // ============================================================================
@@ -714,13 +830,15 @@ impl ModuleHandler for VoiceHandler {
 async fn perform_transcription(
     audio_data: &AudioData,
     asr_config: &ASRConfig,
+    retry_config: &RetryConfig,
 ) -> Result<TranscriptionResult, ASRError> {
     // 验证配置
     asr_config.validate()
         .map_err(|e| ASRError::ConfigError(e.to_string()))?;
     
     // 创建并行兜底策略
-    let strategy = ParallelFallbackStrategy::from_config(asr_config.clone());
+    let strategy = ParallelFallbackStrategy::from_config(asr_config.clone())
+        .with_retry_config(retry_config.clone());
     
     log_info!(
         "使用 ASR 引擎: primary={}, fallback={:?}, enable_fallback={}",
@@ -737,6 +855,7 @@ async fn perform_transcription(
 async fn perform_fallback_transcription(
     audio_data: &AudioData,
     asr_config: &ASRConfig,
+    retry_config: &RetryConfig,
 ) -> Result<TranscriptionResult, ASRError> {
     // 检查音频数据是否为空
     if audio_data.is_empty() {
@@ -757,7 +876,7 @@ async fn perform_fallback_transcription(
             log_info!("使用配置的 fallback 引擎: {}", fallback_config.provider);
             
             // 创建 fallback 引擎
-            let engine = asr::create_engine(fallback_config)?;
+            let engine = asr::create_engine(fallback_config, retry_config)?;
             
             let start_time = std::time::Instant::now();
             let text = engine.transcribe(audio_data).await?;
@@ -780,7 +899,7 @@ async fn perform_fallback_transcription(
     http_config.mode = ASRMode::Http;
     
     // 创建 HTTP 引擎
-    let engine = asr::create_engine(&http_config)?;
+    let engine = asr::create_engine(&http_config, retry_config)?;
     
     let start_time = std::time::Instant::now();
     let text = engine.transcribe(audio_data).await?;
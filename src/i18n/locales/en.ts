@@ -73,6 +73,7 @@ export const en: TranslationKeys = {
     serverStartFailed: 'Server failed to start',
     wsReconnectFailed: 'WebSocket connection lost, max retry attempts reached\nPlease reload the plugin',
     wsReconnectSuccess: 'WebSocket reconnected successfully',
+    serverIncompatible: 'Server binary is incompatible with this plugin version\nPlease rebuild or reinstall the server',
 
   // Chat
   chat: {
@@ -73,6 +73,7 @@ export const zhCN: TranslationKeys = {
     serverStartFailed: '服务器启动失败',
     wsReconnectFailed: 'WebSocket 连接断开，已达到最大重试次数\n请重新加载插件',
     wsReconnectSuccess: 'WebSocket 重连成功',
+    serverIncompatible: '服务器程序与当前插件版本不兼容\n请重新构建或安装服务器',
 
   // Chat
   chat: {
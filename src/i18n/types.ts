@@ -87,6 +87,7 @@ export interface TranslationKeys {
     serverStartFailed: string;
     wsReconnectFailed: string;
     wsReconnectSuccess: string;
+    serverIncompatible: string;
 
   // 聊天界面
   chat: {
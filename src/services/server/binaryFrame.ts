@@ -0,0 +1,69 @@
+/**
+ * 二进制帧编解码
+ *
+ * 与 Rust 端 frame.rs 保持一致:
+ * [channel: 1 字节][stream_id: u32 大端 4 字节][payload]
+ *
+ * hello 握手协商 binary_frames 后启用，未启用时二进制数据不带帧头 (仅 PTY)
+ */
+
+import { ModuleType } from './types';
+
+/** 帧头长度 */
+export const FRAME_HEADER_LEN = 5;
+
+/** 模块通道 ID (0 保留) */
+const CHANNEL_IDS: Record<ModuleType, number> = {
+  pty: 1,
+  voice: 2,
+  llm: 3,
+  utils: 4,
+};
+
+/**
+ * 解码后的二进制帧
+ */
+export interface BinaryFrame {
+  /** 来源模块 */
+  module: ModuleType;
+  /** 流 ID (PTY 为 session_id) */
+  streamId: number;
+  /** 负载数据 */
+  payload: Uint8Array;
+}
+
+/**
+ * 编码二进制帧
+ */
+export function encodeFrame(module: ModuleType, streamId: number, payload: Uint8Array): Uint8Array {
+  const frame = new Uint8Array(FRAME_HEADER_LEN + payload.length);
+  const view = new DataView(frame.buffer);
+  view.setUint8(0, CHANNEL_IDS[module]);
+  view.setUint32(1, streamId, false);
+  frame.set(payload, FRAME_HEADER_LEN);
+  return frame;
+}
+
+/**
+ * 解码二进制帧
+ *
+ * @returns 帧头无效时返回 null
+ */
+export function decodeFrame(data: ArrayBuffer): BinaryFrame | null {
+  if (data.byteLength < FRAME_HEADER_LEN) {
+    return null;
+  }
+
+  const view = new DataView(data);
+  const channel = view.getUint8(0);
+  const module = (Object.keys(CHANNEL_IDS) as ModuleType[]).find(m => CHANNEL_IDS[m] === channel);
+  if (!module) {
+    return null;
+  }
+
+  return {
+    module,
+    streamId: view.getUint32(1, false),
+    payload: new Uint8Array(data, FRAME_HEADER_LEN),
+  };
+}
@@ -12,5 +12,9 @@ export { VoiceClient } from './voiceClient';
 export { LLMClient } from './llmClient';
 export { UtilsClient } from './utilsClient';
 
+// 二进制帧
+export { encodeFrame, decodeFrame, FRAME_HEADER_LEN } from './binaryFrame';
+export type { BinaryFrame } from './binaryFrame';
+
 // 类型
 export * from './types';
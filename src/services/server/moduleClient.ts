@@ -5,6 +5,7 @@
  */
 
 import { ModuleType, ClientMessage, ServerMessage } from './types';
+import { encodeFrame } from './binaryFrame';
 import { debugLog, errorLog } from '../../utils/logger';
 
 /**
@@ -30,6 +31,9 @@ export abstract class ModuleClient {
   /** 是否已初始化 */
   private initialized = false;
 
+  /** 二进制消息是否使用分帧格式 (由 ServerManager 根据 hello 握手设置) */
+  protected binaryFramed = false;
+
   constructor(module: ModuleType) {
     this.module = module;
   }
@@ -47,6 +51,14 @@ export abstract class ModuleClient {
     }
   }
 
+  /**
+   * 设置二进制分帧模式
+   * 由 ServerManager 调用
+   */
+  setBinaryFramed(enabled: boolean): void {
+    this.binaryFramed = enabled;
+  }
+
   /**
    * 检查是否已连接
    */
@@ -83,16 +95,24 @@ export abstract class ModuleClient {
   /**
    * 发送二进制数据
    * 
+   * 分帧模式下加上本模块的通道 ID 和流 ID
+   * 
    * @param data 二进制数据
+   * @param streamId 流 ID
    */
-  protected sendBinary(data: ArrayBuffer | Uint8Array): void {
+  protected sendBinary(data: ArrayBuffer | Uint8Array, streamId = 0): void {
     if (!this.ws || this.ws.readyState !== WebSocket.OPEN) {
       errorLog(`[${this.module}Client] WebSocket 未连接，无法发送二进制数据`);
       return;
     }
 
     try {
-      this.ws.send(data);
+      if (this.binaryFramed) {
+        const payload = data instanceof Uint8Array ? data : new Uint8Array(data);
+        this.ws.send(encodeFrame(this.module, streamId, payload));
+      } else {
+        this.ws.send(data);
+      }
     } catch (error) {
       errorLog(`[${this.module}Client] 发送二进制数据失败:`, error);
     }
@@ -134,6 +154,17 @@ export abstract class ModuleClient {
     return () => this.messageHandlers.delete(handler);
   }
 
+  /**
+   * 处理来自服务器的二进制数据
+   * 由 ServerManager 调用，默认忽略
+   * 
+   * @param data 负载数据
+   * @param streamId 流 ID (旧版未分帧数据为 undefined)
+   */
+  handleBinaryMessage(data: Uint8Array, streamId?: number): void {
+    debugLog(`[${this.module}Client] 忽略二进制数据:`, data.byteLength, streamId);
+  }
+
   /**
    * 子类实现的消息处理方法
    * 
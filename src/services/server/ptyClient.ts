@@ -5,7 +5,17 @@
  */
 
 import { ModuleClient } from './moduleClient';
-import { PtyConfig, PtyEvents, ServerMessage } from './types';
+import {
+  PtyConfig,
+  PtyEvents,
+  PtyScreenFormat,
+  PtyScreenSnapshot,
+  PtyScrollbackLines,
+  PtySearchOptions,
+  PtySearchResults,
+  PtySessionInfo,
+  ServerMessage,
+} from './types';
 import { debugLog } from '../../utils/logger';
 
 /**
@@ -14,6 +24,13 @@ import { debugLog } from '../../utils/logger';
 export class PtyClient extends ModuleClient {
   /** 事件监听器 */
   private eventListeners: Map<keyof PtyEvents, Set<PtyEvents[keyof PtyEvents]>> = new Map();
+  /** 等待 init_complete / reattach_complete 的请求 (服务器按顺序响应) */
+  private pendingSessions: Array<{ resolve: (session: PtySessionInfo) => void; reject: (error: Error) => void }> = [];
+  /** 等待 cwd 响应的 get_cwd 请求 (按请求 ID) */
+  private pendingCwdRequests: Map<string, (cwd: string | undefined) => void> = new Map();
+  /** 等待 screen_snapshot / scrollback_lines / search_results 响应的请求 (按请求 ID) */
+  private pendingScreenRequests: Map<string, { resolve: (msg: ServerMessage) => void; reject: (error: Error) => void }> = new Map();
+  private requestIdCounter = 0;
 
   constructor() {
     super('pty');
@@ -22,9 +39,13 @@ export class PtyClient extends ModuleClient {
   /**
    * 初始化 PTY 会话
    * 
+   * 同一连接可创建多个会话，后续消息通过返回的 session_id 指定目标会话
+   * 
    * @param config PTY 配置
+   * @returns 会话信息
    */
-  init(config: PtyConfig = {}): void {
+  init(config: PtyConfig = {}): Promise<PtySessionInfo> {
+    const promise = this.waitForSession();
     this.send('init', {
       shell_type: config.shell_type,
       shell_args: config.shell_args,
@@ -32,7 +53,53 @@ export class PtyClient extends ModuleClient {
       env: config.env,
       cols: config.cols,
       rows: config.rows,
+      pixel_width: config.pixel_width,
+      pixel_height: config.pixel_height,
     });
+    return promise;
+  }
+
+  /**
+   * 重新连接 PTY 会话
+   * 
+   * 连接断开后会话在服务器上保留一段宽限期，凭会话令牌可在新连接上接管。
+   * 服务器会回放 offset 之后缓存的输出，然后恢复实时输出。
+   * 
+   * @param sessionToken 会话令牌
+   * @param offset 已收到的输出字节数
+   * @returns 会话信息
+   */
+  reattach(sessionToken: string, offset?: number): Promise<PtySessionInfo> {
+    const promise = this.waitForSession();
+    this.send('reattach', { session_token: sessionToken, offset });
+    return promise;
+  }
+
+  /**
+   * 登记等待中的会话请求
+   */
+  private waitForSession(): Promise<PtySessionInfo> {
+    return new Promise<PtySessionInfo>((resolve, reject) => {
+      this.pendingSessions.push({ resolve, reject });
+    });
+  }
+
+  /**
+   * 拒绝所有等待中的会话请求
+   */
+  private rejectPendingSessions(reason: string): void {
+    this.pendingSessions.forEach(pending => pending.reject(new Error(reason)));
+    this.pendingSessions = [];
+  }
+
+  /**
+   * 设置 WebSocket 连接，连接断开时拒绝等待中的会话请求
+   */
+  override setWebSocket(ws: WebSocket | null): void {
+    if (!ws) {
+      this.rejectPendingSessions('WebSocket disconnected');
+    }
+    super.setWebSocket(ws);
   }
 
   /**
@@ -40,17 +107,35 @@ export class PtyClient extends ModuleClient {
    * 
    * @param cols 列数
    * @param rows 行数
+   * @param sessionId 会话 ID (省略时为最近创建的会话)
+   * @param pixelWidth 终端区域宽度 (像素)
+   * @param pixelHeight 终端区域高度 (像素)
    */
-  resize(cols: number, rows: number): void {
-    this.send('resize', { cols, rows });
+  resize(cols: number, rows: number, sessionId?: number, pixelWidth?: number, pixelHeight?: number): void {
+    this.send('resize', {
+      session_id: sessionId,
+      cols,
+      rows,
+      pixel_width: pixelWidth,
+      pixel_height: pixelHeight,
+    });
   }
 
   /**
    * 写入文本数据
    * 
    * @param data 文本数据
+   * @param sessionId 会话 ID (省略时为最近创建的会话)
    */
-  write(data: string): void {
+  write(data: string, sessionId?: number): void {
+    if (sessionId !== undefined) {
+      if (this.binaryFramed) {
+        this.sendBinary(new TextEncoder().encode(data), sessionId);
+      } else {
+        this.send('input', { session_id: sessionId, data });
+      }
+      return;
+    }
     if (!this.ws || this.ws.readyState !== WebSocket.OPEN) {
       return;
     }
@@ -62,9 +147,157 @@ export class PtyClient extends ModuleClient {
    * 写入二进制数据
    * 
    * @param data 二进制数据
+   * @param sessionId 会话 ID (省略时为最近创建的会话)
+   */
+  writeBinary(data: Uint8Array | ArrayBuffer, sessionId?: number): void {
+    if (sessionId !== undefined && !this.binaryFramed) {
+      const bytes = data instanceof Uint8Array ? data : new Uint8Array(data);
+      this.send('input', { session_id: sessionId, data: Array.from(bytes) });
+      return;
+    }
+    this.sendBinary(data, sessionId);
+  }
+
+  /**
+   * 终止 PTY 会话 (不影响同一连接上的其他会话)
+   * 
+   * @param sessionId 会话 ID
+   */
+  kill(sessionId: number): void {
+    this.send('kill', { session_id: sessionId });
+  }
+
+  /**
+   * 查询会话的当前工作目录
+   * 
+   * 服务器优先使用 Shell Integration 上报的目录，否则从 shell 进程读取 (仅 Linux)
+   * 
+   * @param sessionId 会话 ID (省略时为最近创建的会话)
+   * @returns 工作目录，未知或查询失败时为 undefined
+   */
+  getCwd(sessionId?: number): Promise<string | undefined> {
+    return new Promise(resolve => {
+      const requestId = `cwd_${++this.requestIdCounter}`;
+      const timeout = setTimeout(() => {
+        this.pendingCwdRequests.delete(requestId);
+        resolve(undefined);
+      }, 5000);
+      this.pendingCwdRequests.set(requestId, (cwd) => {
+        clearTimeout(timeout);
+        resolve(cwd);
+      });
+      this.send('get_cwd', { id: requestId, session_id: sessionId });
+    });
+  }
+
+  /**
+   * 获取会话可见屏幕的快照
+   * 
+   * 服务器端虚拟终端维护屏幕和回滚历史，快照包含备用屏幕 (全屏程序) 的内容
+   * 
+   * @param format 内容格式 (默认为纯文本)
+   * @param sessionId 会话 ID (省略时为最近创建的会话)
+   * @returns 屏幕快照
+   */
+  async snapshot(format: PtyScreenFormat = 'text', sessionId?: number): Promise<PtyScreenSnapshot> {
+    const requestId = `screen_${++this.requestIdCounter}`;
+    const response = this.waitForScreen(requestId, 'snapshot');
+    this.send('snapshot', { id: requestId, session_id: sessionId, format });
+    const msg = await response;
+    return {
+      sessionId: msg.session_id as number,
+      format: msg.format as PtyScreenFormat,
+      content: msg.content as string,
+      cols: msg.cols as number,
+      rows: msg.rows as number,
+      cursor: msg.cursor as { row: number; col: number } | undefined,
+      alternateScreen: msg.alternate_screen as boolean,
+      historyLines: msg.history_lines as number,
+      outputOffset: msg.output_offset as number,
+    };
+  }
+
+  /**
+   * 获取回滚历史中的行
+   * 
+   * @param start 起始行 (0 为最早保留的一行)
+   * @param count 行数 (服务器最多返回 1000 行)
+   * @param format 内容格式 (默认为纯文本)
+   * @param sessionId 会话 ID (省略时为最近创建的会话)
+   * @returns 历史行
    */
-  writeBinary(data: Uint8Array | ArrayBuffer): void {
-    this.sendBinary(data);
+  async getScrollback(
+    start: number,
+    count: number,
+    format: PtyScreenFormat = 'text',
+    sessionId?: number,
+  ): Promise<PtyScrollbackLines> {
+    const requestId = `screen_${++this.requestIdCounter}`;
+    const response = this.waitForScreen(requestId, 'scrollback_range');
+    this.send('scrollback_range', { id: requestId, session_id: sessionId, start, count, format });
+    const msg = await response;
+    return {
+      sessionId: msg.session_id as number,
+      format: msg.format as PtyScreenFormat,
+      start: msg.start as number,
+      lines: (msg.lines as string[]) ?? [],
+      historyLines: msg.history_lines as number,
+    };
+  }
+
+  /**
+   * 检索会话的历史输出
+   * 
+   * 服务器为每个会话保存去除控制序列的输出 (按字节数限制)，
+   * 终端未显示时同样可以检索
+   * 
+   * @param query 检索内容
+   * @param options 检索选项
+   * @param sessionId 会话 ID (省略时为最近创建的会话)
+   * @returns 检索结果，正则表达式无效时拒绝
+   */
+  async search(query: string, options: PtySearchOptions = {}, sessionId?: number): Promise<PtySearchResults> {
+    const requestId = `screen_${++this.requestIdCounter}`;
+    const response = this.waitForScreen(requestId, 'search');
+    this.send('search', {
+      id: requestId,
+      session_id: sessionId,
+      query,
+      regex: options.regex,
+      ignore_case: options.ignoreCase,
+      context: options.context,
+      max_results: options.maxResults,
+    });
+    const msg = await response;
+    return {
+      sessionId: msg.session_id as number,
+      matches: (msg.matches as PtySearchResults['matches']) ?? [],
+      truncated: msg.truncated as boolean,
+      firstLine: msg.first_line as number,
+      lineCount: msg.line_count as number,
+    };
+  }
+
+  /**
+   * 登记等待中的屏幕查询请求 (按请求 ID 匹配响应，失败或超时时拒绝)
+   */
+  private waitForScreen(requestId: string, type: string): Promise<ServerMessage> {
+    return new Promise((resolve, reject) => {
+      const timeout = setTimeout(() => {
+        this.pendingScreenRequests.delete(requestId);
+        reject(new Error(`${type} timed out`));
+      }, 5000);
+      this.pendingScreenRequests.set(requestId, {
+        resolve: (msg) => {
+          clearTimeout(timeout);
+          resolve(msg);
+        },
+        reject: (error) => {
+          clearTimeout(timeout);
+          reject(error);
+        },
+      });
+    });
   }
 
   /**
@@ -87,6 +320,36 @@ export class PtyClient extends ModuleClient {
     return this.on('exit', handler);
   }
 
+  /**
+   * 注册工作目录变化处理器
+   * 
+   * @param handler 工作目录变化处理器
+   * @returns 取消注册的函数
+   */
+  onCwdChanged(handler: PtyEvents['cwd-changed']): () => void {
+    return this.on('cwd-changed', handler);
+  }
+
+  /**
+   * 注册命令开始处理器
+   * 
+   * @param handler 命令开始处理器
+   * @returns 取消注册的函数
+   */
+  onCommandStarted(handler: PtyEvents['command-started']): () => void {
+    return this.on('command-started', handler);
+  }
+
+  /**
+   * 注册命令结束处理器
+   * 
+   * @param handler 命令结束处理器
+   * @returns 取消注册的函数
+   */
+  onCommandFinished(handler: PtyEvents['command-finished']): () => void {
+    return this.on('command-finished', handler);
+  }
+
   /**
    * 注册错误处理器
    * 
@@ -137,37 +400,100 @@ export class PtyClient extends ModuleClient {
    * 处理服务器消息
    */
   protected onMessage(msg: ServerMessage): void {
+    const sessionId = msg.session_id as number | undefined;
+
     switch (msg.type) {
-      case 'output':
-        // 输出数据 (二进制数据在 ServerManager 中单独处理)
-        if (msg.data) {
-          const data = msg.data as number[];
-          this.emit('output', new Uint8Array(data));
-        }
+      case 'init_complete':
+      case 'reattach_complete':
+        this.pendingSessions.shift()?.resolve({
+          sessionId: sessionId ?? 0,
+          sessionToken: (msg.session_token as string) ?? '',
+        });
         break;
-        
+
+      case 'cwd': {
+        const resolve = this.pendingCwdRequests.get(msg.id as string);
+        this.pendingCwdRequests.delete(msg.id as string);
+        resolve?.(msg.cwd as string | undefined);
+        break;
+      }
+
+      case 'screen_snapshot':
+      case 'scrollback_lines':
+      case 'search_results': {
+        const pending = this.pendingScreenRequests.get(msg.id as string);
+        this.pendingScreenRequests.delete(msg.id as string);
+        pending?.resolve(msg);
+        break;
+      }
+
+      case 'cwd_changed':
+        this.emit('cwd-changed', msg.cwd as string, sessionId, msg.source as string);
+        break;
+
+      case 'command_started':
+        this.emit('command-started', {
+          sessionId: sessionId ?? 0,
+          command: msg.command as string | undefined,
+          cwd: msg.cwd as string | undefined,
+          outputOffset: msg.output_offset as number,
+        });
+        break;
+
+      case 'command_finished':
+        this.emit('command-finished', {
+          sessionId: sessionId ?? 0,
+          command: msg.command as string | undefined,
+          cwd: msg.cwd as string | undefined,
+          outputOffset: msg.output_offset as number,
+          exitCode: msg.exit_code as number | undefined,
+          durationMs: msg.duration_ms as number,
+        });
+        break;
+
       case 'exit':
-        this.emit('exit', (msg.code as number) || 0);
+        this.emit('exit', (msg.code as number) || 0, sessionId, msg.signal as string | undefined);
         break;
         
-      case 'error':
+      case 'error': {
+        // get_cwd / snapshot / scrollback_range / search 失败时按请求 ID 结束查询
+        const cwdRequest = this.pendingCwdRequests.get(msg.id as string);
+        if (cwdRequest) {
+          this.pendingCwdRequests.delete(msg.id as string);
+          cwdRequest(undefined);
+          break;
+        }
+        const screenRequest = this.pendingScreenRequests.get(msg.id as string);
+        if (screenRequest) {
+          this.pendingScreenRequests.delete(msg.id as string);
+          screenRequest.reject(new Error(msg.message as string));
+          break;
+        }
+        // init / reattach 失败时服务器只返回错误，拒绝最早的等待请求
+        this.pendingSessions.shift()?.reject(new Error(msg.message as string));
         this.emit('error', msg.code as string, msg.message as string);
         break;
+      }
     }
   }
 
   /**
    * 处理二进制消息 (PTY 输出)
-   * 由 ServerManager 调用
+   * 由 ServerManager 调用，分帧模式下流 ID 即 session_id
    */
-  handleBinaryMessage(data: ArrayBuffer): void {
-    this.emit('output', new Uint8Array(data));
+  override handleBinaryMessage(data: Uint8Array, streamId?: number): void {
+    this.emit('output', data, streamId);
   }
 
   /**
    * 清理资源
    */
   override destroy(): void {
+    this.rejectPendingSessions('PtyClient destroyed');
+    this.pendingCwdRequests.forEach(resolve => resolve(undefined));
+    this.pendingCwdRequests.clear();
+    this.pendingScreenRequests.forEach(pending => pending.reject(new Error('PtyClient destroyed')));
+    this.pendingScreenRequests.clear();
     this.eventListeners.clear();
     super.destroy();
   }
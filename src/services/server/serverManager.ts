@@ -14,14 +14,17 @@ import * as path from 'path';
 import * as fs from 'fs';
 import { Notice } from 'obsidian';
 import { debugLog, debugWarn, errorLog } from '../../utils/logger';
-import { t } from '../../i18n';
+import { i18n, t } from '../../i18n';
 import { 
   ServerInfo, 
   ServerEvents, 
   ServerErrorCode, 
   ServerManagerError,
   ServerMessage,
+  ServerCapabilities,
+  PROTOCOL_VERSION,
 } from './types';
+import { decodeFrame } from './binaryFrame';
 import { PtyClient } from './ptyClient';
 import { VoiceClient } from './voiceClient';
 import { LLMClient } from './llmClient';
@@ -60,9 +63,21 @@ export class ServerManager {
   /** 服务器端口 */
   private port: number | null = null;
   
+  /** 服务器认证令牌 */
+  private authToken: string | null = null;
+  
+  /** 服务器能力 (hello 握手响应) */
+  private serverCapabilities: ServerCapabilities | null = null;
+  
+  /** 当前连接是否使用二进制分帧 */
+  private binaryFramed = false;
+  
   /** 是否正在关闭 */
   private isShuttingDown = false;
   
+  /** 服务器是否已通知即将关闭 (此时断开不触发 WebSocket 重连) */
+  private serverClosing = false;
+  
   /** 服务器重启尝试次数 */
   private restartAttempts = 0;
   
@@ -139,6 +154,7 @@ export class ServerManager {
       if (this.ws) {
         this._ptyClient.setWebSocket(this.ws);
       }
+      this._ptyClient.setBinaryFramed(this.binaryFramed);
     }
     return this._ptyClient;
   }
@@ -154,6 +170,7 @@ export class ServerManager {
       if (this.ws) {
         this._voiceClient.setWebSocket(this.ws);
       }
+      this._voiceClient.setBinaryFramed(this.binaryFramed);
     }
     return this._voiceClient;
   }
@@ -169,6 +186,7 @@ export class ServerManager {
       if (this.ws) {
         this._llmClient.setWebSocket(this.ws);
       }
+      this._llmClient.setBinaryFramed(this.binaryFramed);
     }
     return this._llmClient;
   }
@@ -184,6 +202,7 @@ export class ServerManager {
       if (this.ws) {
         this._utilsClient.setWebSocket(this.ws);
       }
+      this._utilsClient.setBinaryFramed(this.binaryFramed);
     }
     return this._utilsClient;
   }
@@ -242,6 +261,7 @@ export class ServerManager {
     
     // 清理状态
     this.port = null;
+    this.authToken = null;
     this.serverStartPromise = null;
     this.wsConnectPromise = null;
     
@@ -296,6 +316,13 @@ export class ServerManager {
     return this.port;
   }
 
+  /**
+   * 获取服务器能力 (连接建立后通过 hello 握手获取)
+   */
+  getServerCapabilities(): ServerCapabilities | null {
+    return this.serverCapabilities;
+  }
+
   /**
    * 注册事件监听器
    */
@@ -339,8 +366,8 @@ export class ServerManager {
       // 确保可执行权限 (Unix)
       await this.ensureExecutable(binaryPath);
       
-      // 启动进程
-      this.process = spawn(binaryPath, ['--port', '0'], {
+      // 启动进程 (传入插件进程 PID，插件崩溃后服务器自动退出)
+      this.process = spawn(binaryPath, ['--port', '0', '--parent-pid', String(process.pid)], {
         stdio: ['pipe', 'pipe', 'pipe'],
         env: {
           ...process.env,
@@ -359,9 +386,11 @@ export class ServerManager {
       });
       
       // 等待端口信息
-      const port = await this.waitForServerPort();
+      const { port, token } = await this.waitForServerPort();
       this.port = port;
+      this.authToken = token;
       this.restartAttempts = 0;
+      this.serverClosing = false;
       
       debugLog(`[ServerManager] 服务器已启动，端口: ${port}`);
       
@@ -422,7 +451,7 @@ export class ServerManager {
   /**
    * 等待服务器输出端口信息
    */
-  private async waitForServerPort(): Promise<number> {
+  private async waitForServerPort(): Promise<ServerInfo> {
     return new Promise((resolve, reject) => {
       if (!this.process || !this.process.stdout) {
         reject(new ServerManagerError(
@@ -449,11 +478,11 @@ export class ServerManager {
           const match = buffer.match(/\{[^}]+\}/);
           if (match) {
             const info: ServerInfo = JSON.parse(match[0]);
-            if (info.port && typeof info.port === 'number') {
+            if (info.port && typeof info.port === 'number' && typeof info.token === 'string') {
               clearTimeout(timeout);
               this.process?.stdout?.off('data', onData);
-              debugLog('[ServerManager] 解析到服务器信息:', info);
-              resolve(info.port);
+              debugLog('[ServerManager] 解析到服务器信息:', { port: info.port, pid: info.pid });
+              resolve(info);
             }
           }
         } catch {
@@ -501,7 +530,7 @@ export class ServerManager {
       const wsUrl = `ws://127.0.0.1:${this.port}`;
       debugLog('[ServerManager] 连接 WebSocket:', wsUrl);
       
-      this.ws = new WebSocket(wsUrl);
+      this.ws = new WebSocket(`${wsUrl}/?token=${encodeURIComponent(this.authToken ?? '')}`);
       
       const timeout = setTimeout(() => {
         this.wsConnectPromise = null;
@@ -522,6 +551,18 @@ export class ServerManager {
         // 更新所有模块客户端的 WebSocket
         this.updateClientsWebSocket();
         
+        // 握手，获取服务器版本和能力，并请求启用二进制分帧
+        // 服务器按顺序处理消息，握手之后发送的二进制数据即按分帧格式解析
+        // 同时声明插件当前语言，服务器按该语言返回错误和状态消息
+        this.ws?.send(JSON.stringify({
+          module: 'utils',
+          type: 'hello',
+          protocol_version: PROTOCOL_VERSION,
+          binary_frames: true,
+          locale: i18n.getLocale(),
+        }));
+        this.setBinaryFramed(true);
+        
         this.emit('ws-connected');
         resolve();
       };
@@ -538,8 +579,9 @@ export class ServerManager {
         
         this.emit('ws-disconnected');
         
-        // 如果不是主动关闭，尝试重连
-        if (!this.isShuttingDown && this.port !== null) {
+        // 如果不是主动关闭，且服务器没有通知即将关闭，尝试重连
+        // (服务器关闭后由进程退出处理器决定是否重启)
+        if (!this.isShuttingDown && !this.serverClosing && this.port !== null) {
           this.scheduleReconnect();
         }
       };
@@ -570,19 +612,30 @@ export class ServerManager {
     }
   }
 
+  /**
+   * 设置所有模块客户端的二进制分帧模式
+   */
+  private setBinaryFramed(enabled: boolean): void {
+    this.binaryFramed = enabled;
+    this._ptyClient?.setBinaryFramed(enabled);
+    this._voiceClient?.setBinaryFramed(enabled);
+    this._llmClient?.setBinaryFramed(enabled);
+    this._utilsClient?.setBinaryFramed(enabled);
+  }
+
   /**
    * 处理 WebSocket 消息
    */
   private handleWebSocketMessage(event: MessageEvent): void {
-    // 处理二进制消息 (PTY 输出)
+    // 处理二进制消息
     if (event.data instanceof ArrayBuffer) {
-      this._ptyClient?.handleBinaryMessage(event.data);
+      this.handleBinaryMessage(event.data);
       return;
     }
     
     if (event.data instanceof Blob) {
       event.data.arrayBuffer().then(buffer => {
-        this._ptyClient?.handleBinaryMessage(buffer);
+        this.handleBinaryMessage(buffer);
       });
       return;
     }
@@ -591,6 +644,18 @@ export class ServerManager {
     try {
       const msg: ServerMessage = JSON.parse(event.data);
       
+      // 握手响应由 ServerManager 处理
+      if (msg.type === 'hello') {
+        this.handleHello(msg as unknown as ServerCapabilities);
+        return;
+      }
+      
+      // 服务器关闭通知由 ServerManager 处理
+      if (msg.type === 'server_shutting_down') {
+        this.handleServerShuttingDown(msg);
+        return;
+      }
+      
       // 根据模块分发消息
       switch (msg.module) {
         case 'pty':
@@ -613,6 +678,75 @@ export class ServerManager {
     }
   }
 
+  /**
+   * 处理二进制消息 - 按帧头分发到模块客户端
+   */
+  private handleBinaryMessage(buffer: ArrayBuffer): void {
+    // 旧版模式: 不带帧头的 PTY 输出
+    if (!this.binaryFramed) {
+      this._ptyClient?.handleBinaryMessage(new Uint8Array(buffer));
+      return;
+    }
+    
+    const frame = decodeFrame(buffer);
+    if (!frame) {
+      debugWarn('[ServerManager] 无效的二进制帧:', buffer.byteLength);
+      return;
+    }
+    
+    switch (frame.module) {
+      case 'pty':
+        this._ptyClient?.handleBinaryMessage(frame.payload, frame.streamId);
+        break;
+      case 'voice':
+        this._voiceClient?.handleBinaryMessage(frame.payload, frame.streamId);
+        break;
+      case 'llm':
+        this._llmClient?.handleBinaryMessage(frame.payload, frame.streamId);
+        break;
+      case 'utils':
+        this._utilsClient?.handleBinaryMessage(frame.payload, frame.streamId);
+        break;
+    }
+  }
+
+  /**
+   * 处理 hello 握手响应 - 检查协议兼容性
+   */
+  private handleHello(capabilities: ServerCapabilities): void {
+    this.serverCapabilities = capabilities;
+    
+    // 旧版服务器不支持分帧，回退到旧版二进制格式
+    if (!capabilities.binary_frames) {
+      this.setBinaryFramed(false);
+    }
+    debugLog(
+      '[ServerManager] 服务器版本:', capabilities.server_version,
+      '协议版本:', capabilities.protocol_version,
+      '模块:', Object.keys(capabilities.modules)
+    );
+    
+    if (!capabilities.compatible) {
+      errorLog(
+        `[ServerManager] 服务器协议不兼容: 客户端=${PROTOCOL_VERSION}, ` +
+        `服务器=${capabilities.min_protocol_version}-${capabilities.protocol_version}`
+      );
+      new Notice(t('notices.serverIncompatible'), 0);
+    }
+  }
+
+  /**
+   * 处理服务器关闭通知
+   */
+  private handleServerShuttingDown(msg: ServerMessage): void {
+    const reason = typeof msg.reason === 'string' ? msg.reason : 'unknown';
+    debugLog('[ServerManager] 服务器即将关闭:', reason, msg.message);
+    
+    this.serverClosing = true;
+    this.cancelReconnect();
+    this.emit('server-shutting-down', reason);
+  }
+
   /**
    * 处理 WebSocket 断开 - 调度重连
    */
@@ -708,6 +842,7 @@ export class ServerManager {
 
     this.process.on('exit', (code, signal) => {
       this.port = null;
+      this.authToken = null;
       this.serverStartPromise = null;
       
       if (this.isShuttingDown) {
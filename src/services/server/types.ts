@@ -26,12 +26,48 @@ export interface ServerInfo {
   port: number;
   /** 进程 PID */
   pid: number;
+  /** 本次启动的认证令牌 (连接 WebSocket 时通过 token 查询参数传递) */
+  token: string;
 }
 
 // ============================================================================
 // 统一消息协议
 // ============================================================================
 
+/**
+ * 协议版本
+ * 与 Rust 端 PROTOCOL_VERSION 保持一致
+ */
+export const PROTOCOL_VERSION = 1;
+
+/**
+ * 模块能力描述
+ */
+export interface ModuleCapabilities {
+  /** 支持的消息类型 */
+  message_types: string[];
+  /** 功能详情 (API 格式、ASR 供应商、shell 类型等) */
+  features: Record<string, unknown>;
+}
+
+/**
+ * 服务器能力 (hello 握手响应)
+ */
+export interface ServerCapabilities {
+  /** 服务器版本 */
+  server_version: string;
+  /** 服务器协议版本 */
+  protocol_version: number;
+  /** 服务器兼容的最低客户端协议版本 */
+  min_protocol_version: number;
+  /** 客户端协议版本是否兼容 */
+  compatible: boolean;
+  /** 本连接是否已启用二进制分帧 (旧版服务器不返回此字段) */
+  binary_frames?: boolean;
+  /** 已启用的模块及其能力 */
+  modules: Partial<Record<ModuleType, ModuleCapabilities>>;
+}
+
 /**
  * 客户端发送的消息基础格式
  */
@@ -105,6 +141,8 @@ export interface ServerEvents {
   'server-stopped': () => void;
   /** 服务器错误 */
   'server-error': (error: Error) => void;
+  /** 服务器即将关闭 (reason: signal / requested / parent_exited / idle_timeout) */
+  'server-shutting-down': (reason: string) => void;
   /** WebSocket 已连接 */
   'ws-connected': () => void;
   /** WebSocket 已断开 */
@@ -135,16 +173,151 @@ export interface PtyConfig {
   cols?: number;
   /** 行数 */
   rows?: number;
+  /** 终端区域宽度 (像素) */
+  pixel_width?: number;
+  /** 终端区域高度 (像素) */
+  pixel_height?: number;
+}
+
+/**
+ * PTY 会话信息 (init / reattach 响应)
+ */
+export interface PtySessionInfo {
+  /** 会话 ID */
+  sessionId: number;
+  /** 会话令牌 (连接断开后凭此重新连接) */
+  sessionToken: string;
+}
+
+/**
+ * 屏幕内容格式
+ */
+export type PtyScreenFormat = 'text' | 'ansi' | 'html';
+
+/**
+ * 屏幕快照 (snapshot 响应)
+ */
+export interface PtyScreenSnapshot {
+  /** 会话 ID */
+  sessionId: number;
+  /** 内容格式 */
+  format: PtyScreenFormat;
+  /** 屏幕内容 (text 以 \n 换行，ansi 以 \r\n 换行，html 为 <pre> 片段) */
+  content: string;
+  /** 列数 */
+  cols: number;
+  /** 行数 */
+  rows: number;
+  /** 光标位置 (从 0 开始，光标隐藏时为 undefined) */
+  cursor?: { row: number; col: number };
+  /** 是否处于备用屏幕 (全屏程序运行时) */
+  alternateScreen: boolean;
+  /** 回滚历史行数 */
+  historyLines: number;
+  /** 快照对应的输出位置 (累计字节数，可作为 reattach 的 offset) */
+  outputOffset: number;
+}
+
+/**
+ * 回滚历史中的行 (scrollback_range 响应)
+ */
+export interface PtyScrollbackLines {
+  /** 会话 ID */
+  sessionId: number;
+  /** 内容格式 */
+  format: PtyScreenFormat;
+  /** 第一行的位置 (0 为最早保留的一行) */
+  start: number;
+  /** 各行内容 */
+  lines: string[];
+  /** 当前回滚历史行数 */
+  historyLines: number;
+}
+
+/**
+ * 输出检索选项
+ */
+export interface PtySearchOptions {
+  /** 按正则表达式匹配 (默认按字面文本) */
+  regex?: boolean;
+  /** 忽略大小写 */
+  ignoreCase?: boolean;
+  /** 每个匹配行前后的上下文行数 (默认 2，最多 10) */
+  context?: number;
+  /** 最多返回的匹配行数 (默认 100，最多 1000) */
+  maxResults?: number;
+}
+
+/**
+ * 匹配的行
+ */
+export interface PtySearchMatch {
+  /** 行号 (会话开始以来的行序号，从 0 开始) */
+  line: number;
+  /** 行内容 (已去除控制序列) */
+  text: string;
+  /** 行中所有匹配的位置 (字符偏移，不含 end) */
+  ranges: Array<{ start: number; end: number }>;
+  /** 之前的上下文行 */
+  before: string[];
+  /** 之后的上下文行 */
+  after: string[];
+}
+
+/**
+ * 输出检索结果 (search 响应)
+ */
+export interface PtySearchResults {
+  /** 会话 ID */
+  sessionId: number;
+  /** 匹配的行 (按行号升序) */
+  matches: PtySearchMatch[];
+  /** 是否有更早的匹配因超过 maxResults 未返回 */
+  truncated: boolean;
+  /** 检索缓冲区中最早一行的行号 */
+  firstLine: number;
+  /** 检索缓冲区中的行数 */
+  lineCount: number;
+}
+
+/**
+ * 命令开始执行 (需要 Shell Integration)
+ */
+export interface PtyCommandStarted {
+  /** 会话 ID */
+  sessionId: number;
+  /** 命令行 */
+  command?: string;
+  /** 执行命令时的工作目录 */
+  cwd?: string;
+  /** 命令输出在会话输出流中的起始位置 (累计字节数) */
+  outputOffset: number;
+}
+
+/**
+ * 命令执行结束
+ */
+export interface PtyCommandFinished extends PtyCommandStarted {
+  /** 退出码 */
+  exitCode?: number;
+  /** 执行耗时 (毫秒) */
+  durationMs: number;
 }
 
 /**
  * PTY 事件映射
  */
 export interface PtyEvents {
-  /** 输出数据 */
-  'output': (data: Uint8Array) => void;
-  /** 会话退出 */
-  'exit': (code: number) => void;
+  /** 输出数据 (sessionId 为来源会话，旧格式二进制输出时为 undefined) */
+  'output': (data: Uint8Array, sessionId?: number) => void;
+  /** 会话退出 (signal: 被信号终止时的信号描述) */
+  'exit': (code: number, sessionId?: number, signal?: string) => void;
+  /** 工作目录变化 (source: shell 为 Shell Integration 上报，process 为从 shell 进程读取) */
+  'cwd-changed': (cwd: string, sessionId?: number, source?: string) => void;
+  /** 命令开始执行 */
+  'command-started': (command: PtyCommandStarted) => void;
+  /** 命令执行结束 (outputOffset 为命令输出的结束位置) */
+  'command-finished': (command: PtyCommandFinished) => void;
   /** 错误 */
   'error': (code: string, message: string) => void;
 }
@@ -235,6 +408,11 @@ export interface LanguageDetectionResult {
   is_simplified?: boolean;
 }
 
+/**
+ * 服务器日志级别
+ */
+export type ServerLogLevel = 'error' | 'warn' | 'info' | 'debug';
+
 /**
  * Utils 事件映射
  */
@@ -5,7 +5,7 @@
  */
 
 import { ModuleClient } from './moduleClient';
-import { UtilsEvents, ServerMessage, LanguageDetectionResult } from './types';
+import { UtilsEvents, ServerMessage, LanguageDetectionResult, ServerLogLevel } from './types';
 import { debugLog } from '../../utils/logger';
 
 /**
@@ -64,6 +64,13 @@ export class UtilsClient extends ModuleClient {
     });
   }
 
+  /**
+   * 调整服务器日志级别 (运行时生效)
+   */
+  setLogLevel(level: ServerLogLevel): void {
+    this.send('set_log_level', { level });
+  }
+
   /**
    * 注册语言检测结果处理器
    */
@@ -139,6 +146,10 @@ export class UtilsClient extends ModuleClient {
         break;
       }
         
+      case 'log_level_set':
+        debugLog('[UtilsClient] 服务器日志级别:', msg.previous, '->', msg.level);
+        break;
+        
       case 'error': {
         const code = msg.code as string;
         const message = msg.message as string;
@@ -104,11 +104,22 @@ export class TerminalInstance {
   
   // 使用 PtyClient 替代直接的 WebSocket
   private ptyClient: PtyClient | null = null;
+  /** 本终端对应的 PTY 会话 ID (共享连接上的多个会话之一) */
+  private sessionId: number | null = null;
+  /** PTY 会话令牌 (WebSocket 重新连接后凭此接管会话) */
+  private sessionToken: string | null = null;
+  /** 已收到的 PTY 输出字节数 (重新连接时只回放之后的输出) */
+  private receivedBytes = 0;
+  /** WebSocket 重新连接处理器 */
+  private readonly onWsConnected = (): void => {
+    void this.reattachSession();
+  };
   private serverManager: ServerManager | null = null;
   
   // 事件取消函数
   private outputUnsubscribe: (() => void) | null = null;
   private exitUnsubscribe: (() => void) | null = null;
+  private cwdUnsubscribe: (() => void) | null = null;
   private errorUnsubscribe: (() => void) | null = null;
   
   private containerEl: HTMLElement | null = null;
@@ -135,8 +146,10 @@ export class TerminalInstance {
     onSplitTerminal?: (direction: 'horizontal' | 'vertical') => void;
   } = {};
 
-  // 当前工作目录（通过 shell prompt 输出提取）
+  // 当前工作目录（优先使用服务器上报的值，否则从 shell prompt 输出提取）
   private currentCwd: string | null = null;
+  // 是否已收到服务器上报的工作目录 (收到后不再解析 prompt)
+  private cwdFromServer = false;
 
   constructor(options: TerminalOptions = {}) {
     this.id = `terminal-${Date.now()}-${Math.random().toString(36).substring(2, 9)}`;
@@ -279,19 +292,30 @@ export class TerminalInstance {
       this.setupPtyClientHandlers();
       
       // 初始化 PTY 会话
-      this.ptyClient.init({
+      const session = await this.ptyClient.init({
         shell_type: this.shellType === 'default' ? undefined : this.shellType,
         shell_args: this.options.shellArgs,
         cwd: this.options.cwd,
+        cols: this.xterm.cols,
+        rows: this.xterm.rows,
         env: {
           TERM: process.env.TERM || 'xterm-256color',
           ...this.options.env
         }
       });
       
+      this.sessionId = session.sessionId;
+      this.sessionToken = session.sessionToken;
+      
+      // WebSocket 重新连接后接管原会话
+      serverManager.on('ws-connected', this.onWsConnected);
+      
       this.setupXtermHandlers();
       this.isInitialized = true;
       
+      // 会话创建前的尺寸调整被跳过，此处补发
+      this.sendResize(this.xterm.cols, this.xterm.rows);
+      
       debugLog('[Terminal] 终端已初始化');
     } catch (error) {
       const errorMessage = error instanceof Error ? error.message : String(error);
@@ -310,18 +334,30 @@ export class TerminalInstance {
     if (!this.ptyClient) return;
     
     // 处理输出数据
-    this.outputUnsubscribe = this.ptyClient.onOutput((data: Uint8Array) => {
-      const text = new TextDecoder().decode(data);
-      this.extractCwdFromOutput(text);
+    this.outputUnsubscribe = this.ptyClient.onOutput((data: Uint8Array, sessionId?: number) => {
+      if (sessionId !== undefined && sessionId !== this.sessionId) return;
+      this.receivedBytes += data.byteLength;
+      if (!this.cwdFromServer) {
+        this.extractCwdFromOutput(new TextDecoder().decode(data));
+      }
       this.xterm.write(data);
     });
     
     // 处理退出事件
-    this.exitUnsubscribe = this.ptyClient.onExit((code: number) => {
+    this.exitUnsubscribe = this.ptyClient.onExit((code: number, sessionId?: number) => {
+      if (sessionId !== undefined && sessionId !== this.sessionId) return;
       debugLog('[Terminal] PTY 会话退出, code:', code);
       this.xterm.write(`\r\n\x1b[33m[会话已结束, 退出码: ${code}]\x1b[0m\r\n`);
     });
     
+    // 处理工作目录变化 (服务器解析 OSC 7 或读取 shell 进程)
+    this.cwdUnsubscribe = this.ptyClient.onCwdChanged((cwd: string, sessionId?: number) => {
+      if (sessionId !== undefined && sessionId !== this.sessionId) return;
+      this.currentCwd = cwd;
+      this.cwdFromServer = true;
+      debugLog('[Terminal CWD] 服务器上报:', cwd);
+    });
+    
     // 处理错误事件
     this.errorUnsubscribe = this.ptyClient.onError((code: string, message: string) => {
       errorLog('[Terminal] PTY 错误:', code, message);
@@ -329,18 +365,36 @@ export class TerminalInstance {
     });
   }
 
+  /**
+   * WebSocket 重新连接后接管原 PTY 会话
+   */
+  private async reattachSession(): Promise<void> {
+    if (this.isDestroyed || !this.ptyClient || !this.sessionToken) return;
+    
+    try {
+      const session = await this.ptyClient.reattach(this.sessionToken, this.receivedBytes);
+      this.sessionId = session.sessionId;
+      debugLog('[Terminal] PTY 会话已重新连接:', session.sessionId);
+      this.xterm.write('\r\n\x1b[32m[已重新连接]\x1b[0m\r\n');
+      this.sendResize(this.xterm.cols, this.xterm.rows);
+    } catch (error) {
+      errorLog('[Terminal] PTY 会话重新连接失败:', error);
+      this.sessionId = null;
+      this.sessionToken = null;
+      this.xterm.write('\r\n\x1b[33m[会话已结束]\x1b[0m\r\n');
+    }
+  }
+
   private setupXtermHandlers(): void {
     // 处理用户输入
     this.xterm.onData((data) => {
-      if (this.ptyClient) {
-        this.ptyClient.write(data);
-      }
+      this.write(data);
     });
     
     this.xterm.onBinary((data) => {
-      if (this.ptyClient) {
+      if (this.ptyClient && this.sessionId !== null) {
         const binaryData = Uint8Array.from(atob(data), c => c.charCodeAt(0));
-        this.ptyClient.writeBinary(binaryData);
+        this.ptyClient.writeBinary(binaryData, this.sessionId);
       }
     });
     
@@ -379,8 +433,8 @@ export class TerminalInstance {
       if (event.ctrlKey && event.key === 'v') {
         event.preventDefault();
         navigator.clipboard.readText().then(text => {
-          if (text && this.ptyClient) {
-            this.ptyClient.write(text);
+          if (text) {
+            this.write(text);
           }
         }).catch(error => {
           errorLog('[Terminal] Paste failed:', error);
@@ -397,8 +451,8 @@ export class TerminalInstance {
    * 发送调整大小消息
    */
   private sendResize(cols: number, rows: number): void {
-    if (this.ptyClient) {
-      this.ptyClient.resize(cols, rows);
+    if (this.ptyClient && this.sessionId !== null) {
+      this.ptyClient.resize(cols, rows, this.sessionId);
     }
   }
 
@@ -430,10 +484,12 @@ export class TerminalInstance {
     // 取消事件订阅
     this.outputUnsubscribe?.();
     this.exitUnsubscribe?.();
+    this.cwdUnsubscribe?.();
     this.errorUnsubscribe?.();
     
     this.outputUnsubscribe = null;
     this.exitUnsubscribe = null;
+    this.cwdUnsubscribe = null;
     this.errorUnsubscribe = null;
 
     this.detach();
@@ -443,7 +499,13 @@ export class TerminalInstance {
       this.renderer = null;
     }
 
-    // 清理 PtyClient 引用（不销毁，因为它是共享的）
+    // 终止本终端的会话，并清理 PtyClient 引用（不销毁，因为它是共享的）
+    this.serverManager?.off('ws-connected', this.onWsConnected);
+    if (this.ptyClient && this.sessionId !== null) {
+      this.ptyClient.kill(this.sessionId);
+    }
+    this.sessionId = null;
+    this.sessionToken = null;
     this.ptyClient = null;
     this.serverManager = null;
 
@@ -635,8 +697,8 @@ export class TerminalInstance {
       async () => {
         try {
           const text = await navigator.clipboard.readText();
-          if (text && this.ptyClient) {
-            this.ptyClient.write(text);
+          if (text) {
+            this.write(text);
           }
         } catch (error) {
           errorLog('[Terminal] Paste failed:', error);
@@ -1186,8 +1248,8 @@ export class TerminalInstance {
    * 写入数据到终端
    */
   write(data: string): void {
-    if (this.ptyClient) {
-      this.ptyClient.write(data);
+    if (this.ptyClient && this.sessionId !== null) {
+      this.ptyClient.write(data, this.sessionId);
     }
   }
 
@@ -1216,23 +1278,10 @@ export class TerminalInstance {
 
   /**
    * 从 shell 输出中提取当前工作目录
-   * 支持 OSC 序列和 PowerShell/CMD/Git Bash/Bash prompt 格式
+   * 支持 OSC 9;9 / OSC 0 序列和 PowerShell/CMD/Git Bash/Bash prompt 格式
+   * (OSC 7 由服务器解析，通过 cwd-changed 事件上报)
    */
   private extractCwdFromOutput(data: string): void {
-    // OSC 7 格式 (标准): \x1b]7;file://hostname/path\x07 或 \x1b]7;file://hostname/path\x1b\\
-    // eslint-disable-next-line no-control-regex
-    const osc7Match = data.match(/\x1b\]7;file:\/\/[^/]*([^\x07\x1b]+)[\x07\x1b]/);
-    if (osc7Match) {
-      try {
-        const path = decodeURIComponent(osc7Match[1]);
-        this.currentCwd = path;
-        debugLog('[Terminal CWD] OSC7 matched:', path);
-        return;
-      } catch {
-        // 解码失败，忽略
-      }
-    }
-    
     // OSC 9;9 格式 (Windows Terminal/PowerShell): \x1b]9;9;path\x07
     // eslint-disable-next-line no-control-regex
     const osc9Match = data.match(/\x1b\]9;9;([^\x07\x1b]+)[\x07\x1b]/);
@@ -1427,16 +1476,12 @@ export class TerminalInstance {
    */
   private clearScreen(): void {
     // 先发送 Ctrl+C 中断当前输入
-    if (this.ptyClient) {
-      this.ptyClient.write('\x03');
-    }
+    this.write('\x03');
     
     // 等待一小段时间让中断生效,然后发送清屏命令
     setTimeout(() => {
       const clearCommand = platform() === 'win32' ? 'cls\r' : 'clear\r';
-      if (this.ptyClient) {
-        this.ptyClient.write(clearCommand);
-      }
+      this.write(clearCommand);
       debugLog('[Terminal] Screen cleared');
     }, 50);
   }
@@ -1447,17 +1492,13 @@ export class TerminalInstance {
    */
   clearBuffer(): void {
     // 先发送 Ctrl+C 中断当前输入
-    if (this.ptyClient) {
-      this.ptyClient.write('\x03');
-    }
+    this.write('\x03');
     
     // 等待一小段时间让中断生效
     setTimeout(() => {
       // 发送清屏命令到 shell
       const clearCommand = platform() === 'win32' ? 'cls\r' : 'clear\r';
-      if (this.ptyClient) {
-        this.ptyClient.write(clearCommand);
-      }
+      this.write(clearCommand);
       
       // 清除 xterm.js 的滚动缓冲区和状态
       this.xterm.clear();
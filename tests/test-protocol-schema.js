@@ -0,0 +1,112 @@
+/**
+ * 协议一致性验证脚本
+ *
+ * 用服务器导出的协议 JSON Schema (--print-schema) 检查插件客户端：
+ * 1. 客户端发送的每个消息类型 (this.send('xxx')) 都是服务器接受的请求
+ * 2. 客户端处理的每个消息类型 (case 'xxx') 都是服务器会发送的消息
+ *
+ * 运行方式: node tests/test-protocol-schema.js [服务器二进制路径]
+ * 未指定路径时使用 rust-servers/target/debug 下的调试版本 (需先 cargo build)
+ */
+
+const { execFileSync } = require('child_process');
+const fs = require('fs');
+const path = require('path');
+
+const ROOT = path.join(__dirname, '..');
+const CLIENT_DIR = path.join(ROOT, 'src', 'services', 'server');
+const DEFAULT_BINARY = path.join(
+  ROOT, 'rust-servers', 'target', 'debug',
+  `smart-workflow-server${process.platform === 'win32' ? '.exe' : ''}`,
+);
+
+/** 客户端文件 → 模块 */
+const CLIENTS = {
+  'ptyClient.ts': 'pty',
+  'voiceClient.ts': 'voice',
+  'llmClient.ts': 'llm',
+  'utilsClient.ts': 'utils',
+};
+
+/**
+ * 客户端已处理、服务器尚未实现的消息 (module/type)
+ * 服务器实现后从这里移除
+ */
+const PENDING = new Set([]);
+
+/**
+ * 列出 ClientMessage / ServerMessage 中的所有 module/type
+ */
+function messageTypes(schema, direction) {
+  const types = new Set();
+  for (const message of schema.$defs[direction].oneOf) {
+    const type = message.properties.type.const;
+    const module = message.properties.module;
+    for (const name of module.const !== undefined ? [module.const] : module.enum) {
+      types.add(`${name}/${type}`);
+    }
+  }
+  return types;
+}
+
+/**
+ * 提取源码中匹配的所有消息类型
+ */
+function extract(source, pattern) {
+  return [...source.matchAll(pattern)].map(match => match[1]);
+}
+
+function main() {
+  const binaryPath = process.argv[2] || DEFAULT_BINARY;
+  console.log('=== Protocol Schema Test ===\n');
+  console.log('服务器二进制:', binaryPath);
+
+  const schema = JSON.parse(execFileSync(binaryPath, ['--print-schema'], { encoding: 'utf8' }));
+  console.log(`协议版本: ${schema['x-protocol-version']}，服务器版本: ${schema['x-server-version']}\n`);
+
+  const requests = messageTypes(schema, 'ClientMessage');
+  const events = messageTypes(schema, 'ServerMessage');
+  const errors = [];
+
+  for (const [file, module] of Object.entries(CLIENTS)) {
+    const source = fs.readFileSync(path.join(CLIENT_DIR, file), 'utf8');
+
+    for (const type of extract(source, /this\.send\('(\w+)'/g)) {
+      const key = `${module}/${type}`;
+      if (!requests.has(key)) {
+        errors.push(`${file}: 服务器不接受请求 ${key}`);
+      }
+    }
+
+    for (const type of extract(source, /case '(\w+)':/g)) {
+      const key = `${module}/${type}`;
+      if (events.has(key)) {
+        if (PENDING.has(key)) {
+          errors.push(`${file}: ${key} 已实现，请从 PENDING 中移除`);
+        }
+      } else if (!PENDING.has(key)) {
+        errors.push(`${file}: 服务器不会发送 ${key}`);
+      }
+    }
+  }
+
+  // 路由器级消息 (ServerManager 处理)
+  for (const key of ['utils/hello', 'utils/server_shutting_down']) {
+    if (!events.has(key)) {
+      errors.push(`serverManager.ts: 服务器不会发送 ${key}`);
+    }
+  }
+
+  if (errors.length > 0) {
+    errors.forEach(error => console.error('❌', error));
+    process.exit(1);
+  }
+  console.log(`✓ ${Object.keys(CLIENTS).length} 个客户端与协议一致`);
+}
+
+try {
+  main();
+} catch (err) {
+  console.error('❌ Test failed:', err);
+  process.exit(1);
+}
@@ -21,7 +21,7 @@ async function testPtyServer() {
   });
   
   // Wait for port info
-  const port = await new Promise((resolve, reject) => {
+  const { port, token } = await new Promise((resolve, reject) => {
     let buffer = '';
     const timeout = setTimeout(() => reject(new Error('Timeout')), 5000);
     
@@ -35,7 +35,7 @@ async function testPtyServer() {
           const info = JSON.parse(match[0]);
           if (info.port) {
             clearTimeout(timeout);
-            resolve(info.port);
+            resolve(info);
           }
         }
       } catch (e) {}
@@ -52,7 +52,7 @@ async function testPtyServer() {
   
   // Connect WebSocket
   console.log('3. Connecting WebSocket...');
-  const ws = new WebSocket(`ws://127.0.0.1:${port}`);
+  const ws = new WebSocket(`ws://127.0.0.1:${port}/?token=${token}`);
   
   await new Promise((resolve, reject) => {
     ws.on('open', () => {
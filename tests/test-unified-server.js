@@ -71,7 +71,7 @@ async function startServer() {
             results.serverStart = true;
             results.portParsed = true;
             console.log('[TEST] ✓ 服务器启动成功，端口:', info.port);
-            resolve({ process, port: info.port });
+            resolve({ process, port: info.port, token: info.token });
             return;
           }
         } catch {
@@ -341,14 +341,14 @@ async function testVoiceModule(ws) {
 /**
  * 测试 WebSocket 连接
  */
-async function testWebSocket(port) {
+async function testWebSocket(port, token) {
   return new Promise((resolve, reject) => {
     console.log('\n[TEST] 测试 WebSocket 连接...');
     
     const wsUrl = `ws://127.0.0.1:${port}`;
     console.log('[TEST] 连接到:', wsUrl);
     
-    const ws = new WebSocket(wsUrl);
+    const ws = new WebSocket(`${wsUrl}/?token=${token}`);
     
     const timeout = setTimeout(() => {
       ws.close();
@@ -432,11 +432,11 @@ async function main() {
   
   try {
     // 启动服务器
-    const { process, port } = await startServer();
+    const { process, port, token } = await startServer();
     serverProcess = process;
     
     // 测试 WebSocket 通信
-    await testWebSocket(port);
+    await testWebSocket(port, token);
     
     // 打印结果
     const success = printResults();